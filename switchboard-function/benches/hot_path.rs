@@ -0,0 +1,98 @@
+//! `cargo bench --bench hot_path`
+//!
+//! Decoding the params and building the settlement, the part of a run that
+//! does not wait on the network.
+
+// The function is a binary, so its modules are compiled into the bench and
+// find what main.rs declares through the crate root. A bench is built with
+// cfg(test), which compiles the modules' tests along with the fixtures.
+#[allow(dead_code, unused_imports)]
+#[path = "../src/main.rs"]
+mod function;
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use function::test_fixtures::*;
+use function::*;
+use function::{rpc, test_fixtures};
+use std::str::FromStr;
+
+fn bench_params(c: &mut Criterion) {
+    let encoded = test_params_string().into_bytes();
+
+    let mut group = c.benchmark_group("params");
+    group.bench_function("decode", |b| {
+        b.iter(|| ContainerParams::decode(black_box(&encoded)).unwrap())
+    });
+    let params = ContainerParams::decode(&encoded).unwrap();
+    group.bench_function("to_bytes", |b| b.iter(|| black_box(&params).to_bytes()));
+    group.finish();
+}
+
+fn bench_settlement(c: &mut Criterion) {
+    let params = test_params();
+    let fetcher = test_fetcher(
+        &params,
+        &test_realm(vec![]),
+        [1_000, 1_100, 1_200, 1_300, 1_400, 1_500].map(test_spaceship),
+    );
+    let runner_accounts = test_runner_accounts();
+    let rng = SeededRandomSource::new([7; 32]);
+    let settle = |tier: ExecutionTier| {
+        build_settlement(
+            black_box(&params),
+            &runner_accounts,
+            &runner_accounts.enclave_signer,
+            &fetcher,
+            &rng,
+            None,
+            &mut test_budget(tier),
+        )
+        .unwrap()
+    };
+
+    let mut group = c.benchmark_group("settlement");
+    group.bench_function("build_fast", |b| b.iter(|| settle(ExecutionTier::Fast)));
+    group.bench_function("build_standard", |b| {
+        b.iter(|| settle(ExecutionTier::Standard))
+    });
+
+    let header = SettleHeader::new(
+        &runner_accounts.function_request,
+        ExecutionTier::Standard,
+        false,
+    );
+    let args = ArenaMatchmakingSettleArgs {
+        random_result: 42,
+        faction: params.faction,
+        sub_pool_id: 0,
+        opponent_index: 2,
+        opponent_mask: params.opponent_mask(),
+        requester_power: 1_000,
+        opponent_power: PowerBreakdown::default(),
+        raw_result: 42,
+        bias_strength_bps: 0,
+        faction_win_rate_bps: 0,
+        experiment_id: 0,
+        variant: 0,
+        attestation: ResultAttestation([0; ATTESTATION_LEN]),
+        attestation_signer: None,
+    };
+    group.bench_function("matchmaking_settle_ixn", |b| {
+        b.iter(|| {
+            arena_matchmaking_settle_ixn(
+                black_box(&params),
+                &runner_accounts,
+                &header,
+                black_box(&args),
+            )
+            .unwrap()
+        })
+    });
+    group.bench_function("ixn_discriminator", |b| {
+        b.iter(|| ixn_discriminator(black_box("arena_matchmaking_settle")))
+    });
+    group.finish();
+}
+
+criterion_group!(benches, bench_params, bench_settlement);
+criterion_main!(benches);
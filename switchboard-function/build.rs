@@ -0,0 +1,53 @@
+use sha2::{Digest, Sha256};
+use std::fmt::Write;
+
+// Precomputes the Anchor discriminator of every instruction in the program's
+// IDL into $OUT_DIR/ixn_discriminators.rs, see src/discriminators.rs.
+
+const IDL_PATH: &str = "idl/arena_imperium.json";
+
+/// `arenaMatchmakingSettle` to `arena_matchmaking_settle`, like idl.rs.
+fn snake_case(name: &str) -> String {
+    let mut snake = String::with_capacity(name.len() + 4);
+    for c in name.chars() {
+        if c.is_ascii_uppercase() {
+            snake.push('_');
+            snake.push(c.to_ascii_lowercase());
+        } else {
+            snake.push(c);
+        }
+    }
+    snake
+}
+
+fn main() {
+    println!("cargo:rerun-if-changed={}", IDL_PATH);
+    println!("cargo:rerun-if-changed=build.rs");
+
+    let idl: serde_json::Value = serde_json::from_str(
+        &std::fs::read_to_string(IDL_PATH).expect("failed to read the program IDL"),
+    )
+    .expect("the program IDL is not valid JSON");
+    let mut names: Vec<String> = idl["instructions"]
+        .as_array()
+        .expect("the program IDL has no instructions")
+        .iter()
+        .map(|ixn| snake_case(ixn["name"].as_str().expect("an instruction has no name")))
+        .collect();
+    names.sort();
+    names.dedup();
+
+    let mut table = String::from("pub const IXN_DISCRIMINATORS: &[(&str, [u8; 8])] = &[\n");
+    for name in &names {
+        let hash = Sha256::digest(format!("global:{}", name));
+        writeln!(table, "    ({:?}, {:?}),", name, &hash[..8]).unwrap();
+    }
+    table.push_str("];\n");
+
+    let out_dir = std::env::var("OUT_DIR").expect("OUT_DIR is set by cargo");
+    std::fs::write(
+        std::path::Path::new(&out_dir).join("ixn_discriminators.rs"),
+        table,
+    )
+    .expect("failed to write the discriminator table");
+}
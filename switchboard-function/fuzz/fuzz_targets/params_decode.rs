@@ -0,0 +1,9 @@
+//! `cargo +nightly fuzz run params_decode`
+#![no_main]
+
+use arena_matchmaking_params::ContainerParams;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let _ = ContainerParams::decode(data);
+});
@@ -0,0 +1,234 @@
+//! Harness for the end to end tests: a `solana-test-validator` preloaded with
+//! the stub program and the arena accounts, and the function binary run in
+//! local dev mode against it.
+
+use base64::Engine;
+use solana_client::rpc_client::RpcClient;
+use solana_sdk::hash::hash;
+use solana_sdk::instruction::{AccountMeta, Instruction};
+use solana_sdk::pubkey::Pubkey;
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Stdio};
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+
+const RPC_PORT: u16 = 18_899;
+const FAUCET_PORT: u16 = 19_900;
+const STARTUP_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Spaceships are allocated with headroom, the stub program writes the
+/// match in place.
+pub const SPACESHIP_ACCOUNT_LEN: usize = 128;
+
+/// Where the harness builds the stub program and the function binary.
+pub fn target_dir() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("target")
+}
+
+fn succeeds(program: &str, args: &[&str]) -> bool {
+    Command::new(program)
+        .args(args)
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .is_ok_and(|status| status.success())
+}
+
+/// Whether the Solana CLI tools the tests need are installed.
+pub fn solana_cli_available() -> bool {
+    succeeds("solana-test-validator", &["--version"])
+        && succeeds("cargo", &["build-sbf", "--version"])
+}
+
+/// Builds stub-program/ and returns the path of its shared object.
+pub fn build_stub_program() -> PathBuf {
+    let out_dir = target_dir().join("sbf");
+    let manifest = Path::new(env!("CARGO_MANIFEST_DIR")).join("stub-program/Cargo.toml");
+    let status = Command::new("cargo")
+        .arg("build-sbf")
+        .arg("--manifest-path")
+        .arg(manifest)
+        .arg("--sbf-out-dir")
+        .arg(&out_dir)
+        .status()
+        .expect("failed to run cargo build-sbf");
+    assert!(status.success(), "failed to build the stub program");
+    out_dir.join("arena_stub_program.so")
+}
+
+/// Builds the function binary and returns its path.
+pub fn build_function() -> PathBuf {
+    let target_dir = target_dir().join("function");
+    let manifest = Path::new(env!("CARGO_MANIFEST_DIR")).join("../Cargo.toml");
+    let status = Command::new(std::env::var("CARGO").unwrap_or_else(|_| "cargo".to_string()))
+        .args([
+            "build",
+            "--bin",
+            "arena-matchmaking-function",
+            "--manifest-path",
+        ])
+        .arg(manifest)
+        .arg("--target-dir")
+        .arg(&target_dir)
+        .status()
+        .expect("failed to run cargo build");
+    assert!(status.success(), "failed to build the function");
+    target_dir.join("debug/arena-matchmaking-function")
+}
+
+/// Anchor account discriminator, see state.rs of the function.
+pub fn account_discriminator(account_name: &str) -> [u8; 8] {
+    let mut discriminator = [0u8; 8];
+    discriminator
+        .copy_from_slice(&hash(format!("account:{}", account_name).as_bytes()).to_bytes()[..8]);
+    discriminator
+}
+
+/// A realm without sub-pools or queues.
+pub fn realm_data(admin: &Pubkey) -> Vec<u8> {
+    let mut data = account_discriminator("Realm").to_vec();
+    data.push(255);
+    data.extend_from_slice(admin.as_ref());
+    // no sub-pools, no queues
+    data.extend_from_slice(&0u32.to_le_bytes());
+    data.extend_from_slice(&0u32.to_le_bytes());
+    data
+}
+
+/// A queued spaceship without modules, padded to `SPACESHIP_ACCOUNT_LEN`.
+pub fn spaceship_data(owner: &Pubkey, faction: u8, rating: u32) -> Vec<u8> {
+    let mut data = account_discriminator("Spaceship").to_vec();
+    data.push(255);
+    data.extend_from_slice(owner.as_ref());
+    data.push(faction);
+    data.extend_from_slice(&rating.to_le_bytes());
+    // no current match, no modules
+    data.push(0);
+    data.extend_from_slice(&0u32.to_le_bytes());
+    data.resize(SPACESHIP_ACCOUNT_LEN, 0);
+    data
+}
+
+/// An account the validator starts with.
+pub struct GenesisAccount {
+    pub pubkey: Pubkey,
+    pub owner: Pubkey,
+    pub data: Vec<u8>,
+}
+
+/// A running `solana-test-validator`, killed on drop.
+pub struct TestValidator {
+    child: Child,
+    ledger: PathBuf,
+}
+
+impl TestValidator {
+    /// Starts a fresh ledger with `program` deployed at `program_id` and
+    /// `accounts` loaded, and waits until the RPC answers.
+    pub fn start(program_id: &Pubkey, program: &Path, accounts: &[GenesisAccount]) -> Self {
+        let ledger = target_dir().join("test-ledger");
+        let accounts_dir = target_dir().join("test-accounts");
+        let _ = std::fs::remove_dir_all(&accounts_dir);
+        std::fs::create_dir_all(&accounts_dir).unwrap();
+
+        let mut command = Command::new("solana-test-validator");
+        command
+            .arg("--reset")
+            .arg("--quiet")
+            .arg("--ledger")
+            .arg(&ledger)
+            .args(["--rpc-port", &RPC_PORT.to_string()])
+            .args(["--faucet-port", &FAUCET_PORT.to_string()])
+            .arg("--bpf-program")
+            .arg(program_id.to_string())
+            .arg(program);
+        for account in accounts {
+            let path = accounts_dir.join(format!("{}.json", account.pubkey));
+            let json = serde_json::json!({
+                "pubkey": account.pubkey.to_string(),
+                "account": {
+                    "lamports": 1_000_000_000u64,
+                    "data": [base64::engine::general_purpose::STANDARD.encode(&account.data), "base64"],
+                    "owner": account.owner.to_string(),
+                    "executable": false,
+                    "rentEpoch": 0,
+                    "space": account.data.len(),
+                },
+            });
+            std::fs::write(&path, json.to_string()).unwrap();
+            command
+                .arg("--account")
+                .arg(account.pubkey.to_string())
+                .arg(path);
+        }
+        let child = command
+            .stdout(Stdio::null())
+            .spawn()
+            .expect("failed to start solana-test-validator");
+        let validator = Self { child, ledger };
+
+        let client = validator.client();
+        let started = Instant::now();
+        while client.get_health().is_err() {
+            assert!(
+                started.elapsed() < STARTUP_TIMEOUT,
+                "solana-test-validator did not start"
+            );
+            std::thread::sleep(Duration::from_millis(500));
+        }
+        validator
+    }
+
+    pub fn rpc_url(&self) -> String {
+        format!("http://127.0.0.1:{}", RPC_PORT)
+    }
+
+    pub fn client(&self) -> RpcClient {
+        RpcClient::new_with_commitment(
+            self.rpc_url(),
+            solana_sdk::commitment_config::CommitmentConfig::confirmed(),
+        )
+    }
+}
+
+impl Drop for TestValidator {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+        let _ = std::fs::remove_dir_all(&self.ledger);
+    }
+}
+
+/// Decodes the instructions the function prints with `DRY_RUN=1`, the last
+/// line of its output.
+pub fn ixns_from_dry_run(stdout: &str) -> Vec<Instruction> {
+    let line = stdout
+        .lines()
+        .rev()
+        .find(|line| line.starts_with('{'))
+        .unwrap_or_else(|| panic!("no settlement in the function output:\n{}", stdout));
+    let json: serde_json::Value = serde_json::from_str(line).unwrap();
+    let pubkey = |value: &serde_json::Value| Pubkey::from_str(value.as_str().unwrap()).unwrap();
+
+    json["instructions"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|ixn| Instruction {
+            program_id: pubkey(&ixn["program_id"]),
+            accounts: ixn["accounts"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|account| AccountMeta {
+                    pubkey: pubkey(&account["pubkey"]),
+                    is_signer: account["is_signer"].as_bool().unwrap(),
+                    is_writable: account["is_writable"].as_bool().unwrap(),
+                })
+                .collect(),
+            data: base64::engine::general_purpose::STANDARD
+                .decode(ixn["data"].as_str().unwrap())
+                .unwrap(),
+        })
+        .collect()
+}
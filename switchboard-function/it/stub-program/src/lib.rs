@@ -0,0 +1,52 @@
+//! The part of the arena program the integration tests settle against: an
+//! `arena_matchmaking_settle` handler that checks the enclave signed and
+//! records the match on the requester's spaceship.
+
+use solana_program::account_info::AccountInfo;
+use solana_program::entrypoint::ProgramResult;
+use solana_program::program_error::ProgramError;
+use solana_program::pubkey::Pubkey;
+
+solana_program::entrypoint!(process_instruction);
+
+/// Discriminator, header and the version 11 matchmaking settle args, with
+/// and without an attestation signer.
+const SETTLE_DATA_LENS: [usize; 2] = [165, 197];
+
+/// `current_match` follows the discriminator, bump, owner, faction and rating
+/// of the spaceship account.
+const CURRENT_MATCH_OFFSET: usize = 8 + 1 + 32 + 1 + 4;
+
+fn process_instruction(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    data: &[u8],
+) -> ProgramResult {
+    let discriminator = solana_program::hash::hash(b"global:arena_matchmaking_settle");
+    if !SETTLE_DATA_LENS.contains(&data.len()) || data[..8] != discriminator.to_bytes()[..8] {
+        return Err(ProgramError::InvalidInstructionData);
+    }
+    let [enclave_signer, _user, _realm, _user_account, spaceship, _function, request, ..] =
+        accounts
+    else {
+        return Err(ProgramError::NotEnoughAccountKeys);
+    };
+    if !enclave_signer.is_signer {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+    if spaceship.owner != program_id || !spaceship.is_writable {
+        return Err(ProgramError::IllegalOwner);
+    }
+
+    let mut spaceship = spaceship.try_borrow_mut_data()?;
+    if spaceship.len() < CURRENT_MATCH_OFFSET + 33 {
+        return Err(ProgramError::AccountDataTooSmall);
+    }
+    if spaceship[CURRENT_MATCH_OFFSET] != 0 {
+        return Err(ProgramError::AccountAlreadyInitialized);
+    }
+    spaceship[CURRENT_MATCH_OFFSET] = 1;
+    spaceship[CURRENT_MATCH_OFFSET + 1..CURRENT_MATCH_OFFSET + 33]
+        .copy_from_slice(request.key.as_ref());
+    Ok(())
+}
@@ -0,0 +1,106 @@
+use arena_matchmaking_function_it::*;
+use arena_matchmaking_params::{ContainerParams, Requester};
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::{Keypair, Signer};
+use solana_sdk::transaction::Transaction;
+use std::process::Command;
+
+/// The seed the function draws its randomness from, the mock random source
+/// of the unit tests.
+const RANDOMNESS_SEED: [u8; 32] = [7; 32];
+
+#[test]
+#[ignore = "needs the Solana CLI, run with --ignored"]
+fn test_matchmaking_settles_on_a_local_validator() {
+    assert!(
+        solana_cli_available(),
+        "solana-test-validator or cargo build-sbf is not installed"
+    );
+    let program = build_stub_program();
+    let function = build_function();
+
+    let program_id = Pubkey::new_unique();
+    let requester = Requester {
+        program_id,
+        user: Pubkey::new_unique(),
+        realm_pda: Pubkey::new_unique(),
+        user_account_pda: Pubkey::new_unique(),
+    };
+    let spaceship = Pubkey::new_unique();
+    let opponents = [(); 5].map(|_| Pubkey::new_unique());
+    let mut accounts = vec![
+        GenesisAccount {
+            pubkey: requester.realm_pda,
+            owner: program_id,
+            data: realm_data(&Pubkey::new_unique()),
+        },
+        GenesisAccount {
+            pubkey: spaceship,
+            owner: program_id,
+            data: spaceship_data(&requester.user, 1, 1_200),
+        },
+    ];
+    accounts.extend(
+        opponents
+            .iter()
+            .enumerate()
+            .map(|(i, opponent)| GenesisAccount {
+                pubkey: *opponent,
+                owner: program_id,
+                data: spaceship_data(&Pubkey::new_unique(), 0, 1_000 + 100 * i as u32),
+            }),
+    );
+    let validator = TestValidator::start(&program_id, &program, &accounts);
+    let client = validator.client();
+
+    // the test signs as the enclave and pays for the settlement
+    let enclave_signer = Keypair::new();
+    let airdrop = client
+        .request_airdrop(&enclave_signer.pubkey(), 1_000_000_000)
+        .unwrap();
+    while !client.confirm_transaction(&airdrop).unwrap() {
+        std::thread::sleep(std::time::Duration::from_millis(200));
+    }
+
+    let params = ContainerParams::matchmaking(&requester, spaceship, 1, opponents).unwrap();
+    let request = Pubkey::new_unique();
+    let output = Command::new(function)
+        .env("LOCAL_RANDOMNESS", "1")
+        .env("DRY_RUN", "1")
+        .env("RPC_URL", validator.rpc_url())
+        .env(
+            "CONTAINER_PARAMS",
+            String::from_utf8(params.to_bytes()).unwrap(),
+        )
+        .env("FUNCTION_KEY", Pubkey::new_unique().to_string())
+        .env("FUNCTION_REQUEST_KEY", request.to_string())
+        .env("ENCLAVE_SIGNER", enclave_signer.pubkey().to_string())
+        .env("RANDOMNESS_SEED", hex(&RANDOMNESS_SEED))
+        .output()
+        .expect("failed to run the function");
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(output.status.success(), "the function failed:\n{}", stdout);
+    let ixs = ixns_from_dry_run(&stdout);
+
+    let transaction = Transaction::new_signed_with_payer(
+        &ixs,
+        Some(&enclave_signer.pubkey()),
+        &[&enclave_signer],
+        client.get_latest_blockhash().unwrap(),
+    );
+    client.send_and_confirm_transaction(&transaction).unwrap();
+
+    // the stub program recorded the request as the spaceship's match
+    let spaceship = client.get_account(&spaceship).unwrap();
+    let current_match = &spaceship.data[46..79];
+    assert_eq!(current_match[0], 1);
+    assert_eq!(current_match[1..], request.to_bytes());
+    // and the opponent the function picked is one of the candidates
+    let settle_ixn = ixs.last().unwrap();
+    assert_eq!(settle_ixn.program_id, program_id);
+    assert!((settle_ixn.data[52] as usize) < opponents.len());
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
@@ -0,0 +1,202 @@
+use crate::*;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::Instant;
+
+/// Most keys one `getMultipleAccounts` call accepts.
+pub const MAX_MULTIPLE_ACCOUNTS: usize = 100;
+
+/// Memoizes every account read for the rest of the run. Keys are
+/// deduplicated, only the ones not read yet are fetched, in batches of
+/// `MAX_MULTIPLE_ACCOUNTS`. Missing accounts are remembered too, a request
+/// naming a closed account fails the same way on every read.
+pub struct AccountsCache<'a> {
+    inner: &'a dyn AccountFetcher,
+    accounts: Mutex<HashMap<Pubkey, Option<Vec<u8>>>>,
+}
+
+impl<'a> AccountsCache<'a> {
+    pub fn new(inner: &'a dyn AccountFetcher) -> Self {
+        Self {
+            inner,
+            accounts: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Reads `pubkeys` from the RPC whether or not they are cached, and
+    /// caches the result.
+    fn fetch_and_cache(&self, pubkeys: &[Pubkey]) -> std::result::Result<(), FunctionError> {
+        for batch in pubkeys.chunks(MAX_MULTIPLE_ACCOUNTS) {
+            let started = Instant::now();
+            let fetched = self.inner.fetch_multiple_account_data(batch)?;
+            record_timing("rpc_batch_ms", started.elapsed(), &[]);
+            if fetched.len() != batch.len() {
+                return Err(FunctionError::AccountFetchFailed);
+            }
+            let mut accounts = self.accounts.lock().unwrap_or_else(|p| p.into_inner());
+            accounts.extend(batch.iter().copied().zip(fetched));
+        }
+        Ok(())
+    }
+
+    fn cached(&self, pubkeys: &[Pubkey]) -> Vec<Option<Vec<u8>>> {
+        let accounts = self.accounts.lock().unwrap_or_else(|p| p.into_inner());
+        pubkeys
+            .iter()
+            .map(|pubkey| accounts.get(pubkey).cloned().flatten())
+            .collect()
+    }
+}
+
+impl AccountFetcher for AccountsCache<'_> {
+    fn fetch_multiple_account_data(
+        &self,
+        pubkeys: &[Pubkey],
+    ) -> std::result::Result<Vec<Option<Vec<u8>>>, FunctionError> {
+        let mut missing: Vec<Pubkey> = {
+            let accounts = self.accounts.lock().unwrap_or_else(|p| p.into_inner());
+            pubkeys
+                .iter()
+                .filter(|pubkey| !accounts.contains_key(pubkey))
+                .copied()
+                .collect()
+        };
+        missing.sort_unstable();
+        missing.dedup();
+        let result = if missing.is_empty() { "hit" } else { "miss" };
+        record_counter("rpc_cache_total", &[("result", result)]);
+
+        self.fetch_and_cache(&missing)?;
+        Ok(self.cached(pubkeys))
+    }
+
+    fn fetch_fresh_account_data(
+        &self,
+        pubkeys: &[Pubkey],
+    ) -> std::result::Result<Vec<Option<Vec<u8>>>, FunctionError> {
+        let mut unique = pubkeys.to_vec();
+        unique.sort_unstable();
+        unique.dedup();
+
+        self.fetch_and_cache(&unique)?;
+        Ok(self.cached(pubkeys))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_fixtures::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    /// Counts the keys read through it, and the calls.
+    struct CountingFetcher {
+        inner: MockFetcher,
+        calls: AtomicUsize,
+        keys: AtomicUsize,
+    }
+
+    impl AccountFetcher for CountingFetcher {
+        fn fetch_multiple_account_data(
+            &self,
+            pubkeys: &[Pubkey],
+        ) -> std::result::Result<Vec<Option<Vec<u8>>>, FunctionError> {
+            assert!(pubkeys.len() <= MAX_MULTIPLE_ACCOUNTS);
+            self.calls.fetch_add(1, Ordering::SeqCst);
+            self.keys.fetch_add(pubkeys.len(), Ordering::SeqCst);
+            self.inner.fetch_multiple_account_data(pubkeys)
+        }
+    }
+
+    fn counting_fetcher(pubkeys: &[Pubkey]) -> CountingFetcher {
+        let mut inner = MockFetcher::default();
+        for (i, pubkey) in pubkeys.iter().enumerate() {
+            inner.insert(*pubkey, vec![i as u8]);
+        }
+        CountingFetcher {
+            inner,
+            calls: AtomicUsize::new(0),
+            keys: AtomicUsize::new(0),
+        }
+    }
+
+    #[test]
+    fn test_accounts_cache_dedups_and_memoizes() {
+        let pubkeys: Vec<Pubkey> = (0..3).map(|_| Pubkey::new_unique()).collect();
+        let closed = Pubkey::new_unique();
+        let fetcher = counting_fetcher(&pubkeys);
+        let cache = AccountsCache::new(&fetcher);
+
+        let read = [pubkeys[0], pubkeys[1], pubkeys[0], closed];
+        assert_eq!(
+            cache.fetch_multiple_account_data(&read),
+            Ok(vec![Some(vec![0]), Some(vec![1]), Some(vec![0]), None])
+        );
+        assert_eq!(fetcher.keys.load(Ordering::SeqCst), 3);
+
+        // only the new key is read, the missing one is remembered
+        assert_eq!(
+            cache.fetch_multiple_account_data(&[pubkeys[2], closed, pubkeys[1]]),
+            Ok(vec![Some(vec![2]), None, Some(vec![1])])
+        );
+        assert_eq!(fetcher.calls.load(Ordering::SeqCst), 2);
+        assert_eq!(fetcher.keys.load(Ordering::SeqCst), 4);
+
+        assert_eq!(
+            cache.fetch_multiple_account_data(&pubkeys),
+            Ok(vec![Some(vec![0]), Some(vec![1]), Some(vec![2])])
+        );
+        assert_eq!(fetcher.calls.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn test_accounts_cache_fresh_reads_bypass_the_cache() {
+        let pubkey = Pubkey::new_unique();
+        let fetcher = counting_fetcher(&[pubkey]);
+        let cache = AccountsCache::new(&fetcher);
+
+        cache.fetch_multiple_account_data(&[pubkey]).unwrap();
+        assert_eq!(
+            cache.fetch_fresh_account_data(&[pubkey, pubkey]),
+            Ok(vec![Some(vec![0]), Some(vec![0])])
+        );
+        assert_eq!(fetcher.calls.load(Ordering::SeqCst), 2);
+        assert_eq!(fetcher.keys.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn test_accounts_cache_batches_large_reads() {
+        let pubkeys: Vec<Pubkey> = (0..MAX_MULTIPLE_ACCOUNTS * 2 + 1)
+            .map(|_| Pubkey::new_unique())
+            .collect();
+        let fetcher = counting_fetcher(&pubkeys);
+        let cache = AccountsCache::new(&fetcher);
+
+        let accounts = cache.fetch_multiple_account_data(&pubkeys).unwrap();
+
+        assert_eq!(accounts.len(), pubkeys.len());
+        assert!(accounts.iter().all(Option::is_some));
+        assert_eq!(fetcher.calls.load(Ordering::SeqCst), 3);
+    }
+
+    #[test]
+    fn test_accounts_cache_does_not_cache_failures() {
+        let pubkey = Pubkey::new_unique();
+        let failing = crate::chaos::FlakyRpc::new(
+            counting_fetcher(&[pubkey]).inner,
+            Some(crate::chaos::RpcFault::TimeoutAfterFirst),
+        );
+        let cache = AccountsCache::new(&failing);
+
+        assert!(cache.fetch_multiple_account_data(&[pubkey]).is_ok());
+        assert_eq!(
+            cache.fetch_fresh_account_data(&[pubkey]),
+            Err(FunctionError::AccountFetchFailed)
+        );
+        // the earlier read is still served
+        assert_eq!(
+            cache.fetch_multiple_account_data(&[pubkey]),
+            Ok(vec![Some(vec![0])])
+        );
+    }
+}
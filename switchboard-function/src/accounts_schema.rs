@@ -0,0 +1,311 @@
+use crate::*;
+
+// The accounts each settle instruction passes, declared once per params
+// version rather than hardcoded in the builders, so a handler changing an
+// account's writability is a one line change checked against the IDL, see
+// idl.rs.
+
+/// Where an account's pubkey comes from.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AccountSource {
+    EnclaveSigner,
+    User,
+    Realm,
+    UserAccount,
+    Spaceship,
+    Function,
+    FunctionRequest,
+    Tournament,
+    Seed,
+    Raffle,
+    /// The `OS_<n>_PDA` slot, 0 based.
+    Opponent(u8),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct AccountSpec {
+    /// The account's name in the IDL.
+    pub name: &'static str,
+    pub source: AccountSource,
+    pub writable: bool,
+    pub signer: bool,
+}
+
+const fn readonly(name: &'static str, source: AccountSource) -> AccountSpec {
+    AccountSpec {
+        name,
+        source,
+        writable: false,
+        signer: false,
+    }
+}
+
+const fn writable(name: &'static str, source: AccountSource) -> AccountSpec {
+    AccountSpec {
+        name,
+        source,
+        writable: true,
+        signer: false,
+    }
+}
+
+const ENCLAVE_SIGNER: AccountSpec = AccountSpec {
+    name: "enclaveSigner",
+    source: AccountSource::EnclaveSigner,
+    writable: false,
+    signer: true,
+};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SettleIxn {
+    ArenaMatchmakingSettle,
+    ArenaMatchmakingSettleVsBot,
+    LootOpenSettle,
+    TournamentSeedSettle,
+    DailySeedSettle,
+    RaffleSettle,
+    ArenaMatchmakingCancelSettle,
+    ArenaMatchmakingReportFailure,
+}
+
+impl SettleIxn {
+    pub const ALL: [SettleIxn; 8] = [
+        SettleIxn::ArenaMatchmakingSettle,
+        SettleIxn::ArenaMatchmakingSettleVsBot,
+        SettleIxn::LootOpenSettle,
+        SettleIxn::TournamentSeedSettle,
+        SettleIxn::DailySeedSettle,
+        SettleIxn::RaffleSettle,
+        SettleIxn::ArenaMatchmakingCancelSettle,
+        SettleIxn::ArenaMatchmakingReportFailure,
+    ];
+
+    /// The instruction's name in the program, hashed into its discriminator.
+    pub fn name(&self) -> &'static str {
+        match self {
+            SettleIxn::ArenaMatchmakingSettle => "arena_matchmaking_settle",
+            SettleIxn::ArenaMatchmakingSettleVsBot => "arena_matchmaking_settle_vs_bot",
+            SettleIxn::LootOpenSettle => "loot_open_settle",
+            SettleIxn::TournamentSeedSettle => "tournament_seed_settle",
+            SettleIxn::DailySeedSettle => "daily_seed_settle",
+            SettleIxn::RaffleSettle => "raffle_settle",
+            SettleIxn::ArenaMatchmakingCancelSettle => "arena_matchmaking_cancel_settle",
+            SettleIxn::ArenaMatchmakingReportFailure => "arena_matchmaking_report_failure",
+        }
+    }
+}
+
+const ARENA_MATCHMAKING_SETTLE_V1: &[AccountSpec] = &[
+    ENCLAVE_SIGNER,
+    readonly("user", AccountSource::User),
+    writable("realm", AccountSource::Realm),
+    readonly("userAccount", AccountSource::UserAccount),
+    writable("spaceship", AccountSource::Spaceship),
+    readonly("switchboardFunction", AccountSource::Function),
+    readonly("switchboardRequest", AccountSource::FunctionRequest),
+    writable("opponentSpaceship1", AccountSource::Opponent(0)),
+    writable("opponentSpaceship2", AccountSource::Opponent(1)),
+    writable("opponentSpaceship3", AccountSource::Opponent(2)),
+    writable("opponentSpaceship4", AccountSource::Opponent(3)),
+    writable("opponentSpaceship5", AccountSource::Opponent(4)),
+];
+
+const ARENA_MATCHMAKING_SETTLE_VS_BOT_V1: &[AccountSpec] = &[
+    ENCLAVE_SIGNER,
+    readonly("user", AccountSource::User),
+    writable("realm", AccountSource::Realm),
+    readonly("userAccount", AccountSource::UserAccount),
+    writable("spaceship", AccountSource::Spaceship),
+    readonly("switchboardFunction", AccountSource::Function),
+    readonly("switchboardRequest", AccountSource::FunctionRequest),
+];
+
+const LOOT_OPEN_SETTLE_V1: &[AccountSpec] = &[
+    ENCLAVE_SIGNER,
+    readonly("user", AccountSource::User),
+    readonly("realm", AccountSource::Realm),
+    writable("userAccount", AccountSource::UserAccount),
+    readonly("switchboardFunction", AccountSource::Function),
+    readonly("switchboardRequest", AccountSource::FunctionRequest),
+];
+
+const TOURNAMENT_SEED_SETTLE_V1: &[AccountSpec] = &[
+    ENCLAVE_SIGNER,
+    readonly("user", AccountSource::User),
+    readonly("realm", AccountSource::Realm),
+    writable("tournament", AccountSource::Tournament),
+    readonly("switchboardFunction", AccountSource::Function),
+    readonly("switchboardRequest", AccountSource::FunctionRequest),
+];
+
+const DAILY_SEED_SETTLE_V1: &[AccountSpec] = &[
+    ENCLAVE_SIGNER,
+    readonly("user", AccountSource::User),
+    readonly("realm", AccountSource::Realm),
+    writable("seed", AccountSource::Seed),
+    readonly("switchboardFunction", AccountSource::Function),
+    readonly("switchboardRequest", AccountSource::FunctionRequest),
+];
+
+const RAFFLE_SETTLE_V1: &[AccountSpec] = &[
+    ENCLAVE_SIGNER,
+    readonly("user", AccountSource::User),
+    readonly("realm", AccountSource::Realm),
+    writable("raffle", AccountSource::Raffle),
+    readonly("switchboardFunction", AccountSource::Function),
+    readonly("switchboardRequest", AccountSource::FunctionRequest),
+];
+
+const ARENA_MATCHMAKING_CANCEL_SETTLE_V1: &[AccountSpec] = &[
+    ENCLAVE_SIGNER,
+    readonly("user", AccountSource::User),
+    writable("realm", AccountSource::Realm),
+    readonly("userAccount", AccountSource::UserAccount),
+    writable("spaceship", AccountSource::Spaceship),
+    readonly("switchboardFunction", AccountSource::Function),
+    readonly("switchboardRequest", AccountSource::FunctionRequest),
+];
+
+const ARENA_MATCHMAKING_REPORT_FAILURE_V1: &[AccountSpec] = &[
+    ENCLAVE_SIGNER,
+    writable("user", AccountSource::User),
+    writable("realm", AccountSource::Realm),
+    writable("userAccount", AccountSource::UserAccount),
+    readonly("switchboardFunction", AccountSource::Function),
+    readonly("switchboardRequest", AccountSource::FunctionRequest),
+];
+
+/// CUSTOM_ROLL handlers are not in the IDL, they all take these accounts so
+/// the program can tie the roll to the requester.
+pub const CUSTOM_ROLL_SETTLE_V1: &[AccountSpec] = &[
+    ENCLAVE_SIGNER,
+    readonly("user", AccountSource::User),
+    readonly("realm", AccountSource::Realm),
+    writable("userAccount", AccountSource::UserAccount),
+    readonly("switchboardFunction", AccountSource::Function),
+    readonly("switchboardRequest", AccountSource::FunctionRequest),
+];
+
+/// The accounts `ixn` declares for requests of `params_version`, in order.
+/// Remaining accounts, e.g. an approval or the tournament participants, are
+/// appended by the builders.
+pub fn accounts_schema(
+    ixn: SettleIxn,
+    params_version: u8,
+) -> std::result::Result<&'static [AccountSpec], FunctionError> {
+    match (ixn, params_version) {
+        (SettleIxn::ArenaMatchmakingSettle, 1) => Ok(ARENA_MATCHMAKING_SETTLE_V1),
+        (SettleIxn::ArenaMatchmakingSettleVsBot, 1) => Ok(ARENA_MATCHMAKING_SETTLE_VS_BOT_V1),
+        (SettleIxn::LootOpenSettle, 1) => Ok(LOOT_OPEN_SETTLE_V1),
+        (SettleIxn::TournamentSeedSettle, 1) => Ok(TOURNAMENT_SEED_SETTLE_V1),
+        (SettleIxn::DailySeedSettle, 1) => Ok(DAILY_SEED_SETTLE_V1),
+        (SettleIxn::RaffleSettle, 1) => Ok(RAFFLE_SETTLE_V1),
+        (SettleIxn::ArenaMatchmakingCancelSettle, 1) => Ok(ARENA_MATCHMAKING_CANCEL_SETTLE_V1),
+        (SettleIxn::ArenaMatchmakingReportFailure, 1) => Ok(ARENA_MATCHMAKING_REPORT_FAILURE_V1),
+        _ => Err(FunctionError::UnsupportedParamsVersion),
+    }
+}
+
+/// `ixn`'s schema resolved against the request.
+pub fn schema_account_metas(
+    ixn: SettleIxn,
+    params: &ContainerParams,
+    runner_accounts: &RunnerAccounts,
+) -> std::result::Result<Vec<AccountMeta>, FunctionError> {
+    Ok(resolve_account_metas(
+        accounts_schema(ixn, params.version)?,
+        params,
+        runner_accounts,
+    ))
+}
+
+/// `specs` resolved against the request.
+pub fn resolve_account_metas(
+    specs: &[AccountSpec],
+    params: &ContainerParams,
+    runner_accounts: &RunnerAccounts,
+) -> Vec<AccountMeta> {
+    let opponents = params.opponent_spaceship_pdas();
+    specs
+        .iter()
+        .map(|spec| {
+            let pubkey = match spec.source {
+                AccountSource::EnclaveSigner => runner_accounts.enclave_signer,
+                AccountSource::User => params.user,
+                AccountSource::Realm => params.realm_pda,
+                AccountSource::UserAccount => params.user_account_pda,
+                AccountSource::Spaceship => params.spaceship_pda,
+                AccountSource::Function => runner_accounts.function,
+                AccountSource::FunctionRequest => runner_accounts.function_request,
+                AccountSource::Tournament => params.tournament_pda,
+                AccountSource::Seed => params.seed_pda,
+                AccountSource::Raffle => params.raffle_pda,
+                AccountSource::Opponent(slot) => opponents[slot as usize],
+            };
+            // the sentinel of an empty opponent slot is passed read-only, it
+            // may repeat and is never written
+            let sentinel =
+                matches!(spec.source, AccountSource::Opponent(_)) && pubkey == Pubkey::default();
+            AccountMeta {
+                pubkey,
+                is_signer: spec.signer,
+                is_writable: spec.writable && !sentinel,
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_fixtures::*;
+
+    #[test]
+    fn test_schemas_match_idl() {
+        for ixn in SettleIxn::ALL {
+            let schema = accounts_schema(ixn, PARAMS_VERSION).unwrap();
+
+            assert_eq!(
+                program_idl()
+                    .instruction(ixn.name())
+                    .unwrap()
+                    .validate_accounts(schema),
+                Ok(()),
+                "{}",
+                ixn.name()
+            );
+        }
+    }
+
+    #[test]
+    fn test_unknown_params_version_has_no_schema() {
+        assert_eq!(
+            accounts_schema(SettleIxn::LootOpenSettle, PARAMS_VERSION + 1),
+            Err(FunctionError::UnsupportedParamsVersion)
+        );
+    }
+
+    #[test]
+    fn test_schema_account_metas() {
+        let params = test_params();
+        let runner_accounts = test_runner_accounts();
+
+        let metas =
+            schema_account_metas(SettleIxn::ArenaMatchmakingSettle, &params, &runner_accounts)
+                .unwrap();
+
+        assert_eq!(metas.len(), 12);
+        assert_eq!(
+            metas[0],
+            AccountMeta::new_readonly(runner_accounts.enclave_signer, true)
+        );
+        assert_eq!(metas[4], AccountMeta::new(params.spaceship_pda, false));
+        assert_eq!(
+            metas[7..]
+                .iter()
+                .map(|meta| meta.pubkey)
+                .collect::<Vec<_>>(),
+            params.opponent_spaceship_pdas().to_vec()
+        );
+    }
+}
@@ -0,0 +1,151 @@
+use crate::*;
+
+/// Fetches and checks the settlement approval named by `APPROVAL_PDA`, if
+/// any. The settle handler enforces the approval too, checking it here
+/// reports a mismatch as an error code instead of a failed transaction.
+pub fn load_settlement_approval<F: AccountFetcher + ?Sized>(
+    fetcher: &F,
+    params: &ContainerParams,
+    runner_accounts: &RunnerAccounts,
+) -> std::result::Result<Option<SettlementApproval>, FunctionError> {
+    if params.approval_pda == Pubkey::default() {
+        return Ok(None);
+    }
+
+    let data = fetcher
+        .fetch_multiple_account_data(std::slice::from_ref(&params.approval_pda))?
+        .pop()
+        .flatten()
+        .ok_or(FunctionError::AccountFetchFailed)?;
+    let approval = SettlementApproval::decode(&data)?;
+
+    if approval.realm != params.realm_pda
+        || approval.function_request != runner_accounts.function_request
+    {
+        return Err(FunctionError::ApprovalMismatch);
+    }
+    if approval.status != ApprovalStatus::Approved {
+        return Err(FunctionError::ApprovalNotApproved);
+    }
+    Ok(Some(approval))
+}
+
+/// The accounts appended to the settle instruction of an approved settlement.
+pub fn approval_account_metas(
+    params: &ContainerParams,
+    approval: &SettlementApproval,
+) -> Vec<AccountMeta> {
+    vec![
+        AccountMeta::new(params.approval_pda, false),
+        AccountMeta::new_readonly(approval.multisig, false),
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_fixtures::*;
+
+    fn approval_params() -> ContainerParams {
+        ContainerParams::decode(
+            format!(
+                "{},APPROVAL_PDA={}",
+                test_params_string(),
+                Pubkey::new_unique()
+            )
+            .as_bytes(),
+        )
+        .unwrap()
+    }
+
+    fn approval_fetcher(params: &ContainerParams, approval: &SettlementApproval) -> MockFetcher {
+        let mut fetcher = MockFetcher::default();
+        fetcher.insert(
+            params.approval_pda,
+            encode_account(SettlementApproval::NAME, approval),
+        );
+        fetcher
+    }
+
+    fn test_approval(
+        params: &ContainerParams,
+        runner_accounts: &RunnerAccounts,
+    ) -> SettlementApproval {
+        SettlementApproval {
+            bump: 255,
+            realm: params.realm_pda,
+            function_request: runner_accounts.function_request,
+            multisig: Pubkey::new_unique(),
+            status: ApprovalStatus::Approved,
+        }
+    }
+
+    #[test]
+    fn test_no_approval_required() {
+        let params = test_params();
+
+        assert_eq!(
+            load_settlement_approval(&MockFetcher::default(), &params, &test_runner_accounts()),
+            Ok(None)
+        );
+    }
+
+    #[test]
+    fn test_load_matching_approval() {
+        let params = approval_params();
+        let runner_accounts = test_runner_accounts();
+        let approval = test_approval(&params, &runner_accounts);
+        let fetcher = approval_fetcher(&params, &approval);
+
+        let loaded = load_settlement_approval(&fetcher, &params, &runner_accounts)
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(loaded, approval);
+        let metas = approval_account_metas(&params, &loaded);
+        assert_eq!(metas[0], AccountMeta::new(params.approval_pda, false));
+        assert_eq!(
+            metas[1],
+            AccountMeta::new_readonly(approval.multisig, false)
+        );
+    }
+
+    #[test]
+    fn test_reject_approval_for_another_request() {
+        let params = approval_params();
+        let runner_accounts = test_runner_accounts();
+        let approval = SettlementApproval {
+            function_request: Pubkey::new_unique(),
+            ..test_approval(&params, &runner_accounts)
+        };
+        let fetcher = approval_fetcher(&params, &approval);
+
+        assert_eq!(
+            load_settlement_approval(&fetcher, &params, &runner_accounts),
+            Err(FunctionError::ApprovalMismatch)
+        );
+    }
+
+    #[test]
+    fn test_reject_unapproved_or_consumed_approval() {
+        let params = approval_params();
+        let runner_accounts = test_runner_accounts();
+
+        for status in [ApprovalStatus::AwaitingSignatures, ApprovalStatus::Consumed] {
+            let approval = SettlementApproval {
+                status,
+                ..test_approval(&params, &runner_accounts)
+            };
+            let fetcher = approval_fetcher(&params, &approval);
+
+            assert_eq!(
+                load_settlement_approval(&fetcher, &params, &runner_accounts),
+                Err(FunctionError::ApprovalNotApproved)
+            );
+        }
+        assert_eq!(
+            load_settlement_approval(&MockFetcher::default(), &params, &runner_accounts),
+            Err(FunctionError::AccountFetchFailed)
+        );
+    }
+}
@@ -0,0 +1,45 @@
+use crate::*;
+use ed25519_dalek::Verifier;
+
+// Every matchmaking settlement carries a signature over its roll, so an
+// indexer or the program can trace an on-chain result back to an enclave
+// execution after the fact. Shared with the params builder, verifying needs
+// none of the runtime.
+
+/// An ed25519 signature by the run's enclave key, the settlement's enclave
+/// signer. A batch the runner signs carries the key after it in the settle
+/// args. The function posts the key's SGX quote with the outcomes.
+pub const ATTESTATION_LEN: usize = 64;
+
+/// The attested message: request key ‖ random result ‖ request slot, both
+/// integers little endian.
+pub fn attestation_message(request: &Pubkey, random_result: u64, request_slot: u64) -> [u8; 48] {
+    let mut message = [0u8; 48];
+    message[..32].copy_from_slice(request.as_ref());
+    message[32..40].copy_from_slice(&random_result.to_le_bytes());
+    message[40..].copy_from_slice(&request_slot.to_le_bytes());
+    message
+}
+
+/// Whether `signer` attested this result for the request, see
+/// enclave_key.rs for how the signer is bound to the enclave.
+pub fn verify_result_attestation(
+    attestation: &[u8; ATTESTATION_LEN],
+    signer: &Pubkey,
+    request: &Pubkey,
+    random_result: u64,
+    request_slot: u64,
+) -> bool {
+    let Ok(public_key) = ed25519_dalek::PublicKey::from_bytes(signer.as_ref()) else {
+        return false;
+    };
+    let Ok(signature) = ed25519_dalek::Signature::from_bytes(attestation) else {
+        return false;
+    };
+    public_key
+        .verify(
+            &attestation_message(request, random_result, request_slot),
+            &signature,
+        )
+        .is_ok()
+}
@@ -0,0 +1,207 @@
+use crate::*;
+use serde::Serialize;
+use std::io::Write;
+use std::sync::Mutex;
+
+const AUDIT_WEBHOOK_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(3);
+
+/// Where audit records go, from `AUDIT_LOG_PATH` (an append-only JSONL file)
+/// and `AUDIT_WEBHOOK_URL`. Recording is off unless one of them is set.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct AuditSinks {
+    pub log_path: Option<std::path::PathBuf>,
+    pub webhook_url: Option<String>,
+}
+
+impl AuditSinks {
+    pub fn from_env() -> Self {
+        let non_empty = |key| setting(key).filter(|value| !value.is_empty());
+        Self {
+            log_path: non_empty("AUDIT_LOG_PATH").map(Into::into),
+            webhook_url: non_empty("AUDIT_WEBHOOK_URL"),
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.log_path.is_some() || self.webhook_url.is_some()
+    }
+}
+
+/// Passes draws through to `inner`, keeping a copy of every value handed to
+/// the pipeline.
+pub struct RecordingRandomSource<'a> {
+    inner: &'a dyn RandomSource,
+    values: Mutex<Vec<u64>>,
+    bytes: Mutex<Vec<String>>,
+}
+
+impl<'a> RecordingRandomSource<'a> {
+    pub fn new(inner: &'a dyn RandomSource) -> Self {
+        Self {
+            inner,
+            values: Mutex::new(vec![]),
+            bytes: Mutex::new(vec![]),
+        }
+    }
+
+    /// The ranged values and the hex encoded raw draws, in draw order.
+    pub fn draws(&self) -> (Vec<u64>, Vec<String>) {
+        (
+            self.values.lock().unwrap().clone(),
+            self.bytes.lock().unwrap().clone(),
+        )
+    }
+}
+
+impl RandomSource for RecordingRandomSource<'_> {
+    fn name(&self) -> &'static str {
+        self.inner.name()
+    }
+
+    fn is_fallback(&self) -> bool {
+        self.inner.is_fallback()
+    }
+
+    fn fill_bytes(&self, buf: &mut [u8]) -> std::result::Result<(), FunctionError> {
+        self.inner.fill_bytes(buf)?;
+        self.bytes.lock().unwrap().push(hex::encode(&buf));
+        Ok(())
+    }
+
+    fn generate_u64(&self, min: u64, max: u64) -> std::result::Result<u64, FunctionError> {
+        let value = self.inner.generate_u64(min, max)?;
+        self.values.lock().unwrap().push(value);
+        Ok(value)
+    }
+}
+
+/// What the game team needs to resolve a dispute over a settlement without
+/// decoding the transaction: the request as decoded, every random value
+/// drawn for it and the outcome, whose `outcome_hash` matches the emitted
+/// instruction data.
+#[derive(Serialize, Clone, Debug, PartialEq, Eq)]
+pub struct AuditRecord {
+    /// The decoded params, re-encoded in their canonical form.
+    pub params: String,
+    pub random_values: Vec<u64>,
+    pub random_bytes: Vec<String>,
+    #[serde(flatten)]
+    pub outcome: OutcomeSummary,
+}
+
+impl AuditRecord {
+    pub fn new(
+        params: &ContainerParams,
+        rng: &RecordingRandomSource,
+        outcome: &OutcomeSummary,
+    ) -> Self {
+        let (random_values, random_bytes) = rng.draws();
+        Self {
+            params: String::from_utf8_lossy(&params.to_bytes()).into_owned(),
+            random_values,
+            random_bytes,
+            outcome: outcome.clone(),
+        }
+    }
+}
+
+/// Appends one JSON line per record, a partial line is never written.
+pub fn append_audit_log(path: &std::path::Path, records: &[AuditRecord]) -> std::io::Result<()> {
+    let mut lines = vec![];
+    for record in records {
+        serde_json::to_writer(&mut lines, record)?;
+        lines.push(b'\n');
+    }
+    std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)?
+        .write_all(&lines)
+}
+
+/// Best effort like the outcome webhook: failures are logged to stderr and
+/// never affect the settlement, which was already emitted.
+pub async fn record_audit(sinks: &AuditSinks, records: &[AuditRecord]) {
+    if records.is_empty() {
+        return;
+    }
+    if let Some(path) = &sinks.log_path {
+        if let Err(error) = append_audit_log(path, records) {
+            eprintln!("failed to append audit log {}: {}", path.display(), error);
+        }
+    }
+    if let Some(url) = &sinks.webhook_url {
+        let client = match reqwest::Client::builder()
+            .timeout(AUDIT_WEBHOOK_TIMEOUT)
+            .build()
+        {
+            Ok(client) => client,
+            Err(error) => {
+                eprintln!("failed to build audit webhook client: {}", error);
+                return;
+            }
+        };
+        match client.post(url).json(records).send().await {
+            Ok(response) if response.status().is_success() => (),
+            Ok(response) => eprintln!("audit webhook returned {}", response.status()),
+            Err(error) => eprintln!("failed to post audit records: {}", error),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_fixtures::*;
+
+    #[test]
+    fn test_recording_random_source_keeps_draws() {
+        let rng = RecordingRandomSource::new(&OsRandomSource);
+
+        let value = rng.generate(10, 20).unwrap();
+        let mut bytes = [0u8; 4];
+        rng.fill_bytes(&mut bytes).unwrap();
+
+        assert_eq!(rng.draws(), (vec![value as u64], vec![hex::encode(bytes)]));
+    }
+
+    #[test]
+    fn test_append_audit_log_writes_json_lines() {
+        let params = test_params();
+        let rng = RecordingRandomSource::new(&OsRandomSource);
+        rng.generate(0, 100).unwrap();
+        let settle_ixn = Instruction {
+            program_id: params.program_id,
+            data: vec![1, 2, 3],
+            accounts: vec![],
+        };
+        let outcome = OutcomeSummary::new(&params, &test_runner_accounts(), None, &settle_ixn);
+        let record = AuditRecord::new(&params, &rng, &outcome);
+
+        let dir = test_storage_dir("audit");
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("audit.jsonl");
+        append_audit_log(&path, std::slice::from_ref(&record)).unwrap();
+        append_audit_log(&path, &[record.clone(), record]).unwrap();
+
+        let contents = std::fs::read_to_string(&path).unwrap();
+        let lines: Vec<serde_json::Value> = contents
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[0]["outcome_hash"], outcome.outcome_hash);
+        assert_eq!(lines[0]["random_values"].as_array().unwrap().len(), 1);
+        assert!(ContainerParams::decode(lines[0]["params"].as_str().unwrap().as_bytes()).is_ok());
+    }
+
+    #[test]
+    fn test_audit_sinks_are_opt_in() {
+        assert!(!AuditSinks::default().is_enabled());
+        assert!(AuditSinks {
+            log_path: Some("audit.jsonl".into()),
+            webhook_url: None,
+        }
+        .is_enabled());
+    }
+}
@@ -0,0 +1,732 @@
+use crate::*;
+use std::sync::Arc;
+use tokio::task::JoinSet;
+
+/// Request account reads in flight at once when settling a batch.
+pub const DEFAULT_BATCH_PARALLELISM: usize = 4;
+
+/// Requests a routine run settles, from the comma separated `REQUEST_KEYS`
+/// env var. Only read when the runner was not started for a request.
+pub fn request_keys_from_env() -> Vec<Pubkey> {
+    std::env::var("REQUEST_KEYS")
+        .unwrap_or_default()
+        .split(',')
+        .filter(|entry| !entry.trim().is_empty())
+        .filter_map(|entry| match Pubkey::from_str(entry.trim()) {
+            Ok(request) => Some(request),
+            Err(_) => {
+                println!("ignoring invalid REQUEST_KEYS entry {}", entry);
+                None
+            }
+        })
+        .collect()
+}
+
+/// `BATCH_PARALLELISM`, falling back to `DEFAULT_BATCH_PARALLELISM`.
+pub fn batch_parallelism_from_env() -> usize {
+    setting("BATCH_PARALLELISM")
+        .and_then(|value| value.parse::<usize>().ok())
+        .filter(|parallelism| *parallelism > 0)
+        .unwrap_or(DEFAULT_BATCH_PARALLELISM)
+}
+
+/// A request of the batch waiting to settle.
+#[derive(Clone, Debug)]
+pub struct PendingRequest {
+    pub params: ContainerParams,
+    /// The slot the request was published in, signed with its result.
+    pub request_slot: u64,
+}
+
+/// Reads a request account and decodes its params, refusing requests made
+/// for another function, that expired, that are outside their slot window or
+/// whose program is not allowed.
+pub fn load_request_params<F: AccountFetcher + ?Sized>(
+    fetcher: &F,
+    function: &Pubkey,
+    expiry: Option<RequestExpiry>,
+    program_allowlist: &[Pubkey],
+    request: &Pubkey,
+) -> std::result::Result<PendingRequest, FunctionError> {
+    let data = fetcher
+        .fetch_multiple_account_data(&[*request])?
+        .pop()
+        .flatten()
+        .ok_or(FunctionError::AccountFetchFailed)?;
+    let request_data = FunctionRequestAccountData::try_deserialize(&mut data.as_slice())
+        .map_err(|_| FunctionError::AccountDecodeFailed)?;
+    if request_data.function != *function {
+        return Err(FunctionError::InvalidParams);
+    }
+    check_request_pending(&request_data.active_request)?;
+    if let Some(expiry) = expiry {
+        expiry.check(request_data.active_request.request_slot)?;
+    }
+    let params =
+        ContainerParams::decode_for_programs(&request_data.container_params, program_allowlist)?;
+    check_slot_window(&params, expiry.map(|expiry| expiry.current_slot))?;
+    Ok(PendingRequest {
+        params,
+        request_slot: request_data.active_request.request_slot,
+    })
+}
+
+/// Every pending request, in the order of `requests`, loaded on the
+/// blocking pool with at most `parallelism` reads in flight.
+pub async fn fetch_batch_params<F>(
+    fetcher: Arc<F>,
+    function: Pubkey,
+    expiry: Option<RequestExpiry>,
+    program_allowlist: &[Pubkey],
+    requests: &[Pubkey],
+    parallelism: usize,
+) -> Vec<(Pubkey, std::result::Result<PendingRequest, FunctionError>)>
+where
+    F: AccountFetcher + Send + Sync + ?Sized + 'static,
+{
+    type Loaded = (usize, std::result::Result<PendingRequest, FunctionError>);
+
+    let mut loaded: Vec<Option<std::result::Result<PendingRequest, FunctionError>>> =
+        requests.iter().map(|_| None).collect();
+    let mut store = |joined: std::result::Result<Loaded, tokio::task::JoinError>| match joined {
+        Ok((index, params)) => loaded[index] = Some(params),
+        // the slot stays empty and is reported as an internal error below
+        Err(error) => println!("request params task failed: {}", error),
+    };
+
+    let program_allowlist: Arc<[Pubkey]> = program_allowlist.into();
+    let mut tasks: JoinSet<Loaded> = JoinSet::new();
+    for (index, request) in requests.iter().copied().enumerate() {
+        if tasks.len() >= parallelism.max(1) {
+            if let Some(joined) = tasks.join_next().await {
+                store(joined);
+            }
+        }
+        let fetcher = fetcher.clone();
+        let program_allowlist = program_allowlist.clone();
+        tasks.spawn_blocking(move || {
+            (
+                index,
+                load_request_params(
+                    fetcher.as_ref(),
+                    &function,
+                    expiry,
+                    &program_allowlist,
+                    &request,
+                ),
+            )
+        });
+    }
+    while let Some(joined) = tasks.join_next().await {
+        store(joined);
+    }
+
+    requests
+        .iter()
+        .copied()
+        .zip(loaded)
+        .map(|(request, params)| (request, params.unwrap_or(Err(FunctionError::Internal))))
+        .collect()
+}
+
+type LoadedRequest = (Pubkey, std::result::Result<PendingRequest, FunctionError>);
+
+/// A player who queues again before their earlier request settled leaves
+/// several matchmaking requests for one spaceship in a sweep. Once one of
+/// them settles every other settle instruction for the spaceship fails, and
+/// the whole batch transaction with it, so only the newest is kept: the
+/// latest request slot, then the last in `requests`. Returns the kept
+/// requests in order and the superseded ones.
+pub fn coalesce_requests(
+    requests: Vec<LoadedRequest>,
+) -> (Vec<LoadedRequest>, Vec<(Pubkey, PendingRequest)>) {
+    let mut newest: std::collections::HashMap<(Pubkey, Pubkey), (u64, usize)> =
+        std::collections::HashMap::new();
+    for (index, (_, pending)) in requests.iter().enumerate() {
+        let Some((spaceship, request_slot)) = pending.as_ref().ok().and_then(|pending| {
+            queued_spaceship(pending).map(|spaceship| (spaceship, pending.request_slot))
+        }) else {
+            continue;
+        };
+        let entry = newest.entry(spaceship).or_insert((request_slot, index));
+        if request_slot >= entry.0 {
+            *entry = (request_slot, index);
+        }
+    }
+
+    let mut kept = vec![];
+    let mut superseded = vec![];
+    for (index, (request, pending)) in requests.into_iter().enumerate() {
+        match pending {
+            Ok(pending)
+                if queued_spaceship(&pending)
+                    .is_some_and(|spaceship| newest[&spaceship].1 != index) =>
+            {
+                println!(
+                    "request {} is superseded for {}",
+                    request, pending.params.spaceship_pda
+                );
+                superseded.push((request, pending));
+            }
+            pending => kept.push((request, pending)),
+        }
+    }
+    (kept, superseded)
+}
+
+/// The program and spaceship a matchmaking request queues.
+fn queued_spaceship(pending: &PendingRequest) -> Option<(Pubkey, Pubkey)> {
+    (pending.params.request_type == RequestType::Matchmaking)
+        .then_some((pending.params.program_id, pending.params.spaceship_pda))
+}
+
+/// The settlements of a batch packed into one transaction.
+pub struct BatchSettlement {
+    pub ixs: Vec<Instruction>,
+    pub outcomes: Vec<OutcomeSummary>,
+    pub pool_diversity: Vec<PoolDiversity>,
+    pub audit: Vec<AuditRecord>,
+    /// Requests left for the next run: settlements and reports that did not
+    /// fit, and the newest request of a spaceship with older ones to close.
+    pub deferred: Vec<Pubkey>,
+    /// Requests closed with a `Superseded` failure report.
+    pub superseded: Vec<Pubkey>,
+}
+
+/// Settles every request of the batch that passes its checks, then packs as
+/// many settlements as fit in the transaction, in request order. Requests
+/// that fail are logged and left for their own run, the call only fails
+/// when none settled. `runner_accounts.function_request` and `request_slot`
+/// are replaced by each request's.
+///
+/// Superseded requests are closed with a failure report, which refunds the
+/// fee without touching the spaceship, ahead of any settlement. The newest
+/// request of their spaceship waits for a sweep where they are all closed,
+/// since it no longer supersedes them once it settled and they would be left
+/// pending for good.
+pub fn build_batch_settlement<F: AccountFetcher + ?Sized>(
+    requests: Vec<LoadedRequest>,
+    runner_accounts: &RunnerAccounts,
+    payer: &Pubkey,
+    fetcher: &F,
+    rng: &dyn RandomSource,
+    program_allowlist: &[Pubkey],
+    budget: &mut TierBudget,
+) -> std::result::Result<BatchSettlement, FunctionError> {
+    let (requests, superseded) = coalesce_requests(requests);
+    let closing: std::collections::HashSet<(Pubkey, Pubkey)> = superseded
+        .iter()
+        .filter_map(|(_, pending)| queued_spaceship(pending))
+        .collect();
+    let mut reports: Vec<(Pubkey, Instruction)> = vec![];
+    let mut deferred: Vec<Pubkey> = vec![];
+    for (request, pending) in superseded {
+        record_counter("batch_request_total", &[("result", "superseded")]);
+        let runner_accounts = RunnerAccounts {
+            function_request: request,
+            request_slot: pending.request_slot,
+            ..*runner_accounts
+        };
+        match failure_report_ixn(&pending.params, &runner_accounts, FunctionError::Superseded) {
+            Ok(Some(report)) => reports.push((request, report)),
+            Ok(None) => deferred.push(request),
+            Err(error) => {
+                println!("no failure report for request {}: {}", request, error);
+                deferred.push(request);
+            }
+        }
+    }
+
+    let mut first_error = None;
+    let mut settled: Vec<(Pubkey, Settlement)> = vec![];
+    for (request, pending) in requests {
+        if let Some(spaceship) = pending
+            .as_ref()
+            .ok()
+            .and_then(queued_spaceship)
+            .filter(|spaceship| closing.contains(spaceship))
+        {
+            println!(
+                "deferring request {} until the older ones for {} are closed",
+                request, spaceship.1
+            );
+            record_counter("batch_request_total", &[("result", "deferred")]);
+            deferred.push(request);
+            continue;
+        }
+        let settlement = pending.and_then(
+            |PendingRequest {
+                 params,
+                 request_slot,
+             }| {
+                let runner_accounts = RunnerAccounts {
+                    function_request: request,
+                    request_slot,
+                    ..*runner_accounts
+                };
+                params.report_deprecated_keys();
+                precheck(&params, program_allowlist)?;
+                build_settlement(&params, &runner_accounts, payer, fetcher, rng, None, budget)
+            },
+        );
+        match settlement {
+            Ok(settlement) => settled.push((request, settlement)),
+            Err(error) => {
+                println!("skipping request {}: {}", request, error);
+                record_error(error);
+                record_counter("batch_request_total", &[("result", "failed")]);
+                first_error.get_or_insert(error);
+            }
+        }
+    }
+    if settled.is_empty() && reports.is_empty() {
+        return Err(first_error.unwrap_or(FunctionError::MissingRequestData));
+    }
+
+    // each settlement carries its own compute budget, one for the whole
+    // transaction is kept and dropped last, sized for the most demanding
+    // request since they all share it
+    let cu_limit = settled
+        .iter()
+        .map(|(_, settlement)| settlement.cu_limit)
+        .max()
+        .unwrap_or(DEFAULT_CU_LIMIT);
+    let cu_price = settled
+        .iter()
+        .filter_map(|(_, settlement)| settlement.cu_price)
+        .max();
+    let mut planned: Vec<PlannedIxn> = compute_budget_ixns(cu_limit, cu_price)
+        .into_iter()
+        .map(PlannedIxn::optional)
+        .collect();
+    // the reports come first so they are the last to be dropped, one must go
+    // out for the sweep to make progress, the others and the settlements fill
+    // the space left and the rest wait for the next sweep
+    for (index, (_, report)) in reports.iter().enumerate() {
+        planned.push(match index {
+            0 => PlannedIxn::required(report.clone()),
+            _ => PlannedIxn::optional(report.clone()),
+        });
+    }
+    for (index, (_, settlement)) in settled.iter().enumerate() {
+        planned.extend(
+            settlement
+                .ixs
+                .iter()
+                .filter(|ixn| ixn.program_id != solana_sdk::compute_budget::id())
+                .map(|ixn| match index == 0 && reports.is_empty() {
+                    true => PlannedIxn::required(ixn.clone()),
+                    false => PlannedIxn::optional(ixn.clone()),
+                }),
+        );
+    }
+    let ixs = fit_ixns(
+        planned,
+        payer,
+        &MessageEncoding::legacy(),
+        MAX_IXNS_MESSAGE_SIZE,
+    )?;
+
+    let mut batch = BatchSettlement {
+        ixs,
+        outcomes: vec![],
+        pool_diversity: vec![],
+        audit: vec![],
+        deferred,
+        superseded: vec![],
+    };
+    let fitted = |ixs: &[Instruction], request: &Pubkey| {
+        ixs.iter().any(|ixn| {
+            ixn.accounts
+                .iter()
+                .any(|account| account.pubkey == *request)
+        })
+    };
+    for (request, _) in reports {
+        match fitted(&batch.ixs, &request) {
+            true => batch.superseded.push(request),
+            false => batch.deferred.push(request),
+        }
+    }
+    for (request, settlement) in settled {
+        if fitted(&batch.ixs, &request) {
+            record_counter("batch_request_total", &[("result", "settled")]);
+            batch.outcomes.push(settlement.outcome);
+            batch.pool_diversity.extend(settlement.pool_diversity);
+            batch.audit.push(settlement.audit);
+        } else {
+            println!("deferring request {}, the transaction is full", request);
+            record_counter("batch_request_total", &[("result", "deferred")]);
+            batch.deferred.push(request);
+        }
+    }
+    Ok(batch)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_fixtures::*;
+    use solana_sdk::compute_budget::ComputeBudgetInstruction;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    fn request_account(function: &Pubkey, container_params: &[u8]) -> Vec<u8> {
+        let mut request_data = FunctionRequestAccountData {
+            function: *function,
+            container_params: container_params.to_vec(),
+            ..Default::default()
+        };
+        request_data.active_request.request_slot = 1_000;
+        let mut data = vec![];
+        request_data.try_serialize(&mut data).unwrap();
+        data
+    }
+
+    fn pending(params: &str) -> std::result::Result<PendingRequest, FunctionError> {
+        Ok(PendingRequest {
+            params: ContainerParams::decode(params.as_bytes())?,
+            request_slot: 1_000,
+        })
+    }
+
+    fn loot_open_params_string() -> String {
+        format!(
+            "REQUEST_TYPE=LOOT_OPEN,PID={},USER={},REALM_PDA={},USER_ACCOUNT_PDA={}",
+            anchor_spl::token::ID,
+            Pubkey::new_unique(),
+            anchor_spl::token::ID,
+            Pubkey::new_unique(),
+        )
+    }
+
+    #[test]
+    fn test_load_request_params() {
+        let function = Pubkey::new_unique();
+        let (request, foreign, garbage) = (
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+        );
+        let mut fetcher = MockFetcher::default();
+        fetcher.insert(
+            request,
+            request_account(&function, test_params_string().as_bytes()),
+        );
+        fetcher.insert(
+            foreign,
+            request_account(&Pubkey::new_unique(), test_params_string().as_bytes()),
+        );
+        fetcher.insert(garbage, vec![1, 2, 3]);
+
+        assert_eq!(
+            load_request_params(&fetcher, &function, None, &[], &request)
+                .unwrap()
+                .request_slot,
+            1_000
+        );
+        assert_eq!(
+            load_request_params(&fetcher, &function, None, &[], &foreign).err(),
+            Some(FunctionError::InvalidParams)
+        );
+        assert_eq!(
+            load_request_params(&fetcher, &function, None, &[], &garbage).err(),
+            Some(FunctionError::AccountDecodeFailed)
+        );
+        assert_eq!(
+            load_request_params(&fetcher, &function, None, &[], &Pubkey::new_unique()).err(),
+            Some(FunctionError::AccountFetchFailed)
+        );
+
+        let expiry = |current_slot| {
+            Some(RequestExpiry {
+                current_slot,
+                max_age_slots: DEFAULT_MAX_REQUEST_AGE_SLOTS,
+            })
+        };
+        assert!(load_request_params(&fetcher, &function, expiry(1_200), &[], &request).is_ok());
+        assert_eq!(
+            load_request_params(&fetcher, &function, expiry(2_000), &[], &request).err(),
+            Some(FunctionError::RequestExpired)
+        );
+
+        let windowed = Pubkey::new_unique();
+        fetcher.insert(
+            windowed,
+            request_account(
+                &function,
+                format!("{},VALID_BEFORE_SLOT=1100", test_params_string()).as_bytes(),
+            ),
+        );
+        assert_eq!(
+            load_request_params(&fetcher, &function, expiry(1_200), &[], &windowed).err(),
+            Some(FunctionError::OutsideSlotWindow)
+        );
+        assert!(load_request_params(&fetcher, &function, expiry(1_050), &[], &windowed).is_ok());
+
+        let settled = Pubkey::new_unique();
+        let mut request_data = FunctionRequestAccountData {
+            function,
+            container_params: test_params_string().into_bytes(),
+            ..Default::default()
+        };
+        request_data.active_request.status = RequestStatus::RequestSuccess;
+        let mut data = vec![];
+        request_data.try_serialize(&mut data).unwrap();
+        fetcher.insert(settled, data);
+        assert_eq!(
+            load_request_params(&fetcher, &function, None, &[], &settled).err(),
+            Some(FunctionError::AlreadySettled)
+        );
+
+        let staging = [Pubkey::new_unique()];
+        assert_eq!(
+            load_request_params(&fetcher, &function, None, &staging, &request).err(),
+            Some(FunctionError::ProgramNotAllowed)
+        );
+    }
+
+    /// Counts the reads in flight, each read takes a little while.
+    struct SlowFetcher {
+        inner: MockFetcher,
+        in_flight: AtomicUsize,
+        max_in_flight: AtomicUsize,
+    }
+
+    impl AccountFetcher for SlowFetcher {
+        fn fetch_multiple_account_data(
+            &self,
+            pubkeys: &[Pubkey],
+        ) -> std::result::Result<Vec<Option<Vec<u8>>>, FunctionError> {
+            let in_flight = self.in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+            self.max_in_flight.fetch_max(in_flight, Ordering::SeqCst);
+            std::thread::sleep(std::time::Duration::from_millis(20));
+            self.in_flight.fetch_sub(1, Ordering::SeqCst);
+            self.inner.fetch_multiple_account_data(pubkeys)
+        }
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_fetch_batch_params_is_bounded_and_ordered() {
+        let function = Pubkey::new_unique();
+        let requests: Vec<Pubkey> = (0..10).map(|_| Pubkey::new_unique()).collect();
+        let mut inner = MockFetcher::default();
+        for request in requests.iter().skip(1) {
+            inner.insert(
+                *request,
+                request_account(&function, test_params_string().as_bytes()),
+            );
+        }
+        let fetcher = Arc::new(SlowFetcher {
+            inner,
+            in_flight: AtomicUsize::new(0),
+            max_in_flight: AtomicUsize::new(0),
+        });
+
+        let loaded = fetch_batch_params(fetcher.clone(), function, None, &[], &requests, 3).await;
+
+        assert_eq!(
+            loaded
+                .iter()
+                .map(|(request, _)| *request)
+                .collect::<Vec<_>>(),
+            requests
+        );
+        assert_eq!(
+            loaded[0].1.as_ref().err(),
+            Some(&FunctionError::AccountFetchFailed)
+        );
+        assert!(loaded[1..].iter().all(|(_, params)| params.is_ok()));
+        let max_in_flight = fetcher.max_in_flight.load(Ordering::SeqCst);
+        assert!((2..=3).contains(&max_in_flight), "{}", max_in_flight);
+    }
+
+    #[test]
+    fn test_build_batch_settlement_packs_and_defers() {
+        let runner_accounts = test_runner_accounts();
+        let requests: Vec<(Pubkey, std::result::Result<PendingRequest, FunctionError>)> = (0..6)
+            .map(|_| (Pubkey::new_unique(), pending(&loot_open_params_string())))
+            .chain([(Pubkey::new_unique(), Err(FunctionError::InvalidParams))])
+            .collect();
+        let keys: Vec<Pubkey> = requests.iter().map(|(request, _)| *request).collect();
+
+        let batch = build_batch_settlement(
+            requests,
+            &runner_accounts,
+            &runner_accounts.enclave_signer,
+            &MockFetcher::default(),
+            &OsRandomSource,
+            &[],
+            &mut test_budget(ExecutionTier::Standard),
+        )
+        .unwrap();
+
+        // a few loot settlements share one transaction, the rest wait
+        let settled = batch.outcomes.len();
+        assert!(settled >= 2, "{} settled", settled);
+        assert_eq!(settled + batch.deferred.len(), 6);
+        assert_eq!(batch.deferred, keys[settled..6]);
+        for (outcome, request) in batch.outcomes.iter().zip(&keys) {
+            assert_eq!(outcome.request, request.to_string());
+        }
+        assert!(message_size(&batch.ixs, &runner_accounts.enclave_signer) <= MAX_IXNS_MESSAGE_SIZE);
+    }
+
+    fn pending_at(params: &ContainerParams, request_slot: u64) -> LoadedRequest {
+        (
+            Pubkey::new_unique(),
+            Ok(PendingRequest {
+                params: params.clone(),
+                request_slot,
+            }),
+        )
+    }
+
+    #[test]
+    fn test_coalesce_requests_keeps_the_newest_per_spaceship() {
+        let params = test_params();
+        let mut other_spaceship = params.clone();
+        other_spaceship.spaceship_pda = Pubkey::new_unique();
+        let loot = pending(&loot_open_params_string()).unwrap();
+        let requests = vec![
+            pending_at(&params, 1_000),
+            pending_at(&other_spaceship, 900),
+            pending_at(&params, 1_200),
+            (
+                Pubkey::new_unique(),
+                Err(FunctionError::AccountDecodeFailed),
+            ),
+            pending_at(&params, 1_200),
+            pending_at(&params, 1_100),
+            (Pubkey::new_unique(), Ok(loot.clone())),
+            (Pubkey::new_unique(), Ok(loot)),
+        ];
+        let keys: Vec<Pubkey> = requests.iter().map(|(request, _)| *request).collect();
+
+        let (kept, superseded) = coalesce_requests(requests);
+
+        // the later of two requests in the same slot wins
+        assert_eq!(
+            kept.iter().map(|(request, _)| *request).collect::<Vec<_>>(),
+            vec![keys[1], keys[3], keys[4], keys[6], keys[7]]
+        );
+        assert_eq!(
+            superseded
+                .iter()
+                .map(|(request, _)| *request)
+                .collect::<Vec<_>>(),
+            vec![keys[0], keys[2], keys[5]]
+        );
+    }
+
+    #[test]
+    fn test_superseded_requests_get_a_failure_report() {
+        let params = test_params();
+        let fetcher = test_fetcher(&params, &test_realm(vec![]), [0; 6].map(test_spaceship));
+        let runner_accounts = test_runner_accounts();
+        let requests = vec![
+            pending_at(&params, 1_000),
+            pending_at(&params, 1_001),
+            (Pubkey::new_unique(), pending(&loot_open_params_string())),
+        ];
+        let keys: Vec<Pubkey> = requests.iter().map(|(request, _)| *request).collect();
+        let settle = |requests| {
+            build_batch_settlement(
+                requests,
+                &runner_accounts,
+                &runner_accounts.enclave_signer,
+                &fetcher,
+                &OsRandomSource,
+                &[],
+                &mut test_budget(ExecutionTier::Standard),
+            )
+            .unwrap()
+        };
+
+        // the report goes out first, the newest request waits for it while
+        // the other spaceship's request settles in the space left
+        let batch = settle(requests.clone());
+        assert_eq!(batch.superseded, vec![keys[0]]);
+        assert_eq!(batch.deferred, vec![keys[1]]);
+        assert_eq!(batch.outcomes.len(), 1);
+        assert_eq!(batch.outcomes[0].request, keys[2].to_string());
+        let report = batch
+            .ixs
+            .iter()
+            .find(|ixn| ixn.program_id == params.program_id)
+            .unwrap();
+        assert_eq!(
+            report.data[..8],
+            get_ixn_discriminator("arena_matchmaking_report_failure")
+        );
+        assert_eq!(report.data[43], FunctionError::Superseded.code());
+        assert!(report
+            .accounts
+            .iter()
+            .any(|account| account.pubkey == keys[0]));
+
+        // once closed the older request no longer loads and the newest
+        // settles on the next sweep
+        let mut requests = requests[..2].to_vec();
+        requests[0].1 = Err(FunctionError::AlreadySettled);
+        let batch = settle(requests);
+        assert_eq!(batch.outcomes.len(), 1);
+        assert_eq!(batch.outcomes[0].request, keys[1].to_string());
+        assert!(batch.superseded.is_empty());
+        assert!(batch.deferred.is_empty());
+    }
+
+    #[test]
+    fn test_batch_compute_budget_covers_every_request() {
+        let runner_accounts = test_runner_accounts();
+        let requests = ["CU_LIMIT=300000,CU_PRICE=7", "CU_LIMIT=900000"]
+            .iter()
+            .map(|compute_budget| {
+                (
+                    Pubkey::new_unique(),
+                    pending(&format!("{},{}", loot_open_params_string(), compute_budget)),
+                )
+            })
+            .collect();
+
+        let batch = build_batch_settlement(
+            requests,
+            &runner_accounts,
+            &runner_accounts.enclave_signer,
+            &MockFetcher::default(),
+            &OsRandomSource,
+            &[],
+            &mut test_budget(ExecutionTier::Standard),
+        )
+        .unwrap();
+
+        assert_eq!(batch.outcomes.len(), 2);
+        assert_eq!(
+            ComputeBudgetInstruction::try_from_slice(&batch.ixs[0].data).unwrap(),
+            ComputeBudgetInstruction::SetComputeUnitLimit(900_000)
+        );
+        assert_eq!(
+            ComputeBudgetInstruction::try_from_slice(&batch.ixs[1].data).unwrap(),
+            ComputeBudgetInstruction::SetComputeUnitPrice(7)
+        );
+    }
+
+    #[test]
+    fn test_build_batch_settlement_fails_when_nothing_settles() {
+        let runner_accounts = test_runner_accounts();
+
+        let result = build_batch_settlement(
+            vec![(
+                Pubkey::new_unique(),
+                Err(FunctionError::AccountDecodeFailed),
+            )],
+            &runner_accounts,
+            &runner_accounts.enclave_signer,
+            &MockFetcher::default(),
+            &OsRandomSource,
+            &[],
+            &mut test_budget(ExecutionTier::Standard),
+        );
+
+        assert_eq!(result.err(), Some(FunctionError::AccountDecodeFailed));
+    }
+}
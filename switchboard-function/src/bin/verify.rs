@@ -0,0 +1,153 @@
+use arena_matchmaking_params::*;
+use base64::Engine;
+use std::str::FromStr;
+use switchboard_solana::prelude::solana_client::rpc_client::RpcClient;
+use switchboard_solana::prelude::solana_client::rpc_request::RpcRequest;
+use switchboard_solana::prelude::solana_sdk::signature::Signature;
+use switchboard_solana::prelude::solana_sdk::transaction::VersionedTransaction;
+
+// Reads the matchmaking settlements of a settled transaction back from chain:
+//
+//   verify <signature> [--request-slot <slot>]
+//
+// against `RPC_URL`, mainnet-beta by default. With the request's slot the
+// result attestation is checked too, by the key the instruction names.
+
+const USAGE: &str = "usage: verify <signature> [--request-slot <slot>]";
+
+struct Args {
+    signature: Signature,
+    request_slot: Option<u64>,
+}
+
+fn parse_args(mut args: impl Iterator<Item = String>) -> Option<Args> {
+    let signature = Signature::from_str(&args.next()?).ok()?;
+    let mut parsed = Args {
+        signature,
+        request_slot: None,
+    };
+    while let Some(flag) = args.next() {
+        let value = args.next()?;
+        match flag.as_str() {
+            "--request-slot" => parsed.request_slot = Some(value.parse().ok()?),
+            _ => return None,
+        }
+    }
+    Some(parsed)
+}
+
+/// The transaction and its account keys, those loaded from lookup tables
+/// following the static ones.
+fn fetch_transaction(
+    client: &RpcClient,
+    signature: &Signature,
+) -> Result<(VersionedTransaction, Vec<Pubkey>), String> {
+    let response: serde_json::Value = client
+        .send(
+            RpcRequest::GetTransaction,
+            serde_json::json!([
+                signature.to_string(),
+                {"encoding": "base64", "maxSupportedTransactionVersion": 0, "commitment": "confirmed"}
+            ]),
+        )
+        .map_err(|error| error.to_string())?;
+    if response.is_null() {
+        return Err("transaction not found".to_string());
+    }
+    let encoded = response["transaction"][0]
+        .as_str()
+        .ok_or("transaction is not base64 encoded")?;
+    let bytes = base64::engine::general_purpose::STANDARD
+        .decode(encoded)
+        .map_err(|error| error.to_string())?;
+    let tx: VersionedTransaction =
+        bincode::deserialize(&bytes).map_err(|error| error.to_string())?;
+
+    let mut keys = tx.message.static_account_keys().to_vec();
+    for loaded in ["writable", "readonly"] {
+        if let Some(addresses) = response["meta"]["loadedAddresses"][loaded].as_array() {
+            for address in addresses {
+                let address = address.as_str().and_then(|a| Pubkey::from_str(a).ok());
+                keys.push(address.ok_or("invalid loaded address")?);
+            }
+        }
+    }
+    Ok((tx, keys))
+}
+
+fn settled_matches(tx: &VersionedTransaction, keys: &[Pubkey]) -> Vec<SettledMatch> {
+    tx.message
+        .instructions()
+        .iter()
+        .filter_map(|ixn| {
+            let accounts: Option<Vec<Pubkey>> = ixn
+                .accounts
+                .iter()
+                .map(|index| keys.get(*index as usize).copied())
+                .collect();
+            SettledMatch::decode(&ixn.data, &accounts?)
+        })
+        .collect()
+}
+
+fn main() {
+    let Some(args) = parse_args(std::env::args().skip(1)) else {
+        eprintln!("{}", USAGE);
+        std::process::exit(2);
+    };
+    let rpc_url = std::env::var("RPC_URL")
+        .unwrap_or_else(|_| "https://api.mainnet-beta.solana.com".to_string());
+    let client = RpcClient::new(rpc_url);
+
+    let (tx, keys) = match fetch_transaction(&client, &args.signature) {
+        Ok(fetched) => fetched,
+        Err(error) => {
+            eprintln!("failed to fetch {}: {}", args.signature, error);
+            std::process::exit(1);
+        }
+    };
+    let settled = settled_matches(&tx, &keys);
+    if settled.is_empty() {
+        eprintln!(
+            "{} holds no version {} matchmaking settlement",
+            args.signature, ARGS_VERSION
+        );
+        std::process::exit(1);
+    }
+
+    let mut valid = true;
+    for settled in &settled {
+        println!("request:        {}", settled.request);
+        println!("enclave signer: {}", settled.enclave_signer);
+        println!(
+            "roll:           {} (drawn {})",
+            settled.random_result, settled.raw_result
+        );
+        println!("faction:        {}", settled.faction);
+        println!(
+            "opponent:       {} (slot {})",
+            settled.opponent, settled.opponent_index
+        );
+        println!(
+            "tier:           {}{}",
+            settled.execution_tier,
+            if settled.entropy_fallback {
+                ", settled with the OS RNG fallback"
+            } else {
+                ""
+            }
+        );
+        if let Some(request_slot) = args.request_slot {
+            let attested = settled.verify_attestation(request_slot);
+            println!(
+                "attestation:    {} by {}",
+                if attested { "valid" } else { "INVALID" },
+                settled.attestation_signer
+            );
+            valid &= attested;
+        }
+    }
+    if !valid {
+        std::process::exit(1);
+    }
+}
@@ -0,0 +1,98 @@
+use crate::*;
+
+/// Spread of a bot's rating around the requester's, so bot fights are not
+/// all even.
+pub const BOT_RATING_STD_DEV: f64 = 50.0;
+
+/// An opponent rolled inside the enclave for requests without candidates.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BotOpponent {
+    pub faction: u8,
+    pub rating: u32,
+    /// Expanded by the program into the bot's loadout, so the function does
+    /// not need to know the module set.
+    pub stats_seed: u64,
+}
+
+/// Reads the requester's spaceship, the only account a bot match needs.
+pub fn load_requester_spaceship<F: AccountFetcher + ?Sized>(
+    fetcher: &F,
+    params: &ContainerParams,
+) -> std::result::Result<Spaceship, FunctionError> {
+    let data = fetcher
+        .fetch_multiple_account_data(std::slice::from_ref(&params.spaceship_pda))?
+        .pop()
+        .flatten()
+        .ok_or(FunctionError::AccountFetchFailed)?;
+    Spaceship::decode(&data)
+}
+
+/// Rolls a bot rated around the requester. With `exclude_same_faction` the
+/// bot is drawn among the other factions, like a real opponent would be.
+pub fn roll_bot_opponent(
+    requester: &Spaceship,
+    faction: u8,
+    exclude_same_faction: bool,
+    rng: &dyn RandomSource,
+) -> std::result::Result<BotOpponent, FunctionError> {
+    let faction = if exclude_same_faction {
+        // skip over the requester's faction
+        let other = rng.generate(0, (FACTION_COUNT - 2) as u32)? as u8;
+        if other >= faction {
+            other + 1
+        } else {
+            other
+        }
+    } else {
+        rng.generate(0, (FACTION_COUNT - 1) as u32)? as u8
+    };
+    let rating = normal(
+        rng,
+        requester.rating as f64,
+        BOT_RATING_STD_DEV,
+        0,
+        u32::MAX as u64,
+    )? as u32;
+
+    Ok(BotOpponent {
+        faction,
+        rating,
+        stats_seed: rng.generate_u64(0, u64::MAX)?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_fixtures::*;
+
+    #[test]
+    fn test_roll_bot_opponent_excludes_same_faction() {
+        for faction in 0..FACTION_COUNT {
+            for _ in 0..50 {
+                let bot = roll_bot_opponent(&test_spaceship(1_200), faction, true, &OsRandomSource)
+                    .unwrap();
+
+                assert_ne!(bot.faction, faction);
+                assert!(bot.faction < FACTION_COUNT);
+            }
+        }
+    }
+
+    #[test]
+    fn test_roll_bot_opponent_rating_near_requester() {
+        let ratings: Vec<u32> = (0..500)
+            .map(|_| {
+                roll_bot_opponent(&test_spaceship(1_200), 0, false, &OsRandomSource)
+                    .unwrap()
+                    .rating
+            })
+            .collect();
+
+        let mean = ratings.iter().map(|r| *r as f64).sum::<f64>() / ratings.len() as f64;
+        assert!((mean - 1_200.0).abs() < 15.0, "mean {}", mean);
+        // a requester at the bottom of the ladder never gets a negative bot
+        let bot = roll_bot_opponent(&test_spaceship(0), 0, false, &OsRandomSource).unwrap();
+        assert!(bot.rating < 1_000);
+    }
+}
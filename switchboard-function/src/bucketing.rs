@@ -0,0 +1,118 @@
+use crate::*;
+
+// A matchmaking experiment (`EXPERIMENT_ID`) splits requesters between the
+// current selection and a candidate replacement. The split hashes the user
+// with the experiment id, so a player stays in one variant for the whole
+// experiment while different experiments bucket independently, and the
+// program or an indexer can recompute any player's variant.
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[repr(u8)]
+pub enum Variant {
+    /// Selection as without an experiment, and every request outside one.
+    #[default]
+    Control = 0,
+    /// Power weighted selection, with `DEFAULT_POWER_WEIGHTS` unless the
+    /// request set its own.
+    Treatment = 1,
+}
+
+/// Where `user` falls in `experiment_id`, in basis points.
+pub fn experiment_bucket(experiment_id: u32, user: &Pubkey) -> u16 {
+    let hash =
+        solana_program::hash::hashv(&[b"experiment", &experiment_id.to_le_bytes(), user.as_ref()]);
+    (u64::from_le_bytes(hash.to_bytes()[..8].try_into().unwrap()) % 10_000) as u16
+}
+
+/// The variant the request's user is bucketed into, the lowest
+/// `experiment_share_bps` buckets get the treatment.
+pub fn experiment_variant(params: &ContainerParams) -> Variant {
+    if params.experiment_id == 0 {
+        return Variant::Control;
+    }
+    if experiment_bucket(params.experiment_id, &params.user) < params.experiment_share_bps {
+        Variant::Treatment
+    } else {
+        Variant::Control
+    }
+}
+
+/// The power weights opponent selection uses for `variant`.
+pub fn variant_power_weights(
+    variant: Variant,
+    power_weights: Option<&PowerWeights>,
+) -> Option<&PowerWeights> {
+    match variant {
+        Variant::Control => power_weights,
+        Variant::Treatment => Some(power_weights.unwrap_or(&DEFAULT_POWER_WEIGHTS)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_fixtures::*;
+
+    #[test]
+    fn test_experiment_variant_is_sticky_and_split_by_share() {
+        let mut params = test_params();
+        assert_eq!(experiment_variant(&params), Variant::Control);
+
+        params.experiment_id = 3;
+        let bucket = experiment_bucket(3, &params.user);
+        assert_eq!(bucket, experiment_bucket(3, &params.user));
+        params.experiment_share_bps = bucket;
+        assert_eq!(experiment_variant(&params), Variant::Control);
+        params.experiment_share_bps = bucket + 1;
+        assert_eq!(experiment_variant(&params), Variant::Treatment);
+        params.experiment_share_bps = 0;
+        assert_eq!(experiment_variant(&params), Variant::Control);
+        params.experiment_share_bps = 10_000;
+        assert_eq!(experiment_variant(&params), Variant::Treatment);
+    }
+
+    #[test]
+    fn test_experiment_buckets_are_spread() {
+        let users: Vec<Pubkey> = (0..2_000).map(|_| Pubkey::new_unique()).collect();
+        let treated = users
+            .iter()
+            .filter(|user| experiment_bucket(9, user) < 2_500)
+            .count();
+        // a quarter, give or take
+        assert!((400..600).contains(&treated), "{}", treated);
+
+        // experiments bucket independently
+        let moved = users
+            .iter()
+            .filter(|user| {
+                (experiment_bucket(9, user) < 5_000) != (experiment_bucket(10, user) < 5_000)
+            })
+            .count();
+        assert!((800..1_200).contains(&moved), "{}", moved);
+    }
+
+    #[test]
+    fn test_variant_power_weights() {
+        let weights = PowerWeights {
+            rating: 2,
+            weapon: 0,
+            shield: 1,
+            engine: 1,
+            hull: 1,
+        };
+
+        assert_eq!(variant_power_weights(Variant::Control, None), None);
+        assert_eq!(
+            variant_power_weights(Variant::Control, Some(&weights)),
+            Some(&weights)
+        );
+        assert_eq!(
+            variant_power_weights(Variant::Treatment, None),
+            Some(&DEFAULT_POWER_WEIGHTS)
+        );
+        assert_eq!(
+            variant_power_weights(Variant::Treatment, Some(&weights)),
+            Some(&weights)
+        );
+    }
+}
@@ -0,0 +1,120 @@
+use crate::*;
+use serde::Serialize;
+
+/// Written into the image by `/get_measurement.sh` when it is built.
+pub const MEASUREMENT_FILE: &str = "/measurement.txt";
+
+/// Where the reported `mr_enclave` was read from.
+#[derive(Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum MeasurementSource {
+    /// Our own SGX quote, only available inside the enclave.
+    Quote,
+    /// The measurement recorded when the image was built.
+    Image,
+}
+
+/// What operators correlate a container image with its on-chain
+/// `mr_enclave` allowlist entry by.
+#[derive(Serialize, Clone, Debug, PartialEq, Eq)]
+pub struct BuildInfo {
+    pub crate_version: &'static str,
+    /// From `GIT_COMMIT` at build time, see the Makefile.
+    pub git_commit: &'static str,
+    /// Hex encoded.
+    pub mr_enclave: Option<String>,
+    pub measurement_source: Option<MeasurementSource>,
+    pub params_versions: Vec<u8>,
+    pub args_version: u8,
+    /// From `BAKED_PROGRAM_ALLOWLIST` at build time, empty allows any program.
+    pub program_allowlist: Vec<String>,
+    /// From `BAKED_ROLL_IXN_ALLOWLIST` at build time, empty allows no custom
+    /// roll.
+    pub roll_ixn_allowlist: Vec<String>,
+}
+
+impl BuildInfo {
+    /// `from_quote` generates a quote for the measurement, which takes a
+    /// round trip to the quoting enclave, so the startup log line skips it.
+    pub fn collect(from_quote: bool) -> Self {
+        let measurement = from_quote
+            .then(mr_enclave_from_quote)
+            .flatten()
+            .map(|mr_enclave| (mr_enclave, MeasurementSource::Quote))
+            .or_else(|| {
+                std::fs::read_to_string(MEASUREMENT_FILE)
+                    .ok()
+                    .and_then(|contents| parse_measurement(&contents))
+                    .map(|mr_enclave| (mr_enclave, MeasurementSource::Image))
+            });
+
+        Self {
+            crate_version: env!("CARGO_PKG_VERSION"),
+            git_commit: option_env!("GIT_COMMIT").unwrap_or("unknown"),
+            mr_enclave: measurement
+                .as_ref()
+                .map(|(mr_enclave, _)| hex::encode(mr_enclave)),
+            measurement_source: measurement.map(|(_, source)| source),
+            params_versions: vec![PARAMS_VERSION],
+            args_version: ARGS_VERSION,
+            program_allowlist: baked_program_allowlist()
+                .unwrap_or_default()
+                .iter()
+                .map(Pubkey::to_string)
+                .collect(),
+            roll_ixn_allowlist: baked_roll_ixn_allowlist().unwrap_or_default(),
+        }
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).unwrap_or_default()
+    }
+}
+
+fn mr_enclave_from_quote() -> Option<[u8; 32]> {
+    let quote_raw = Gramine::generate_quote(&[0u8; 32]).ok()?;
+    let quote = sgx_quote::Quote::parse(&quote_raw).ok()?;
+    quote.isv_report.mrenclave.try_into().ok()
+}
+
+/// The measurement file holds the hex measurement, optionally 0x prefixed.
+pub fn parse_measurement(contents: &str) -> Option<[u8; 32]> {
+    let contents = contents.trim();
+    let contents = contents.strip_prefix("0x").unwrap_or(contents);
+    hex::decode(contents).ok()?.try_into().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_measurement() {
+        let measurement = [7u8; 32];
+
+        assert_eq!(
+            parse_measurement(&format!("{}\n", hex::encode(measurement))),
+            Some(measurement)
+        );
+        assert_eq!(
+            parse_measurement(&format!("0x{}", hex::encode(measurement))),
+            Some(measurement)
+        );
+        assert_eq!(parse_measurement("abcd"), None);
+        assert_eq!(parse_measurement("not hex"), None);
+    }
+
+    #[test]
+    fn test_build_info_json() {
+        let info = BuildInfo::collect(false);
+        let json: serde_json::Value = serde_json::from_str(&info.to_json()).unwrap();
+
+        assert_eq!(json["crate_version"], env!("CARGO_PKG_VERSION"));
+        assert_eq!(json["params_versions"], serde_json::json!([PARAMS_VERSION]));
+        assert_eq!(json["args_version"], ARGS_VERSION);
+        assert!(json.get("git_commit").is_some());
+        assert!(json.get("mr_enclave").is_some());
+        assert!(json["program_allowlist"].is_array());
+        assert!(json["roll_ixn_allowlist"].is_array());
+    }
+}
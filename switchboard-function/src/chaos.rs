@@ -0,0 +1,348 @@
+//! Fault injection for the oracle flow: a runner and an RPC that fail on
+//! demand, and the single request flow of `run` driven through them, so
+//! every failure path is checked to end with the code the program expects.
+
+use crate::test_fixtures::*;
+use crate::*;
+use futures::future::LocalBoxFuture;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RpcFault {
+    /// Every read fails like a request that timed out.
+    Timeout,
+    /// Reads succeed once then time out, the first read is the realm's rules.
+    TimeoutAfterFirst,
+    /// Every account comes back cut in half.
+    PartialData,
+    /// The read panics, e.g. an unwrap deep in the client.
+    Panic,
+}
+
+/// Serves the accounts of `inner` with `fault` injected.
+pub struct FlakyRpc {
+    pub inner: MockFetcher,
+    pub fault: Option<RpcFault>,
+    reads: AtomicUsize,
+}
+
+impl FlakyRpc {
+    pub fn new(inner: MockFetcher, fault: Option<RpcFault>) -> Self {
+        Self {
+            inner,
+            fault,
+            reads: AtomicUsize::new(0),
+        }
+    }
+}
+
+impl AccountFetcher for FlakyRpc {
+    fn fetch_multiple_account_data(
+        &self,
+        pubkeys: &[Pubkey],
+    ) -> std::result::Result<Vec<Option<Vec<u8>>>, FunctionError> {
+        let reads = self.reads.fetch_add(1, Ordering::SeqCst);
+        match self.fault {
+            Some(RpcFault::Timeout) => Err(FunctionError::AccountFetchFailed),
+            Some(RpcFault::TimeoutAfterFirst) if reads > 0 => {
+                Err(FunctionError::AccountFetchFailed)
+            }
+            Some(RpcFault::PartialData) => Ok(self
+                .inner
+                .fetch_multiple_account_data(pubkeys)?
+                .into_iter()
+                .map(|data| {
+                    data.map(|mut data| {
+                        data.truncate(data.len() / 2);
+                        data
+                    })
+                })
+                .collect()),
+            Some(RpcFault::Panic) => panic!("injected rpc panic"),
+            _ => self.inner.fetch_multiple_account_data(pubkeys),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EmitFault {
+    /// Settlements fail to emit, error codes still go through.
+    Settlement,
+    /// Nothing can be emitted.
+    Everything,
+}
+
+/// Records what the flow emits, failing with `fault`.
+#[derive(Default)]
+pub struct FaultyRunner {
+    pub fault: Option<EmitFault>,
+    pub emitted: Mutex<Vec<Vec<Instruction>>>,
+    pub error_codes: Mutex<Vec<u8>>,
+}
+
+impl FaultyRunner {
+    pub fn new(fault: Option<EmitFault>) -> Self {
+        Self {
+            fault,
+            ..Default::default()
+        }
+    }
+}
+
+impl ResultEmitter for FaultyRunner {
+    fn emit(&self, ixs: Vec<Instruction>) -> LocalBoxFuture<'_, std::result::Result<(), SbError>> {
+        Box::pin(async move {
+            if self.fault.is_some() {
+                return Err(SbError::CustomMessage("injected emit failure".to_string()));
+            }
+            self.emitted.lock().unwrap().push(ixs);
+            Ok(())
+        })
+    }
+
+    fn emit_signed<'a>(
+        &'a self,
+        ixs: Vec<Instruction>,
+        _encoding: &'a MessageEncoding,
+    ) -> LocalBoxFuture<'a, std::result::Result<(), SbError>> {
+        self.emit(ixs)
+    }
+
+    fn emit_error(&self, error_code: u8) -> LocalBoxFuture<'_, std::result::Result<(), SbError>> {
+        Box::pin(async move {
+            if self.fault == Some(EmitFault::Everything) {
+                return Err(SbError::CustomMessage("injected emit failure".to_string()));
+            }
+            self.error_codes.lock().unwrap().push(error_code);
+            Ok(())
+        })
+    }
+}
+
+/// The single request flow of `run` and `settle_request`, with failure
+/// reports enabled.
+async fn run_flow(runner: &FaultyRunner, rpc: &FlakyRpc, container_params: &[u8]) -> Termination {
+    let runner_accounts = test_runner_accounts();
+    let result = catch_panic(async {
+        let params = ContainerParams::decode_for_programs(container_params, &[])?;
+        precheck(&params, &[])?;
+        let settlement = build_settlement(
+            &params,
+            &runner_accounts,
+            &runner_accounts.enclave_signer,
+            rpc,
+            &OsRandomSource,
+            None,
+            &mut test_budget(ExecutionTier::Standard),
+        )?;
+        emit_settlement(
+            runner,
+            settlement.ixs,
+            &SettlementSigner::EnclaveKey(settlement.encoding),
+            &[settlement.outcome],
+            settlement.pool_diversity.as_slice(),
+            &[settlement.audit],
+        )
+        .await
+    })
+    .await;
+    finish_request(runner, result, |error| {
+        ContainerParams::decode_for_programs(container_params, &[])
+            .ok()
+            .and_then(|params| failure_report_ixn(&params, &runner_accounts, error).ok())
+            .flatten()
+    })
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A matchmaking request whose accounts all exist and settle.
+    fn healthy_request() -> (String, MockFetcher) {
+        let params_string = test_params_string();
+        let params = ContainerParams::decode(params_string.as_bytes()).unwrap();
+        let fetcher = test_fetcher(&params, &test_realm(vec![]), [0; 6].map(test_spaceship));
+        (params_string, fetcher)
+    }
+
+    #[tokio::test]
+    async fn test_healthy_flow_settles() {
+        let (params, fetcher) = healthy_request();
+        let runner = FaultyRunner::new(None);
+
+        let termination = run_flow(&runner, &FlakyRpc::new(fetcher, None), params.as_bytes()).await;
+
+        assert_eq!(termination, Termination::Settled);
+        assert_eq!(runner.emitted.lock().unwrap().len(), 1);
+        assert!(runner.error_codes.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_undecodable_params_are_reported() {
+        let (params, fetcher) = healthy_request();
+        let rpc = FlakyRpc::new(fetcher, None);
+
+        // a garbled pubkey is permanent, reported with the bare code since the
+        // params cannot be decoded for a failure report
+        let garbled = params.replacen("PID=", "PID=0", 1);
+        let runner = FaultyRunner::new(None);
+        assert_eq!(
+            run_flow(&runner, &rpc, garbled.as_bytes()).await,
+            Termination::ErrorCode(FunctionError::InvalidParams)
+        );
+        assert_eq!(
+            *runner.error_codes.lock().unwrap(),
+            vec![FunctionError::InvalidParams.code()]
+        );
+
+        let runner = FaultyRunner::new(None);
+        assert_eq!(
+            run_flow(&runner, &rpc, &[0xff, 0xfe, 0x00]).await,
+            Termination::ErrorCode(FunctionError::InvalidParams)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_rpc_faults_end_with_their_code() {
+        let cases = [
+            (RpcFault::Timeout, FunctionError::AccountFetchFailed),
+            (
+                RpcFault::TimeoutAfterFirst,
+                FunctionError::AccountFetchFailed,
+            ),
+            (RpcFault::PartialData, FunctionError::AccountDecodeFailed),
+            (RpcFault::Panic, FunctionError::Panicked),
+        ];
+        for (fault, expected) in cases {
+            let (params, fetcher) = healthy_request();
+            let runner = FaultyRunner::new(None);
+
+            let termination = run_flow(
+                &runner,
+                &FlakyRpc::new(fetcher, Some(fault)),
+                params.as_bytes(),
+            )
+            .await;
+
+            // transient failures are left to a retry, never refunded
+            assert_eq!(termination, Termination::ErrorCode(expected), "{:?}", fault);
+            assert_eq!(*runner.error_codes.lock().unwrap(), vec![expected.code()]);
+            assert!(runner.emitted.lock().unwrap().is_empty());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_failover_recovers_from_rpc_timeouts() {
+        let (params, fetcher) = healthy_request();
+        let primary = FlakyRpc::new(MockFetcher::default(), Some(RpcFault::Timeout));
+        let fallback = FlakyRpc::new(fetcher, None);
+        let failover = FailoverFetcher {
+            primary: &primary,
+            fallback: Some(Box::new(fallback)),
+        };
+        let runner = FaultyRunner::new(None);
+
+        let runner_accounts = test_runner_accounts();
+        let params = ContainerParams::decode(params.as_bytes()).unwrap();
+        let settlement = build_settlement(
+            &params,
+            &runner_accounts,
+            &runner_accounts.enclave_signer,
+            &failover,
+            &OsRandomSource,
+            None,
+            &mut test_budget(ExecutionTier::Standard),
+        )
+        .unwrap();
+        let result = emit_settlement(
+            &runner,
+            settlement.ixs,
+            &SettlementSigner::EnclaveKey(settlement.encoding),
+            &[settlement.outcome],
+            settlement.pool_diversity.as_slice(),
+            &[settlement.audit],
+        )
+        .await;
+
+        assert_eq!(
+            finish_request(&runner, result, |_| None).await,
+            Termination::Settled
+        );
+    }
+
+    #[tokio::test]
+    async fn test_final_matchmaking_failure_is_reported() {
+        let (params, mut fetcher) = healthy_request();
+        let decoded = ContainerParams::decode(params.as_bytes()).unwrap();
+        // every candidate is already in a match
+        for pubkey in decoded.opponent_spaceship_pdas() {
+            let mut spaceship = test_spaceship(0);
+            spaceship.current_match = Some(Pubkey::new_unique());
+            fetcher.insert(pubkey, encode_account(Spaceship::NAME, &spaceship));
+        }
+        let runner = FaultyRunner::new(None);
+
+        let termination = run_flow(&runner, &FlakyRpc::new(fetcher, None), params.as_bytes()).await;
+
+        let Termination::Reported(error) = termination else {
+            panic!("expected a failure report, got {:?}", termination);
+        };
+        let emitted = runner.emitted.lock().unwrap();
+        assert_eq!(emitted.len(), 1);
+        assert_eq!(
+            emitted[0][0].data[..8],
+            get_ixn_discriminator("arena_matchmaking_report_failure")
+        );
+        assert_eq!(emitted[0][0].data[43], error.code());
+        assert!(runner.error_codes.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_emit_failures_fall_back_to_the_error_code() {
+        let (params, fetcher) = healthy_request();
+        let rpc = FlakyRpc::new(fetcher, None);
+
+        let runner = FaultyRunner::new(Some(EmitFault::Settlement));
+        assert_eq!(
+            run_flow(&runner, &rpc, params.as_bytes()).await,
+            Termination::ErrorCode(FunctionError::EmitFailed)
+        );
+        assert_eq!(
+            *runner.error_codes.lock().unwrap(),
+            vec![FunctionError::EmitFailed.code()]
+        );
+
+        let runner = FaultyRunner::new(Some(EmitFault::Everything));
+        assert_eq!(
+            run_flow(&runner, &rpc, params.as_bytes()).await,
+            Termination::Unreported(FunctionError::EmitFailed)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_failed_failure_report_falls_back_to_the_error_code() {
+        let (params, _) = healthy_request();
+        let runner = FaultyRunner::new(Some(EmitFault::Settlement));
+        let report = failure_report_ixn(
+            &ContainerParams::decode(params.as_bytes()).unwrap(),
+            &test_runner_accounts(),
+            FunctionError::NoEligibleOpponent,
+        )
+        .unwrap();
+
+        let termination =
+            finish_request(&runner, Err(FunctionError::NoEligibleOpponent), |_| report).await;
+
+        assert_eq!(
+            termination,
+            Termination::ErrorCode(FunctionError::NoEligibleOpponent)
+        );
+        assert_eq!(
+            *runner.error_codes.lock().unwrap(),
+            vec![FunctionError::NoEligibleOpponent.code()]
+        );
+    }
+}
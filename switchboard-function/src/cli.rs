@@ -0,0 +1,187 @@
+use crate::*;
+
+pub const USAGE: &str =
+    "usage: arena-matchmaking-function [--storage <list|verify|prune> [--all] | --storage allow-programs <pubkey,...> | --self-test | --version-info | --replay <file> | --rng-audit [samples]]";
+
+/// What the binary was asked to do. Without arguments it settles the request
+/// it was started for.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Mode {
+    Run,
+    Storage(StorageCommand),
+    /// Validates the image and its environment, see self_test.rs.
+    SelfTest,
+    /// Prints the build and measurement JSON, see build_info.rs.
+    VersionInfo,
+    /// Re-runs a recorded request, see replay.rs.
+    Replay(std::path::PathBuf),
+    /// Runs statistical tests on enclave entropy, see rng_audit.rs.
+    RngAudit(u32),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum StorageCommand {
+    List,
+    Verify,
+    /// Removes corrupt artifacts, or all of them with `--all`.
+    Prune {
+        all: bool,
+    },
+    /// Seals a program allowlist overriding the baked in one.
+    AllowPrograms(Vec<Pubkey>),
+}
+
+impl Mode {
+    /// Parses the process arguments, excluding the binary name.
+    pub fn from_args(args: &[String]) -> std::result::Result<Self, String> {
+        let args: Vec<&str> = args.iter().map(|arg| arg.as_str()).collect();
+        match args.as_slice() {
+            [] => Ok(Mode::Run),
+            ["--storage", "list"] => Ok(Mode::Storage(StorageCommand::List)),
+            ["--storage", "verify"] => Ok(Mode::Storage(StorageCommand::Verify)),
+            ["--storage", "prune"] => Ok(Mode::Storage(StorageCommand::Prune { all: false })),
+            ["--storage", "prune", "--all"] => {
+                Ok(Mode::Storage(StorageCommand::Prune { all: true }))
+            }
+            ["--storage", "allow-programs", programs] => parse_program_allowlist(programs)
+                .map(|programs| Mode::Storage(StorageCommand::AllowPrograms(programs)))
+                .map_err(|_| USAGE.to_string()),
+            ["--self-test"] => Ok(Mode::SelfTest),
+            ["--version-info"] => Ok(Mode::VersionInfo),
+            ["--replay", path] => Ok(Mode::Replay(path.into())),
+            ["--rng-audit"] => Ok(Mode::RngAudit(DEFAULT_RNG_AUDIT_SAMPLES)),
+            ["--rng-audit", samples] => samples
+                .parse()
+                .ok()
+                .filter(|samples| (MIN_RNG_AUDIT_SAMPLES..=MAX_RNG_AUDIT_SAMPLES).contains(samples))
+                .map(Mode::RngAudit)
+                .ok_or_else(|| USAGE.to_string()),
+            _ => Err(USAGE.to_string()),
+        }
+    }
+}
+
+/// Runs an operator storage command and returns the process exit code:
+/// 0 on success, 1 if any artifact failed its integrity check or could not be
+/// removed.
+pub fn run_storage_command(storage: &SealedStorage, command: &StorageCommand) -> i32 {
+    match command {
+        StorageCommand::List | StorageCommand::Verify => {
+            let statuses = storage.list();
+            if statuses.is_empty() {
+                println!("no artifacts in {}", storage.dir().display());
+            }
+            for status in statuses.iter() {
+                println!(
+                    "{:<24} {:>10} bytes  {:?}",
+                    status.kind.file_name(),
+                    status.size,
+                    status.integrity
+                );
+            }
+            let failed = statuses
+                .iter()
+                .any(|status| status.integrity != Integrity::Ok);
+            if *command == StorageCommand::Verify && failed {
+                1
+            } else {
+                0
+            }
+        }
+        StorageCommand::Prune { all } => match storage.prune(*all) {
+            Ok(pruned) => {
+                for kind in pruned.iter() {
+                    println!("pruned {}", kind.file_name());
+                }
+                0
+            }
+            Err(error) => {
+                println!("failed to prune {}: {}", storage.dir().display(), error);
+                1
+            }
+        },
+        StorageCommand::AllowPrograms(programs) => {
+            match save_program_allowlist(storage, programs) {
+                Ok(()) => {
+                    println!("allowed {} programs", programs.len());
+                    0
+                }
+                Err(error) => {
+                    println!("failed to seal the program allowlist: {}", error);
+                    1
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    #[test]
+    fn test_mode_from_args() {
+        assert_eq!(Mode::from_args(&args(&[])), Ok(Mode::Run));
+        assert_eq!(
+            Mode::from_args(&args(&["--storage", "verify"])),
+            Ok(Mode::Storage(StorageCommand::Verify))
+        );
+        assert_eq!(
+            Mode::from_args(&args(&["--storage", "prune", "--all"])),
+            Ok(Mode::Storage(StorageCommand::Prune { all: true }))
+        );
+        assert_eq!(Mode::from_args(&args(&["--self-test"])), Ok(Mode::SelfTest));
+        assert_eq!(
+            Mode::from_args(&args(&["--version-info"])),
+            Ok(Mode::VersionInfo)
+        );
+        let program = Pubkey::new_unique();
+        assert_eq!(
+            Mode::from_args(&args(&[
+                "--storage",
+                "allow-programs",
+                &program.to_string()
+            ])),
+            Ok(Mode::Storage(StorageCommand::AllowPrograms(vec![program])))
+        );
+        assert!(Mode::from_args(&args(&["--storage", "allow-programs", "bogus"])).is_err());
+        assert_eq!(
+            Mode::from_args(&args(&["--replay", "request.json"])),
+            Ok(Mode::Replay("request.json".into()))
+        );
+        assert!(Mode::from_args(&args(&["--replay"])).is_err());
+        assert_eq!(
+            Mode::from_args(&args(&["--rng-audit"])),
+            Ok(Mode::RngAudit(DEFAULT_RNG_AUDIT_SAMPLES))
+        );
+        assert_eq!(
+            Mode::from_args(&args(&["--rng-audit", "5000"])),
+            Ok(Mode::RngAudit(5_000))
+        );
+        assert!(Mode::from_args(&args(&["--rng-audit", "10"])).is_err());
+        assert!(Mode::from_args(&args(&["--rng-audit", "many"])).is_err());
+        assert!(Mode::from_args(&args(&["--storage"])).is_err());
+        assert!(Mode::from_args(&args(&["--bogus"])).is_err());
+    }
+
+    #[test]
+    fn test_verify_exit_code() {
+        let storage = SealedStorage::new(test_storage_dir("cli-verify"));
+        storage.write(ArtifactKind::Stats, b"{}").unwrap();
+        assert_eq!(run_storage_command(&storage, &StorageCommand::Verify), 0);
+
+        std::fs::write(storage.dir().join(ArtifactKind::Stats.file_name()), b"{ }").unwrap();
+        assert_eq!(run_storage_command(&storage, &StorageCommand::Verify), 1);
+        assert_eq!(run_storage_command(&storage, &StorageCommand::List), 0);
+
+        assert_eq!(
+            run_storage_command(&storage, &StorageCommand::Prune { all: false }),
+            0
+        );
+        assert_eq!(run_storage_command(&storage, &StorageCommand::Verify), 0);
+    }
+}
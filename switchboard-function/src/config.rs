@@ -0,0 +1,320 @@
+use crate::*;
+use serde::Deserialize;
+use std::sync::OnceLock;
+
+// Operator settings come from env vars or from a TOML file at
+// `FUNCTION_CONFIG`. An env var always wins over the file, so a single setting
+// can be overridden for one run without touching the file. Settings from the
+// file are typed and validated once at startup, env vars are parsed where they
+// are used, as before.
+
+/// The `FUNCTION_CONFIG` file. Keys are the env var names in lower case.
+#[derive(Deserialize, Clone, Debug, Default, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct FunctionConfig {
+    /// `devnet` (default), `mainnet`, `testnet`, `localnet` or an RPC URL,
+    /// used without a private endpoint.
+    pub cluster: Option<String>,
+    pub private_rpc_url: Option<String>,
+    pub private_rpc_api_key: Option<String>,
+    pub secrets_server_url: Option<String>,
+    /// Only read in local dev mode, see local_dev.rs.
+    pub rpc_url: Option<String>,
+    pub local_randomness: Option<bool>,
+    /// Only honoured on devnet, testnet or localnet, see entropy.rs.
+    pub entropy_fallback: Option<bool>,
+    /// Narrows the image's allowlist, see program_allowlist.rs.
+    pub program_allowlist: Option<Vec<String>>,
+    pub address_lookup_table: Option<String>,
+    /// `legacy` (default) or `v0`, see emission.rs.
+    pub tx_format: Option<String>,
+    pub execution_tier: Option<String>,
+    pub execution_deadline_ms: Option<u64>,
+    pub max_request_age_slots: Option<u64>,
+    pub batch_parallelism: Option<usize>,
+    pub rate_limit: Option<String>,
+    /// `<strength_bps>[:<min_matches>]`, see faction_bias.rs.
+    pub faction_bias: Option<String>,
+    pub dry_run: Option<bool>,
+    /// See shadow.rs.
+    pub shadow_selection: Option<bool>,
+    pub failure_reports: Option<bool>,
+    pub sealed_storage_dir: Option<String>,
+    pub audit_log_path: Option<String>,
+    pub audit_webhook_url: Option<String>,
+    pub outcome_webhook_url: Option<String>,
+    pub metrics_statsd_addr: Option<String>,
+    pub metrics_pushgateway_url: Option<String>,
+    /// In lamports, see escrow.rs.
+    pub escrow_min_balance: Option<u64>,
+    pub escrow_alert_webhook_url: Option<String>,
+}
+
+/// Never logged, not even redacted.
+const SECRET_SETTINGS: &[&str] = &["PRIVATE_RPC_API_KEY"];
+
+/// Logged with their scheme and host only, the rest may carry credentials.
+const URL_SETTINGS: &[&str] = &[
+    "CLUSTER",
+    "PRIVATE_RPC_URL",
+    "SECRETS_SERVER_URL",
+    "RPC_URL",
+    "AUDIT_WEBHOOK_URL",
+    "OUTCOME_WEBHOOK_URL",
+    "METRICS_PUSHGATEWAY_URL",
+    "ESCROW_ALERT_WEBHOOK_URL",
+];
+
+fn flag(value: Option<bool>) -> Option<String> {
+    value.map(|value| if value { "1" } else { "0" }.to_string())
+}
+
+impl FunctionConfig {
+    /// Every setting by env var name, as the env var would spell it.
+    pub fn settings(&self) -> Vec<(&'static str, Option<String>)> {
+        vec![
+            ("CLUSTER", self.cluster.clone()),
+            ("PRIVATE_RPC_URL", self.private_rpc_url.clone()),
+            ("PRIVATE_RPC_API_KEY", self.private_rpc_api_key.clone()),
+            ("SECRETS_SERVER_URL", self.secrets_server_url.clone()),
+            ("RPC_URL", self.rpc_url.clone()),
+            ("LOCAL_RANDOMNESS", flag(self.local_randomness)),
+            ("ENTROPY_FALLBACK", flag(self.entropy_fallback)),
+            (
+                "PROGRAM_ALLOWLIST",
+                self.program_allowlist
+                    .as_ref()
+                    .map(|programs| programs.join(",")),
+            ),
+            ("ADDRESS_LOOKUP_TABLE", self.address_lookup_table.clone()),
+            ("TX_FORMAT", self.tx_format.clone()),
+            ("EXECUTION_TIER", self.execution_tier.clone()),
+            (
+                "EXECUTION_DEADLINE_MS",
+                self.execution_deadline_ms.map(|ms| ms.to_string()),
+            ),
+            (
+                "MAX_REQUEST_AGE_SLOTS",
+                self.max_request_age_slots.map(|slots| slots.to_string()),
+            ),
+            (
+                "BATCH_PARALLELISM",
+                self.batch_parallelism
+                    .map(|parallelism| parallelism.to_string()),
+            ),
+            ("RATE_LIMIT", self.rate_limit.clone()),
+            ("FACTION_BIAS", self.faction_bias.clone()),
+            ("DRY_RUN", flag(self.dry_run)),
+            ("SHADOW_SELECTION", flag(self.shadow_selection)),
+            ("FAILURE_REPORTS", flag(self.failure_reports)),
+            ("SEALED_STORAGE_DIR", self.sealed_storage_dir.clone()),
+            ("AUDIT_LOG_PATH", self.audit_log_path.clone()),
+            ("AUDIT_WEBHOOK_URL", self.audit_webhook_url.clone()),
+            ("OUTCOME_WEBHOOK_URL", self.outcome_webhook_url.clone()),
+            ("METRICS_STATSD_ADDR", self.metrics_statsd_addr.clone()),
+            (
+                "METRICS_PUSHGATEWAY_URL",
+                self.metrics_pushgateway_url.clone(),
+            ),
+            (
+                "ESCROW_MIN_BALANCE",
+                self.escrow_min_balance.map(|balance| balance.to_string()),
+            ),
+            (
+                "ESCROW_ALERT_WEBHOOK_URL",
+                self.escrow_alert_webhook_url.clone(),
+            ),
+        ]
+    }
+
+    pub fn get(&self, key: &str) -> Option<String> {
+        self.settings()
+            .into_iter()
+            .find(|(setting, _)| *setting == key)
+            .and_then(|(_, value)| value)
+    }
+
+    /// Parses and validates a config file, rejecting unknown keys and values
+    /// the function would otherwise ignore.
+    pub fn parse(contents: &str) -> std::result::Result<Self, String> {
+        let config: Self = toml::from_str(contents).map_err(|error| error.to_string())?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    fn validate(&self) -> std::result::Result<(), String> {
+        let invalid = |key: &str, value: &str| format!("invalid {}: {}", key.to_lowercase(), value);
+        let cluster = self
+            .cluster
+            .as_ref()
+            .map(|cluster| Cluster::from_str(cluster).map_err(|_| invalid("CLUSTER", cluster)))
+            .transpose()?;
+        if self.entropy_fallback == Some(true) && !cluster.as_ref().is_some_and(fallback_allowed_on)
+        {
+            return Err("entropy_fallback needs cluster set to devnet, testnet or localnet".into());
+        }
+        let pubkeys = self
+            .program_allowlist
+            .iter()
+            .flatten()
+            .map(|program| ("PROGRAM_ALLOWLIST", program))
+            .chain(
+                self.address_lookup_table
+                    .iter()
+                    .map(|lookup_table| ("ADDRESS_LOOKUP_TABLE", lookup_table)),
+            );
+        for (key, pubkey) in pubkeys {
+            Pubkey::from_str(pubkey).map_err(|_| invalid(key, pubkey))?;
+        }
+        if let Some(format) = &self.tx_format {
+            TxFormat::from_str(format).map_err(|_| invalid("TX_FORMAT", format))?;
+        }
+        if let Some(tier) = &self.execution_tier {
+            ExecutionTier::from_str(tier).map_err(|_| invalid("EXECUTION_TIER", tier))?;
+        }
+        if let Some(rate_limit) = &self.rate_limit {
+            RateLimit::from_str(rate_limit).map_err(|_| invalid("RATE_LIMIT", rate_limit))?;
+        }
+        if let Some(bias) = &self.faction_bias {
+            FactionBias::from_str(bias).map_err(|_| invalid("FACTION_BIAS", bias))?;
+        }
+        if self.batch_parallelism == Some(0) {
+            return Err(invalid("BATCH_PARALLELISM", "0"));
+        }
+        Ok(())
+    }
+}
+
+/// The config file as loaded at startup. Unloaded, e.g. in tests, settings
+/// only come from env vars.
+static CONFIG: OnceLock<std::result::Result<FunctionConfig, String>> = OnceLock::new();
+
+/// Loads the `FUNCTION_CONFIG` file, an unset path is an empty config. Only
+/// the first call reads the file.
+pub fn load_config() -> std::result::Result<&'static FunctionConfig, String> {
+    CONFIG
+        .get_or_init(|| match std::env::var("FUNCTION_CONFIG") {
+            Err(_) => Ok(FunctionConfig::default()),
+            Ok(path) => std::fs::read_to_string(&path)
+                .map_err(|error| error.to_string())
+                .and_then(|contents| FunctionConfig::parse(&contents))
+                .map_err(|error| format!("failed to load config {}: {}", path, error)),
+        })
+        .as_ref()
+        .map_err(Clone::clone)
+}
+
+/// Fails the run when the config file did not load, rather than settling
+/// with the settings it meant to change.
+pub fn check_config() -> std::result::Result<(), FunctionError> {
+    match CONFIG.get() {
+        Some(Err(_)) => Err(FunctionError::InvalidConfig),
+        _ => Ok(()),
+    }
+}
+
+/// An operator setting: the env var when set, else the config file's value.
+pub fn setting(key: &str) -> Option<String> {
+    std::env::var(key).ok().or_else(|| match CONFIG.get() {
+        Some(Ok(config)) => config.get(key),
+        _ => None,
+    })
+}
+
+fn redacted(key: &str, value: &str) -> String {
+    if SECRET_SETTINGS.contains(&key) {
+        "<redacted>".to_string()
+    } else if URL_SETTINGS.contains(&key) && value.contains("://") {
+        redact_url(value)
+    } else {
+        value.to_string()
+    }
+}
+
+/// The settings in effect and where each comes from, secrets redacted.
+pub fn effective_config(config: &FunctionConfig) -> Vec<String> {
+    config
+        .settings()
+        .into_iter()
+        .filter_map(|(key, file_value)| {
+            let (value, source) = match (std::env::var(key).ok(), file_value) {
+                (Some(value), _) => (value, "env"),
+                (None, Some(value)) => (value, "file"),
+                (None, None) => return None,
+            };
+            Some(format!("{}={} ({})", key, redacted(key, &value), source))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_config() {
+        let config = FunctionConfig::parse(
+            r#"
+            cluster = "mainnet"
+            private_rpc_url = "https://rpc.example.com/{RPC_API_KEY}"
+            private_rpc_api_key = "hunter2"
+            program_allowlist = ["TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA"]
+            execution_tier = "RICH"
+            max_request_age_slots = 150
+            rate_limit = "5/1500"
+            dry_run = true
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(config.get("CLUSTER").as_deref(), Some("mainnet"));
+        assert_eq!(config.get("DRY_RUN").as_deref(), Some("1"));
+        assert_eq!(config.get("MAX_REQUEST_AGE_SLOTS").as_deref(), Some("150"));
+        assert_eq!(
+            config.get("PROGRAM_ALLOWLIST").as_deref(),
+            Some("TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA")
+        );
+        assert_eq!(config.get("FAILURE_REPORTS"), None);
+
+        let config =
+            FunctionConfig::parse("cluster = \"devnet\"\nentropy_fallback = true").unwrap();
+        assert_eq!(config.get("ENTROPY_FALLBACK").as_deref(), Some("1"));
+    }
+
+    #[test]
+    fn test_invalid_config_is_rejected() {
+        for contents in [
+            "unknown_setting = 1",
+            "max_request_age_slots = \"soon\"",
+            "cluster = \"moonnet\"",
+            "program_allowlist = [\"not-a-pubkey\"]",
+            "execution_tier = \"LUDICROUS\"",
+            "tx_format = \"v1\"",
+            "rate_limit = \"0/100\"",
+            "faction_bias = \"12000\"",
+            "batch_parallelism = 0",
+            "entropy_fallback = true",
+            "cluster = \"mainnet\"\nentropy_fallback = true",
+        ] {
+            assert!(FunctionConfig::parse(contents).is_err(), "{}", contents);
+        }
+    }
+
+    #[test]
+    fn test_effective_config_is_redacted() {
+        let config = FunctionConfig {
+            private_rpc_url: Some("https://rpc.example.com/secret-path".to_string()),
+            private_rpc_api_key: Some("hunter2".to_string()),
+            execution_deadline_ms: Some(5_000),
+            ..Default::default()
+        };
+
+        let effective = effective_config(&config).join("\n");
+
+        assert!(effective.contains("PRIVATE_RPC_URL=https://rpc.example.com (file)"));
+        assert!(effective.contains("PRIVATE_RPC_API_KEY=<redacted> (file)"));
+        assert!(effective.contains("EXECUTION_DEADLINE_MS=5000 (file)"));
+        assert!(!effective.contains("hunter2"));
+        assert!(!effective.contains("secret-path"));
+    }
+}
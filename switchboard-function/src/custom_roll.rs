@@ -0,0 +1,41 @@
+use crate::*;
+
+// CUSTOM_ROLL requests describe their instruction data in the params rather
+// than in this crate, so a new on-chain mechanic only needs a handler taking
+// the settle header followed by the rolled fields, and an entry in the baked
+// roll allowlist since the enclave signs whatever the handler receives.
+
+/// The rolled fields in schema order, each little endian at its kind's width.
+pub fn encode_roll_args(
+    schema: &[RollField],
+    rng: &dyn RandomSource,
+) -> std::result::Result<Vec<u8>, FunctionError> {
+    let mut data = Vec::with_capacity(schema.iter().map(|field| field.kind.size()).sum());
+    for field in schema {
+        let value = rng.generate_u64(field.min, field.max)?;
+        data.extend_from_slice(&value.to_le_bytes()[..field.kind.size()]);
+    }
+    Ok(data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_encode_roll_args() {
+        let schema: Vec<RollField> = ["U8/1/6", "U16", "U32/7/7", "U64/0/0"]
+            .iter()
+            .map(|field| RollField::from_str(field).unwrap())
+            .collect();
+
+        for _ in 0..100 {
+            let data = encode_roll_args(&schema, &OsRandomSource).unwrap();
+
+            assert_eq!(data.len(), 1 + 2 + 4 + 8);
+            assert!((1..=6).contains(&data[0]));
+            assert_eq!(data[3..7], 7u32.to_le_bytes());
+            assert_eq!(data[7..], [0u8; 8]);
+        }
+    }
+}
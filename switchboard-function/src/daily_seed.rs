@@ -0,0 +1,105 @@
+use crate::*;
+
+/// Offset of `unix_timestamp` in the Clock sysvar, after the slot, epoch
+/// start timestamp, epoch and leader schedule epoch.
+pub const CLOCK_UNIX_TIMESTAMP_OFFSET: usize = 32;
+
+/// Domain separator, so the seed can't collide with a hash the program
+/// derives for something else.
+const DAILY_SEED_DOMAIN: &[u8] = b"arena-imperium-daily-seed";
+
+/// The cluster time, read from the Clock sysvar rather than the enclave's
+/// clock which the host controls.
+pub fn load_unix_timestamp<F: AccountFetcher + ?Sized>(
+    fetcher: &F,
+) -> std::result::Result<i64, FunctionError> {
+    let data = fetcher
+        .fetch_multiple_account_data(&[solana_program::sysvar::clock::ID])?
+        .pop()
+        .flatten()
+        .ok_or(FunctionError::AccountFetchFailed)?;
+    let timestamp = data
+        .get(CLOCK_UNIX_TIMESTAMP_OFFSET..CLOCK_UNIX_TIMESTAMP_OFFSET + 8)
+        .ok_or(FunctionError::AccountDecodeFailed)?;
+    Ok(i64::from_le_bytes(timestamp.try_into().unwrap()))
+}
+
+/// Index of the period `unix_timestamp` falls in, periods start `offset`
+/// seconds after a multiple of `period`. `period` is never 0, decode
+/// rejects it.
+pub fn seed_period_index(unix_timestamp: i64, period: u64, offset: u64) -> u64 {
+    (unix_timestamp.max(0) as u64).saturating_sub(offset) / period
+}
+
+/// H(domain || entropy || period_index). The entropy makes the seed
+/// unpredictable, the period binds it to the period it was drawn for.
+pub fn derive_daily_seed(entropy: &[u8; 32], period_index: u64) -> [u8; 32] {
+    solana_program::hash::hashv(&[DAILY_SEED_DOMAIN, entropy, &period_index.to_le_bytes()])
+        .to_bytes()
+}
+
+pub fn draw_daily_seed(
+    rng: &dyn RandomSource,
+    period_index: u64,
+) -> std::result::Result<[u8; 32], FunctionError> {
+    let mut entropy = [0u8; 32];
+    rng.fill_bytes(&mut entropy)?;
+    Ok(derive_daily_seed(&entropy, period_index))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_fixtures::*;
+
+    #[test]
+    fn test_seed_period_index() {
+        // 2024-01-02 03:00 UTC
+        let timestamp = 1_704_164_400;
+
+        assert_eq!(seed_period_index(timestamp, 86_400, 0), 19_724);
+        // the 04:00 rotation has not happened yet
+        assert_eq!(seed_period_index(timestamp, 86_400, 14_400), 19_723);
+        assert_eq!(seed_period_index(timestamp, 3_600, 0), 473_379);
+        assert_eq!(seed_period_index(-1, 86_400, 0), 0);
+    }
+
+    #[test]
+    fn test_derive_daily_seed_binds_period() {
+        let entropy = [7u8; 32];
+
+        assert_eq!(
+            derive_daily_seed(&entropy, 1),
+            derive_daily_seed(&entropy, 1)
+        );
+        assert_ne!(
+            derive_daily_seed(&entropy, 1),
+            derive_daily_seed(&entropy, 2)
+        );
+        assert_ne!(
+            draw_daily_seed(&OsRandomSource, 1).unwrap(),
+            draw_daily_seed(&OsRandomSource, 1).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_load_unix_timestamp() {
+        let mut fetcher = MockFetcher::default();
+        assert_eq!(
+            load_unix_timestamp(&fetcher),
+            Err(FunctionError::AccountFetchFailed)
+        );
+
+        fetcher.insert(solana_program::sysvar::clock::ID, vec![0u8; 16]);
+        assert_eq!(
+            load_unix_timestamp(&fetcher),
+            Err(FunctionError::AccountDecodeFailed)
+        );
+
+        fetcher.insert(
+            solana_program::sysvar::clock::ID,
+            clock_account(1_704_164_400),
+        );
+        assert_eq!(load_unix_timestamp(&fetcher), Ok(1_704_164_400));
+    }
+}
@@ -0,0 +1,197 @@
+use crate::*;
+use std::time::{Duration, Instant};
+
+/// Kept free at all times for the quote generation and emit, whatever the
+/// deadline.
+pub const EMIT_RESERVE: Duration = Duration::from_secs(5);
+/// What a single account read is expected to take.
+pub const FETCH_ESTIMATE: Duration = Duration::from_secs(2);
+/// What a single simulation is expected to take.
+pub const SIMULATION_ESTIMATE: Duration = Duration::from_secs(3);
+
+/// The stages of a settlement, in the order they run.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Phase {
+    /// Reading the accounts the request type relies on.
+    Fetch,
+    /// Re-reading the picked opponent before settling against it.
+    Validate,
+    /// Fitting the settle transaction in the size budget.
+    Build,
+    /// Simulating the transaction before it is emitted.
+    Simulate,
+    Emit,
+}
+
+impl Phase {
+    pub const ALL: [Phase; 5] = [
+        Phase::Fetch,
+        Phase::Validate,
+        Phase::Build,
+        Phase::Simulate,
+        Phase::Emit,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Phase::Fetch => "fetch",
+            Phase::Validate => "validate",
+            Phase::Build => "build",
+            Phase::Simulate => "simulation",
+            Phase::Emit => "emit",
+        }
+    }
+
+    /// A settlement cannot go out without these, time is set aside for them
+    /// before any optional work in an earlier phase is admitted.
+    fn required(&self) -> bool {
+        matches!(self, Phase::Build | Phase::Emit)
+    }
+
+    /// Share in percent of the time left after `EMIT_RESERVE`.
+    fn share(&self) -> u32 {
+        match self {
+            Phase::Fetch => 50,
+            Phase::Validate => 20,
+            Phase::Build => 5,
+            Phase::Simulate => 25,
+            Phase::Emit => 0,
+        }
+    }
+
+    /// What one piece of optional work in the phase is expected to take.
+    pub fn estimate(&self) -> Duration {
+        match self {
+            Phase::Fetch | Phase::Validate => FETCH_ESTIMATE,
+            Phase::Simulate => SIMULATION_ESTIMATE,
+            Phase::Build | Phase::Emit => Duration::ZERO,
+        }
+    }
+}
+
+/// Splits the execution deadline into an allotment per phase. Optional work
+/// is only admitted while its phase is within its allotment and the required
+/// phases after it still have theirs, so a slow RPC read costs the
+/// validation or the simulation rather than the whole settlement.
+pub struct Deadline {
+    started: Instant,
+    deadline: Duration,
+    spent: [Duration; Phase::ALL.len()],
+    skipped: Vec<Phase>,
+}
+
+impl Deadline {
+    pub fn new(started: Instant, deadline: Duration) -> Self {
+        Self {
+            started,
+            deadline,
+            spent: [Duration::ZERO; Phase::ALL.len()],
+            skipped: vec![],
+        }
+    }
+
+    pub fn remaining(&self) -> Duration {
+        self.deadline.saturating_sub(self.started.elapsed())
+    }
+
+    /// The time `phase` may take, the emit always gets `EMIT_RESERVE`.
+    pub fn allotment(&self, phase: Phase) -> Duration {
+        match phase {
+            Phase::Emit => EMIT_RESERVE,
+            _ => self.deadline.saturating_sub(EMIT_RESERVE) * phase.share() / 100,
+        }
+    }
+
+    /// Time set aside for the required phases after `phase`.
+    fn reserved_after(&self, phase: Phase) -> Duration {
+        Phase::ALL
+            .iter()
+            .filter(|later| **later > phase && later.required())
+            .map(|later| self.allotment(*later))
+            .sum()
+    }
+
+    pub fn spent(&self, phase: Phase) -> Duration {
+        self.spent[phase as usize]
+    }
+
+    /// Whether another piece of optional work can run in `phase`. A refusal
+    /// is logged and remembered, see `skipped`.
+    pub fn admit(&mut self, phase: Phase) -> bool {
+        let spent = self.spent(phase);
+        let available = self.remaining().saturating_sub(self.reserved_after(phase));
+        if spent < self.allotment(phase) && available >= phase.estimate() {
+            return true;
+        }
+        println!(
+            "skipping {}: {}ms spent of its {}ms, {}ms left with {}ms reserved",
+            phase.name(),
+            spent.as_millis(),
+            self.allotment(phase).as_millis(),
+            self.remaining().as_millis(),
+            self.reserved_after(phase).as_millis()
+        );
+        record_counter("phase_skipped_total", &[("phase", phase.name())]);
+        if !self.skipped.contains(&phase) {
+            self.skipped.push(phase);
+        }
+        false
+    }
+
+    pub fn record(&mut self, phase: Phase, elapsed: Duration) {
+        self.spent[phase as usize] += elapsed;
+    }
+
+    /// The phases that had optional work refused, in the order they were.
+    pub fn skipped(&self) -> &[Phase] {
+        &self.skipped
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_allotments_split_the_deadline() {
+        let deadline = Deadline::new(Instant::now(), Duration::from_secs(25));
+
+        assert_eq!(deadline.allotment(Phase::Emit), EMIT_RESERVE);
+        assert_eq!(deadline.allotment(Phase::Fetch), Duration::from_secs(10));
+        assert_eq!(deadline.allotment(Phase::Simulate), Duration::from_secs(5));
+        let total: Duration = Phase::ALL
+            .iter()
+            .map(|phase| deadline.allotment(*phase))
+            .sum();
+        assert_eq!(total, Duration::from_secs(25));
+    }
+
+    #[test]
+    fn test_admit_keeps_the_required_phases_reserved() {
+        // a fetch that ran 13s of 20s leaves no room for a simulation before
+        // the emit, but the settlement still goes out
+        let mut deadline = Deadline::new(
+            Instant::now() - Duration::from_secs(13),
+            Duration::from_secs(20),
+        );
+        deadline.record(Phase::Fetch, Duration::from_secs(13));
+
+        assert!(!deadline.admit(Phase::Simulate));
+        assert!(deadline.remaining() >= deadline.allotment(Phase::Emit));
+        assert_eq!(deadline.skipped(), &[Phase::Simulate]);
+    }
+
+    #[test]
+    fn test_admit_refuses_a_phase_past_its_allotment() {
+        let mut deadline = Deadline::new(Instant::now(), Duration::from_secs(60));
+
+        assert!(deadline.admit(Phase::Validate));
+        deadline.record(Phase::Validate, deadline.allotment(Phase::Validate));
+
+        // plenty of time is left, but the rechecks have had their share
+        assert!(!deadline.admit(Phase::Validate));
+        assert!(!deadline.admit(Phase::Validate));
+        assert!(deadline.admit(Phase::Simulate));
+        assert_eq!(deadline.skipped(), &[Phase::Validate]);
+    }
+}
@@ -0,0 +1,86 @@
+// Hashing an instruction name for its discriminator on every settlement adds
+// up, so build.rs precomputes one for each instruction in the program's IDL.
+// Only names outside it, such as a realm's custom roll handler, are hashed.
+
+/// Snake case instruction name to its Anchor discriminator, sorted by name.
+mod table {
+    include!(concat!(env!("OUT_DIR"), "/ixn_discriminators.rs"));
+}
+
+pub use table::IXN_DISCRIMINATORS;
+
+pub const MATCHMAKING_SETTLE_DISCRIMINATOR: [u8; 8] =
+    match known_ixn_discriminator("arena_matchmaking_settle") {
+        Some(discriminator) => discriminator,
+        None => panic!("arena_matchmaking_settle is not in the program IDL"),
+    };
+
+const fn str_eq(a: &str, b: &str) -> bool {
+    let (a, b) = (a.as_bytes(), b.as_bytes());
+    if a.len() != b.len() {
+        return false;
+    }
+    let mut i = 0;
+    while i < a.len() {
+        if a[i] != b[i] {
+            return false;
+        }
+        i += 1;
+    }
+    true
+}
+
+/// The precomputed discriminator of `name`, `None` outside the IDL.
+pub const fn known_ixn_discriminator(name: &str) -> Option<[u8; 8]> {
+    let mut i = 0;
+    while i < IXN_DISCRIMINATORS.len() {
+        let (known, discriminator) = IXN_DISCRIMINATORS[i];
+        if str_eq(known, name) {
+            return Some(discriminator);
+        }
+        i += 1;
+    }
+    None
+}
+
+/// The discriminator of instruction `name`, `arena_matchmaking_settle` style.
+pub fn ixn_discriminator(name: &str) -> [u8; 8] {
+    known_ixn_discriminator(name).unwrap_or_else(|| {
+        let preimage = format!("global:{}", name);
+        solana_program::hash::hash(preimage.as_bytes()).to_bytes()[..8]
+            .try_into()
+            .unwrap()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::*;
+
+    #[test]
+    fn test_table_matches_anchor() {
+        for (name, discriminator) in IXN_DISCRIMINATORS {
+            assert_eq!(*discriminator, get_ixn_discriminator(name), "{}", name);
+        }
+        assert_eq!(
+            MATCHMAKING_SETTLE_DISCRIMINATOR,
+            get_ixn_discriminator("arena_matchmaking_settle")
+        );
+        assert!(IXN_DISCRIMINATORS.windows(2).all(|w| w[0].0 < w[1].0));
+    }
+
+    #[test]
+    fn test_table_covers_every_settle_ixn() {
+        assert_eq!(IXN_DISCRIMINATORS.len(), program_idl().instructions.len());
+        for ixn in SettleIxn::ALL {
+            assert!(known_ixn_discriminator(ixn.name()).is_some(), "{:?}", ixn);
+        }
+        // a custom roll handler is hashed
+        assert_eq!(known_ixn_discriminator("crit_roll_settle"), None);
+        assert_eq!(
+            ixn_discriminator("crit_roll_settle"),
+            get_ixn_discriminator("crit_roll_settle")
+        );
+    }
+}
@@ -0,0 +1,210 @@
+use crate::*;
+
+/// `[0, 1)` from one full range draw.
+fn unit_interval(rng: &dyn RandomSource) -> std::result::Result<f64, FunctionError> {
+    Ok(rng.generate(0, u32::MAX)? as f64 / (u32::MAX as f64 + 1.0))
+}
+
+/// A uniform u64 in the inclusive range `[min, max]`, bounds may be flipped.
+pub fn uniform(
+    rng: &dyn RandomSource,
+    min: u64,
+    max: u64,
+) -> std::result::Result<u64, FunctionError> {
+    rng.generate_u64(min, max)
+}
+
+/// A normal draw rounded to the nearest integer and clamped to `[min, max]`,
+/// so the tails pile up on the bounds rather than falling outside them.
+pub fn normal(
+    rng: &dyn RandomSource,
+    mean: f64,
+    std_dev: f64,
+    min: u64,
+    max: u64,
+) -> std::result::Result<u64, FunctionError> {
+    let (min, max) = (min.min(max), min.max(max));
+    // Box-Muller, shifted to (0, 1] so the log is finite
+    let u1 = 1.0 - unit_interval(rng)?;
+    let u2 = unit_interval(rng)?;
+    let z = (-2.0 * u1.ln()).sqrt() * (2.0 * std::f64::consts::PI * u2).cos();
+
+    let value = (mean + std_dev * z).round();
+    // the float cast saturates, so bounds near u64::MAX stay in range
+    Ok((value.clamp(min as f64, max as f64) as u64).clamp(min, max))
+}
+
+/// Index of the picked weight, each index is chosen with probability
+/// `weight / total`. Fails without a positive total that fits in a u32.
+pub fn weighted_choice(
+    rng: &dyn RandomSource,
+    weights: &[u32],
+) -> std::result::Result<usize, FunctionError> {
+    let total = match weights.iter().try_fold(0u32, |acc, w| acc.checked_add(*w)) {
+        None | Some(0) => return Err(FunctionError::InvalidParams),
+        Some(total) => total,
+    };
+
+    let roll = rng.generate(0, total - 1)?;
+    let mut cumulative = 0u32;
+    for (index, weight) in weights.iter().enumerate() {
+        cumulative += weight;
+        if roll < cumulative {
+            return Ok(index);
+        }
+    }
+    unreachable!("roll is below the total")
+}
+
+impl RollDistribution {
+    /// Draws a roll in `[min, max]` shaped by the distribution.
+    pub fn sample(
+        &self,
+        rng: &dyn RandomSource,
+        min: u64,
+        max: u64,
+    ) -> std::result::Result<u64, FunctionError> {
+        let (min, max) = (min.min(max), min.max(max));
+        match self {
+            RollDistribution::Uniform => uniform(rng, min, max),
+            RollDistribution::Normal { mean, std_dev } => {
+                normal(rng, *mean as f64, *std_dev as f64, min, max)
+            }
+            RollDistribution::Weighted(weights) => {
+                // split the bounds in one band per weight, then draw uniformly
+                // within the picked band. Bands narrower than one value
+                // collapse onto their lower edge.
+                let band = weighted_choice(rng, weights)? as u128;
+                let bands = weights.len() as u128;
+                let span = max as u128 - min as u128 + 1;
+                let low = min as u128 + band * span / bands;
+                let high = (min as u128 + (band + 1) * span / bands).saturating_sub(1);
+                uniform(rng, low as u64, high.max(low) as u64)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DRAWS: u32 = 10_000;
+
+    fn mean_and_std_dev(samples: &[u64]) -> (f64, f64) {
+        let mean = samples.iter().map(|s| *s as f64).sum::<f64>() / samples.len() as f64;
+        let variance = samples
+            .iter()
+            .map(|s| (*s as f64 - mean).powi(2))
+            .sum::<f64>()
+            / samples.len() as f64;
+        (mean, variance.sqrt())
+    }
+
+    #[test]
+    fn test_uniform_fills_every_bucket_evenly() {
+        let mut counts = [0u32; 10];
+        for _ in 0..DRAWS {
+            let value = uniform(&OsRandomSource, 0, 999).unwrap();
+            counts[(value / 100) as usize] += 1;
+        }
+
+        // 1000 expected per bucket, a fair source stays well within 20%
+        for (bucket, count) in counts.iter().enumerate() {
+            assert!(
+                (800..1_200).contains(count),
+                "bucket {} seen {} times",
+                bucket,
+                count
+            );
+        }
+    }
+
+    #[test]
+    fn test_normal_matches_mean_and_std_dev() {
+        let samples: Vec<u64> = (0..DRAWS)
+            .map(|_| normal(&OsRandomSource, 50_000.0, 10_000.0, 0, 100_000).unwrap())
+            .collect();
+
+        // the standard error of the mean is 100, of the std dev about 70
+        let (mean, std_dev) = mean_and_std_dev(&samples);
+        assert!((mean - 50_000.0).abs() < 500.0, "mean {}", mean);
+        assert!((std_dev - 10_000.0).abs() < 500.0, "std dev {}", std_dev);
+
+        // about 68% within one standard deviation
+        let within_one = samples
+            .iter()
+            .filter(|s| (40_000..=60_000).contains(*s))
+            .count();
+        assert!((6_400..7_200).contains(&within_one), "{}", within_one);
+    }
+
+    #[test]
+    fn test_normal_is_clamped() {
+        for _ in 0..1_000 {
+            let value = normal(&OsRandomSource, 50.0, 1_000.0, 40, 60).unwrap();
+            assert!((40..=60).contains(&value));
+        }
+        assert_eq!(normal(&OsRandomSource, 70.0, 0.0, 0, 100).unwrap(), 70);
+        assert_eq!(normal(&OsRandomSource, 500.0, 0.0, 0, 100).unwrap(), 100);
+        assert_eq!(
+            normal(&OsRandomSource, u64::MAX as f64, 0.0, 0, u64::MAX - 1).unwrap(),
+            u64::MAX - 1
+        );
+    }
+
+    #[test]
+    fn test_weighted_choice_follows_weights() {
+        let mut counts = [0u32; 4];
+        for _ in 0..DRAWS {
+            counts[weighted_choice(&OsRandomSource, &[1_000, 2_000, 0, 7_000]).unwrap()] += 1;
+        }
+
+        assert_eq!(counts[2], 0);
+        for (count, expected) in [(counts[0], 1_000), (counts[1], 2_000), (counts[3], 7_000)] {
+            assert!(
+                (count as i64 - expected).abs() < 300,
+                "{} seen for {} expected",
+                count,
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn test_weighted_choice_rejects_empty_weights() {
+        assert_eq!(
+            weighted_choice(&OsRandomSource, &[]),
+            Err(FunctionError::InvalidParams)
+        );
+        assert_eq!(
+            weighted_choice(&OsRandomSource, &[0, 0]),
+            Err(FunctionError::InvalidParams)
+        );
+        assert_eq!(
+            weighted_choice(&OsRandomSource, &[u32::MAX, 1]),
+            Err(FunctionError::InvalidParams)
+        );
+    }
+
+    #[test]
+    fn test_weighted_sample_draws_from_bands() {
+        let distribution = RollDistribution::Weighted(vec![64, 16, 4, 1]);
+        let mut counts = [0u32; 4];
+        for _ in 0..DRAWS {
+            let value = distribution.sample(&OsRandomSource, 1, 100_000).unwrap();
+            assert!((1..=100_000).contains(&value));
+            counts[((value - 1) / 25_000) as usize] += 1;
+        }
+
+        // 64/85 of the draws in the lowest band, about 7530
+        assert!((7_200..7_900).contains(&counts[0]), "{:?}", counts);
+        assert!(counts[0] > counts[1] && counts[1] > counts[2] && counts[2] > counts[3]);
+
+        // more bands than values, every draw still lies within the bounds
+        let narrow = RollDistribution::Weighted(vec![1; 8]);
+        for _ in 0..1_000 {
+            assert!((0..=3).contains(&narrow.sample(&OsRandomSource, 0, 3).unwrap()));
+        }
+    }
+}
@@ -0,0 +1,89 @@
+use crate::*;
+use base64::Engine;
+use serde_json::json;
+use solana_sdk::compute_budget::ComputeBudgetInstruction;
+
+/// `DRY_RUN=1` prints the settlement as JSON instead of emitting it, so
+/// integrators can check account ordering against the IDL for free.
+pub fn dry_run_enabled() -> bool {
+    setting("DRY_RUN").is_some_and(|v| v == "1")
+}
+
+fn compute_budget_to_json(data: &[u8]) -> serde_json::Value {
+    match ComputeBudgetInstruction::try_from_slice(data) {
+        Ok(ComputeBudgetInstruction::SetComputeUnitLimit(units)) => {
+            json!({ "compute_unit_limit": units })
+        }
+        Ok(ComputeBudgetInstruction::SetComputeUnitPrice(micro_lamports)) => {
+            json!({ "compute_unit_price": micro_lamports })
+        }
+        Ok(ComputeBudgetInstruction::RequestHeapFrame(bytes)) => {
+            json!({ "heap_frame": bytes })
+        }
+        _ => serde_json::Value::Null,
+    }
+}
+
+pub fn ixns_to_json(ixs: &[Instruction]) -> serde_json::Value {
+    let ixs: Vec<serde_json::Value> = ixs
+        .iter()
+        .map(|ixn| {
+            let accounts: Vec<serde_json::Value> = ixn
+                .accounts
+                .iter()
+                .map(|account| {
+                    json!({
+                        "pubkey": account.pubkey.to_string(),
+                        "is_signer": account.is_signer,
+                        "is_writable": account.is_writable,
+                    })
+                })
+                .collect();
+            let mut value = json!({
+                "program_id": ixn.program_id.to_string(),
+                "accounts": accounts,
+                "data": base64::engine::general_purpose::STANDARD.encode(&ixn.data),
+            });
+            if ixn.program_id == solana_sdk::compute_budget::id() {
+                value["compute_budget"] = compute_budget_to_json(&ixn.data);
+            }
+            value
+        })
+        .collect();
+
+    json!({ "instructions": ixs })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ixns_to_json() {
+        let settle_ixn = Instruction {
+            program_id: Pubkey::new_unique(),
+            data: vec![1, 2, 3],
+            accounts: vec![
+                AccountMeta::new_readonly(Pubkey::new_unique(), true),
+                AccountMeta::new(Pubkey::new_unique(), false),
+            ],
+        };
+        let compute_budget_ixn = Instruction::new_with_borsh(
+            solana_sdk::compute_budget::id(),
+            &ComputeBudgetInstruction::SetComputeUnitLimit(1_200_000),
+            vec![],
+        );
+
+        let value = ixns_to_json(&[compute_budget_ixn, settle_ixn.clone()]);
+
+        let ixs = value["instructions"].as_array().unwrap();
+        assert_eq!(ixs.len(), 2);
+        assert_eq!(ixs[0]["compute_budget"]["compute_unit_limit"], 1_200_000);
+        assert_eq!(ixs[1]["program_id"], settle_ixn.program_id.to_string());
+        assert_eq!(ixs[1]["data"], "AQID");
+        assert!(ixs[1].get("compute_budget").is_none());
+        assert_eq!(ixs[1]["accounts"][0]["is_signer"], true);
+        assert_eq!(ixs[1]["accounts"][0]["is_writable"], false);
+        assert_eq!(ixs[1]["accounts"][1]["is_writable"], true);
+    }
+}
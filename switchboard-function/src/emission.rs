@@ -0,0 +1,342 @@
+use crate::*;
+use solana_program::address_lookup_table_account::AddressLookupTableAccount;
+use solana_program::message::VersionedMessage;
+use solana_sdk::signature::Signature;
+use solana_sdk::transaction::VersionedTransaction;
+use switchboard_solana::{ChainResultInfo, SOLFunctionResult};
+
+// The Switchboard runner only emits legacy transactions, signed with a signer
+// it keeps private. A request's settlement is signed with the run's enclave
+// key (see enclave_key.rs) instead, in either format, so its result
+// attestation verifies against the instruction's signer: the key's SGX quote
+// is the one the verifier checks, and the function_request_verify instruction
+// the runner would prepend is rebuilt here. A routine run's batch, failure
+// reports and error codes still go out through the runner, as legacy
+// transactions, the batch carrying the attesting key in its settle args.
+
+/// How a settlement is encoded for the runner, from `TX_FORMAT`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum TxFormat {
+    #[default]
+    Legacy,
+    /// Resolves accounts through the request's address lookup table, see
+    /// lookup_table.rs.
+    V0,
+}
+
+impl FromStr for TxFormat {
+    type Err = SwitchboardError;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "legacy" => Ok(TxFormat::Legacy),
+            "v0" => Ok(TxFormat::V0),
+            _ => Err(SwitchboardError::InvalidFunctionInput),
+        }
+    }
+}
+
+impl TxFormat {
+    pub fn from_env() -> Self {
+        setting("TX_FORMAT")
+            .and_then(|format| TxFormat::from_str(&format).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            TxFormat::Legacy => "legacy",
+            TxFormat::V0 => "v0",
+        }
+    }
+}
+
+/// The format a settlement goes out in, and the lookup tables a v0 message
+/// resolves accounts through.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct MessageEncoding {
+    pub format: TxFormat,
+    pub lookup_tables: Vec<AddressLookupTableAccount>,
+}
+
+impl MessageEncoding {
+    pub fn legacy() -> Self {
+        Self::default()
+    }
+
+    /// What `ixs` weigh as a message of this format.
+    pub fn message_size(
+        &self,
+        ixs: &[Instruction],
+        payer: &Pubkey,
+    ) -> std::result::Result<usize, FunctionError> {
+        match self.format {
+            TxFormat::Legacy => Ok(message_size(ixs, payer)),
+            TxFormat::V0 => compile_v0_message(
+                ixs,
+                payer,
+                &self.lookup_tables,
+                solana_program::hash::Hash::default(),
+            )
+            .map(|message| versioned_message_size(&message)),
+        }
+    }
+
+    /// Logs the message size in both formats, so request types can be sized
+    /// for either while migrating.
+    pub fn log_sizes(&self, ixs: &[Instruction], payer: &Pubkey) {
+        let v0 = MessageEncoding {
+            format: TxFormat::V0,
+            ..self.clone()
+        }
+        .message_size(ixs, payer)
+        .map_or("failed to compile".to_string(), |size| {
+            format!("{} bytes", size)
+        });
+        println!(
+            "emitting {} message: legacy {} bytes, v0 {} with {} lookup tables",
+            self.format.name(),
+            message_size(ixs, payer),
+            v0,
+            self.lookup_tables.len()
+        );
+    }
+}
+
+/// Who signs a settlement, see above.
+#[derive(Clone, Debug, PartialEq)]
+pub enum SettlementSigner {
+    /// The runner, along with the function_verify instruction of a routine
+    /// run.
+    Runner,
+    EnclaveKey(MessageEncoding),
+}
+
+/// A transaction of `ixs` in `encoding` signed by `key` only, the payer's
+/// signature is left for the oracle like in the runner's transactions.
+pub fn partially_signed_transaction(
+    ixs: &[Instruction],
+    payer: &Pubkey,
+    key: &EnclaveKey,
+    encoding: &MessageEncoding,
+    recent_blockhash: solana_program::hash::Hash,
+) -> std::result::Result<VersionedTransaction, FunctionError> {
+    let message = match encoding.format {
+        TxFormat::Legacy => VersionedMessage::Legacy(Message::new_with_blockhash(
+            ixs,
+            Some(payer),
+            &recent_blockhash,
+        )),
+        TxFormat::V0 => compile_v0_message(ixs, payer, &encoding.lookup_tables, recent_blockhash)?,
+    };
+    let signers = message.header().num_required_signatures as usize;
+    let signer_index = message.static_account_keys()[..signers]
+        .iter()
+        .position(|signer| *signer == key.pubkey())
+        .ok_or(FunctionError::Internal)?;
+    let mut signatures = vec![Signature::default(); signers];
+    signatures[signer_index] = key.sign(&message.serialize());
+    Ok(VersionedTransaction {
+        signatures,
+        message,
+    })
+}
+
+fn emit_error(message: impl std::fmt::Display) -> SbError {
+    SbError::CustomMessage(format!("failed to build signed settlement: {}", message))
+}
+
+/// The runner's `FunctionResult` for a settlement signed with `key`.
+pub async fn signed_function_result(
+    runner: &FunctionRunner,
+    key: &EnclaveKey,
+    mut ixs: Vec<Instruction>,
+    encoding: &MessageEncoding,
+) -> std::result::Result<FunctionResult, SbError> {
+    let quote = key.quote();
+    let mr_enclave: [u8; 32] = match sgx_quote::Quote::parse(&quote) {
+        Ok(parsed) => parsed
+            .isv_report
+            .mrenclave
+            .try_into()
+            .map_err(|_| emit_error("malformed quote"))?,
+        Err(_) => {
+            println!("WARNING: no SGX quote for the enclave key, expected outside an enclave");
+            [0u8; 32]
+        }
+    };
+
+    let function_data = runner
+        .function_data
+        .as_ref()
+        .ok_or_else(|| emit_error("missing function data"))?;
+    // the oracle usually passes both, the runner reads them when it did not
+    let verifier_enclave_signer = match runner.verifier_enclave_signer {
+        Some(signer) => signer,
+        None => {
+            VerifierAccountData::fetch(&runner.client, runner.verifier)
+                .await?
+                .enclave
+                .enclave_signer
+        }
+    };
+    let queue_authority = match runner.queue_authority {
+        Some(authority) => authority,
+        None => {
+            AttestationQueueAccountData::fetch(&runner.client, function_data.attestation_queue)
+                .await?
+                .authority
+        }
+    };
+    let verify_ixn = function_request_verify_ixn(
+        runner,
+        key.pubkey(),
+        mr_enclave,
+        verifier_enclave_signer,
+        queue_authority,
+    )
+    .ok_or_else(|| emit_error("missing request data"))?;
+    ixs.insert(0, verify_ixn);
+
+    let blockhash = runner.client.get_latest_blockhash().map_err(emit_error)?;
+    let tx = partially_signed_transaction(&ixs, &runner.payer, key, encoding, blockhash)
+        .map_err(emit_error)?;
+
+    Ok(FunctionResult {
+        version: 1,
+        quote,
+        fn_key: runner.function.to_bytes().into(),
+        signer: key.pubkey().to_bytes().into(),
+        fn_request_key: runner
+            .function_request_key
+            .map(|request| request.to_bytes().to_vec())
+            .unwrap_or_default(),
+        fn_request_hash: vec![],
+        chain_result_info: ChainResultInfo::Solana(SOLFunctionResult {
+            serialized_tx: bincode::serialize(&tx).map_err(emit_error)?,
+        }),
+        error_code: 0,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn settle_ixn(signer: Pubkey, accounts: &[Pubkey]) -> Instruction {
+        let mut metas = vec![AccountMeta::new_readonly(signer, true)];
+        metas.extend(
+            accounts
+                .iter()
+                .map(|pubkey| AccountMeta::new(*pubkey, false)),
+        );
+        Instruction {
+            program_id: Pubkey::new_unique(),
+            data: vec![0u8; 64],
+            accounts: metas,
+        }
+    }
+
+    #[test]
+    fn test_tx_format_from_str() {
+        assert_eq!(TxFormat::from_str("legacy").unwrap(), TxFormat::Legacy);
+        assert_eq!(TxFormat::from_str("v0").unwrap(), TxFormat::V0);
+        assert!(TxFormat::from_str("V1").is_err());
+        assert_eq!(TxFormat::default(), TxFormat::Legacy);
+    }
+
+    #[test]
+    fn test_message_size_per_format() {
+        let payer = Pubkey::new_unique();
+        let accounts: Vec<Pubkey> = (0..12).map(|_| Pubkey::new_unique()).collect();
+        let ixs = [settle_ixn(Pubkey::new_unique(), &accounts)];
+        let lookup_table = AddressLookupTableAccount {
+            key: Pubkey::new_unique(),
+            addresses: accounts.clone(),
+        };
+
+        let legacy = MessageEncoding::legacy();
+        assert_eq!(
+            legacy.message_size(&ixs, &payer),
+            Ok(message_size(&ixs, &payer))
+        );
+        let v0 = MessageEncoding {
+            format: TxFormat::V0,
+            lookup_tables: vec![],
+        };
+        let v0_with_table = MessageEncoding {
+            format: TxFormat::V0,
+            lookup_tables: vec![lookup_table],
+        };
+        let v0_size = v0.message_size(&ixs, &payer).unwrap();
+        let v0_with_table_size = v0_with_table.message_size(&ixs, &payer).unwrap();
+        // the version prefix and an empty lookup table list
+        assert_eq!(v0_size, message_size(&ixs, &payer) + 2);
+        assert!(v0_with_table_size < v0_size - 12 * 24);
+    }
+
+    #[test]
+    fn test_partially_signed_transaction() {
+        let payer = Pubkey::new_unique();
+        let key = EnclaveKey::generate().unwrap();
+        let accounts: Vec<Pubkey> = (0..3).map(|_| Pubkey::new_unique()).collect();
+        let lookup_table = AddressLookupTableAccount {
+            key: Pubkey::new_unique(),
+            addresses: accounts.clone(),
+        };
+
+        let v0 = MessageEncoding {
+            format: TxFormat::V0,
+            lookup_tables: vec![lookup_table],
+        };
+
+        let tx = partially_signed_transaction(
+            &[settle_ixn(key.pubkey(), &accounts)],
+            &payer,
+            &key,
+            &v0,
+            solana_program::hash::Hash::new_unique(),
+        )
+        .unwrap();
+
+        assert!(matches!(tx.message, VersionedMessage::V0(_)));
+        // the payer signs first, left to the oracle
+        assert_eq!(tx.message.static_account_keys()[0], payer);
+        assert_eq!(tx.signatures.len(), 2);
+        assert_eq!(tx.signatures[0], Signature::default());
+        assert!(tx.signatures[1].verify(key.pubkey().as_ref(), &tx.message.serialize()));
+        let decoded: VersionedTransaction =
+            bincode::deserialize(&bincode::serialize(&tx).unwrap()).unwrap();
+        assert_eq!(decoded, tx);
+
+        // the key has to be one of the signers
+        assert_eq!(
+            partially_signed_transaction(
+                &[settle_ixn(Pubkey::new_unique(), &accounts)],
+                &payer,
+                &key,
+                &v0,
+                solana_program::hash::Hash::new_unique(),
+            )
+            .unwrap_err(),
+            FunctionError::Internal
+        );
+
+        // a legacy one decodes as the runner's transactions do
+        let blockhash = solana_program::hash::Hash::new_unique();
+        let tx = partially_signed_transaction(
+            &[settle_ixn(key.pubkey(), &accounts)],
+            &payer,
+            &key,
+            &MessageEncoding::legacy(),
+            blockhash,
+        )
+        .unwrap();
+        assert!(matches!(tx.message, VersionedMessage::Legacy(_)));
+        assert!(tx.signatures[1].verify(key.pubkey().as_ref(), &tx.message.serialize()));
+        let decoded: solana_sdk::transaction::Transaction =
+            bincode::deserialize(&bincode::serialize(&tx).unwrap()).unwrap();
+        assert_eq!(decoded.message.recent_blockhash, blockhash);
+        assert_eq!(decoded.signatures, tx.signatures);
+    }
+}
@@ -0,0 +1,140 @@
+use crate::*;
+use solana_sdk::signer::Signer;
+
+/// A keypair generated inside the enclave for signing off-chain payloads.
+///
+/// The runner keeps its own signer private, so this key is bound to the
+/// enclave the same way: the SGX quote commits to its pubkey, letting anyone
+/// check it was produced by a function with our MRENCLAVE.
+pub struct EnclaveKey {
+    keypair: Keypair,
+}
+
+impl EnclaveKey {
+    /// A key seeded from the enclave's entropy, retried like the run's draws.
+    pub fn generate() -> std::result::Result<Self, FunctionError> {
+        Self::generate_from(&RetryingRandomSource::new(
+            GramineRandomSource,
+            ENTROPY_ATTEMPTS,
+        ))
+    }
+
+    /// A key seeded from `rng`, the run's entropy once it was selected.
+    pub fn generate_from(rng: &dyn RandomSource) -> std::result::Result<Self, FunctionError> {
+        let mut seed = [0u8; 32];
+        rng.fill_bytes(&mut seed)
+            .map_err(|_| FunctionError::EntropyUnavailable)?;
+        let keypair = keypair_from_seed(&seed).map_err(|_| FunctionError::EntropyUnavailable)?;
+        Ok(Self { keypair })
+    }
+
+    pub fn pubkey(&self) -> Pubkey {
+        self.keypair.pubkey()
+    }
+
+    pub fn sign(&self, message: &[u8]) -> solana_sdk::signature::Signature {
+        self.keypair.sign_message(message)
+    }
+
+    /// The raw SGX quote over this key's pubkey, empty outside an enclave.
+    pub fn quote(&self) -> Vec<u8> {
+        Gramine::generate_quote(&self.pubkey().to_bytes()).unwrap_or_default()
+    }
+
+    /// An attestation of a settled roll, see attestation.rs.
+    pub fn attest_result(
+        &self,
+        request: &Pubkey,
+        random_result: u64,
+        request_slot: u64,
+    ) -> [u8; ATTESTATION_LEN] {
+        self.sign(&attestation_message(request, random_result, request_slot))
+            .into()
+    }
+}
+
+// One key signs everything a run publishes, so the quote posted with its
+// outcomes also covers the result attestations
+static RUN_KEY: std::sync::OnceLock<EnclaveKey> = std::sync::OnceLock::new();
+
+/// The run's key, seeded from `rng` unless it was generated already. A run
+/// seeds it from the entropy `select_entropy` picked, so the key gets the
+/// same retries and fallback as the rolls.
+pub fn run_enclave_key_from(
+    rng: &dyn RandomSource,
+) -> std::result::Result<&'static EnclaveKey, FunctionError> {
+    if let Some(key) = RUN_KEY.get() {
+        return Ok(key);
+    }
+    let key = EnclaveKey::generate_from(rng)?;
+    Ok(RUN_KEY.get_or_init(|| key))
+}
+
+/// The run's key, generated from the enclave's entropy on first use.
+pub fn run_enclave_key() -> std::result::Result<&'static EnclaveKey, FunctionError> {
+    run_enclave_key_from(&RetryingRandomSource::new(
+        GramineRandomSource,
+        ENTROPY_ATTEMPTS,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_enclave_key_signature_verifies() {
+        let key = EnclaveKey::generate().unwrap();
+        let signature = key.sign(b"outcome");
+
+        assert!(signature.verify(key.pubkey().as_ref(), b"outcome"));
+        assert!(!signature.verify(key.pubkey().as_ref(), b"tampered"));
+    }
+
+    #[test]
+    fn test_result_attestation_verifies() {
+        let key = run_enclave_key().unwrap();
+        assert_eq!(run_enclave_key().unwrap().pubkey(), key.pubkey());
+        let (signer, request) = (key.pubkey(), Pubkey::new_unique());
+
+        let attestation = key.attest_result(&request, 42, 1_000);
+
+        assert!(verify_result_attestation(
+            &attestation,
+            &signer,
+            &request,
+            42,
+            1_000
+        ));
+        assert!(!verify_result_attestation(
+            &attestation,
+            &signer,
+            &request,
+            43,
+            1_000
+        ));
+        assert!(!verify_result_attestation(
+            &attestation,
+            &signer,
+            &request,
+            42,
+            1_001
+        ));
+        let other_request = Pubkey::new_unique();
+        assert!(!verify_result_attestation(
+            &attestation,
+            &signer,
+            &other_request,
+            42,
+            1_000
+        ));
+        let other_signer = EnclaveKey::generate().unwrap().pubkey();
+        assert!(!verify_result_attestation(
+            &attestation,
+            &other_signer,
+            &request,
+            42,
+            1_000
+        ));
+    }
+}
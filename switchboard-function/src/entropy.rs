@@ -0,0 +1,255 @@
+use crate::*;
+use std::time::{Duration, Instant};
+
+// Gramine's entropy can fail to read, e.g. when the enclave is misconfigured
+// or the device is briefly unavailable. A run probes it before drawing,
+// backing off between attempts, and otherwise fails with
+// `EntropyUnavailable`. Reads after the probe are retried without a pause. Outside production an
+// operator may opt into settling with the OS RNG instead, which every
+// instruction of the run then flags in its header so the program and
+// players can tell such results apart.
+
+/// Reads of the enclave's entropy before it is given up on.
+pub const ENTROPY_ATTEMPTS: u32 = 3;
+
+const ENTROPY_RETRY_DELAY: Duration = Duration::from_millis(10);
+
+/// `ENTROPY_FALLBACK=1` allows the OS RNG fallback. Only honoured with
+/// `CLUSTER` explicitly set to devnet, testnet or localnet and the run
+/// reading from that cluster's own RPC, so a mainnet run can never settle
+/// with it.
+pub fn entropy_fallback_enabled(endpoint: &RpcEndpoint) -> bool {
+    if setting("ENTROPY_FALLBACK").is_none_or(|v| v != "1") {
+        return false;
+    }
+    let cluster = setting("CLUSTER").and_then(|cluster| Cluster::from_str(&cluster).ok());
+    if !cluster.as_ref().is_some_and(fallback_allowed_on) {
+        println!("ignoring ENTROPY_FALLBACK, CLUSTER is not devnet, testnet or localnet");
+        return false;
+    }
+    if !fallback_allowed_through(endpoint) {
+        println!("ignoring ENTROPY_FALLBACK, the RPC endpoint is not the cluster's public one");
+        return false;
+    }
+    true
+}
+
+pub fn fallback_allowed_on(cluster: &Cluster) -> bool {
+    matches!(
+        cluster,
+        Cluster::Devnet | Cluster::Testnet | Cluster::Localnet
+    )
+}
+
+/// A private endpoint from the secrets server or the sealed env may point at
+/// mainnet whatever `CLUSTER` says.
+pub fn fallback_allowed_through(endpoint: &RpcEndpoint) -> bool {
+    endpoint.source == EndpointSource::ClusterDefault
+}
+
+/// Reads `inner` up to `attempts` times, returning the last error. Draws run
+/// inside the settlement, so there is no pause between the attempts.
+pub struct RetryingRandomSource<S> {
+    inner: S,
+    attempts: u32,
+}
+
+impl<S: RandomSource> RetryingRandomSource<S> {
+    pub fn new(inner: S, attempts: u32) -> Self {
+        Self {
+            inner,
+            attempts: attempts.max(1),
+        }
+    }
+}
+
+impl<S: RandomSource> RandomSource for RetryingRandomSource<S> {
+    fn name(&self) -> &'static str {
+        self.inner.name()
+    }
+
+    fn fill_bytes(&self, buf: &mut [u8]) -> std::result::Result<(), FunctionError> {
+        let mut attempt = 1;
+        loop {
+            match self.inner.fill_bytes(buf) {
+                Ok(()) => return Ok(()),
+                Err(error) if attempt >= self.attempts => return Err(error),
+                Err(_) => {
+                    record_counter("entropy_retries_total", &[("source", self.name())]);
+                    attempt += 1;
+                }
+            }
+        }
+    }
+}
+
+/// The entropy a run settles with, picked once before anything is drawn so
+/// every instruction of the run carries the same flag.
+pub enum RunEntropy<S> {
+    Enclave(RetryingRandomSource<S>),
+    /// The enclave's entropy was unavailable and the fallback is enabled.
+    Fallback(OsRandomSource),
+}
+
+impl<S: RandomSource> RandomSource for RunEntropy<S> {
+    fn name(&self) -> &'static str {
+        match self {
+            RunEntropy::Enclave(source) => source.name(),
+            RunEntropy::Fallback(_) => "os-fallback",
+        }
+    }
+
+    fn fill_bytes(&self, buf: &mut [u8]) -> std::result::Result<(), FunctionError> {
+        match self {
+            RunEntropy::Enclave(source) => source.fill_bytes(buf),
+            RunEntropy::Fallback(source) => source.fill_bytes(buf),
+        }
+    }
+
+    fn is_fallback(&self) -> bool {
+        matches!(self, RunEntropy::Fallback(_))
+    }
+}
+
+/// Up to `ENTROPY_ATTEMPTS` reads of `source`, sleeping between them
+/// without holding the runtime's thread. A pause the budget cannot spare
+/// next to the emit's reserve is skipped, and a probe that had to back off
+/// is charged to the fetch phase.
+async fn probe_entropy<S: RandomSource>(
+    source: &S,
+    budget: &mut TierBudget,
+) -> std::result::Result<(), FunctionError> {
+    let started = Instant::now();
+    let mut probe = [0u8; 8];
+    let mut attempt = 1;
+    let result = loop {
+        match source.fill_bytes(&mut probe) {
+            Ok(()) => break Ok(()),
+            Err(error) if attempt >= ENTROPY_ATTEMPTS => break Err(error),
+            Err(_) => {
+                record_counter("entropy_retries_total", &[("source", source.name())]);
+                let delay = ENTROPY_RETRY_DELAY * attempt;
+                if budget.remaining() > EMIT_RESERVE + delay {
+                    tokio::time::sleep(delay).await;
+                }
+                attempt += 1;
+            }
+        }
+    };
+    if attempt > 1 {
+        budget.record(Phase::Fetch, started);
+    }
+    result
+}
+
+/// Probes `source`. When it stays unavailable the run falls back to the OS
+/// RNG if `allow_fallback`, or fails with `EntropyUnavailable`.
+pub async fn select_entropy<S: RandomSource>(
+    source: S,
+    allow_fallback: bool,
+    budget: &mut TierBudget,
+) -> std::result::Result<RunEntropy<S>, FunctionError> {
+    let source = RetryingRandomSource::new(source, ENTROPY_ATTEMPTS);
+    match probe_entropy(&source.inner, budget).await {
+        Ok(()) => Ok(RunEntropy::Enclave(source)),
+        Err(error) => {
+            println!(
+                "{} entropy unavailable after {} attempts: {}",
+                source.name(),
+                ENTROPY_ATTEMPTS,
+                error
+            );
+            if !allow_fallback {
+                return Err(FunctionError::EntropyUnavailable);
+            }
+            println!("WARNING: settling with the OS RNG, flagged in every instruction");
+            record_counter("entropy_fallback_total", &[]);
+            Ok(RunEntropy::Fallback(OsRandomSource))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_fixtures::*;
+    use std::sync::atomic::Ordering;
+
+    #[test]
+    fn test_retrying_random_source() {
+        let source = RetryingRandomSource::new(FlakySource::new(2), 3);
+        let mut buf = [0u8; 4];
+        source.fill_bytes(&mut buf).unwrap();
+        assert_eq!(buf, [7; 4]);
+        assert_eq!(source.inner.reads.load(Ordering::SeqCst), 3);
+
+        let source = RetryingRandomSource::new(FlakySource::new(3), 3);
+        assert_eq!(
+            source.fill_bytes(&mut buf),
+            Err(FunctionError::EntropyUnavailable)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_select_entropy() {
+        let mut budget = test_budget(ExecutionTier::Standard);
+        let entropy = select_entropy(FlakySource::new(1), false, &mut budget)
+            .await
+            .unwrap();
+        assert!(!entropy.is_fallback());
+        assert_eq!(entropy.name(), "flaky");
+        // the probe backed off once
+        assert_eq!(budget.stage_timings().len(), 1);
+
+        assert_eq!(
+            select_entropy(FlakySource::new(u32::MAX), false, &mut budget)
+                .await
+                .err(),
+            Some(FunctionError::EntropyUnavailable)
+        );
+
+        let entropy = select_entropy(FlakySource::new(u32::MAX), true, &mut budget)
+            .await
+            .unwrap();
+        assert!(entropy.is_fallback());
+        assert_eq!(entropy.name(), "os-fallback");
+        assert!((1..=6).contains(&entropy.generate(1, 6).unwrap()));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_probe_backs_off_unless_short_on_time() {
+        let mut budget = test_budget(ExecutionTier::Standard);
+        let started = tokio::time::Instant::now();
+        assert!(probe_entropy(&FlakySource::new(u32::MAX), &mut budget)
+            .await
+            .is_err());
+        // the paused clock only moves by the pauses
+        assert_eq!(started.elapsed(), ENTROPY_RETRY_DELAY * 3);
+        assert_eq!(budget.stage_timings().len(), 1);
+
+        // within the emit's reserve already
+        let mut budget = TierBudget::new(Instant::now(), EMIT_RESERVE, ExecutionTier::Standard);
+        let source = FlakySource::new(u32::MAX);
+        let started = tokio::time::Instant::now();
+        assert!(probe_entropy(&source, &mut budget).await.is_err());
+        assert_eq!(source.reads.load(Ordering::SeqCst), ENTROPY_ATTEMPTS);
+        assert_eq!(started.elapsed(), Duration::ZERO);
+    }
+
+    #[test]
+    fn test_fallback_is_never_allowed_on_mainnet() {
+        assert!(fallback_allowed_on(&Cluster::Devnet));
+        assert!(fallback_allowed_on(&Cluster::Localnet));
+        assert!(!fallback_allowed_on(&Cluster::Mainnet));
+        // a custom URL may well be a mainnet endpoint
+        assert!(!fallback_allowed_on(
+            &Cluster::from_str("https://rpc.example.com").unwrap()
+        ));
+        // nor is an endpoint other than the cluster's own
+        assert!(fallback_allowed_through(&RpcEndpoint::cluster_default()));
+        for source in [EndpointSource::SecretsServer, EndpointSource::SealedEnv] {
+            let endpoint = RpcEndpoint::new("https://api.devnet.solana.com", None, source);
+            assert!(!fallback_allowed_through(&endpoint));
+        }
+    }
+}
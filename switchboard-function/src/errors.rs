@@ -0,0 +1,57 @@
+/// Error codes relayed on-chain through `runner.emit_error` so the game
+/// program can tell why a request was not settled.
+#[repr(u8)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FunctionError {
+    InvalidParams = 1,
+    /// Catch-all for failures without a dedicated code.
+    Internal = 2,
+    EmitFailed = 3,
+    AccountFetchFailed = 4,
+    AccountDecodeFailed = 5,
+    NoMatchingSubPool = 6,
+    NoEligibleOpponent = 7,
+    TransactionTooLarge = 8,
+    EntropyUnavailable = 9,
+    SimulationFailed = 10,
+    ApprovalMismatch = 11,
+    ApprovalNotApproved = 12,
+    ProgramNotAllowed = 13,
+    UnsupportedParamsVersion = 14,
+    OpponentUnavailable = 15,
+    /// The runner was started without a function request to settle.
+    MissingRequestData = 16,
+    /// The params carried a `CHECKSUM` that does not match their bytes.
+    ParamsChecksumMismatch = 17,
+    /// The request sat in the queue longer than `MAX_REQUEST_AGE_SLOTS`.
+    RequestExpired = 18,
+    /// The request or the requester's spaceship was already settled, e.g.
+    /// by a previous run of a retried request.
+    AlreadySettled = 19,
+    /// A settle instruction did not match the embedded program IDL.
+    IdlMismatch = 20,
+    /// The user made more requests than `RATE_LIMIT` allows.
+    RateLimited = 21,
+    /// The `FUNCTION_CONFIG` file is missing, malformed or invalid.
+    InvalidConfig = 22,
+    /// The settlement panicked, the run's logs carry the message and where.
+    Panicked = 23,
+    /// The current slot is outside the request's `VALID_AFTER_SLOT` and
+    /// `VALID_BEFORE_SLOT` window.
+    OutsideSlotWindow = 24,
+    /// A sweep found a newer matchmaking request for the same spaceship, the
+    /// player queued again before this one settled.
+    Superseded = 25,
+}
+
+impl FunctionError {
+    pub fn code(&self) -> u8 {
+        *self as u8
+    }
+}
+
+impl std::fmt::Display for FunctionError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:?} (code {})", self, self.code())
+    }
+}
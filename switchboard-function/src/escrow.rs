@@ -0,0 +1,163 @@
+use crate::*;
+use serde::Serialize;
+
+const ESCROW_ALERT_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(3);
+
+/// The `amount` of an SPL token account, the escrow wallet holds wrapped SOL.
+const TOKEN_ACCOUNT_AMOUNT: std::ops::Range<usize> = 64..72;
+
+/// Every settlement is paid out of the function's escrow and stops once it
+/// runs dry. Below `ESCROW_MIN_BALANCE` lamports a run warns the operator so
+/// it is topped up before players notice.
+pub fn escrow_min_balance_from_env() -> Option<u64> {
+    setting("ESCROW_MIN_BALANCE").and_then(|balance| balance.parse().ok())
+}
+
+/// The operator endpoint notified of a low escrow, from the
+/// `ESCROW_ALERT_WEBHOOK_URL` env var.
+pub fn escrow_alert_webhook_url_from_env() -> Option<String> {
+    setting("ESCROW_ALERT_WEBHOOK_URL").filter(|url| !url.is_empty())
+}
+
+pub fn fetch_escrow_balance<F: AccountFetcher + ?Sized>(
+    fetcher: &F,
+    escrow_token_wallet: &Pubkey,
+) -> std::result::Result<u64, FunctionError> {
+    let data = fetcher
+        .fetch_multiple_account_data(std::slice::from_ref(escrow_token_wallet))?
+        .pop()
+        .flatten()
+        .ok_or(FunctionError::AccountFetchFailed)?;
+    data.get(TOKEN_ACCOUNT_AMOUNT)
+        .map(|amount| u64::from_le_bytes(amount.try_into().unwrap()))
+        .ok_or(FunctionError::AccountFetchFailed)
+}
+
+/// The body POSTed to the escrow alert webhook.
+#[derive(Serialize, Clone, Debug, PartialEq, Eq)]
+pub struct EscrowAlert {
+    pub function: String,
+    pub escrow_token_wallet: String,
+    pub balance: u64,
+    pub min_balance: u64,
+}
+
+/// Logs the escrow balance and records it as a gauge. Returns an alert when
+/// it is below `min_balance`. Best effort, a failed read never stops the run.
+pub fn check_escrow_balance<F: AccountFetcher + ?Sized>(
+    fetcher: &F,
+    function: &Pubkey,
+    escrow_token_wallet: &Pubkey,
+    min_balance: Option<u64>,
+) -> Option<EscrowAlert> {
+    let balance = match fetch_escrow_balance(fetcher, escrow_token_wallet) {
+        Ok(balance) => balance,
+        Err(error) => {
+            println!(
+                "failed to read escrow {} balance: {}",
+                escrow_token_wallet, error
+            );
+            return None;
+        }
+    };
+    println!(
+        "escrow {} balance: {} lamports",
+        escrow_token_wallet, balance
+    );
+    record_gauge("escrow_balance_lamports", balance as f64, &[]);
+
+    let min_balance = min_balance.filter(|min_balance| balance < *min_balance)?;
+    println!(
+        "warning: escrow balance {} lamports is below {} lamports, settlements stop once it runs dry",
+        balance, min_balance
+    );
+    record_counter("escrow_low_total", &[]);
+    Some(EscrowAlert {
+        function: function.to_string(),
+        escrow_token_wallet: escrow_token_wallet.to_string(),
+        balance,
+        min_balance,
+    })
+}
+
+/// Best effort like the outcome webhook: failures are logged and never affect
+/// the settlement.
+pub async fn post_escrow_alert(url: &str, alert: &EscrowAlert) {
+    let client = match reqwest::Client::builder()
+        .timeout(ESCROW_ALERT_TIMEOUT)
+        .build()
+    {
+        Ok(client) => client,
+        Err(error) => {
+            println!("failed to build escrow alert client: {}", error);
+            return;
+        }
+    };
+
+    match client.post(url).json(alert).send().await {
+        Ok(response) if response.status().is_success() => (),
+        Ok(response) => println!("escrow alert webhook returned {}", response.status()),
+        Err(error) => println!("failed to post escrow alert webhook: {}", error),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_fixtures::*;
+
+    fn token_account(amount: u64) -> Vec<u8> {
+        let mut data = vec![0; 165];
+        data[TOKEN_ACCOUNT_AMOUNT].copy_from_slice(&amount.to_le_bytes());
+        data
+    }
+
+    #[test]
+    fn test_fetch_escrow_balance() {
+        let wallet = Pubkey::new_unique();
+        let mut fetcher = MockFetcher::default();
+
+        assert_eq!(
+            fetch_escrow_balance(&fetcher, &wallet),
+            Err(FunctionError::AccountFetchFailed)
+        );
+        fetcher.insert(wallet, vec![0; 40]);
+        assert_eq!(
+            fetch_escrow_balance(&fetcher, &wallet),
+            Err(FunctionError::AccountFetchFailed)
+        );
+        fetcher.insert(wallet, token_account(1_500_000));
+        assert_eq!(fetch_escrow_balance(&fetcher, &wallet), Ok(1_500_000));
+    }
+
+    #[test]
+    fn test_check_escrow_balance_alerts_below_threshold() {
+        let function = Pubkey::new_unique();
+        let wallet = Pubkey::new_unique();
+        let mut fetcher = MockFetcher::default();
+        fetcher.insert(wallet, token_account(40_000));
+
+        assert_eq!(
+            check_escrow_balance(&fetcher, &function, &wallet, None),
+            None
+        );
+        assert_eq!(
+            check_escrow_balance(&fetcher, &function, &wallet, Some(40_000)),
+            None
+        );
+        assert_eq!(
+            check_escrow_balance(&fetcher, &function, &wallet, Some(40_001)),
+            Some(EscrowAlert {
+                function: function.to_string(),
+                escrow_token_wallet: wallet.to_string(),
+                balance: 40_000,
+                min_balance: 40_001,
+            })
+        );
+        // an unreadable escrow is logged, not alerted on
+        assert_eq!(
+            check_escrow_balance(&MockFetcher::default(), &function, &wallet, Some(u64::MAX)),
+            None
+        );
+    }
+}
@@ -0,0 +1,120 @@
+use crate::*;
+
+/// Oldest request settled when `MAX_REQUEST_AGE_SLOTS` is unset, about two
+/// minutes of slots. Spaceship state has usually moved on by then.
+pub const DEFAULT_MAX_REQUEST_AGE_SLOTS: u64 = 300;
+
+pub fn max_request_age_from_env() -> u64 {
+    setting("MAX_REQUEST_AGE_SLOTS")
+        .and_then(|value| value.parse::<u64>().ok())
+        .unwrap_or(DEFAULT_MAX_REQUEST_AGE_SLOTS)
+}
+
+/// Reads the current slot, `None` when the RPC cannot tell. Requests are
+/// then not checked for expiry rather than all rejected.
+pub fn fetch_current_slot(client: &solana_client::rpc_client::RpcClient) -> Option<u64> {
+    client
+        .get_slot()
+        .inspect_err(|error| println!("failed to fetch the current slot: {}", error))
+        .ok()
+}
+
+/// The slot requests are aged against.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RequestExpiry {
+    pub current_slot: u64,
+    pub max_age_slots: u64,
+}
+
+impl RequestExpiry {
+    pub fn at(current_slot: u64) -> Self {
+        Self {
+            current_slot,
+            max_age_slots: max_request_age_from_env(),
+        }
+    }
+
+    /// Fails for a request published more than `max_age_slots` ago.
+    pub fn check(&self, request_slot: u64) -> std::result::Result<(), FunctionError> {
+        let age = self.current_slot.saturating_sub(request_slot);
+        if age > self.max_age_slots {
+            println!(
+                "request published at slot {} is {} slots old (max {})",
+                request_slot, age, self.max_age_slots
+            );
+            return Err(FunctionError::RequestExpired);
+        }
+        Ok(())
+    }
+}
+
+/// Fails outside the request's slot window, e.g. of a game event. Unlike
+/// expiry the window is a game rule, so a windowed request is not settled
+/// when the current slot is unknown.
+pub fn check_slot_window(
+    params: &ContainerParams,
+    current_slot: Option<u64>,
+) -> std::result::Result<(), FunctionError> {
+    if params.valid_after_slot.is_none() && params.valid_before_slot.is_none() {
+        return Ok(());
+    }
+    let current_slot = current_slot.ok_or(FunctionError::AccountFetchFailed)?;
+    if params
+        .valid_after_slot
+        .is_some_and(|after| current_slot <= after)
+        || params
+            .valid_before_slot
+            .is_some_and(|before| current_slot >= before)
+    {
+        println!(
+            "slot {} is outside the request's window after {:?} and before {:?}",
+            current_slot, params.valid_after_slot, params.valid_before_slot
+        );
+        record_counter("outside_slot_window_total", &[]);
+        return Err(FunctionError::OutsideSlotWindow);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_request_expiry() {
+        let expiry = RequestExpiry {
+            current_slot: 1_000,
+            max_age_slots: 300,
+        };
+
+        assert_eq!(expiry.check(1_000), Ok(()));
+        assert_eq!(expiry.check(700), Ok(()));
+        assert_eq!(expiry.check(699), Err(FunctionError::RequestExpired));
+        // published after the slot the RPC reported, a lagging node
+        assert_eq!(expiry.check(1_010), Ok(()));
+    }
+
+    #[test]
+    fn test_check_slot_window() {
+        let mut params = crate::test_fixtures::test_params();
+        assert_eq!(check_slot_window(&params, None), Ok(()));
+
+        params.valid_after_slot = Some(100);
+        assert_eq!(
+            check_slot_window(&params, Some(100)),
+            Err(FunctionError::OutsideSlotWindow)
+        );
+        assert_eq!(check_slot_window(&params, Some(101)), Ok(()));
+
+        params.valid_before_slot = Some(200);
+        assert_eq!(check_slot_window(&params, Some(199)), Ok(()));
+        assert_eq!(
+            check_slot_window(&params, Some(200)),
+            Err(FunctionError::OutsideSlotWindow)
+        );
+        assert_eq!(
+            check_slot_window(&params, None),
+            Err(FunctionError::AccountFetchFailed)
+        );
+    }
+}
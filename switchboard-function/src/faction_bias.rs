@@ -0,0 +1,229 @@
+use crate::*;
+
+// A faction that keeps losing empties out of the arena. With `FACTION_BIAS`
+// set, the matchmaking roll of a faction winning less than half of its
+// matches is pushed toward `MAX`, one winning more toward `MIN`, by how far
+// the realm's stats put it from an even win rate. The curve is integer only
+// so the program and players can recompute the settled roll from the raw
+// roll and the bias parameters the settle instruction carries.
+
+/// Even, the win rate the curve leaves the roll alone at.
+pub const EVEN_WIN_RATE_BPS: u16 = 5_000;
+/// Factions with fewer matches recorded are not biased by default.
+pub const DEFAULT_FACTION_BIAS_MIN_MATCHES: u32 = 100;
+
+/// From `FACTION_BIAS=<strength_bps>[:<min_matches>]`, a strength of 10000
+/// is the steepest curve that still maps the range onto itself. Unset, rolls
+/// are settled as drawn.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct FactionBias {
+    pub strength_bps: u16,
+    pub min_matches: u32,
+}
+
+impl FromStr for FactionBias {
+    type Err = FunctionError;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        let (strength_bps, min_matches) = match s.split_once(':') {
+            Some((strength_bps, min_matches)) => (
+                strength_bps,
+                min_matches
+                    .parse()
+                    .map_err(|_| FunctionError::InvalidParams)?,
+            ),
+            None => (s, DEFAULT_FACTION_BIAS_MIN_MATCHES),
+        };
+        let strength_bps: u16 = strength_bps
+            .parse()
+            .map_err(|_| FunctionError::InvalidParams)?;
+        if strength_bps > 10_000 {
+            return Err(FunctionError::InvalidParams);
+        }
+        Ok(FactionBias {
+            strength_bps,
+            min_matches,
+        })
+    }
+}
+
+impl FactionBias {
+    pub fn from_env() -> Option<Self> {
+        let value = setting("FACTION_BIAS")?;
+        match FactionBias::from_str(&value) {
+            Ok(bias) => Some(bias),
+            Err(_) => {
+                println!("ignoring invalid FACTION_BIAS {}", value);
+                None
+            }
+        }
+    }
+}
+
+/// The settled roll and what it was shaped with. A roll no bias applied to
+/// is settled with a zero strength and an even win rate.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ShapedRoll {
+    pub raw_result: u64,
+    pub random_result: u64,
+    pub bias_strength_bps: u16,
+    pub faction_win_rate_bps: u16,
+}
+
+impl ShapedRoll {
+    pub fn unbiased(raw_result: u64) -> Self {
+        Self {
+            raw_result,
+            random_result: raw_result,
+            bias_strength_bps: 0,
+            faction_win_rate_bps: EVEN_WIN_RATE_BPS,
+        }
+    }
+}
+
+/// With `x` the roll's offset from `min` and `s` the span of the range,
+/// settles `x + k * (x * (s - x) / s)` where `k` is the strength times the
+/// win rate's distance from even, each division rounding toward zero. For
+/// `|k| <= 1` the curve is monotonic and keeps `min` and `max` in place.
+pub fn shape_roll(
+    bias: Option<&FactionBias>,
+    stats: Option<&FactionStats>,
+    raw_result: u64,
+    min: u64,
+    max: u64,
+) -> ShapedRoll {
+    let (Some(bias), Some(stats)) = (bias, stats) else {
+        return ShapedRoll::unbiased(raw_result);
+    };
+    if stats.matches < bias.min_matches.max(1) || bias.strength_bps == 0 {
+        return ShapedRoll::unbiased(raw_result);
+    }
+    let win_rate_bps = stats.win_rate_bps();
+
+    let x = (raw_result - min) as u128;
+    let s = (max - min) as u128;
+    let bend = (x * (s - x)).checked_div(s).unwrap_or(0) as i128;
+    let shift =
+        bend * (EVEN_WIN_RATE_BPS as i128 - win_rate_bps as i128) * bias.strength_bps as i128
+            / (EVEN_WIN_RATE_BPS as i128 * 10_000);
+    ShapedRoll {
+        raw_result,
+        random_result: (min as i128 + x as i128 + shift) as u64,
+        bias_strength_bps: bias.strength_bps,
+        faction_win_rate_bps: win_rate_bps,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stats(matches: u32, wins: u32) -> FactionStats {
+        FactionStats { matches, wins }
+    }
+
+    #[test]
+    fn test_faction_bias_from_str() {
+        assert_eq!(
+            FactionBias::from_str("2500").unwrap(),
+            FactionBias {
+                strength_bps: 2_500,
+                min_matches: DEFAULT_FACTION_BIAS_MIN_MATCHES,
+            }
+        );
+        assert_eq!(
+            FactionBias::from_str("10000:20").unwrap(),
+            FactionBias {
+                strength_bps: 10_000,
+                min_matches: 20,
+            }
+        );
+        for invalid in ["", "10001", "-1", "2500:", "2500:x", "25%"] {
+            assert!(FactionBias::from_str(invalid).is_err(), "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn test_shape_roll_by_hand() {
+        let bias = FactionBias {
+            strength_bps: 10_000,
+            min_matches: 10,
+        };
+        // a faction winning a quarter of its matches, k = 1/2: the middle of
+        // 0..=100 bends by half of 50 * 50 / 100
+        let shaped = shape_roll(Some(&bias), Some(&stats(40, 10)), 50, 0, 100);
+        assert_eq!(
+            shaped,
+            ShapedRoll {
+                raw_result: 50,
+                random_result: 62,
+                bias_strength_bps: 10_000,
+                faction_win_rate_bps: 2_500,
+            }
+        );
+        // a faction winning three quarters bends the other way, offset by `min`
+        assert_eq!(
+            shape_roll(Some(&bias), Some(&stats(40, 30)), 1_050, 1_000, 1_100).random_result,
+            1_038
+        );
+        // the ends of the range stay in place
+        assert_eq!(
+            shape_roll(Some(&bias), Some(&stats(40, 0)), 100, 0, 100).random_result,
+            100
+        );
+        assert_eq!(
+            shape_roll(Some(&bias), Some(&stats(40, 40)), 0, 0, 100).random_result,
+            0
+        );
+    }
+
+    #[test]
+    fn test_shape_roll_leaves_unbiased_rolls_alone() {
+        let bias = FactionBias {
+            strength_bps: 10_000,
+            min_matches: 10,
+        };
+
+        assert_eq!(
+            shape_roll(None, Some(&stats(40, 0)), 50, 0, 100),
+            ShapedRoll::unbiased(50)
+        );
+        assert_eq!(
+            shape_roll(Some(&bias), None, 50, 0, 100),
+            ShapedRoll::unbiased(50)
+        );
+        // too few matches to judge the faction by
+        assert_eq!(
+            shape_roll(Some(&bias), Some(&stats(9, 0)), 50, 0, 100),
+            ShapedRoll::unbiased(50)
+        );
+        // an even faction is shaped with an identity curve
+        assert_eq!(
+            shape_roll(Some(&bias), Some(&stats(40, 20)), 50, 0, 100).random_result,
+            50
+        );
+        assert_eq!(
+            shape_roll(Some(&bias), Some(&stats(40, 0)), 7, 7, 7).random_result,
+            7
+        );
+    }
+
+    #[test]
+    fn test_shape_roll_stays_monotonic_and_in_range() {
+        let bias = FactionBias {
+            strength_bps: 10_000,
+            min_matches: 1,
+        };
+        for wins in [0, 13, 50, 87, 100] {
+            let mut previous = 0;
+            for raw in 0..=1_000u64 {
+                let shaped = shape_roll(Some(&bias), Some(&stats(100, wins)), raw, 0, 1_000);
+                assert!(shaped.random_result <= 1_000);
+                assert!(shaped.random_result >= previous, "{} {}", wins, raw);
+                previous = shaped.random_result;
+            }
+        }
+        let full = shape_roll(Some(&bias), Some(&stats(100, 0)), u64::MAX / 2, 0, u64::MAX);
+        assert!(full.random_result > u64::MAX / 2);
+    }
+}
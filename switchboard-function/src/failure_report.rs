@@ -0,0 +1,142 @@
+use crate::*;
+use serde::Serialize;
+
+/// Reasons longer than this are truncated, the report only needs to tell a
+/// player or an operator what went wrong.
+pub const MAX_FAILURE_REASON_LEN: usize = 64;
+
+/// `FAILURE_REPORTS=1` reports failed matchmaking requests through
+/// `arena_matchmaking_report_failure` instead of a bare error code, for
+/// programs that handle it.
+pub fn failure_reports_enabled() -> bool {
+    setting("FAILURE_REPORTS").is_some_and(|v| v == "1")
+}
+
+#[derive(AnchorSerialize, Serialize, Clone, Debug, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct FailureReportArgs {
+    pub error_code: u8,
+    /// UTF-8, at most `MAX_FAILURE_REASON_LEN` bytes.
+    pub reason: Vec<u8>,
+}
+
+/// Why the request was rejected, for the failures that mean it will never
+/// settle. Transient failures are left to a retry, and settled requests must
+/// not be refunded.
+pub fn failure_reason(error: FunctionError) -> Option<&'static str> {
+    let reason = match error {
+        FunctionError::InvalidParams => "invalid request params",
+        FunctionError::NoMatchingSubPool => "rating outside every sub-pool of the realm",
+        FunctionError::NoEligibleOpponent => "no eligible opponent among the candidates",
+        FunctionError::OpponentUnavailable => "every eligible opponent was matched meanwhile",
+        FunctionError::SimulationFailed => "settlement failed in simulation",
+        FunctionError::ApprovalMismatch => "settlement approval is for another request",
+        FunctionError::ApprovalNotApproved => "settlement approval is not approved",
+        FunctionError::ProgramNotAllowed => "program is not allowed by the function",
+        FunctionError::UnsupportedParamsVersion => "unsupported params version",
+        FunctionError::ParamsChecksumMismatch => "params checksum mismatch",
+        FunctionError::RequestExpired => "request expired in the queue",
+        FunctionError::RateLimited => "too many requests from the user",
+        FunctionError::OutsideSlotWindow => "settled outside the request's slot window",
+        FunctionError::Superseded => "superseded by a newer request for the spaceship",
+        _ => return None,
+    };
+    Some(reason)
+}
+
+/// A failure report for a matchmaking request, `None` for other request
+/// types and for failures without a reason. It carries the request's
+/// idempotency token, so the program accepts either the settlement or the
+/// report but never both.
+pub fn failure_report_ixn(
+    params: &ContainerParams,
+    runner_accounts: &RunnerAccounts,
+    error: FunctionError,
+) -> std::result::Result<Option<Instruction>, FunctionError> {
+    let reason = match (params.request_type, failure_reason(error)) {
+        (RequestType::Matchmaking, Some(reason)) => reason,
+        _ => return Ok(None),
+    };
+    let mut reason = reason.as_bytes().to_vec();
+    reason.truncate(MAX_FAILURE_REASON_LEN);
+
+    // nothing is drawn for a failure
+    let header = SettleHeader::new(
+        &runner_accounts.function_request,
+        ExecutionTier::Fast,
+        false,
+    );
+    let args = FailureReportArgs {
+        error_code: error.code(),
+        reason,
+    };
+    arena_matchmaking_report_failure_ixn(params, runner_accounts, &header, &args).map(Some)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_fixtures::*;
+
+    #[test]
+    fn test_failure_report_ixn() {
+        let params = test_params();
+        let runner_accounts = test_runner_accounts();
+
+        let ixn = failure_report_ixn(&params, &runner_accounts, FunctionError::NoEligibleOpponent)
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(
+            ixn.data[..8],
+            get_ixn_discriminator("arena_matchmaking_report_failure")
+        );
+        assert_eq!(
+            ixn.data[9..41],
+            idempotency_token(&runner_accounts.function_request, ARGS_VERSION)
+        );
+        assert_eq!(ixn.data[43], FunctionError::NoEligibleOpponent.code());
+        let reason = failure_reason(FunctionError::NoEligibleOpponent).unwrap();
+        assert_eq!(ixn.data[44..48], (reason.len() as u32).to_le_bytes());
+        assert_eq!(&ixn.data[48..], reason.as_bytes());
+        assert!(ixn.accounts[1].is_writable);
+    }
+
+    #[test]
+    fn test_failure_report_only_for_final_matchmaking_failures() {
+        let params = test_params();
+        let runner_accounts = test_runner_accounts();
+
+        for error in [
+            FunctionError::AlreadySettled,
+            FunctionError::AccountFetchFailed,
+            FunctionError::EmitFailed,
+        ] {
+            assert_eq!(
+                failure_report_ixn(&params, &runner_accounts, error),
+                Ok(None)
+            );
+        }
+
+        let mut loot_open = test_params();
+        loot_open.request_type = RequestType::LootOpen;
+        assert_eq!(
+            failure_report_ixn(&loot_open, &runner_accounts, FunctionError::InvalidParams),
+            Ok(None)
+        );
+    }
+
+    #[test]
+    fn test_failure_reasons_fit() {
+        for error in [
+            FunctionError::InvalidParams,
+            FunctionError::NoMatchingSubPool,
+            FunctionError::OpponentUnavailable,
+            FunctionError::ApprovalMismatch,
+            FunctionError::RequestExpired,
+            FunctionError::RateLimited,
+        ] {
+            assert!(failure_reason(error).unwrap().len() <= MAX_FAILURE_REASON_LEN);
+        }
+    }
+}
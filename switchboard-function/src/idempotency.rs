@@ -0,0 +1,76 @@
+use crate::*;
+
+// The oracle may retry a request we already settled. The on-chain handlers
+// reject a repeated idempotency token, these checks keep the duplicate
+// settlement from being built in the first place.
+
+/// Fails once the request's settlement landed.
+pub fn check_request_pending(
+    round: &FunctionRequestTriggerRound,
+) -> std::result::Result<(), FunctionError> {
+    if round.status == RequestStatus::RequestSuccess {
+        println!("request already fulfilled at slot {}", round.fulfilled_slot);
+        return Err(FunctionError::AlreadySettled);
+    }
+    Ok(())
+}
+
+/// The requester's spaceship stays queued for matchmaking until a
+/// settlement assigns it a match, by a previous run of this request or by
+/// another request for the same spaceship.
+pub fn check_matchmaking_queued(
+    spaceship: &Spaceship,
+    function_request: &Pubkey,
+) -> std::result::Result<(), FunctionError> {
+    match spaceship.current_match {
+        None => Ok(()),
+        Some(current_match) => {
+            println!(
+                "spaceship is no longer queued, matched by {}{}",
+                current_match,
+                if current_match == *function_request {
+                    " (this request)"
+                } else {
+                    ""
+                }
+            );
+            Err(FunctionError::AlreadySettled)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_fixtures::*;
+
+    #[test]
+    fn test_check_request_pending() {
+        let mut round = FunctionRequestTriggerRound {
+            status: RequestStatus::RequestPending,
+            ..Default::default()
+        };
+        assert_eq!(check_request_pending(&round), Ok(()));
+
+        round.status = RequestStatus::RequestSuccess;
+        assert_eq!(
+            check_request_pending(&round),
+            Err(FunctionError::AlreadySettled)
+        );
+    }
+
+    #[test]
+    fn test_check_matchmaking_queued() {
+        let request = Pubkey::new_unique();
+        let mut spaceship = test_spaceship(1_000);
+        assert_eq!(check_matchmaking_queued(&spaceship, &request), Ok(()));
+
+        for current_match in [request, Pubkey::new_unique()] {
+            spaceship.current_match = Some(current_match);
+            assert_eq!(
+                check_matchmaking_queued(&spaceship, &request),
+                Err(FunctionError::AlreadySettled)
+            );
+        }
+    }
+}
@@ -0,0 +1,312 @@
+use crate::*;
+use serde::Deserialize;
+use serde_json::Value;
+use std::sync::OnceLock;
+
+/// The game program's Anchor IDL, embedded when the function is built so the
+/// instructions we emit are derived from the same file the program publishes.
+pub const PROGRAM_IDL: &str = include_str!("../idl/arena_imperium.json");
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct Idl {
+    pub name: String,
+    pub instructions: Vec<IdlInstruction>,
+    #[serde(default)]
+    pub types: Vec<IdlTypeDef>,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct IdlAccount {
+    pub name: String,
+    pub is_mut: bool,
+    pub is_signer: bool,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct IdlField {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub ty: IdlType,
+}
+
+#[derive(Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(untagged)]
+pub enum IdlType {
+    /// `u8`, `u64`, `bool`, `bytes`, `publicKey`...
+    Primitive(String),
+    Defined {
+        defined: String,
+    },
+    Array {
+        array: (Box<IdlType>, usize),
+    },
+    Vec {
+        vec: Box<IdlType>,
+    },
+    Option {
+        option: Box<IdlType>,
+    },
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct IdlTypeDef {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub ty: IdlTypeDefBody,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct IdlTypeDefBody {
+    pub kind: String,
+    #[serde(default)]
+    pub fields: Vec<IdlField>,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct IdlInstruction {
+    pub name: String,
+    pub accounts: Vec<IdlAccount>,
+    pub args: Vec<IdlField>,
+}
+
+/// `arenaMatchmakingSettle` to `arena_matchmaking_settle`, the name Anchor
+/// hashes into the discriminator.
+fn snake_case(name: &str) -> String {
+    let mut snake = String::with_capacity(name.len() + 4);
+    for c in name.chars() {
+        if c.is_ascii_uppercase() {
+            snake.push('_');
+            snake.push(c.to_ascii_lowercase());
+        } else {
+            snake.push(c);
+        }
+    }
+    snake
+}
+
+/// The embedded IDL, parsed on first use.
+pub fn program_idl() -> &'static Idl {
+    static IDL: OnceLock<Idl> = OnceLock::new();
+    IDL.get_or_init(|| serde_json::from_str(PROGRAM_IDL).expect("embedded IDL is valid"))
+}
+
+impl Idl {
+    /// Looks an instruction up by its snake case name.
+    pub fn instruction(&self, name: &str) -> std::result::Result<&IdlInstruction, FunctionError> {
+        self.instructions
+            .iter()
+            .find(|ixn| snake_case(&ixn.name) == name)
+            .ok_or_else(|| {
+                println!("{} is not in the {} IDL", name, self.name);
+                FunctionError::IdlMismatch
+            })
+    }
+
+    fn type_def(&self, name: &str) -> std::result::Result<&IdlTypeDef, FunctionError> {
+        self.types
+            .iter()
+            .find(|type_def| type_def.name == name)
+            .ok_or(FunctionError::IdlMismatch)
+    }
+
+    /// Borsh encodes `value` the way the IDL declares `ty`. Fields are looked
+    /// up by their IDL name, so a renamed or missing field fails rather than
+    /// shifting the rest of the layout.
+    fn encode(
+        &self,
+        ty: &IdlType,
+        value: &Value,
+        out: &mut Vec<u8>,
+    ) -> std::result::Result<(), FunctionError> {
+        let mismatch = || FunctionError::IdlMismatch;
+        match ty {
+            IdlType::Primitive(primitive) => match primitive.as_str() {
+                "bool" => out.push(value.as_bool().ok_or_else(mismatch)? as u8),
+                "u8" | "u16" | "u32" | "u64" => {
+                    let width = match primitive.as_str() {
+                        "u8" => 1,
+                        "u16" => 2,
+                        "u32" => 4,
+                        _ => 8,
+                    };
+                    let number = value.as_u64().ok_or_else(mismatch)?;
+                    if width < 8 && number >> (width * 8) != 0 {
+                        return Err(mismatch());
+                    }
+                    out.extend_from_slice(&number.to_le_bytes()[..width]);
+                }
+                "bytes" => self.encode(
+                    &IdlType::Vec {
+                        vec: Box::new(IdlType::Primitive("u8".to_string())),
+                    },
+                    value,
+                    out,
+                )?,
+                "publicKey" => {
+                    let pubkey = Pubkey::from_str(value.as_str().ok_or_else(mismatch)?)
+                        .map_err(|_| mismatch())?;
+                    out.extend_from_slice(pubkey.as_ref());
+                }
+                _ => return Err(mismatch()),
+            },
+            IdlType::Defined { defined } => {
+                let type_def = self.type_def(defined)?;
+                if type_def.ty.kind != "struct" {
+                    return Err(mismatch());
+                }
+                for field in type_def.ty.fields.iter() {
+                    self.encode(&field.ty, value.get(&field.name).ok_or_else(mismatch)?, out)?;
+                }
+            }
+            IdlType::Array { array: (ty, len) } => {
+                let items = value.as_array().ok_or_else(mismatch)?;
+                if items.len() != *len {
+                    return Err(mismatch());
+                }
+                for item in items {
+                    self.encode(ty, item, out)?;
+                }
+            }
+            IdlType::Vec { vec: ty } => {
+                let items = value.as_array().ok_or_else(mismatch)?;
+                out.extend_from_slice(&(items.len() as u32).to_le_bytes());
+                for item in items {
+                    self.encode(ty, item, out)?;
+                }
+            }
+            IdlType::Option { option: ty } => match value {
+                Value::Null => out.push(0),
+                value => {
+                    out.push(1);
+                    self.encode(ty, value, out)?;
+                }
+            },
+        }
+        Ok(())
+    }
+}
+
+impl IdlInstruction {
+    pub fn discriminator(&self) -> [u8; 8] {
+        ixn_discriminator(&snake_case(&self.name))
+    }
+
+    /// The discriminator followed by every arg, `args` maps the IDL arg names
+    /// to their values.
+    pub fn encode_data(
+        &self,
+        idl: &Idl,
+        args: &Value,
+    ) -> std::result::Result<Vec<u8>, FunctionError> {
+        let mut data = self.discriminator().to_vec();
+        for arg in self.args.iter() {
+            let value = args.get(&arg.name).ok_or_else(|| {
+                println!("{} is missing arg {}", self.name, arg.name);
+                FunctionError::IdlMismatch
+            })?;
+            idl.encode(&arg.ty, value, &mut data)?;
+        }
+        Ok(data)
+    }
+
+    /// Fails unless `schema` lists the IDL's accounts in the same order and
+    /// with the same writability and signers. Remaining accounts come after
+    /// and are not declared in the IDL.
+    pub fn validate_accounts(
+        &self,
+        schema: &[AccountSpec],
+    ) -> std::result::Result<(), FunctionError> {
+        let matches = schema.len() == self.accounts.len()
+            && schema
+                .iter()
+                .zip(self.accounts.iter())
+                .all(|(spec, account)| {
+                    spec.name == account.name
+                        && spec.writable == account.is_mut
+                        && spec.signer == account.is_signer
+                });
+        if !matches {
+            println!("{} accounts do not match the IDL", self.name);
+            return Err(FunctionError::IdlMismatch);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_program_idl_covers_every_settle_ixn() {
+        for ixn in SettleIxn::ALL {
+            let idl_ixn = program_idl().instruction(ixn.name()).unwrap();
+
+            assert_eq!(idl_ixn.discriminator(), get_ixn_discriminator(ixn.name()));
+        }
+        assert_eq!(
+            program_idl().instruction("unknown_settle").err(),
+            Some(FunctionError::IdlMismatch)
+        );
+    }
+
+    #[test]
+    fn test_validate_accounts_rejects_reordered_schema() {
+        let idl_ixn = program_idl().instruction("loot_open_settle").unwrap();
+        let mut schema = accounts_schema(SettleIxn::LootOpenSettle, PARAMS_VERSION)
+            .unwrap()
+            .to_vec();
+
+        schema.swap(2, 3);
+        assert_eq!(
+            idl_ixn.validate_accounts(&schema),
+            Err(FunctionError::IdlMismatch)
+        );
+        schema.swap(2, 3);
+        schema[3].writable = false;
+        assert_eq!(
+            idl_ixn.validate_accounts(&schema),
+            Err(FunctionError::IdlMismatch)
+        );
+    }
+
+    #[test]
+    fn test_encode_data_checks_values() {
+        let idl = program_idl();
+        let idl_ixn = idl.instruction("loot_open_settle").unwrap();
+        let header = serde_json::json!({
+            "argsVersion": 4,
+            "idempotencyToken": vec![7u8; 32],
+            "executionTier": 1,
+            "entropyFallback": false,
+        });
+
+        let data = idl_ixn
+            .encode_data(
+                idl,
+                &serde_json::json!({"header": header, "args": {"itemId": 4_001, "rarity": 3}}),
+            )
+            .unwrap();
+        assert_eq!(data.len(), 48);
+        assert_eq!(data[42], 0);
+        assert_eq!(data[43..47], 4_001u32.to_le_bytes());
+
+        // out of range for a u8
+        assert_eq!(
+            idl_ixn.encode_data(
+                idl,
+                &serde_json::json!({"header": header, "args": {"itemId": 1, "rarity": 256}})
+            ),
+            Err(FunctionError::IdlMismatch)
+        );
+        // renamed field
+        assert_eq!(
+            idl_ixn.encode_data(
+                idl,
+                &serde_json::json!({"header": header, "args": {"item": 1, "rarity": 3}})
+            ),
+            Err(FunctionError::IdlMismatch)
+        );
+    }
+}
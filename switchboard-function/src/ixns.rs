@@ -0,0 +1,853 @@
+use crate::*;
+use serde::Serialize;
+
+/// The function accounts every settle instruction is signed with.
+#[derive(Clone, Copy, Debug)]
+pub struct RunnerAccounts {
+    /// Our Gramine generated keypair
+    pub enclave_signer: Pubkey,
+    pub function: Pubkey,
+    pub function_request: Pubkey,
+    /// The slot the request was published in, signed with the result.
+    pub request_slot: u64,
+    /// How the settlement is emitted, see emission.rs.
+    pub tx_format: TxFormat,
+}
+
+impl RunnerAccounts {
+    /// Fails when the runner was not started for a request. Signed with the
+    /// runner's signer, for what goes out through its own legacy emit.
+    pub fn from_runner(runner: &FunctionRunner) -> std::result::Result<Self, FunctionError> {
+        Ok(Self {
+            enclave_signer: runner.signer,
+            function: runner.function,
+            function_request: runner
+                .function_request_key
+                .ok_or(FunctionError::MissingRequestData)?,
+            request_slot: runner
+                .function_request_data
+                .as_ref()
+                .ok_or(FunctionError::MissingRequestData)?
+                .active_request
+                .request_slot,
+            tx_format: TxFormat::Legacy,
+        })
+    }
+
+    /// A request's settlement, signed with the run's enclave key in either
+    /// format so its result attestation verifies against the instruction's
+    /// signer, see emission.rs.
+    pub fn for_settlement(
+        runner: &FunctionRunner,
+        tx_format: TxFormat,
+        enclave_key: &EnclaveKey,
+    ) -> std::result::Result<Self, FunctionError> {
+        Ok(Self {
+            enclave_signer: enclave_key.pubkey(),
+            tx_format,
+            ..Self::from_runner(runner)?
+        })
+    }
+}
+
+/// H(request_key || args_version). The same request always settles with the
+/// same token, so the on-chain handlers can reject a duplicate submission.
+pub fn idempotency_token(function_request: &Pubkey, args_version: u8) -> [u8; 32] {
+    solana_program::hash::hashv(&[function_request.as_ref(), &[args_version]]).to_bytes()
+}
+
+/// Prefixes the args of every instruction we emit.
+#[derive(AnchorSerialize, Serialize, Clone, Debug, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct SettleHeader {
+    pub args_version: u8,
+    pub idempotency_token: [u8; 32],
+    /// `ExecutionTier` the settlement was built with, lower tiers skip the
+    /// off-chain validation.
+    pub execution_tier: u8,
+    /// Set when the enclave's entropy was unavailable and the result was
+    /// drawn from the OS RNG instead, never on mainnet, see entropy.rs.
+    pub entropy_fallback: bool,
+}
+
+impl SettleHeader {
+    pub fn new(
+        function_request: &Pubkey,
+        execution_tier: ExecutionTier,
+        entropy_fallback: bool,
+    ) -> Self {
+        Self {
+            args_version: ARGS_VERSION,
+            idempotency_token: idempotency_token(function_request, ARGS_VERSION),
+            execution_tier: execution_tier as u8,
+            entropy_fallback,
+        }
+    }
+}
+
+// the IDL encodes a publicKey from its base58 string
+fn serialize_optional_pubkey<S: serde::Serializer>(
+    pubkey: &Option<Pubkey>,
+    serializer: S,
+) -> std::result::Result<S::Ok, S::Error> {
+    match pubkey {
+        Some(pubkey) => serializer.serialize_some(&pubkey.to_string()),
+        None => serializer.serialize_none(),
+    }
+}
+
+/// The enclave key's signature over a roll, see attestation.rs.
+#[derive(AnchorSerialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ResultAttestation(pub [u8; ATTESTATION_LEN]);
+
+// serde only derives arrays up to 32 long, encoded like one
+impl Serialize for ResultAttestation {
+    fn serialize<S: serde::Serializer>(
+        &self,
+        serializer: S,
+    ) -> std::result::Result<S::Ok, S::Error> {
+        serializer.collect_seq(self.0.iter())
+    }
+}
+
+#[derive(AnchorSerialize, Serialize, Clone, Debug, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct ArenaMatchmakingSettleArgs {
+    /// Within the request's `MIN`/`MAX`.
+    pub random_result: u64,
+    pub faction: u8,
+    pub sub_pool_id: u8,
+    /// Index of the selected spaceship among the opponent accounts.
+    pub opponent_index: u8,
+    /// Bit `n` is set when opponent account `n` is a spaceship, empty slots
+    /// pass `Pubkey::default()`.
+    pub opponent_mask: u8,
+    /// Power scores the pairing was made with, see power_score.rs. Zero at
+    /// the fast tier.
+    pub requester_power: u32,
+    pub opponent_power: PowerBreakdown,
+    /// The roll as drawn, `random_result` is it shaped by the faction bias
+    /// curve, see faction_bias.rs. Equal when no bias applied.
+    pub raw_result: u64,
+    pub bias_strength_bps: u16,
+    /// The requester's faction win rate the roll was shaped with.
+    pub faction_win_rate_bps: u16,
+    /// The request's `EXPERIMENT_ID` and the variant the requester was
+    /// selected with, see bucketing.rs.
+    pub experiment_id: u32,
+    pub variant: u8,
+    pub attestation: ResultAttestation,
+    /// The run's enclave key the attestation verifies with, `None` when it
+    /// is the instruction's enclave signer.
+    #[serde(serialize_with = "serialize_optional_pubkey")]
+    pub attestation_signer: Option<Pubkey>,
+}
+
+#[derive(AnchorSerialize, Serialize, Clone, Debug, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct ArenaMatchmakingVsBotSettleArgs {
+    /// Within the request's `MIN`/`MAX`.
+    pub random_result: u64,
+    pub faction: u8,
+    pub bot_faction: u8,
+    pub bot_rating: u32,
+    pub bot_stats_seed: u64,
+    pub attestation: ResultAttestation,
+    /// The run's enclave key the attestation verifies with, `None` when it
+    /// is the instruction's enclave signer.
+    #[serde(serialize_with = "serialize_optional_pubkey")]
+    pub attestation_signer: Option<Pubkey>,
+}
+
+impl ArenaMatchmakingVsBotSettleArgs {
+    pub fn new(
+        random_result: u64,
+        faction: u8,
+        bot: &BotOpponent,
+        attestation: ResultAttestation,
+        attestation_signer: Option<Pubkey>,
+    ) -> Self {
+        Self {
+            random_result,
+            faction,
+            bot_faction: bot.faction,
+            bot_rating: bot.rating,
+            bot_stats_seed: bot.stats_seed,
+            attestation,
+            attestation_signer,
+        }
+    }
+}
+
+#[derive(AnchorSerialize, Serialize, Clone, Debug, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct LootOpenSettleArgs {
+    pub item_id: u32,
+    pub rarity: u8,
+}
+
+#[derive(AnchorSerialize, Serialize, Clone, Debug, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct DailySeedSettleArgs {
+    /// The period the seed is for, see daily_seed.rs.
+    pub period_index: u64,
+    pub seed: [u8; 32],
+}
+
+#[derive(AnchorSerialize, Serialize, Clone, Debug, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct RaffleSettleArgs {
+    pub participant_count: u32,
+    /// Distinct participant indices in the order they were drawn, see
+    /// raffle.rs.
+    pub winners: Vec<u32>,
+}
+
+#[derive(AnchorSerialize, Serialize, Clone, Debug, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct ArenaMatchmakingCancelSettleArgs {
+    /// The faction the spaceship was queued for.
+    pub faction: u8,
+}
+
+#[derive(AnchorSerialize, Serialize, Clone, Debug, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct TournamentSeedSettleArgs {
+    /// Participant account index placed at each seed, a permutation.
+    pub seed_order: Vec<u8>,
+}
+
+/// Encodes the header and args through the program IDL and checks the
+/// accounts schema against it, so a layout change in the program fails here
+/// rather than on-chain.
+fn build_ixn<T: Serialize>(
+    ixn: SettleIxn,
+    params: &ContainerParams,
+    runner_accounts: &RunnerAccounts,
+    header: &SettleHeader,
+    args: &T,
+) -> std::result::Result<Instruction, FunctionError> {
+    let idl = program_idl();
+    let idl_ixn = idl.instruction(ixn.name())?;
+    idl_ixn.validate_accounts(accounts_schema(ixn, params.version)?)?;
+    let values = serde_json::json!({ "header": header, "args": args });
+    Ok(Instruction {
+        program_id: params.program_id,
+        data: idl_ixn.encode_data(idl, &values)?,
+        accounts: schema_account_metas(ixn, params, runner_accounts)?,
+    })
+}
+
+// IXN DATA, read back by settle_layout.rs:
+// LEN: 165 bytes, 197 with an attestation signer
+// [0-8]: Anchor Ixn Discriminator
+// [9]: Args Version as u8
+// [10-41]: Idempotency Token as [u8; 32]
+// [42]: Execution Tier as u8
+// [43]: Entropy Fallback as bool, set when drawn from the OS RNG
+// [44-51]: Random Result as u64
+// [52]: Faction as u8
+// [53]: Sub-pool Id as u8
+// [54]: Opponent Index as u8
+// [55]: Opponent Mask as u8, bit n set when opponent account n is a spaceship
+// [56-59]: Requester Power Score as u32
+// [60-83]: Opponent Power Breakdown as rating, weapon, shield, engine, hull and total u32s
+// [84-91]: Raw Result as u64, the random result before the faction bias
+// [92-93]: Faction Bias Strength as u16 basis points
+// [94-95]: Faction Win Rate as u16 basis points
+// [96-99]: Experiment Id as u32, 0 outside an experiment
+// [100]: Experiment Variant as u8
+// [101-164]: Result Attestation as ed25519 signature by the run's enclave key
+// [165-197]: Attestation Signer as Option<Pubkey>, the run's enclave key
+//   unless it is the enclave signer
+//
+// ACCOUNTS:
+// 1. Enclave Signer (signer): our Gramine generated keypair
+// 2. User: our user who made the request
+// 3. Realm
+// 4. User Account PDA
+// 5. Spaceship PDA (mut)
+// 6. Switchboard Function (arena_matchmaking_function)
+// 7. Switchboard Function Request
+// 8-9-10-11-12. the spaceships that are potentially being matched with the spaceship_pda,
+//    (mut) except for empty slots passed as Pubkey::default()
+// 13-14. Settlement Approval PDA (mut) and Realm Multisig, only with APPROVAL_PDA, see approval.rs
+pub fn arena_matchmaking_settle_ixn(
+    params: &ContainerParams,
+    runner_accounts: &RunnerAccounts,
+    header: &SettleHeader,
+    args: &ArenaMatchmakingSettleArgs,
+) -> std::result::Result<Instruction, FunctionError> {
+    build_ixn(
+        SettleIxn::ArenaMatchmakingSettle,
+        params,
+        runner_accounts,
+        header,
+        args,
+    )
+}
+
+// IXN DATA:
+// LEN: 130 bytes, 162 with an attestation signer
+// [0-8]: Anchor Ixn Discriminator
+// [9]: Args Version as u8
+// [10-41]: Idempotency Token as [u8; 32]
+// [42]: Execution Tier as u8
+// [43]: Entropy Fallback as bool, set when drawn from the OS RNG
+// [44-51]: Random Result as u64
+// [52]: Faction as u8
+// [53]: Bot Faction as u8
+// [54-57]: Bot Rating as u32
+// [58-65]: Bot Stats Seed as u64
+// [66-129]: Result Attestation as ed25519 signature by the run's enclave key
+// [130-162]: Attestation Signer as Option<Pubkey>, the run's enclave key
+//   unless it is the enclave signer
+//
+// ACCOUNTS:
+// 1. Enclave Signer (signer): our Gramine generated keypair
+// 2. User: our user who made the request
+// 3. Realm
+// 4. User Account PDA
+// 5. Spaceship PDA (mut)
+// 6. Switchboard Function (arena_matchmaking_function)
+// 7. Switchboard Function Request
+// 8-9. Settlement Approval PDA (mut) and Realm Multisig, only with APPROVAL_PDA, see approval.rs
+pub fn arena_matchmaking_settle_vs_bot_ixn(
+    params: &ContainerParams,
+    runner_accounts: &RunnerAccounts,
+    header: &SettleHeader,
+    args: &ArenaMatchmakingVsBotSettleArgs,
+) -> std::result::Result<Instruction, FunctionError> {
+    build_ixn(
+        SettleIxn::ArenaMatchmakingSettleVsBot,
+        params,
+        runner_accounts,
+        header,
+        args,
+    )
+}
+
+// IXN DATA:
+// LEN: 48 bytes
+// [0-8]: Anchor Ixn Discriminator
+// [9]: Args Version as u8
+// [10-41]: Idempotency Token as [u8; 32]
+// [42]: Execution Tier as u8
+// [43]: Entropy Fallback as bool, set when drawn from the OS RNG
+// [44-47]: Item Id as u32
+// [48]: Rarity as u8
+//
+// ACCOUNTS:
+// 1. Enclave Signer (signer): our Gramine generated keypair
+// 2. User: our user who made the request
+// 3. Realm
+// 4. User Account PDA (mut): receives the item
+// 5. Switchboard Function (arena_matchmaking_function)
+// 6. Switchboard Function Request
+// 7-8. Settlement Approval PDA (mut) and Realm Multisig, only with APPROVAL_PDA, see approval.rs
+pub fn loot_open_settle_ixn(
+    params: &ContainerParams,
+    runner_accounts: &RunnerAccounts,
+    header: &SettleHeader,
+    args: &LootOpenSettleArgs,
+) -> std::result::Result<Instruction, FunctionError> {
+    build_ixn(
+        SettleIxn::LootOpenSettle,
+        params,
+        runner_accounts,
+        header,
+        args,
+    )
+}
+
+// IXN DATA:
+// LEN: 47 + N bytes
+// [0-8]: Anchor Ixn Discriminator
+// [9]: Args Version as u8
+// [10-41]: Idempotency Token as [u8; 32]
+// [42]: Execution Tier as u8
+// [43]: Entropy Fallback as bool, set when drawn from the OS RNG
+// [44-47]: Seed Count N as u32
+// [48-(47+N)]: Seed Order as packed u8 participant indices
+//
+// ACCOUNTS:
+// 1. Enclave Signer (signer): our Gramine generated keypair
+// 2. User: the tournament organiser who made the request
+// 3. Realm
+// 4. Tournament PDA (mut): receives the bracket
+// 5. Switchboard Function (arena_matchmaking_function)
+// 6. Switchboard Function Request
+// 7..(6+N). the participants, in the order the seed indices refer to
+pub fn tournament_seed_settle_ixn(
+    params: &ContainerParams,
+    runner_accounts: &RunnerAccounts,
+    header: &SettleHeader,
+    args: &TournamentSeedSettleArgs,
+) -> std::result::Result<Instruction, FunctionError> {
+    let mut ixn = build_ixn(
+        SettleIxn::TournamentSeedSettle,
+        params,
+        runner_accounts,
+        header,
+        args,
+    )?;
+    ixn.accounts.extend(
+        params
+            .participants
+            .iter()
+            .map(|participant| AccountMeta::new_readonly(*participant, false)),
+    );
+    Ok(ixn)
+}
+
+// IXN DATA:
+// LEN: 83 bytes
+// [0-8]: Anchor Ixn Discriminator
+// [9]: Args Version as u8
+// [10-41]: Idempotency Token as [u8; 32]
+// [42]: Execution Tier as u8
+// [43]: Entropy Fallback as bool, set when drawn from the OS RNG
+// [44-51]: Period Index as u64
+// [52-83]: Seed as [u8; 32]
+//
+// ACCOUNTS:
+// 1. Enclave Signer (signer): our Gramine generated keypair
+// 2. User: the realm admin who made the request
+// 3. Realm
+// 4. Seed PDA (mut): receives the seed
+// 5. Switchboard Function (arena_matchmaking_function)
+// 6. Switchboard Function Request
+pub fn daily_seed_settle_ixn(
+    params: &ContainerParams,
+    runner_accounts: &RunnerAccounts,
+    header: &SettleHeader,
+    args: &DailySeedSettleArgs,
+) -> std::result::Result<Instruction, FunctionError> {
+    build_ixn(
+        SettleIxn::DailySeedSettle,
+        params,
+        runner_accounts,
+        header,
+        args,
+    )
+}
+
+// IXN DATA:
+// LEN: 51 + 4N bytes
+// [0-8]: Anchor Ixn Discriminator
+// [9]: Args Version as u8
+// [10-41]: Idempotency Token as [u8; 32]
+// [42]: Execution Tier as u8
+// [43]: Entropy Fallback as bool, set when drawn from the OS RNG
+// [44-47]: Participant Count as u32
+// [48-51]: Winner Count N as u32
+// [52-(51+4N)]: Winners as packed u32 participant indices
+//
+// ACCOUNTS:
+// 1. Enclave Signer (signer): our Gramine generated keypair
+// 2. User: the realm admin who made the request
+// 3. Realm
+// 4. Raffle PDA (mut): receives the winners
+// 5. Switchboard Function (arena_matchmaking_function)
+// 6. Switchboard Function Request
+pub fn raffle_settle_ixn(
+    params: &ContainerParams,
+    runner_accounts: &RunnerAccounts,
+    header: &SettleHeader,
+    args: &RaffleSettleArgs,
+) -> std::result::Result<Instruction, FunctionError> {
+    build_ixn(
+        SettleIxn::RaffleSettle,
+        params,
+        runner_accounts,
+        header,
+        args,
+    )
+}
+
+// IXN DATA:
+// LEN: 44 bytes
+// [0-8]: Anchor Ixn Discriminator
+// [9]: Args Version as u8
+// [10-41]: Idempotency Token as [u8; 32]
+// [42]: Execution Tier as u8
+// [43]: Entropy Fallback as bool, set when drawn from the OS RNG
+// [44]: Faction as u8
+//
+// ACCOUNTS:
+// 1. Enclave Signer (signer): our Gramine generated keypair
+// 2. User: the player who made the request
+// 3. Realm (mut): holds the matchmaking queue
+// 4. User Account PDA
+// 5. Spaceship PDA (mut): the spaceship leaving the queue
+// 6. Switchboard Function (arena_matchmaking_function)
+// 7. Switchboard Function Request
+pub fn arena_matchmaking_cancel_settle_ixn(
+    params: &ContainerParams,
+    runner_accounts: &RunnerAccounts,
+    header: &SettleHeader,
+    args: &ArenaMatchmakingCancelSettleArgs,
+) -> std::result::Result<Instruction, FunctionError> {
+    build_ixn(
+        SettleIxn::ArenaMatchmakingCancelSettle,
+        params,
+        runner_accounts,
+        header,
+        args,
+    )
+}
+
+// IXN DATA:
+// LEN: 48 + N bytes
+// [0-8]: Anchor Ixn Discriminator
+// [9]: Args Version as u8
+// [10-41]: Idempotency Token as [u8; 32]
+// [42]: Execution Tier as u8
+// [43]: Entropy Fallback as bool, set when drawn from the OS RNG
+// [44]: Error Code as u8
+// [45-48]: Reason Length N as u32
+// [49-(48+N)]: Reason as UTF-8, see failure_report.rs
+//
+// ACCOUNTS:
+// 1. Enclave Signer (signer): our Gramine generated keypair
+// 2. User (mut): our user who made the request, refunded
+// 3. Realm (mut): holds the matchmaking fee
+// 4. User Account PDA (mut)
+// 5. Switchboard Function (arena_matchmaking_function)
+// 6. Switchboard Function Request
+pub fn arena_matchmaking_report_failure_ixn(
+    params: &ContainerParams,
+    runner_accounts: &RunnerAccounts,
+    header: &SettleHeader,
+    args: &FailureReportArgs,
+) -> std::result::Result<Instruction, FunctionError> {
+    build_ixn(
+        SettleIxn::ArenaMatchmakingReportFailure,
+        params,
+        runner_accounts,
+        header,
+        args,
+    )
+}
+
+// IXN DATA:
+// LEN: 44 + N bytes
+// [0-8]: Anchor Ixn Discriminator of ROLL_IXN
+// [9]: Args Version as u8
+// [10-41]: Idempotency Token as [u8; 32]
+// [42]: Execution Tier as u8
+// [43]: Entropy Fallback as bool, set when drawn from the OS RNG
+// [44-(43+N)]: the ROLL_SCHEMA fields, see custom_roll.rs
+//
+// ACCOUNTS:
+// 1. Enclave Signer (signer): our Gramine generated keypair
+// 2. User: our user who made the request
+// 3. Realm
+// 4. User Account PDA (mut)
+// 5. Switchboard Function (arena_matchmaking_function)
+// 6. Switchboard Function Request
+pub fn custom_roll_settle_ixn(
+    params: &ContainerParams,
+    runner_accounts: &RunnerAccounts,
+    header: &SettleHeader,
+    roll_args: &[u8],
+) -> std::result::Result<Instruction, FunctionError> {
+    // the handler is not in the IDL, the header is the only layout we own
+    let mut data = ixn_discriminator(&params.roll_ixn).to_vec();
+    data.extend(header.try_to_vec().map_err(|_| FunctionError::Internal)?);
+    data.extend_from_slice(roll_args);
+    Ok(Instruction {
+        program_id: params.program_id,
+        data,
+        accounts: resolve_account_metas(CUSTOM_ROLL_SETTLE_V1, params, runner_accounts),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_fixtures::test_runner_accounts;
+
+    #[test]
+    fn test_idempotency_token_is_deterministic() {
+        let request = Pubkey::new_unique();
+
+        assert_eq!(
+            idempotency_token(&request, ARGS_VERSION),
+            idempotency_token(&request, ARGS_VERSION)
+        );
+        assert_ne!(
+            idempotency_token(&request, ARGS_VERSION),
+            idempotency_token(&request, ARGS_VERSION + 1)
+        );
+        assert_ne!(
+            idempotency_token(&request, ARGS_VERSION),
+            idempotency_token(&Pubkey::new_unique(), ARGS_VERSION)
+        );
+    }
+
+    #[test]
+    fn test_matchmaking_settle_data_layout() {
+        let args = ArenaMatchmakingSettleArgs {
+            random_result: 0x0807_0605_0403_0201,
+            faction: 2,
+            sub_pool_id: 7,
+            opponent_index: 4,
+            opponent_mask: 0b1_0011,
+            requester_power: 0x0c0b_0a09,
+            opponent_power: PowerBreakdown {
+                total: 0x100f_0e0d,
+                ..PowerBreakdown::default()
+            },
+            raw_result: 0x1817_1615_1413_1211,
+            bias_strength_bps: 0x1a19,
+            faction_win_rate_bps: 0x1c1b,
+            experiment_id: 0x201f_1e1d,
+            variant: 1,
+            attestation: ResultAttestation([0xaa; ATTESTATION_LEN]),
+            attestation_signer: Some(Pubkey::new_from_array([0xbb; 32])),
+        };
+
+        let runner_accounts = test_runner_accounts();
+
+        let header = SettleHeader::new(
+            &runner_accounts.function_request,
+            ExecutionTier::Rich,
+            false,
+        );
+
+        let data = arena_matchmaking_settle_ixn(
+            &crate::test_fixtures::test_params(),
+            &runner_accounts,
+            &header,
+            &args,
+        )
+        .unwrap()
+        .data;
+
+        assert_eq!(data.len(), MATCHMAKING_SETTLE_DATA_LEN + 32);
+        assert_eq!(data[..8], get_ixn_discriminator("arena_matchmaking_settle"));
+        assert_eq!(data[8], ARGS_VERSION);
+        assert_eq!(
+            data[9..41],
+            idempotency_token(&runner_accounts.function_request, ARGS_VERSION)
+        );
+        assert_eq!(data[41], ExecutionTier::Rich as u8);
+        assert_eq!(data[43..51], [1, 2, 3, 4, 5, 6, 7, 8]);
+        assert_eq!(data[51], 2);
+        assert_eq!(data[52], 7);
+        assert_eq!(data[53], 4);
+        assert_eq!(data[54], 0b1_0011);
+        assert_eq!(data[55..59], [9, 10, 11, 12]);
+        assert_eq!(data[79..83], [13, 14, 15, 16]);
+        assert_eq!(data[83..91], [17, 18, 19, 20, 21, 22, 23, 24]);
+        assert_eq!(data[91..93], [25, 26]);
+        assert_eq!(data[93..95], [27, 28]);
+        assert_eq!(data[95..99], [29, 30, 31, 32]);
+        assert_eq!(data[99], 1);
+        assert_eq!(data[100..164], [0xaa; ATTESTATION_LEN]);
+        assert_eq!(data[164], 1);
+        assert_eq!(data[165..], [0xbb; 32]);
+    }
+
+    /// The IDL driven encoding must match what borsh derives from the
+    /// structs, which mirror the program's arg types.
+    #[test]
+    fn test_idl_encoding_matches_borsh() {
+        fn borsh<T: AnchorSerialize>(header: &SettleHeader, args: &T) -> Vec<u8> {
+            [header.try_to_vec().unwrap(), args.try_to_vec().unwrap()].concat()
+        }
+
+        let params = crate::test_fixtures::test_params();
+        let runner_accounts = test_runner_accounts();
+        let header = SettleHeader::new(
+            &runner_accounts.function_request,
+            ExecutionTier::Standard,
+            false,
+        );
+
+        let matchmaking = ArenaMatchmakingSettleArgs {
+            random_result: u64::MAX - 1,
+            faction: 1,
+            sub_pool_id: 2,
+            opponent_index: 3,
+            opponent_mask: 0b1_1111,
+            requester_power: 1_320,
+            opponent_power: PowerBreakdown {
+                rating: 1_000,
+                weapon: 200,
+                shield: 30,
+                engine: 0,
+                hull: 120,
+                total: 1_350,
+            },
+            raw_result: u64::MAX - 7,
+            bias_strength_bps: 2_500,
+            faction_win_rate_bps: 4_100,
+            experiment_id: 12,
+            variant: Variant::Treatment as u8,
+            attestation: ResultAttestation([3; ATTESTATION_LEN]),
+            attestation_signer: None,
+        };
+        let ixn =
+            arena_matchmaking_settle_ixn(&params, &runner_accounts, &header, &matchmaking).unwrap();
+        assert_eq!(ixn.data[8..], borsh(&header, &matchmaking));
+
+        let vs_bot = ArenaMatchmakingVsBotSettleArgs {
+            random_result: 1,
+            faction: 0,
+            bot_faction: 2,
+            bot_rating: u32::MAX,
+            bot_stats_seed: 42,
+            attestation: ResultAttestation([4; ATTESTATION_LEN]),
+            attestation_signer: Some(Pubkey::new_unique()),
+        };
+        let ixn = arena_matchmaking_settle_vs_bot_ixn(&params, &runner_accounts, &header, &vs_bot)
+            .unwrap();
+        assert_eq!(ixn.data[8..], borsh(&header, &vs_bot));
+
+        let loot_open = LootOpenSettleArgs {
+            item_id: 3_002,
+            rarity: 2,
+        };
+        let ixn = loot_open_settle_ixn(&params, &runner_accounts, &header, &loot_open).unwrap();
+        assert_eq!(ixn.data[8..], borsh(&header, &loot_open));
+
+        let tournament_seed = TournamentSeedSettleArgs {
+            seed_order: vec![2, 0, 1],
+        };
+        let ixn = tournament_seed_settle_ixn(&params, &runner_accounts, &header, &tournament_seed)
+            .unwrap();
+        assert_eq!(ixn.data[8..], borsh(&header, &tournament_seed));
+
+        let daily_seed = DailySeedSettleArgs {
+            period_index: 19_724,
+            seed: [9; 32],
+        };
+        let ixn = daily_seed_settle_ixn(&params, &runner_accounts, &header, &daily_seed).unwrap();
+        assert_eq!(ixn.data.len(), 83);
+        assert_eq!(ixn.data[8..], borsh(&header, &daily_seed));
+
+        let raffle = RaffleSettleArgs {
+            participant_count: 1_000,
+            winners: vec![17, 999, 0],
+        };
+        let ixn = raffle_settle_ixn(&params, &runner_accounts, &header, &raffle).unwrap();
+        assert_eq!(ixn.data.len(), 51 + 4 * 3);
+        assert_eq!(ixn.data[8..], borsh(&header, &raffle));
+        assert_eq!(ixn.data[55..59], 999u32.to_le_bytes());
+
+        let cancel = ArenaMatchmakingCancelSettleArgs { faction: 2 };
+        let ixn = arena_matchmaking_cancel_settle_ixn(&params, &runner_accounts, &header, &cancel)
+            .unwrap();
+        assert_eq!(ixn.data.len(), 44);
+        assert_eq!(ixn.data[8..], borsh(&header, &cancel));
+    }
+
+    #[test]
+    fn test_custom_roll_settle_ixn() {
+        let mut params = crate::test_fixtures::test_params();
+        params.roll_ixn = "crit_roll_settle".to_string();
+        let runner_accounts = test_runner_accounts();
+        let header = SettleHeader::new(
+            &runner_accounts.function_request,
+            ExecutionTier::Standard,
+            false,
+        );
+
+        let ixn = custom_roll_settle_ixn(&params, &runner_accounts, &header, &[6, 1, 2]).unwrap();
+
+        assert_eq!(ixn.data.len(), 46);
+        assert_eq!(ixn.data[..8], get_ixn_discriminator("crit_roll_settle"));
+        assert_eq!(ixn.data[8..43], header.try_to_vec().unwrap());
+        assert_eq!(ixn.data[43..], [6, 1, 2]);
+        assert_eq!(ixn.accounts.len(), CUSTOM_ROLL_SETTLE_V1.len());
+        assert!(ixn.accounts[0].is_signer);
+        assert_eq!(ixn.accounts[3].pubkey, params.user_account_pda);
+        assert!(ixn.accounts[3].is_writable);
+    }
+
+    #[test]
+    fn test_matchmaking_settle_vs_bot_ixn() {
+        let mut params = crate::test_fixtures::test_params();
+        params.opponent_spaceship_1_pda = Pubkey::default();
+        let runner_accounts = test_runner_accounts();
+        let bot = BotOpponent {
+            faction: 2,
+            rating: 1_250,
+            stats_seed: 0x0807_0605_0403_0201,
+        };
+        let args = ArenaMatchmakingVsBotSettleArgs::new(
+            7,
+            1,
+            &bot,
+            ResultAttestation([5; ATTESTATION_LEN]),
+            Some(Pubkey::new_from_array([6; 32])),
+        );
+
+        let header = SettleHeader::new(
+            &runner_accounts.function_request,
+            ExecutionTier::Standard,
+            false,
+        );
+
+        let ixn =
+            arena_matchmaking_settle_vs_bot_ixn(&params, &runner_accounts, &header, &args).unwrap();
+
+        assert_eq!(ixn.data.len(), 162);
+        assert_eq!(
+            ixn.data[..8],
+            get_ixn_discriminator("arena_matchmaking_settle_vs_bot")
+        );
+        assert_eq!(ixn.data[43..51], 7u64.to_le_bytes());
+        assert_eq!(ixn.data[51], 1);
+        assert_eq!(ixn.data[52], 2);
+        assert_eq!(ixn.data[53..57], 1_250u32.to_le_bytes());
+        assert_eq!(ixn.data[57..65], [1, 2, 3, 4, 5, 6, 7, 8]);
+        assert_eq!(ixn.data[65..129], [5; ATTESTATION_LEN]);
+        assert_eq!(ixn.data[129], 1);
+        assert_eq!(ixn.data[130..], [6; 32]);
+        assert_eq!(ixn.accounts.len(), 7);
+        assert!(ixn.accounts[4].is_writable);
+        assert_eq!(ixn.accounts[4].pubkey, params.spaceship_pda);
+    }
+
+    #[test]
+    fn test_loot_open_settle_ixn() {
+        let params = ContainerParams::decode(
+            format!(
+                "REQUEST_TYPE=LOOT_OPEN,PID={},USER={},REALM_PDA={},USER_ACCOUNT_PDA={}",
+                Pubkey::new_unique(),
+                Pubkey::new_unique(),
+                Pubkey::new_unique(),
+                Pubkey::new_unique(),
+            )
+            .as_bytes(),
+        )
+        .unwrap();
+        let runner_accounts = test_runner_accounts();
+        let args = LootOpenSettleArgs {
+            item_id: 4_001,
+            rarity: Rarity::Legendary as u8,
+        };
+
+        let header = SettleHeader::new(
+            &runner_accounts.function_request,
+            ExecutionTier::Fast,
+            false,
+        );
+
+        let ixn = loot_open_settle_ixn(&params, &runner_accounts, &header, &args).unwrap();
+
+        assert_eq!(ixn.program_id, params.program_id);
+        assert_eq!(ixn.data[..8], get_ixn_discriminator("loot_open_settle"));
+        assert_eq!(ixn.data[8], ARGS_VERSION);
+        assert_eq!(ixn.data[41], ExecutionTier::Fast as u8);
+        assert_eq!(ixn.data[43..47], 4_001u32.to_le_bytes());
+        assert_eq!(ixn.data[47], 3);
+        assert_eq!(ixn.accounts.len(), 6);
+        assert!(ixn.accounts[0].is_signer);
+        assert!(ixn.accounts[3].is_writable);
+        assert_eq!(ixn.accounts[3].pubkey, params.user_account_pda);
+    }
+}
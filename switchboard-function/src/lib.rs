@@ -0,0 +1,28 @@
+//! Client side of the params encoding, for the Anchor program's tests and
+//! the TypeScript bindgen to build byte-identical params with
+//! `ContainerParams::to_bytes`. Shares its modules with the function binary
+//! but none of the Switchboard runtime, build it with
+//! `default-features = false` to leave the runtime out entirely.
+//!
+//! The modules' tests need the binary's fixtures and run through it.
+#![cfg(not(test))]
+
+pub use attestation::*;
+pub use discriminators::*;
+pub use errors::*;
+pub use loot_tables::*;
+pub use params::*;
+pub use settle_layout::*;
+pub use solana_program::pubkey::Pubkey;
+use std::str::FromStr;
+
+mod attestation;
+mod discriminators;
+mod errors;
+mod loot_tables;
+mod params;
+mod settle_layout;
+
+/// params.rs reports deprecated keys through the function's metrics, which
+/// clients have no use for.
+fn record_counter(_name: &'static str, _tags: &[(&'static str, &str)]) {}
@@ -0,0 +1,142 @@
+use crate::*;
+use solana_sdk::signer::Signer;
+
+/// Local development swaps Gramine for the OS RNG and prints the settlement
+/// instead of emitting a quote. Enabled by building with the `local-dev`
+/// feature or running with `LOCAL_RANDOMNESS=1`.
+pub fn local_dev_enabled() -> bool {
+    cfg!(feature = "local-dev") || setting("LOCAL_RANDOMNESS").is_some_and(|v| v == "1")
+}
+
+fn env_pubkey(key: &str) -> Pubkey {
+    std::env::var(key)
+        .ok()
+        .and_then(|value| Pubkey::from_str(&value).ok())
+        .unwrap_or_default()
+}
+
+/// Human readable dump of the instructions that would have been emitted.
+pub fn describe_ixns(ixs: &[Instruction]) -> String {
+    let mut description = String::new();
+    for (i, ixn) in ixs.iter().enumerate() {
+        description += &format!("ixn {}: program {}\n", i, ixn.program_id);
+        for (j, account) in ixn.accounts.iter().enumerate() {
+            description += &format!(
+                "  account {:>2}: {}{}{}\n",
+                j,
+                account.pubkey,
+                if account.is_signer { " (signer)" } else { "" },
+                if account.is_writable { " (mut)" } else { "" },
+            );
+        }
+        description += &format!("  data: {}\n", hex::encode(&ixn.data));
+    }
+    description
+}
+
+/// Settles the params in `CONTAINER_PARAMS` against `RPC_URL` (devnet by
+/// default) and prints the result. `FUNCTION_KEY` and `FUNCTION_REQUEST_KEY`
+/// may be set to get realistic account metas, `ENCLAVE_SIGNER` to submit the
+/// settlement with a keypair of our own and `RANDOMNESS_SEED` (32 hex encoded
+/// bytes) to draw reproducible randomness. There is no verify instruction to
+/// simulate behind, so the rich tier runs as standard. Returns the exit code.
+pub fn run_local_dev() -> i32 {
+    let started = std::time::Instant::now();
+    let program_allowlist = match load_program_allowlist(&SealedStorage::from_env()) {
+        Ok(program_allowlist) => program_allowlist,
+        Err(error) => {
+            println!("invalid program allowlist: {}", error);
+            return error.code() as i32;
+        }
+    };
+    let container_params = std::env::var("CONTAINER_PARAMS").unwrap_or_default();
+    let params =
+        match ContainerParams::decode_for_programs(container_params.as_bytes(), &program_allowlist)
+        {
+            Ok(params) => params,
+            Err(error) => {
+                println!("invalid CONTAINER_PARAMS: {}", error);
+                return error.code() as i32;
+            }
+        };
+    params.report_deprecated_keys();
+    if let Err(error) = precheck(&params, &program_allowlist) {
+        println!("rejected request: {}", error);
+        return error.code() as i32;
+    }
+
+    let enclave_signer = match env_pubkey("ENCLAVE_SIGNER") {
+        signer if signer != Pubkey::default() => signer,
+        _ => Keypair::new().pubkey(),
+    };
+    let seeded_rng = std::env::var("RANDOMNESS_SEED")
+        .ok()
+        .and_then(|seed| hex::decode(seed).ok())
+        .and_then(|seed| <[u8; 32]>::try_from(seed).ok())
+        .map(SeededRandomSource::new);
+    let rng: &dyn RandomSource = match &seeded_rng {
+        Some(seeded_rng) => seeded_rng,
+        None => &OsRandomSource,
+    };
+    let runner_accounts = RunnerAccounts {
+        enclave_signer,
+        function: env_pubkey("FUNCTION_KEY"),
+        function_request: env_pubkey("FUNCTION_REQUEST_KEY"),
+        // there is no request account to read the slot from
+        request_slot: 0,
+        tx_format: TxFormat::from_env(),
+    };
+    let rpc_url = setting("RPC_URL").unwrap_or_else(|| default_cluster().url().to_string());
+    let client = solana_client::rpc_client::RpcClient::new(rpc_url);
+
+    match build_settlement(
+        &params,
+        &runner_accounts,
+        &enclave_signer,
+        &client,
+        rng,
+        None,
+        &mut TierBudget::from_env(started),
+    ) {
+        Ok(settlement) => {
+            if dry_run_enabled() {
+                println!("{}", ixns_to_json(&settlement.ixs));
+            } else {
+                if let Some(pool_diversity) = &settlement.pool_diversity {
+                    println!("{}", pool_diversity.metric_line());
+                }
+                print!("{}", describe_ixns(&settlement.ixs));
+            }
+            0
+        }
+        Err(error) => {
+            println!("failed to build settlement: {}", error);
+            error.code() as i32
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_describe_ixns() {
+        let signer = Pubkey::new_unique();
+        let ixn = Instruction {
+            program_id: Pubkey::new_unique(),
+            data: vec![0xde, 0xad],
+            accounts: vec![
+                AccountMeta::new_readonly(signer, true),
+                AccountMeta::new(Pubkey::new_unique(), false),
+            ],
+        };
+
+        let description = describe_ixns(std::slice::from_ref(&ixn));
+
+        assert!(description.contains(&format!("ixn 0: program {}", ixn.program_id)));
+        assert!(description.contains(&format!("{} (signer)\n", signer)));
+        assert!(description.contains(" (mut)\n"));
+        assert!(description.contains("data: dead\n"));
+    }
+}
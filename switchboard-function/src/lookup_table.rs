@@ -0,0 +1,121 @@
+use crate::*;
+use solana_address_lookup_table_program::state::AddressLookupTable;
+use solana_program::address_lookup_table_account::AddressLookupTableAccount;
+use solana_program::message::{v0, VersionedMessage};
+
+/// Resolves the address lookup table for this request. Params take precedence
+/// over the `ADDRESS_LOOKUP_TABLE` env var set on the function container.
+pub fn configured_lookup_table(params: &ContainerParams) -> Option<Pubkey> {
+    if params.lookup_table != Pubkey::default() {
+        return Some(params.lookup_table);
+    }
+    setting("ADDRESS_LOOKUP_TABLE").and_then(|lookup_table| Pubkey::from_str(&lookup_table).ok())
+}
+
+pub fn load_lookup_table<F: AccountFetcher + ?Sized>(
+    fetcher: &F,
+    key: Pubkey,
+) -> std::result::Result<AddressLookupTableAccount, FunctionError> {
+    let data = fetcher
+        .fetch_multiple_account_data(&[key])?
+        .pop()
+        .flatten()
+        .ok_or(FunctionError::AccountFetchFailed)?;
+    let lookup_table =
+        AddressLookupTable::deserialize(&data).map_err(|_| FunctionError::AccountDecodeFailed)?;
+
+    Ok(AddressLookupTableAccount {
+        key,
+        addresses: lookup_table.addresses.to_vec(),
+    })
+}
+
+/// Compiles a v0 message where every non-signer account found in the lookup
+/// tables is referenced by a one byte index instead of its full pubkey.
+pub fn compile_v0_message(
+    ixs: &[Instruction],
+    payer: &Pubkey,
+    lookup_tables: &[AddressLookupTableAccount],
+    recent_blockhash: solana_program::hash::Hash,
+) -> std::result::Result<VersionedMessage, FunctionError> {
+    v0::Message::try_compile(payer, ixs, lookup_tables, recent_blockhash)
+        .map(VersionedMessage::V0)
+        .map_err(|error| {
+            println!("failed to compile v0 message: {}", error);
+            FunctionError::TransactionTooLarge
+        })
+}
+
+pub fn versioned_message_size(message: &VersionedMessage) -> usize {
+    message.serialize().len()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rpc::mock::MockFetcher;
+    use solana_address_lookup_table_program::state::LookupTableMeta;
+    use std::borrow::Cow;
+
+    fn lookup_table_data(addresses: &[Pubkey]) -> Vec<u8> {
+        AddressLookupTable {
+            meta: LookupTableMeta::new(Pubkey::new_unique()),
+            addresses: Cow::Borrowed(addresses),
+        }
+        .serialize_for_tests()
+        .unwrap()
+    }
+
+    fn wide_ixn(accounts: &[Pubkey]) -> Instruction {
+        Instruction {
+            program_id: Pubkey::new_unique(),
+            data: vec![0u8; 16],
+            accounts: accounts
+                .iter()
+                .map(|pubkey| AccountMeta::new(*pubkey, false))
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn test_load_lookup_table() {
+        let key = Pubkey::new_unique();
+        let addresses: Vec<Pubkey> = (0..3).map(|_| Pubkey::new_unique()).collect();
+        let mut fetcher = MockFetcher::default();
+        fetcher.insert(key, lookup_table_data(&addresses));
+
+        let lookup_table = load_lookup_table(&fetcher, key).unwrap();
+        assert_eq!(lookup_table.key, key);
+        assert_eq!(lookup_table.addresses, addresses);
+
+        assert_eq!(
+            load_lookup_table(&fetcher, Pubkey::new_unique()).unwrap_err(),
+            FunctionError::AccountFetchFailed
+        );
+    }
+
+    #[test]
+    fn test_v0_message_fits_many_accounts() {
+        let payer = Pubkey::new_unique();
+        let accounts: Vec<Pubkey> = (0..24).map(|_| Pubkey::new_unique()).collect();
+        let ixn = wide_ixn(&accounts);
+        let lookup_table = AddressLookupTableAccount {
+            key: Pubkey::new_unique(),
+            addresses: accounts.clone(),
+        };
+
+        let message = compile_v0_message(
+            std::slice::from_ref(&ixn),
+            &payer,
+            &[lookup_table],
+            solana_program::hash::Hash::default(),
+        )
+        .unwrap();
+
+        let legacy_size = message_size(&[ixn], &payer);
+        let v0_size = versioned_message_size(&message);
+        assert!(legacy_size > MAX_IXNS_MESSAGE_SIZE);
+        assert!(v0_size <= MAX_IXNS_MESSAGE_SIZE);
+        assert!(v0_size < legacy_size - 24 * 28);
+    }
+}
@@ -0,0 +1,203 @@
+use crate::*;
+
+/// The table used when a loot request does not specify `LOOT_TABLE`.
+pub const DEFAULT_LOOT_TABLE: u8 = 0;
+
+#[repr(u8)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Rarity {
+    Common = 0,
+    Rare = 1,
+    Epic = 2,
+    Legendary = 3,
+}
+
+impl Rarity {
+    pub const ALL: [Rarity; 4] = [
+        Rarity::Common,
+        Rarity::Rare,
+        Rarity::Epic,
+        Rarity::Legendary,
+    ];
+}
+
+/// Relative weights for common/rare/epic/legendary, in that order.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RarityWeights(pub [u32; 4]);
+
+impl RarityWeights {
+    pub fn total(&self) -> u32 {
+        self.0.iter().sum()
+    }
+
+    /// Maps a roll in `[0, total)` to the rarity bucket it falls in.
+    pub fn rarity_for_roll(&self, roll: u32) -> Rarity {
+        let mut cumulative = 0u32;
+        for (rarity, weight) in Rarity::ALL.iter().zip(self.0.iter()) {
+            cumulative += weight;
+            if roll < cumulative {
+                return *rarity;
+            }
+        }
+        // only reachable with an out of range roll, clamp to the rarest bucket
+        Rarity::Legendary
+    }
+}
+
+/// Parses weights given as `COMMON:RARE:EPIC:LEGENDARY`, e.g. `70:20:8:2`.
+impl FromStr for RarityWeights {
+    type Err = FunctionError;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        let parts: Vec<&str> = s.split(':').collect();
+        if parts.len() != 4 {
+            return Err(FunctionError::InvalidParams);
+        }
+
+        let mut weights = [0u32; 4];
+        for (weight, part) in weights.iter_mut().zip(parts.iter()) {
+            *weight = part
+                .parse::<u32>()
+                .map_err(|_| FunctionError::InvalidParams)?;
+        }
+
+        let weights = RarityWeights(weights);
+        // a zero total would make every roll out of range, and an overflowing
+        // total cannot be rolled against
+        let total = weights
+            .0
+            .iter()
+            .try_fold(0u32, |acc, w| acc.checked_add(*w));
+        match total {
+            None | Some(0) => Err(FunctionError::InvalidParams),
+            Some(_) => Ok(weights),
+        }
+    }
+}
+
+impl std::fmt::Display for RarityWeights {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let [common, rare, epic, legendary] = self.0;
+        write!(f, "{}:{}:{}:{}", common, rare, epic, legendary)
+    }
+}
+
+pub struct LootTable {
+    pub id: u8,
+    pub weights: RarityWeights,
+    /// Item ids that can drop, indexed by `Rarity as usize`.
+    pub items: [&'static [u32]; 4],
+}
+
+impl LootTable {
+    pub fn items_for(&self, rarity: Rarity) -> &'static [u32] {
+        self.items[rarity as usize]
+    }
+}
+
+pub const LOOT_TABLES: &[LootTable] = &[
+    // Standard crate
+    LootTable {
+        id: 0,
+        weights: RarityWeights([7_000, 2_200, 700, 100]),
+        items: [
+            &[1_000, 1_001, 1_002, 1_003, 1_004, 1_005],
+            &[2_000, 2_001, 2_002, 2_003],
+            &[3_000, 3_001, 3_002],
+            &[4_000, 4_001],
+        ],
+    },
+    // Premium crate, no commons
+    LootTable {
+        id: 1,
+        weights: RarityWeights([0, 6_500, 2_800, 700]),
+        items: [
+            &[1_000],
+            &[2_000, 2_001, 2_002, 2_003],
+            &[3_000, 3_001, 3_002],
+            &[4_000, 4_001],
+        ],
+    },
+];
+
+pub fn find_loot_table(id: u8) -> Option<&'static LootTable> {
+    LOOT_TABLES.iter().find(|table| table.id == id)
+}
+
+/// Picks the dropped item for a rarity roll in `[0, weights.total())` and an
+/// item roll used as an index into the rarity's item list.
+pub fn open_loot(
+    table: &LootTable,
+    weights: &RarityWeights,
+    rarity_roll: u32,
+    item_roll: u32,
+) -> (u32, Rarity) {
+    let rarity = weights.rarity_for_roll(rarity_roll);
+    let items = table.items_for(rarity);
+    let item_id = items[(item_roll as usize) % items.len()];
+    (item_id, rarity)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rarity_for_roll_boundaries() {
+        let weights = RarityWeights([70, 20, 8, 2]);
+
+        assert_eq!(weights.rarity_for_roll(0), Rarity::Common);
+        assert_eq!(weights.rarity_for_roll(69), Rarity::Common);
+        assert_eq!(weights.rarity_for_roll(70), Rarity::Rare);
+        assert_eq!(weights.rarity_for_roll(89), Rarity::Rare);
+        assert_eq!(weights.rarity_for_roll(90), Rarity::Epic);
+        assert_eq!(weights.rarity_for_roll(97), Rarity::Epic);
+        assert_eq!(weights.rarity_for_roll(98), Rarity::Legendary);
+        assert_eq!(weights.rarity_for_roll(99), Rarity::Legendary);
+    }
+
+    #[test]
+    fn test_zero_weight_rarity_never_rolls() {
+        let table = find_loot_table(1).unwrap();
+        for roll in 0..table.weights.total() {
+            assert_ne!(table.weights.rarity_for_roll(roll), Rarity::Common);
+        }
+    }
+
+    #[test]
+    fn test_rarity_weights_from_str() {
+        assert_eq!(
+            RarityWeights::from_str("70:20:8:2").unwrap(),
+            RarityWeights([70, 20, 8, 2])
+        );
+        assert_eq!(RarityWeights([70, 20, 8, 2]).to_string(), "70:20:8:2");
+        assert!(RarityWeights::from_str("70:20:8").is_err());
+        assert!(RarityWeights::from_str("0:0:0:0").is_err());
+        assert!(RarityWeights::from_str("a:1:1:1").is_err());
+        assert!(RarityWeights::from_str("4294967295:1:0:0").is_err());
+    }
+
+    #[test]
+    fn test_loot_tables_are_well_formed() {
+        for (i, table) in LOOT_TABLES.iter().enumerate() {
+            assert_eq!(find_loot_table(table.id).unwrap().id, table.id);
+            assert!(table.weights.total() > 0, "table {} has no weight", i);
+            for items in table.items.iter() {
+                assert!(!items.is_empty(), "table {} has an empty rarity", i);
+            }
+        }
+    }
+
+    #[test]
+    fn test_open_loot_with_generated_randomness() {
+        let table = find_loot_table(DEFAULT_LOOT_TABLE).unwrap();
+
+        for _ in 0..100 {
+            let rarity_roll = generate_randomness(0, table.weights.total() - 1);
+            let item_roll = generate_randomness(0, u32::MAX - 1);
+            let (item_id, rarity) = open_loot(table, &table.weights, rarity_roll, item_roll);
+
+            assert!(table.items_for(rarity).contains(&item_id));
+        }
+    }
+}
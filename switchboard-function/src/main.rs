@@ -1,109 +1,528 @@
+pub use accounts_cache::*;
+pub use accounts_schema::*;
+pub use approval::*;
+pub use attestation::*;
+pub use audit::*;
+pub use batch::*;
+pub use bot::*;
+pub use bucketing::*;
+pub use build_info::*;
+pub use cli::*;
+pub use config::*;
+pub use custom_roll::*;
+pub use daily_seed::*;
+pub use deadline::*;
+pub use discriminators::*;
+pub use distributions::*;
+pub use dry_run::*;
+pub use emission::*;
+pub use enclave_key::*;
+pub use entropy::*;
+pub use errors::*;
+pub use escrow::*;
+pub use expiry::*;
+pub use faction_bias::*;
+pub use failure_report::*;
+use futures::FutureExt;
+pub use idempotency::*;
+pub use idl::*;
+pub use ixns::*;
+pub use local_dev::*;
+pub use lookup_table::*;
+pub use loot_tables::*;
+pub use matchmaking::*;
+pub use metrics::*;
+pub use panic_report::*;
 pub use params::*;
+pub use pipeline::*;
+pub use pool_diversity::*;
+pub use power_score::*;
+pub use precheck::*;
+pub use program_allowlist::*;
+pub use raffle::*;
+pub use randomness::*;
+pub use rate_limit::*;
+pub use realm_registry::*;
+pub use replay::*;
+pub use rng_audit::*;
+pub use rpc::*;
+pub use rpc_endpoint::*;
+pub use self_test::*;
+pub use settle_layout::*;
+pub use shadow::*;
+pub use simulation::*;
+pub use size_guard::*;
+pub use state::*;
+use std::panic::AssertUnwindSafe;
 use std::str::FromStr;
+pub use storage::*;
 pub use switchboard_solana::get_ixn_discriminator;
 pub use switchboard_solana::prelude::*;
+pub use termination::*;
+pub use tiering::*;
+pub use tournament::*;
+pub use webhook::*;
 
+mod accounts_cache;
+mod accounts_schema;
+mod approval;
+mod attestation;
+mod audit;
+mod batch;
+mod bot;
+mod bucketing;
+mod build_info;
+#[cfg(test)]
+mod chaos;
+mod cli;
+mod config;
+mod custom_roll;
+mod daily_seed;
+mod deadline;
+mod discriminators;
+mod distributions;
+mod dry_run;
+mod emission;
+mod enclave_key;
+mod entropy;
+mod errors;
+mod escrow;
+mod expiry;
+mod faction_bias;
+mod failure_report;
+mod idempotency;
+mod idl;
+mod ixns;
+mod local_dev;
+mod lookup_table;
+mod loot_tables;
+mod matchmaking;
+mod metrics;
+mod panic_report;
 mod params;
+mod pipeline;
+mod pool_diversity;
+mod power_score;
+mod precheck;
+mod program_allowlist;
+mod raffle;
+mod randomness;
+mod rate_limit;
+mod realm_registry;
+mod replay;
+mod rng_audit;
+// the pub(crate) items are reached through benches/hot_path.rs too, which
+// compiles this file as a module
+pub(crate) mod rpc;
+mod rpc_endpoint;
+mod self_test;
+mod settle_layout;
+mod shadow;
+mod simulation;
+mod size_guard;
+mod state;
+mod storage;
+mod termination;
+#[cfg(test)]
+pub(crate) mod test_fixtures;
+mod tiering;
+mod tournament;
+mod webhook;
 
 #[tokio::main(worker_threads = 12)]
 async fn main() {
+    let started = std::time::Instant::now();
+    // Logs where any panic happened, settlement panics are also reported
+    // on-chain by `catch_panic`
+    install_panic_hook();
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let mode = Mode::from_args(&args);
+    // Every mode reads its settings through the config file, a run with a
+    // broken one still starts so it can report the failure on-chain
+    let config = load_config().inspect_err(|error| println!("{}", error));
+    if let (Err(_), Ok(mode)) = (&config, &mode) {
+        if *mode != Mode::Run {
+            std::process::exit(2);
+        }
+    }
+    match mode {
+        Ok(Mode::Run) => (),
+        Ok(Mode::Storage(command)) => {
+            std::process::exit(run_storage_command(&SealedStorage::from_env(), &command));
+        }
+        Ok(Mode::SelfTest) => std::process::exit(run_self_test().await),
+        Ok(Mode::VersionInfo) => {
+            println!("{}", BuildInfo::collect(true).to_json());
+            std::process::exit(0);
+        }
+        Ok(Mode::Replay(path)) => std::process::exit(run_replay(&path)),
+        Ok(Mode::RngAudit(samples)) => {
+            std::process::exit(run_rng_audit(&GramineRandomSource, samples))
+        }
+        Err(usage) => {
+            println!("{}", usage);
+            std::process::exit(2);
+        }
+    }
+
+    println!("build info: {}", BuildInfo::collect(false).to_json());
+    if let Ok(config) = config {
+        for setting in effective_config(config) {
+            println!("config: {}", setting);
+        }
+    }
+
+    if local_dev_enabled() {
+        std::process::exit(if config.is_ok() { run_local_dev() } else { 2 });
+    }
+
+    settle_request(started).await;
+    record_timing("execution_ms", started.elapsed(), &[]);
+    flush_metrics().await;
+}
+
+/// Settles the request the function was started for. Once the runner exists
+/// every outcome ends in an emit, failures and panics included, so the game
+/// program always learns why a request was not settled.
+async fn settle_request(started: std::time::Instant) {
+    // Prefer a private RPC endpoint, the public ones rate limit us into timeouts
+    let endpoint = resolve_rpc_endpoint().await;
+    println!(
+        "using {:?} rpc endpoint {}",
+        endpoint.source,
+        endpoint.redacted()
+    );
+
     // First, initialize the runner instance with a freshly generated Gramine keypair
-    let runner = FunctionRunner::new_from_cluster(Cluster::Devnet, None).unwrap();
+    let runner = match FunctionRunner::new_with_client(endpoint.client()) {
+        Ok(runner) => runner,
+        Err(error) => {
+            // without a runner there is no way to report on-chain
+            println!("failed to initialize the function runner: {:?}", error);
+            record_error(FunctionError::Internal);
+            return;
+        }
+    };
+
+    let result = catch_panic(async {
+        // Settlements stop once the escrow runs dry, warn while it still pays
+        if let Some(function_data) = &runner.function_data {
+            let alert = check_escrow_balance(
+                runner.client.as_ref(),
+                &runner.function,
+                &function_data.escrow_token_wallet,
+                escrow_min_balance_from_env(),
+            );
+            if let Some((url, alert)) = escrow_alert_webhook_url_from_env().zip(alert) {
+                post_escrow_alert(&url, &alert).await;
+            }
+        }
+
+        run(&runner, &endpoint, started).await
+    })
+    .await;
+    finish_request(&runner, result, |error| {
+        failure_reports_enabled()
+            .then(|| failure_report(&runner, error))
+            .flatten()
+    })
+    .await;
+}
+
+/// The failure report letting the program refund the request, `None` when
+/// the request gets a bare error code instead.
+fn failure_report(runner: &FunctionRunner, error: FunctionError) -> Option<Instruction> {
+    runner
+        .function_request_data
+        .as_ref()
+        .zip(load_program_allowlist(&SealedStorage::from_env()).ok())
+        .and_then(|(request_data, program_allowlist)| {
+            ContainerParams::decode_for_programs(&request_data.container_params, &program_allowlist)
+                .ok()
+        })
+        // reports go out through the runner's own legacy emit
+        .zip(RunnerAccounts::from_runner(runner).ok())
+        .and_then(|(params, runner_accounts)| {
+            match failure_report_ixn(&params, &runner_accounts, error) {
+                Ok(report) => report,
+                Err(report_error) => {
+                    println!("failed to build failure report: {}", report_error);
+                    None
+                }
+            }
+        })
+}
+
+/// Maps a panic anywhere in the settlement to the `Panicked` code, logged
+/// with where it happened.
+pub(crate) async fn catch_panic<F>(settlement: F) -> std::result::Result<(), FunctionError>
+where
+    F: std::future::Future<Output = std::result::Result<(), FunctionError>>,
+{
+    AssertUnwindSafe(settlement)
+        .catch_unwind()
+        .await
+        .unwrap_or_else(|payload| {
+            println!("settlement {}", take_panic_report(payload.as_ref()));
+            Err(FunctionError::Panicked)
+        })
+}
+
+async fn run(
+    runner: &FunctionRunner,
+    endpoint: &RpcEndpoint,
+    started: std::time::Instant,
+) -> std::result::Result<(), FunctionError> {
+    // Settling with the defaults a broken config meant to override could
+    // settle against the wrong cluster or programs
+    check_config()?;
+
+    let fetcher = FailoverFetcher {
+        primary: runner.client.as_ref(),
+        fallback: endpoint
+            .fallback()
+            .map(|fallback| Box::new(fallback.lazy_client()) as Box<dyn AccountFetcher>),
+    };
+    // every read of the run goes through one cache, so the stages can read
+    // what they need without paying for the same account twice
+    let fetcher = AccountsCache::new(&fetcher);
+
+    // Only settle for the game programs this deployment serves
+    let program_allowlist = load_program_allowlist(&SealedStorage::from_env())?;
+
+    let allow_entropy_fallback = entropy_fallback_enabled(endpoint);
+
+    // A routine run settles the pending requests it was handed in one go
+    let request_keys = request_keys_from_env();
+    if runner.function_request_key.is_none() && !request_keys.is_empty() {
+        return run_batch(
+            runner,
+            &fetcher,
+            &program_allowlist,
+            &request_keys,
+            allow_entropy_fallback,
+            started,
+        )
+        .await;
+    }
 
     // parse and validate user provided request params
-    let maybe_params = ContainerParams::decode(
-        &runner
-            .function_request_data
+    let request_data = runner
+        .function_request_data
+        .as_ref()
+        .ok_or(FunctionError::MissingRequestData)?;
+    let params =
+        ContainerParams::decode_for_programs(&request_data.container_params, &program_allowlist)?;
+    params.report_deprecated_keys();
+
+    // Reject what we can before spending any RPC calls on the request
+    precheck(&params, &program_allowlist).inspect_err(|error| {
+        println!(
+            "rejected request after {}ms: {}",
+            started.elapsed().as_millis(),
+            error
+        )
+    })?;
+
+    // A retried request may have settled since the oracle queued this run
+    check_request_pending(&request_data.active_request)?;
+
+    // Settling against spaceship state that moved on fails on-chain
+    let current_slot = fetch_current_slot(&runner.client);
+    if let Some(current_slot) = current_slot {
+        RequestExpiry::at(current_slot).check(request_data.active_request.request_slot)?;
+    }
+    // Event windows are enforced here, the program cannot check them when
+    // the request is made
+    check_slot_window(&params, current_slot)?;
+
+    // A user flooding the queue is refused before anything is rolled
+    if let Some(rate_limit) = RateLimit::from_env() {
+        check_user_rate_limit(
+            &fetcher,
+            &params,
+            &rate_limit,
+            request_data.active_request.request_slot,
+        )?;
+    }
+
+    let mut budget = TierBudget::from_env(started);
+    let (rng, enclave_key) =
+        select_run_entropy(GramineRandomSource, allow_entropy_fallback, &mut budget).await?;
+    let runner_accounts =
+        RunnerAccounts::for_settlement(runner, TxFormat::from_env(), enclave_key)?;
+    let simulator =
+        simulation_verify_ixn(runner, runner_accounts.enclave_signer).map(|verify_ixn| {
+            RpcSimulator {
+                client: runner.client.as_ref(),
+                prefix_ixs: vec![verify_ixn],
+            }
+        });
+
+    let settlement = build_settlement(
+        &params,
+        &runner_accounts,
+        &runner.payer,
+        &fetcher,
+        &rng,
+        simulator
             .as_ref()
-            .unwrap()
-            .container_params,
-    );
+            .map(|simulator| simulator as &dyn TransactionSimulator),
+        &mut budget,
+    )?;
 
-    if maybe_params.is_err() {
-        runner.emit_error(1).await.unwrap();
-        return;
-    }
-    let params = maybe_params.unwrap();
-
-    // Generate our random result
-    let random_result = generate_randomness(1, 100_000);
-    let mut random_bytes = random_result.to_le_bytes().to_vec();
-
-    // IXN DATA:
-    // LEN: 13 bytes
-    // [0-8]: Anchor Ixn Discriminator
-    // [9-12]: Random Result as u32
-    // [13]: Faction as u8
-    let mut ixn_data = get_ixn_discriminator("arena_matchmaking_settle").to_vec();
-    ixn_data.append(&mut random_bytes);
-    ixn_data.push(params.faction);
-
-    // ACCOUNTS:
-    // 1. Enclave Signer (signer): our Gramine generated keypair
-    // 2. User: our user who made the request
-    // 3. Realm
-    // 4. User Account PDA
-    // 5. Spaceship PDA (mut)
-    // 6. Switchboard Function (arena_matchmaking_function)
-    // 7. Switchboard Function Request
-    // 8-9-10-11-12. the spaceships that are potentially being matched with the spaceship_pda
-    let settle_ixn = Instruction {
-        program_id: params.program_id,
-        data: ixn_data,
-        accounts: vec![
-            AccountMeta::new_readonly(runner.signer, true),
-            AccountMeta::new_readonly(params.user, false),
-            AccountMeta::new(params.realm_pda, false),
-            AccountMeta::new_readonly(params.user_account_pda, false),
-            AccountMeta::new(params.spaceship_pda, false),
-            AccountMeta::new_readonly(runner.function, false),
-            AccountMeta::new_readonly(runner.function_request_key.unwrap(), false),
-            AccountMeta::new(params.opponent_spaceship_1_pda, false),
-            AccountMeta::new(params.opponent_spaceship_2_pda, false),
-            AccountMeta::new(params.opponent_spaceship_3_pda, false),
-            AccountMeta::new(params.opponent_spaceship_4_pda, false),
-            AccountMeta::new(params.opponent_spaceship_5_pda, false),
-        ],
+    let emit_started = std::time::Instant::now();
+    let result = emit_settlement(
+        runner,
+        settlement.ixs,
+        &SettlementSigner::EnclaveKey(settlement.encoding),
+        &[settlement.outcome],
+        settlement.pool_diversity.as_slice(),
+        &[settlement.audit],
+    )
+    .await;
+    budget.record(Phase::Emit, emit_started);
+    result
+}
+
+/// Settles every request in `REQUEST_KEYS` that fits in one transaction.
+async fn run_batch(
+    runner: &FunctionRunner,
+    fetcher: &AccountsCache<'_>,
+    program_allowlist: &[Pubkey],
+    request_keys: &[Pubkey],
+    allow_entropy_fallback: bool,
+    started: std::time::Instant,
+) -> std::result::Result<(), FunctionError> {
+    let requests = fetch_batch_params(
+        runner.client.clone(),
+        runner.function,
+        fetch_current_slot(&runner.client).map(RequestExpiry::at),
+        program_allowlist,
+        request_keys,
+        batch_parallelism_from_env(),
+    )
+    .await;
+    let requests = match RateLimit::from_env() {
+        Some(rate_limit) => limit_batch_requests(requests, &rate_limit, &SealedStorage::from_env()),
+        None => requests,
     };
 
-    let increase_compute_budget_ix = Instruction::new_with_borsh(
-        solana_sdk::compute_budget::id(),
-        &solana_sdk::compute_budget::ComputeBudgetInstruction::SetComputeUnitLimit(1_200_000),
-        vec![],
+    // the runner builds the function_verify instruction of a routine run,
+    // which it only emits as a legacy transaction
+    let runner_accounts = RunnerAccounts {
+        enclave_signer: runner.signer,
+        function: runner.function,
+        function_request: Pubkey::default(),
+        request_slot: 0,
+        tx_format: TxFormat::Legacy,
+    };
+    let mut budget = TierBudget::from_env(started);
+    // the runner signs the batch, the run's key still attests the results
+    let (rng, _) =
+        select_run_entropy(GramineRandomSource, allow_entropy_fallback, &mut budget).await?;
+    let batch = build_batch_settlement(
+        requests,
+        &runner_accounts,
+        &runner.payer,
+        fetcher,
+        &rng,
+        program_allowlist,
+        &mut budget,
+    )?;
+    println!(
+        "settling {} of {} requests, {} superseded, {} deferred",
+        batch.outcomes.len(),
+        request_keys.len(),
+        batch.superseded.len(),
+        batch.deferred.len()
     );
 
-    // Then, write your own Rust logic and build a Vec of instructions.
-    // Should  be under 700 bytes after serialization
-    let ixs: Vec<solana_program::instruction::Instruction> =
-        vec![increase_compute_budget_ix, settle_ixn];
+    let emit_started = std::time::Instant::now();
+    let result = emit_settlement(
+        runner,
+        batch.ixs,
+        &SettlementSigner::Runner,
+        &batch.outcomes,
+        &batch.pool_diversity,
+        &batch.audit,
+    )
+    .await;
+    budget.record(Phase::Emit, emit_started);
+    result
+}
 
-    // Finally, emit the signed quote and partially signed transaction to the functionRunner oracle
-    // The functionRunner oracle will use the last outputted word to stdout as the serialized result. This is what gets executed on-chain.
-    match runner.emit(ixs).await {
-        Ok(_) => (),
-        Err(_error) => {
-            let _ = runner.emit_error(3).await;
-            return;
-        }
-    };
+/// The entropy the run settles with and the run's enclave key, seeded from it
+/// before anything else draws the key.
+async fn select_run_entropy<S: RandomSource>(
+    source: S,
+    allow_fallback: bool,
+    budget: &mut TierBudget,
+) -> std::result::Result<(RunEntropy<S>, &'static EnclaveKey), FunctionError> {
+    let rng = select_entropy(source, allow_fallback, budget).await?;
+    let enclave_key = run_enclave_key_from(&rng)?;
+    Ok((rng, enclave_key))
 }
 
-fn generate_randomness(min: u32, max: u32) -> u32 {
-    if min == max {
-        return min;
+pub(crate) async fn emit_settlement<E: ResultEmitter + ?Sized>(
+    emitter: &E,
+    ixs: Vec<Instruction>,
+    signer: &SettlementSigner,
+    outcomes: &[OutcomeSummary],
+    pool_diversity: &[PoolDiversity],
+    audit: &[AuditRecord],
+) -> std::result::Result<(), FunctionError> {
+    if dry_run_enabled() {
+        println!("{}", ixns_to_json(&ixs));
+        return Ok(());
     }
-    if min > max {
-        return generate_randomness(max, min);
+
+    if !pool_diversity.is_empty() {
+        let storage = SealedStorage::from_env();
+        for pool_diversity in pool_diversity {
+            record_pool_diversity(&storage, pool_diversity);
+        }
     }
 
-    // We add one so its inclusive [min, max]
-    let window = (max + 1) - min;
+    // Finally, emit the signed quote and partially signed transaction to the functionRunner oracle
+    // The functionRunner oracle will use the last outputted word to stdout as the serialized result. This is what gets executed on-chain.
+    let emitted = match signer {
+        SettlementSigner::Runner => emitter.emit(ixs).await,
+        SettlementSigner::EnclaveKey(encoding) => emitter.emit_signed(ixs, encoding).await,
+    };
+    if let Err(error) = emitted {
+        println!("failed to emit settlement: {:?}", error);
+        record_counter("emit_total", &[("result", "failed")]);
+        return Err(FunctionError::EmitFailed);
+    }
+    record_counter("emit_total", &[("result", "ok")]);
+
+    // Let the game backend update without polling the chain
+    if let Some(url) = webhook_url_from_env() {
+        match run_enclave_key() {
+            Ok(key) => {
+                for outcome in outcomes {
+                    post_outcome(&url, &SignedOutcome::sign(outcome, key)).await;
+                }
+            }
+            Err(error) => eprintln!("failed to generate webhook key: {}", error),
+        }
+    }
 
-    let mut bytes: [u8; 4] = [0u8; 4];
-    Gramine::read_rand(&mut bytes).expect("gramine failed to generate randomness");
-    let raw_result: &[u32] = bytemuck::cast_slice(&bytes[..]);
+    let audit_sinks = AuditSinks::from_env();
+    if audit_sinks.is_enabled() {
+        record_audit(&audit_sinks, audit).await;
+    }
+    Ok(())
+}
 
-    (raw_result[0] % window) + min
+/// Production code draws through a `RandomSource`, this is the Gramine
+/// shorthand used by the tests.
+#[cfg(test)]
+fn generate_randomness(min: u32, max: u32) -> u32 {
+    GramineRandomSource
+        .generate(min, max)
+        .expect("gramine failed to generate randomness")
 }
 
 #[cfg(test)]
@@ -140,6 +559,7 @@ mod tests {
 
     // 4. Test randomness distribution (not truly deterministic, but a sanity check)
     #[test]
+    #[allow(clippy::useless_vec)]
     fn test_generate_randomness_distribution() {
         let min = 0;
         let max = 9;
@@ -171,4 +591,61 @@ mod tests {
         ixn_data.push(faction);
         // ixn_data.append(&mut faction.to_le_bytes().to_vec());
     }
+
+    #[test]
+    fn test_catch_panic_maps_to_panicked() {
+        let settled = futures::executor::block_on(catch_panic(async { Ok(()) }));
+        assert_eq!(settled, Ok(()));
+
+        let failed =
+            futures::executor::block_on(catch_panic(async { Err(FunctionError::InvalidParams) }));
+        assert_eq!(failed, Err(FunctionError::InvalidParams));
+
+        let panicked = futures::executor::block_on(catch_panic(async {
+            panic!("settlement bug");
+        }));
+        assert_eq!(panicked, Err(FunctionError::Panicked));
+    }
+
+    #[tokio::test]
+    async fn test_run_settles_after_a_failed_entropy_read() {
+        let params = test_fixtures::test_params();
+        let fetcher = test_fixtures::test_fetcher(
+            &params,
+            &test_fixtures::test_realm(vec![]),
+            [0; 6].map(test_fixtures::test_spaceship),
+        );
+        let mut budget = test_fixtures::test_budget(ExecutionTier::Standard);
+
+        // the key is drawn after the probe retried the failed read
+        let (rng, enclave_key) =
+            select_run_entropy(test_fixtures::FlakySource::new(1), false, &mut budget)
+                .await
+                .unwrap();
+        assert!(!rng.is_fallback());
+        let runner_accounts = RunnerAccounts {
+            enclave_signer: enclave_key.pubkey(),
+            ..test_fixtures::test_runner_accounts()
+        };
+        let settlement = build_settlement(
+            &params,
+            &runner_accounts,
+            &runner_accounts.enclave_signer,
+            &fetcher,
+            &rng,
+            None,
+            &mut budget,
+        )
+        .unwrap();
+
+        let settle_ixn = settlement.ixs.last().unwrap();
+        let accounts: Vec<Pubkey> = settle_ixn
+            .accounts
+            .iter()
+            .map(|account| account.pubkey)
+            .collect();
+        let settled = SettledMatch::decode(&settle_ixn.data, &accounts).unwrap();
+        assert_eq!(settled.attestation_signer, enclave_key.pubkey());
+        assert!(settled.verify_attestation(runner_accounts.request_slot));
+    }
 }
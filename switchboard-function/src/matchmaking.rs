@@ -0,0 +1,670 @@
+use crate::*;
+use std::time::Instant;
+
+/// Settled when the realm does not define any sub-pools.
+pub const DEFAULT_SUB_POOL_ID: u8 = 0;
+/// Number of opponent spaceships a matchmaking request passes.
+pub const OPPONENT_SLOTS: u32 = OPPONENT_SLOT_COUNT as u32;
+
+/// One of the opponent slots passed in params, along with its decoded account.
+#[derive(Clone, Debug)]
+pub struct Candidate {
+    /// Index of the slot in the settle instruction's opponent accounts.
+    pub slot: u8,
+    pub pubkey: Pubkey,
+    pub spaceship: Spaceship,
+}
+
+pub struct MatchmakingAccounts {
+    pub realm: Realm,
+    pub spaceship: Spaceship,
+    pub candidates: Vec<Candidate>,
+}
+
+impl MatchmakingAccounts {
+    /// Fetches the realm, the requester's spaceship and every candidate in a
+    /// single round trip. Empty opponent slots have no candidate.
+    pub fn load<F: AccountFetcher + ?Sized>(
+        fetcher: &F,
+        params: &ContainerParams,
+    ) -> std::result::Result<Self, FunctionError> {
+        let opponents: Vec<(usize, Pubkey)> = params
+            .opponent_spaceship_pdas()
+            .into_iter()
+            .enumerate()
+            .filter(|(_, pda)| *pda != Pubkey::default())
+            .collect();
+
+        let mut pubkeys = vec![params.realm_pda, params.spaceship_pda];
+        pubkeys.extend(opponents.iter().map(|(_, pda)| *pda));
+
+        let accounts = fetcher.fetch_multiple_account_data(&pubkeys)?;
+        if accounts.len() != pubkeys.len() {
+            return Err(FunctionError::AccountFetchFailed);
+        }
+        let mut accounts = accounts.into_iter();
+        let mut next_account = || {
+            accounts
+                .next()
+                .flatten()
+                .ok_or(FunctionError::AccountFetchFailed)
+        };
+
+        let realm = Realm::decode(&next_account()?)?;
+        let spaceship = Spaceship::decode(&next_account()?)?;
+
+        let mut candidates = Vec::with_capacity(opponents.len());
+        for (slot, pubkey) in opponents {
+            candidates.push(Candidate {
+                slot: slot as u8,
+                pubkey,
+                spaceship: Spaceship::decode(&next_account()?)?,
+            });
+        }
+
+        Ok(Self {
+            realm,
+            spaceship,
+            candidates,
+        })
+    }
+
+    /// Reads the candidates from the realm's queue for the requester's
+    /// sub-pool instead of params: the oldest queued spaceships besides the
+    /// requester's, returned in slot order. The settle instruction takes
+    /// exactly `OPPONENT_SLOT_COUNT` opponents, a shorter queue has no
+    /// eligible opponent yet.
+    pub fn load_from_queue<F: AccountFetcher + ?Sized>(
+        fetcher: &F,
+        params: &ContainerParams,
+    ) -> std::result::Result<(Self, [Pubkey; OPPONENT_SLOT_COUNT]), FunctionError> {
+        let accounts =
+            fetcher.fetch_multiple_account_data(&[params.realm_pda, params.spaceship_pda])?;
+        let [Some(realm), Some(spaceship)] =
+            <[_; 2]>::try_from(accounts).map_err(|_| FunctionError::AccountFetchFailed)?
+        else {
+            return Err(FunctionError::AccountFetchFailed);
+        };
+        let realm = Realm::decode(&realm)?;
+        let spaceship = Spaceship::decode(&spaceship)?;
+
+        let sub_pool_id = resolve_sub_pool(&realm.config, spaceship.rating)?
+            .map_or(DEFAULT_SUB_POOL_ID, |sub_pool| sub_pool.id);
+        let queued: Vec<Pubkey> = realm
+            .queue(sub_pool_id)
+            .map(|queue| {
+                queue
+                    .owners
+                    .iter()
+                    .map(|owner| spaceship_pda(&params.program_id, &params.realm_pda, owner))
+                    .filter(|pda| *pda != params.spaceship_pda)
+                    .take(OPPONENT_SLOT_COUNT)
+                    .collect()
+            })
+            .unwrap_or_default();
+        let opponents: [Pubkey; OPPONENT_SLOT_COUNT] = queued
+            .try_into()
+            .map_err(|_| FunctionError::NoEligibleOpponent)?;
+
+        let accounts = fetcher.fetch_multiple_account_data(&opponents)?;
+        if accounts.len() != opponents.len() {
+            return Err(FunctionError::AccountFetchFailed);
+        }
+        let mut candidates = Vec::with_capacity(opponents.len());
+        for (slot, (pubkey, data)) in opponents.iter().zip(accounts).enumerate() {
+            candidates.push(Candidate {
+                slot: slot as u8,
+                pubkey: *pubkey,
+                spaceship: Spaceship::decode(&data.ok_or(FunctionError::AccountFetchFailed)?)?,
+            });
+        }
+
+        Ok((
+            Self {
+                realm,
+                spaceship,
+                candidates,
+            },
+            opponents,
+        ))
+    }
+}
+
+/// Finds the sub-pool a rating belongs to. Realms without sub-pools put
+/// everyone in the default pool.
+pub fn resolve_sub_pool(
+    config: &RealmConfig,
+    rating: u32,
+) -> std::result::Result<Option<&SubPool>, FunctionError> {
+    if config.sub_pools.is_empty() {
+        return Ok(None);
+    }
+    config
+        .sub_pools
+        .iter()
+        .find(|sub_pool| sub_pool.contains(rating))
+        .map(Some)
+        .ok_or(FunctionError::NoMatchingSubPool)
+}
+
+/// The outcome of opponent selection, as encoded in the settle instruction.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Selection {
+    pub sub_pool_id: u8,
+    pub opponent_slot: u8,
+    /// Zero when the accounts were not fetched.
+    pub requester_power: u32,
+    pub opponent_power: PowerBreakdown,
+}
+
+/// Restricts the candidates to the requester's sub-pool, and to other
+/// factions when `exclude_same_faction` is set, then picks one of them with
+/// `roll`, which may be any value. Candidates already in a match and the
+/// `excluded_slots` are never picked. With `power_weights` the roll favours
+/// candidates close to the requester's power score, otherwise every eligible
+/// candidate is equally likely.
+pub fn select_opponent(
+    accounts: &MatchmakingAccounts,
+    roll: u32,
+    exclude_same_faction: bool,
+    excluded_slots: &[u8],
+    power_weights: Option<&PowerWeights>,
+) -> std::result::Result<Selection, FunctionError> {
+    let sub_pool = resolve_sub_pool(&accounts.realm.config, accounts.spaceship.rating)?;
+
+    let eligible: Vec<&Candidate> = accounts
+        .candidates
+        .iter()
+        .filter(|candidate| match sub_pool {
+            Some(sub_pool) => sub_pool.contains(candidate.spaceship.rating),
+            None => true,
+        })
+        .filter(|candidate| {
+            !exclude_same_faction || candidate.spaceship.faction != accounts.spaceship.faction
+        })
+        .filter(|candidate| {
+            candidate.spaceship.current_match.is_none() && !excluded_slots.contains(&candidate.slot)
+        })
+        .collect();
+
+    if eligible.is_empty() {
+        return Err(FunctionError::NoEligibleOpponent);
+    }
+
+    let weights = power_weights.unwrap_or(&DEFAULT_POWER_WEIGHTS);
+    let requester_power = power_breakdown(&accounts.spaceship, weights).total;
+    let index = match power_weights {
+        Some(_) => {
+            let powers: Vec<u32> = eligible
+                .iter()
+                .map(|candidate| power_breakdown(&candidate.spaceship, weights).total)
+                .collect();
+            pick_by_power(requester_power, &powers, roll).ok_or(FunctionError::Internal)?
+        }
+        None => (roll as usize) % eligible.len(),
+    };
+    let opponent = eligible[index];
+
+    Ok(Selection {
+        sub_pool_id: sub_pool.map_or(DEFAULT_SUB_POOL_ID, |sub_pool| sub_pool.id),
+        opponent_slot: opponent.slot,
+        requester_power,
+        opponent_power: power_breakdown(&opponent.spaceship, weights),
+    })
+}
+
+/// Re-reads the selected opponent right before settling. Another settlement
+/// may have matched it since the candidates were loaded, in which case it
+/// is excluded and the opponent re-rolled among the remaining candidates, up
+/// to the request's `max_rerolls` times and while the budget allows another
+/// fetch.
+pub fn select_fresh_opponent<F: AccountFetcher + ?Sized>(
+    fetcher: &F,
+    accounts: &MatchmakingAccounts,
+    roll: u32,
+    params: &ContainerParams,
+    rng: &dyn RandomSource,
+    budget: &mut TierBudget,
+) -> std::result::Result<Selection, FunctionError> {
+    let power_weights =
+        variant_power_weights(experiment_variant(params), params.power_weights.as_ref());
+    let mut excluded_slots = vec![];
+    let mut selection = select_opponent(
+        accounts,
+        roll,
+        params.exclude_same_faction,
+        &excluded_slots,
+        power_weights,
+    )?;
+
+    while budget.admit(ExecutionTier::Standard, Phase::Validate) {
+        let candidate = accounts
+            .candidates
+            .iter()
+            .find(|candidate| candidate.slot == selection.opponent_slot)
+            .ok_or(FunctionError::NoEligibleOpponent)?;
+        let started = Instant::now();
+        let fresh = fetcher
+            .fetch_fresh_account_data(std::slice::from_ref(&candidate.pubkey))?
+            .pop()
+            .flatten()
+            .ok_or(FunctionError::AccountFetchFailed)?;
+        budget.record(Phase::Validate, started);
+        if Spaceship::decode(&fresh)?.current_match.is_none() {
+            break;
+        }
+
+        println!("opponent {} was matched meanwhile", candidate.pubkey);
+        if excluded_slots.len() >= params.max_rerolls as usize {
+            return Err(FunctionError::OpponentUnavailable);
+        }
+        excluded_slots.push(selection.opponent_slot);
+        selection = select_opponent(
+            accounts,
+            rng.generate(0, u32::MAX - 1)?,
+            params.exclude_same_faction,
+            &excluded_slots,
+            power_weights,
+        )
+        .map_err(|_| FunctionError::OpponentUnavailable)?;
+    }
+    Ok(selection)
+}
+
+/// Fast tier selection, the roll picks among the slots set in
+/// `opponent_mask` without reading their ratings. The instruction handler
+/// still checks the pairing, this only saves the account fetch.
+pub fn select_opponent_unvalidated(roll: u32, opponent_mask: u8) -> Selection {
+    let slots: Vec<u8> = (0..OPPONENT_SLOTS as u8)
+        .filter(|slot| opponent_mask & 1 << slot != 0)
+        .collect();
+    Selection {
+        sub_pool_id: DEFAULT_SUB_POOL_ID,
+        opponent_slot: slots[roll as usize % slots.len()],
+        requester_power: 0,
+        opponent_power: PowerBreakdown::default(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_fixtures::*;
+
+    fn tiered_realm() -> Realm {
+        test_realm(vec![
+            SubPool {
+                id: 1,
+                min_rating: 0,
+                max_rating: 999,
+            },
+            SubPool {
+                id: 2,
+                min_rating: 1_000,
+                max_rating: 1_999,
+            },
+        ])
+    }
+
+    fn rated_fetcher(params: &ContainerParams, realm: &Realm, ratings: [u32; 6]) -> MockFetcher {
+        test_fetcher(params, realm, ratings.map(test_spaceship))
+    }
+
+    /// A queue mode request and a realm queueing `owners` in sub-pool 1, with
+    /// the requester's spaceship and every queued one in the fetcher.
+    fn queued_fetcher(owners: &[Pubkey]) -> (ContainerParams, MockFetcher) {
+        let requester = Requester {
+            program_id: Pubkey::new_unique(),
+            user: owners[0],
+            realm_pda: Pubkey::new_unique(),
+            user_account_pda: Pubkey::new_unique(),
+        };
+        let params = ContainerParams::matchmaking_from_queue(
+            &requester,
+            spaceship_pda(&requester.program_id, &requester.realm_pda, &owners[0]),
+            0,
+        )
+        .unwrap();
+        let mut realm = tiered_realm();
+        realm.queues = vec![MatchmakingQueue {
+            sub_pool_id: 1,
+            owners: owners.to_vec(),
+        }];
+
+        let mut fetcher = MockFetcher::default();
+        fetcher.insert(params.realm_pda, encode_account(Realm::NAME, &realm));
+        for owner in owners {
+            fetcher.insert(
+                spaceship_pda(&params.program_id, &params.realm_pda, owner),
+                encode_account(Spaceship::NAME, &test_spaceship(500)),
+            );
+        }
+        (params, fetcher)
+    }
+
+    #[test]
+    fn test_load_from_queue() {
+        let owners: Vec<Pubkey> = (0..8).map(|_| Pubkey::new_unique()).collect();
+        let (params, fetcher) = queued_fetcher(&owners);
+
+        let (accounts, opponents) =
+            MatchmakingAccounts::load_from_queue(&fetcher, &params).unwrap();
+
+        // the requester is skipped, the oldest five others are the candidates
+        let expected: Vec<Pubkey> = owners[1..6]
+            .iter()
+            .map(|owner| spaceship_pda(&params.program_id, &params.realm_pda, owner))
+            .collect();
+        assert_eq!(opponents.to_vec(), expected);
+        assert_eq!(
+            accounts
+                .candidates
+                .iter()
+                .map(|candidate| (candidate.slot, candidate.pubkey))
+                .collect::<Vec<_>>(),
+            expected
+                .iter()
+                .enumerate()
+                .map(|(slot, pubkey)| (slot as u8, *pubkey))
+                .collect::<Vec<_>>()
+        );
+        assert!(select_opponent(&accounts, 3, false, &[], None).is_ok());
+    }
+
+    #[test]
+    fn test_load_from_short_queue() {
+        let owners: Vec<Pubkey> = (0..5).map(|_| Pubkey::new_unique()).collect();
+        let (params, fetcher) = queued_fetcher(&owners);
+
+        assert_eq!(
+            MatchmakingAccounts::load_from_queue(&fetcher, &params).err(),
+            Some(FunctionError::NoEligibleOpponent)
+        );
+    }
+
+    #[test]
+    fn test_resolve_sub_pool() {
+        let realm = tiered_realm();
+
+        assert_eq!(resolve_sub_pool(&realm.config, 500).unwrap().unwrap().id, 1);
+        assert_eq!(
+            resolve_sub_pool(&realm.config, 1_000).unwrap().unwrap().id,
+            2
+        );
+        assert_eq!(
+            resolve_sub_pool(&realm.config, 5_000),
+            Err(FunctionError::NoMatchingSubPool)
+        );
+        assert_eq!(resolve_sub_pool(&RealmConfig::default(), 5_000), Ok(None));
+    }
+
+    #[test]
+    fn test_select_opponent_restricted_to_sub_pool() {
+        let params = test_params();
+        let fetcher = rated_fetcher(
+            &params,
+            &tiered_realm(),
+            [1_200, 100, 1_500, 900, 1_999, 2_500],
+        );
+        let accounts = MatchmakingAccounts::load(&fetcher, &params).unwrap();
+
+        for roll in 0..100 {
+            let selection = select_opponent(&accounts, roll, false, &[], None).unwrap();
+            assert_eq!(selection.sub_pool_id, 2);
+            assert!(selection.opponent_slot == 1 || selection.opponent_slot == 3);
+        }
+    }
+
+    #[test]
+    fn test_select_opponent_without_sub_pools() {
+        let params = test_params();
+        let realm = test_realm(vec![]);
+        let fetcher = rated_fetcher(&params, &realm, [1_200, 100, 1_500, 900, 1_999, 2_500]);
+        let accounts = MatchmakingAccounts::load(&fetcher, &params).unwrap();
+
+        let slots: Vec<u8> = (0..5)
+            .map(|roll| {
+                select_opponent(&accounts, roll, false, &[], None)
+                    .unwrap()
+                    .opponent_slot
+            })
+            .collect();
+        assert_eq!(slots, vec![0, 1, 2, 3, 4]);
+        assert_eq!(
+            select_opponent(&accounts, 0, false, &[], None)
+                .unwrap()
+                .sub_pool_id,
+            DEFAULT_SUB_POOL_ID
+        );
+    }
+
+    #[test]
+    fn test_select_opponent_empty_sub_pool() {
+        let params = test_params();
+        let fetcher = rated_fetcher(&params, &tiered_realm(), [1_200, 100, 200, 300, 400, 500]);
+        let accounts = MatchmakingAccounts::load(&fetcher, &params).unwrap();
+
+        assert_eq!(
+            select_opponent(&accounts, 0, false, &[], None),
+            Err(FunctionError::NoEligibleOpponent)
+        );
+    }
+
+    #[test]
+    fn test_select_opponent_excluding_same_faction() {
+        let params = test_params();
+        let spaceships = [0, 1, 0, 2, 0, 1].map(|faction| Spaceship {
+            faction,
+            ..test_spaceship(1_000)
+        });
+        let fetcher = test_fetcher(&params, &test_realm(vec![]), spaceships);
+        let accounts = MatchmakingAccounts::load(&fetcher, &params).unwrap();
+
+        for roll in 0..100 {
+            let slot = select_opponent(&accounts, roll, true, &[], None)
+                .unwrap()
+                .opponent_slot;
+            assert!(slot == 0 || slot == 2 || slot == 4);
+        }
+        let slots: Vec<u8> = (0..5)
+            .map(|roll| {
+                select_opponent(&accounts, roll, false, &[], None)
+                    .unwrap()
+                    .opponent_slot
+            })
+            .collect();
+        assert_eq!(slots, vec![0, 1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn test_select_opponent_only_same_faction() {
+        let params = test_params();
+        let fetcher = rated_fetcher(&params, &test_realm(vec![]), [1_000; 6]);
+        let accounts = MatchmakingAccounts::load(&fetcher, &params).unwrap();
+
+        assert_eq!(
+            select_opponent(&accounts, 0, true, &[], None),
+            Err(FunctionError::NoEligibleOpponent)
+        );
+    }
+
+    #[test]
+    fn test_load_missing_candidate_account() {
+        let params = test_params();
+        let mut fetcher = rated_fetcher(&params, &tiered_realm(), [0; 6]);
+        fetcher.accounts.remove(&params.opponent_spaceship_3_pda);
+
+        assert!(matches!(
+            MatchmakingAccounts::load(&fetcher, &params),
+            Err(FunctionError::AccountFetchFailed)
+        ));
+    }
+
+    /// Loads the candidates, then marks the given slots as matched on chain
+    /// so only the re-check sees it.
+    fn stale_accounts(
+        params: &ContainerParams,
+        matched_slots: &[usize],
+    ) -> (MatchmakingAccounts, MockFetcher) {
+        let mut fetcher = rated_fetcher(params, &test_realm(vec![]), [1_000; 6]);
+        let accounts = MatchmakingAccounts::load(&fetcher, params).unwrap();
+        for slot in matched_slots {
+            let spaceship = Spaceship {
+                current_match: Some(Pubkey::new_unique()),
+                ..accounts.candidates[*slot].spaceship.clone()
+            };
+            fetcher.insert(
+                accounts.candidates[*slot].pubkey,
+                encode_account(Spaceship::NAME, &spaceship),
+            );
+        }
+        (accounts, fetcher)
+    }
+
+    #[test]
+    fn test_select_opponent_skips_matched_candidates() {
+        let params = test_params();
+        let mut fetcher = rated_fetcher(&params, &test_realm(vec![]), [1_000; 6]);
+        let matched = Spaceship {
+            current_match: Some(Pubkey::new_unique()),
+            ..test_spaceship(1_000)
+        };
+        fetcher.insert(
+            params.opponent_spaceship_1_pda,
+            encode_account(Spaceship::NAME, &matched),
+        );
+        let accounts = MatchmakingAccounts::load(&fetcher, &params).unwrap();
+
+        for roll in 0..20 {
+            let selection = select_opponent(&accounts, roll, false, &[1], None).unwrap();
+            assert!(![0, 1].contains(&selection.opponent_slot));
+        }
+    }
+
+    #[test]
+    fn test_select_fresh_opponent_rerolls_on_conflict() {
+        let params = test_params();
+        let (accounts, fetcher) = stale_accounts(&params, &[0]);
+        let mut budget = test_budget(ExecutionTier::Standard);
+
+        // roll 0 picks slot 0, which was matched after the candidates loaded
+        let selection = select_fresh_opponent(
+            &fetcher,
+            &accounts,
+            0,
+            &params,
+            &OsRandomSource,
+            &mut budget,
+        )
+        .unwrap();
+
+        assert_ne!(selection.opponent_slot, 0);
+        let rechecks = budget
+            .stage_timings()
+            .iter()
+            .filter(|(stage, _)| *stage == "validate")
+            .count();
+        assert_eq!(rechecks, 2);
+    }
+
+    #[test]
+    fn test_select_fresh_opponent_gives_up_after_max_rerolls() {
+        let params = ContainerParams {
+            max_rerolls: 3,
+            ..test_params()
+        };
+        let (accounts, fetcher) = stale_accounts(&params, &[0, 1, 2, 3, 4]);
+
+        assert_eq!(
+            select_fresh_opponent(
+                &fetcher,
+                &accounts,
+                0,
+                &params,
+                &OsRandomSource,
+                &mut test_budget(ExecutionTier::Standard)
+            ),
+            Err(FunctionError::OpponentUnavailable)
+        );
+        assert_eq!(
+            select_fresh_opponent(
+                &fetcher,
+                &accounts,
+                0,
+                &ContainerParams {
+                    max_rerolls: 0,
+                    ..params
+                },
+                &OsRandomSource,
+                &mut test_budget(ExecutionTier::Standard)
+            ),
+            Err(FunctionError::OpponentUnavailable)
+        );
+    }
+
+    #[test]
+    fn test_select_opponent_biased_by_power() {
+        let params = test_params();
+        let realm = test_realm(vec![]);
+        let fetcher = rated_fetcher(&params, &realm, [1_000, 3_000, 1_000, 4_000, 5_000, 6_000]);
+        let accounts = MatchmakingAccounts::load(&fetcher, &params).unwrap();
+
+        let mut even = 0;
+        for roll in (0..u32::MAX).step_by(1_000_003) {
+            let selection =
+                select_opponent(&accounts, roll, false, &[], Some(&DEFAULT_POWER_WEIGHTS)).unwrap();
+            assert_eq!(selection.requester_power, 1_000);
+            assert_eq!(
+                selection.opponent_power.total,
+                accounts.candidates[selection.opponent_slot as usize]
+                    .spaceship
+                    .rating
+            );
+            if selection.opponent_slot == 1 {
+                even += 1;
+            }
+        }
+        // the even match weighs 20 times any other candidate
+        assert!(even > 4_294 * 8 / 10);
+
+        // unbiased selection still reports the scores
+        let selection = select_opponent(&accounts, 1, false, &[], None).unwrap();
+        assert_eq!(selection.opponent_slot, 1);
+        assert_eq!(selection.opponent_power.total, 1_000);
+    }
+
+    #[test]
+    fn test_select_opponent_unvalidated() {
+        assert_eq!(select_opponent_unvalidated(0, 0b1_1111).opponent_slot, 0);
+        assert_eq!(select_opponent_unvalidated(7, 0b1_1111).opponent_slot, 2);
+        assert_eq!(
+            select_opponent_unvalidated(u32::MAX, 0b1_1111).sub_pool_id,
+            DEFAULT_SUB_POOL_ID
+        );
+        // only slots holding a spaceship are picked
+        for roll in 0..10 {
+            assert!([1, 3].contains(&select_opponent_unvalidated(roll, 0b0_1010).opponent_slot));
+        }
+    }
+
+    #[test]
+    fn test_load_skips_empty_slots() {
+        let mut params = test_params();
+        params.opponent_spaceship_2_pda = Pubkey::default();
+        params.opponent_spaceship_5_pda = Pubkey::default();
+        let fetcher = rated_fetcher(&params, &tiered_realm(), [1_500; 6]);
+
+        let accounts = MatchmakingAccounts::load(&fetcher, &params).unwrap();
+        let slots: Vec<u8> = accounts
+            .candidates
+            .iter()
+            .map(|candidate| candidate.slot)
+            .collect();
+        assert_eq!(slots, vec![0, 2, 3]);
+
+        for roll in (0..u32::MAX).step_by(100_000_007) {
+            let selection = select_opponent(&accounts, roll, false, &[], None).unwrap();
+            assert!(slots.contains(&selection.opponent_slot));
+        }
+    }
+}
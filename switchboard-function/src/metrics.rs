@@ -0,0 +1,254 @@
+use crate::*;
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// Prefix of every exported metric name.
+pub const METRICS_PREFIX: &str = "arena_matchmaking";
+
+const PUSH_TIMEOUT: Duration = Duration::from_secs(2);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MetricKind {
+    Counter,
+    Gauge,
+    /// Milliseconds.
+    Timing,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Metric {
+    pub name: &'static str,
+    pub kind: MetricKind,
+    pub value: f64,
+    pub tags: Vec<(&'static str, String)>,
+}
+
+// One run settles one request, so a process wide buffer flushed on exit is
+// all the aggregation needed
+static RECORDED: Mutex<Vec<Metric>> = Mutex::new(Vec::new());
+
+fn record(name: &'static str, kind: MetricKind, value: f64, tags: &[(&'static str, &str)]) {
+    let metric = Metric {
+        name,
+        kind,
+        value,
+        tags: tags
+            .iter()
+            .map(|(key, value)| (*key, value.to_string()))
+            .collect(),
+    };
+    RECORDED
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+        .push(metric);
+}
+
+pub fn record_counter(name: &'static str, tags: &[(&'static str, &str)]) {
+    record(name, MetricKind::Counter, 1.0, tags);
+}
+
+pub fn record_gauge(name: &'static str, value: f64, tags: &[(&'static str, &str)]) {
+    record(name, MetricKind::Gauge, value, tags);
+}
+
+pub fn record_timing(name: &'static str, elapsed: Duration, tags: &[(&'static str, &str)]) {
+    record(
+        name,
+        MetricKind::Timing,
+        elapsed.as_secs_f64() * 1_000.0,
+        tags,
+    );
+}
+
+/// Counts the error codes relayed on-chain.
+pub fn record_error(error: FunctionError) {
+    record_counter("error_total", &[("code", &error.code().to_string())]);
+}
+
+/// Drains everything recorded so far.
+pub fn take_recorded() -> Vec<Metric> {
+    std::mem::take(
+        &mut *RECORDED
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner()),
+    )
+}
+
+/// Where metrics are sent. Metrics are opt-in, nothing is sent unless one of
+/// `METRICS_STATSD_ADDR` (`host:port`) or `METRICS_PUSHGATEWAY_URL` is set.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum MetricsSink {
+    Statsd(String),
+    Pushgateway(String),
+}
+
+impl MetricsSink {
+    pub fn from_env() -> Option<Self> {
+        let env = |key: &str| setting(key).filter(|value| !value.is_empty());
+        env("METRICS_STATSD_ADDR")
+            .map(MetricsSink::Statsd)
+            .or_else(|| env("METRICS_PUSHGATEWAY_URL").map(MetricsSink::Pushgateway))
+    }
+}
+
+/// DogStatsD lines, one per recorded metric.
+pub fn statsd_lines(metrics: &[Metric]) -> Vec<String> {
+    metrics
+        .iter()
+        .map(|metric| {
+            let kind = match metric.kind {
+                MetricKind::Counter => "c",
+                MetricKind::Gauge => "g",
+                MetricKind::Timing => "ms",
+            };
+            let mut line = format!(
+                "{}.{}:{}|{}",
+                METRICS_PREFIX, metric.name, metric.value, kind
+            );
+            if !metric.tags.is_empty() {
+                let tags: Vec<String> = metric
+                    .tags
+                    .iter()
+                    .map(|(key, value)| format!("{}:{}", key, value))
+                    .collect();
+                line += &format!("|#{}", tags.join(","));
+            }
+            line
+        })
+        .collect()
+}
+
+/// Prometheus text exposition of the run. Counters with the same name and
+/// labels are summed, gauges and timings keep the latest value.
+pub fn prometheus_text(metrics: &[Metric]) -> String {
+    let mut series: BTreeMap<String, f64> = BTreeMap::new();
+    for metric in metrics {
+        let name = format!("{}_{}", METRICS_PREFIX, metric.name);
+        let labels: Vec<String> = metric
+            .tags
+            .iter()
+            .map(|(key, value)| format!("{}=\"{}\"", key, value.replace('"', "\\\"")))
+            .collect();
+        let key = if labels.is_empty() {
+            name
+        } else {
+            format!("{}{{{}}}", name, labels.join(","))
+        };
+        let value = series.entry(key).or_default();
+        match metric.kind {
+            MetricKind::Counter => *value += metric.value,
+            MetricKind::Gauge | MetricKind::Timing => *value = metric.value,
+        }
+    }
+    series
+        .iter()
+        .map(|(key, value)| format!("{} {}\n", key, value))
+        .collect()
+}
+
+/// Sends everything recorded to the configured sink. Runs after the emit,
+/// so failures go to stderr and never fail the run.
+pub async fn flush_metrics() {
+    let metrics = take_recorded();
+    let sink = match MetricsSink::from_env() {
+        Some(sink) if !metrics.is_empty() => sink,
+        _ => return,
+    };
+
+    let result: std::result::Result<(), String> = match &sink {
+        MetricsSink::Statsd(addr) => std::net::UdpSocket::bind("0.0.0.0:0")
+            .and_then(|socket| {
+                for line in statsd_lines(&metrics) {
+                    socket.send_to(line.as_bytes(), addr)?;
+                }
+                Ok(())
+            })
+            .map_err(|error| error.to_string()),
+        MetricsSink::Pushgateway(url) => reqwest::Client::new()
+            .post(format!(
+                "{}/metrics/job/arena_matchmaking_function",
+                url.trim_end_matches('/')
+            ))
+            .timeout(PUSH_TIMEOUT)
+            .body(prometheus_text(&metrics))
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map(|_| ())
+            .map_err(|error| error.to_string()),
+    };
+    if let Err(error) = result {
+        eprintln!("failed to flush metrics to {:?}: {}", sink, error);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn metric(
+        name: &'static str,
+        kind: MetricKind,
+        value: f64,
+        tags: &[(&'static str, &str)],
+    ) -> Metric {
+        Metric {
+            name,
+            kind,
+            value,
+            tags: tags
+                .iter()
+                .map(|(key, value)| (*key, value.to_string()))
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn test_statsd_lines() {
+        let metrics = [
+            metric("error_total", MetricKind::Counter, 1.0, &[("code", "6")]),
+            metric("execution", MetricKind::Timing, 12.5, &[]),
+        ];
+
+        assert_eq!(
+            statsd_lines(&metrics),
+            vec![
+                "arena_matchmaking.error_total:1|c|#code:6".to_string(),
+                "arena_matchmaking.execution:12.5|ms".to_string(),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_prometheus_text_aggregates_counters() {
+        let metrics = [
+            metric("error_total", MetricKind::Counter, 1.0, &[("code", "6")]),
+            metric("error_total", MetricKind::Counter, 1.0, &[("code", "6")]),
+            metric("error_total", MetricKind::Counter, 1.0, &[("code", "7")]),
+            metric("pool_rating_spread", MetricKind::Gauge, 10.0, &[]),
+            metric("pool_rating_spread", MetricKind::Gauge, 20.0, &[]),
+        ];
+
+        assert_eq!(
+            prometheus_text(&metrics),
+            "arena_matchmaking_error_total{code=\"6\"} 2\n\
+             arena_matchmaking_error_total{code=\"7\"} 1\n\
+             arena_matchmaking_pool_rating_spread 20\n"
+        );
+    }
+
+    #[test]
+    fn test_record_and_take() {
+        record_error(FunctionError::NoEligibleOpponent);
+
+        // other tests record concurrently, only look for ours
+        let recorded = take_recorded();
+        assert!(recorded.contains(&metric(
+            "error_total",
+            MetricKind::Counter,
+            1.0,
+            &[("code", "7")]
+        )));
+    }
+}
@@ -0,0 +1,113 @@
+use std::any::Any;
+use std::cell::RefCell;
+use std::panic::PanicHookInfo;
+
+// A panic unwinding out of the settlement only leaves its payload to the
+// `catch_unwind` in `catch_panic`, the location is only known to the panic
+// hook. The hook keeps both for the thread that panicked, so the run can log
+// where it failed before emitting the `Panicked` code.
+
+thread_local! {
+    static LAST_PANIC: RefCell<Option<PanicReport>> = const { RefCell::new(None) };
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PanicReport {
+    pub message: String,
+    /// `file:line:column`, unknown when the panic was not seen by the hook.
+    pub location: Option<String>,
+}
+
+impl PanicReport {
+    pub fn from_payload(payload: &(dyn Any + Send)) -> Self {
+        let message = payload
+            .downcast_ref::<&str>()
+            .map(|message| message.to_string())
+            .or_else(|| payload.downcast_ref::<String>().cloned())
+            .unwrap_or_else(|| "non-string panic payload".to_string());
+        Self {
+            message,
+            location: None,
+        }
+    }
+
+    fn from_hook(info: &PanicHookInfo) -> Self {
+        Self {
+            location: info.location().map(|location| location.to_string()),
+            ..Self::from_payload(info.payload())
+        }
+    }
+}
+
+impl std::fmt::Display for PanicReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "panicked at {}: {}",
+            self.location.as_deref().unwrap_or("unknown location"),
+            self.message
+        )
+    }
+}
+
+/// Records every panic for `take_panic_report` on top of the default hook's
+/// output. Installed once, first thing in `main`.
+pub fn install_panic_hook() {
+    static INSTALL: std::sync::Once = std::sync::Once::new();
+    INSTALL.call_once(|| {
+        let default_hook = std::panic::take_hook();
+        std::panic::set_hook(Box::new(move |info| {
+            let report = PanicReport::from_hook(info);
+            LAST_PANIC.with(|last| *last.borrow_mut() = Some(report));
+            default_hook(info);
+        }));
+    });
+}
+
+/// The last panic on this thread, falling back to `payload` when the hook
+/// did not see it.
+pub fn take_panic_report(payload: &(dyn Any + Send)) -> PanicReport {
+    LAST_PANIC
+        .with(|last| last.borrow_mut().take())
+        .unwrap_or_else(|| PanicReport::from_payload(payload))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_panic_report_from_payload() {
+        let report = PanicReport::from_payload(&"settlement bug");
+        assert_eq!(report.message, "settlement bug");
+        assert_eq!(report.location, None);
+        assert_eq!(
+            report.to_string(),
+            "panicked at unknown location: settlement bug"
+        );
+        assert_eq!(
+            PanicReport::from_payload(&format!("slot {}", 7)).message,
+            "slot 7"
+        );
+        assert_eq!(
+            PanicReport::from_payload(&7u8).message,
+            "non-string panic payload"
+        );
+    }
+
+    #[test]
+    fn test_hook_records_the_location() {
+        install_panic_hook();
+
+        let payload = std::panic::catch_unwind(|| {
+            panic!("index {} out of bounds", 5);
+        })
+        .unwrap_err();
+        let report = take_panic_report(payload.as_ref());
+
+        assert_eq!(report.message, "index 5 out of bounds");
+        assert!(report.location.unwrap().contains("panic_report.rs:"));
+        // taken once per panic
+        assert_eq!(take_panic_report(payload.as_ref()).location, None);
+    }
+}
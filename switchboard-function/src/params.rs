@@ -1,105 +1,1316 @@
 use crate::*;
 
+/// The kind of settlement the requester is asking the oracle to produce.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum RequestType {
+    #[default]
+    Matchmaking,
+    LootOpen,
+    TournamentSeed,
+    DailySeed,
+    /// Takes the requester's spaceship out of the matchmaking queue.
+    Cancel,
+    /// Rolls the fields of `ROLL_SCHEMA` for the instruction named by
+    /// `ROLL_IXN`, see custom_roll.rs.
+    CustomRoll,
+    /// Draws distinct winners among the participants of a raffle, see
+    /// raffle.rs.
+    RaffleDraw,
+}
+
+impl RequestType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RequestType::Matchmaking => "MATCHMAKING",
+            RequestType::LootOpen => "LOOT_OPEN",
+            RequestType::TournamentSeed => "TOURNAMENT_SEED",
+            RequestType::DailySeed => "DAILY_SEED",
+            RequestType::Cancel => "CANCEL",
+            RequestType::CustomRoll => "CUSTOM_ROLL",
+            RequestType::RaffleDraw => "RAFFLE_DRAW",
+        }
+    }
+}
+
+impl FromStr for RequestType {
+    type Err = FunctionError;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "MATCHMAKING" => Ok(RequestType::Matchmaking),
+            "LOOT_OPEN" => Ok(RequestType::LootOpen),
+            "TOURNAMENT_SEED" => Ok(RequestType::TournamentSeed),
+            "DAILY_SEED" => Ok(RequestType::DailySeed),
+            "CANCEL" => Ok(RequestType::Cancel),
+            "CUSTOM_ROLL" => Ok(RequestType::CustomRoll),
+            "RAFFLE_DRAW" => Ok(RequestType::RaffleDraw),
+            _ => Err(FunctionError::InvalidParams),
+        }
+    }
+}
+
+/// Shape of the request's outcome roll, given as `DISTRIBUTION`. Sampling
+/// lives in distributions.rs.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub enum RollDistribution {
+    #[default]
+    Uniform,
+    /// `NORMAL:<mean>:<std_dev>`, clamped to the roll's bounds.
+    Normal { mean: u64, std_dev: u64 },
+    /// `WEIGHTED:<weight>:<weight>:...`, one weight per equal width band of
+    /// the roll's bounds, lowest band first.
+    Weighted(Vec<u32>),
+}
+
+impl FromStr for RollDistribution {
+    type Err = FunctionError;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        let mut parts = s.split(':');
+        let kind = parts.next().unwrap_or_default();
+        let values = parts
+            .map(|part| {
+                part.parse::<u64>()
+                    .map_err(|_| FunctionError::InvalidParams)
+            })
+            .collect::<std::result::Result<Vec<u64>, _>>()?;
+
+        match (kind, values.as_slice()) {
+            ("UNIFORM", []) => Ok(RollDistribution::Uniform),
+            ("NORMAL", [mean, std_dev]) => Ok(RollDistribution::Normal {
+                mean: *mean,
+                std_dev: *std_dev,
+            }),
+            ("WEIGHTED", weights) if !weights.is_empty() => {
+                let weights = weights
+                    .iter()
+                    .map(|weight| u32::try_from(*weight).map_err(|_| FunctionError::InvalidParams))
+                    .collect::<std::result::Result<Vec<u32>, _>>()?;
+                // same rule as the loot weights, the total has to be rollable
+                match weights.iter().try_fold(0u32, |acc, w| acc.checked_add(*w)) {
+                    None | Some(0) => Err(FunctionError::InvalidParams),
+                    Some(_) => Ok(RollDistribution::Weighted(weights)),
+                }
+            }
+            _ => Err(FunctionError::InvalidParams),
+        }
+    }
+}
+
+impl std::fmt::Display for RollDistribution {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RollDistribution::Uniform => write!(f, "UNIFORM"),
+            RollDistribution::Normal { mean, std_dev } => write!(f, "NORMAL:{}:{}", mean, std_dev),
+            RollDistribution::Weighted(weights) => write!(
+                f,
+                "WEIGHTED:{}",
+                weights
+                    .iter()
+                    .map(|weight| weight.to_string())
+                    .collect::<Vec<String>>()
+                    .join(":")
+            ),
+        }
+    }
+}
+
+/// Width of a rolled field in the settle instruction data, little endian.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RollFieldKind {
+    U8,
+    U16,
+    U32,
+    U64,
+}
+
+impl RollFieldKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RollFieldKind::U8 => "U8",
+            RollFieldKind::U16 => "U16",
+            RollFieldKind::U32 => "U32",
+            RollFieldKind::U64 => "U64",
+        }
+    }
+
+    pub fn size(&self) -> usize {
+        match self {
+            RollFieldKind::U8 => 1,
+            RollFieldKind::U16 => 2,
+            RollFieldKind::U32 => 4,
+            RollFieldKind::U64 => 8,
+        }
+    }
+
+    pub fn max_value(&self) -> u64 {
+        match self {
+            RollFieldKind::U8 => u8::MAX as u64,
+            RollFieldKind::U16 => u16::MAX as u64,
+            RollFieldKind::U32 => u32::MAX as u64,
+            RollFieldKind::U64 => u64::MAX,
+        }
+    }
+}
+
+/// One field of a `ROLL_SCHEMA`, given as `<kind>` for the kind's full range
+/// or `<kind>/<min>/<max>` for an inclusive range, e.g. `U8/1/6` for a die.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RollField {
+    pub kind: RollFieldKind,
+    pub min: u64,
+    pub max: u64,
+}
+
+impl FromStr for RollField {
+    type Err = FunctionError;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        let mut parts = s.split('/');
+        let kind = match parts.next().unwrap_or_default() {
+            "U8" => RollFieldKind::U8,
+            "U16" => RollFieldKind::U16,
+            "U32" => RollFieldKind::U32,
+            "U64" => RollFieldKind::U64,
+            _ => return Err(FunctionError::InvalidParams),
+        };
+        let bounds = parts
+            .map(|part| {
+                part.parse::<u64>()
+                    .map_err(|_| FunctionError::InvalidParams)
+            })
+            .collect::<std::result::Result<Vec<u64>, _>>()?;
+        let (min, max) = match bounds.as_slice() {
+            [] => (0, kind.max_value()),
+            [min, max] => (*min, *max),
+            _ => return Err(FunctionError::InvalidParams),
+        };
+        if min > max || max > kind.max_value() {
+            return Err(FunctionError::InvalidParams);
+        }
+        Ok(RollField { kind, min, max })
+    }
+}
+
+impl std::fmt::Display for RollField {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.min == 0 && self.max == self.kind.max_value() {
+            write!(f, "{}", self.kind.as_str())
+        } else {
+            write!(f, "{}/{}/{}", self.kind.as_str(), self.min, self.max)
+        }
+    }
+}
+
+/// Most fields a `ROLL_SCHEMA` may list, the settle data stays well within
+/// the transaction size at 8 bytes each.
+pub const MAX_ROLL_FIELDS: usize = 16;
+/// Longest `ROLL_IXN` name accepted.
+pub const MAX_ROLL_IXN_LEN: usize = 64;
+
+/// Instruction names are the program's snake_case handler names.
+fn valid_roll_ixn(name: &str) -> bool {
+    !name.is_empty()
+        && name.len() <= MAX_ROLL_IXN_LEN
+        && name
+            .bytes()
+            .all(|byte| byte.is_ascii_lowercase() || byte.is_ascii_digit() || byte == b'_')
+}
+
+/// Per unit weights of a spaceship's power score, given as
+/// `POWER_WEIGHTS=<rating>:<weapon>:<shield>:<engine>:<hull>`. The rating
+/// weight applies to the ELO rating, the others to the summed levels of the
+/// modules of that kind. Scoring lives in power_score.rs.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PowerWeights {
+    pub rating: u32,
+    pub weapon: u32,
+    pub shield: u32,
+    pub engine: u32,
+    pub hull: u32,
+}
+
+/// Reported in the settlement of every validated match, and the weights
+/// requests without `POWER_WEIGHTS` would be biased with.
+pub const DEFAULT_POWER_WEIGHTS: PowerWeights = PowerWeights {
+    rating: 1,
+    weapon: 40,
+    shield: 30,
+    engine: 20,
+    hull: 30,
+};
+
+impl FromStr for PowerWeights {
+    type Err = FunctionError;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        let weights = s
+            .split(':')
+            .map(|part| {
+                part.parse::<u32>()
+                    .map_err(|_| FunctionError::InvalidParams)
+            })
+            .collect::<std::result::Result<Vec<u32>, _>>()?;
+        match weights.as_slice() {
+            // all zero weights would score every spaceship the same
+            [0, 0, 0, 0, 0] => Err(FunctionError::InvalidParams),
+            [rating, weapon, shield, engine, hull] => Ok(PowerWeights {
+                rating: *rating,
+                weapon: *weapon,
+                shield: *shield,
+                engine: *engine,
+                hull: *hull,
+            }),
+            _ => Err(FunctionError::InvalidParams),
+        }
+    }
+}
+
+impl std::fmt::Display for PowerWeights {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}:{}:{}:{}:{}",
+            self.rating, self.weapon, self.shield, self.engine, self.hull
+        )
+    }
+}
+
+/// A params key that still decodes but is scheduled for removal.
+#[derive(Debug, PartialEq, Eq)]
+pub struct DeprecatedParam {
+    pub key: &'static str,
+    /// Function version the key was deprecated in.
+    pub since: &'static str,
+    pub replacement: Option<&'static str>,
+}
+
+/// Keys clients should stop sending. An entry is removed, along with its
+/// decoding, once the usage metric shows no request sent it for a release.
+pub const DEPRECATED_PARAMS: &[DeprecatedParam] = &[];
+
+pub fn find_deprecated_param<'a>(
+    deprecated: &'a [DeprecatedParam],
+    key: &str,
+) -> Option<&'a DeprecatedParam> {
+    deprecated.iter().find(|param| param.key == key)
+}
+
+/// Params layout the function understands, sent by clients as `VERSION`.
+/// Requests without it predate the key and are treated as version 1.
+pub const PARAMS_VERSION: u8 = 1;
+
+/// Re-rolls allowed when the selected opponent was matched meanwhile.
+pub const DEFAULT_MAX_REROLLS: u8 = 2;
+
+/// Bounds of the matchmaking random result when the request sets no
+/// `MIN`/`MAX`.
+pub const DEFAULT_ROLL_MIN: u64 = 1;
+pub const DEFAULT_ROLL_MAX: u64 = 100_000;
+
+/// Number of factions in the arena, used to reject out of range `FACTION`s.
+pub const FACTION_COUNT: u8 = 3;
+
+/// Comma separated game program ids baked in from the `BAKED_PROGRAM_ALLOWLIST`
+/// build env var, so the allowlist is covered by the enclave measurement.
+/// Unset in builds that settle for any program.
+pub const BAKED_PROGRAM_ALLOWLIST: Option<&str> = option_env!("BAKED_PROGRAM_ALLOWLIST");
+
+/// Parses a comma separated program allowlist. A malformed entry fails the
+/// whole list, an allowlist silently missing a program would be wider than
+/// intended rather than narrower.
+pub fn parse_program_allowlist(list: &str) -> std::result::Result<Vec<Pubkey>, FunctionError> {
+    list.split(',')
+        .map(str::trim)
+        .filter(|entry| !entry.is_empty())
+        .map(|entry| Pubkey::from_str(entry).map_err(|_| FunctionError::ProgramNotAllowed))
+        .collect()
+}
+
+/// `BAKED_PROGRAM_ALLOWLIST`, empty when any program is allowed.
+pub fn baked_program_allowlist() -> std::result::Result<Vec<Pubkey>, FunctionError> {
+    BAKED_PROGRAM_ALLOWLIST.map_or(Ok(vec![]), parse_program_allowlist)
+}
+
+/// Comma separated `ROLL_IXN` handlers baked in from the
+/// `BAKED_ROLL_IXN_ALLOWLIST` build env var. The enclave signs whatever
+/// handler a custom roll names with the fields its requester picked, so only
+/// handlers the operator listed as custom rolls may receive one. Unset in
+/// builds that settle no custom rolls.
+pub const BAKED_ROLL_IXN_ALLOWLIST: Option<&str> = option_env!("BAKED_ROLL_IXN_ALLOWLIST");
+
+/// Parses a comma separated custom roll handler allowlist. Like the program
+/// allowlist a malformed entry fails the whole list, and so does a handler of
+/// the program's IDL, whose layout is not a roll's.
+pub fn parse_roll_ixn_allowlist(list: &str) -> std::result::Result<Vec<String>, FunctionError> {
+    list.split(',')
+        .map(str::trim)
+        .filter(|entry| !entry.is_empty())
+        .map(
+            |entry| match valid_roll_ixn(entry) && known_ixn_discriminator(entry).is_none() {
+                true => Ok(entry.to_string()),
+                false => Err(FunctionError::InvalidParams),
+            },
+        )
+        .collect()
+}
+
+/// `BAKED_ROLL_IXN_ALLOWLIST`, empty when no custom roll is allowed.
+pub fn baked_roll_ixn_allowlist() -> std::result::Result<Vec<String>, FunctionError> {
+    BAKED_ROLL_IXN_ALLOWLIST.map_or(Ok(vec![]), parse_roll_ixn_allowlist)
+}
+
+/// Daily seeds unless the request sets `SEED_PERIOD`.
+pub const DEFAULT_SEED_PERIOD_SECS: u64 = 86_400;
+
+/// `OS_<n>_PDA` keys a matchmaking request passes.
+pub const OPPONENT_SLOT_COUNT: usize = 5;
+
+/// Largest bracket seeded in one request, every participant is passed as an
+/// account so the settle transaction has to stay under the size budget.
+pub const MAX_TOURNAMENT_PARTICIPANTS: usize = 8;
+
+/// Most winners drawn in one raffle, the winner indices are packed into the
+/// settle instruction so it has to stay under the size budget.
+pub const MAX_RAFFLE_WINNERS: u32 = 32;
+
+/// Compute unit limit of the settle transaction unless the request sets
+/// `CU_LIMIT`.
+pub const DEFAULT_CU_LIMIT: u32 = 1_200_000;
+/// The runtime's per transaction maximum, a higher request fails in decode
+/// rather than as a rejected transaction.
+pub const MAX_CU_LIMIT: u32 = 1_400_000;
+/// Highest `CU_PRICE` in micro-lamports per compute unit, the function's
+/// payer covers the priority fee so a request cannot drain it.
+pub const MAX_CU_PRICE: u64 = 1_000_000;
+
+/// Share of an experiment's requesters bucketed into the treatment unless
+/// the request sets `EXPERIMENT_SHARE`, in basis points.
+pub const DEFAULT_EXPERIMENT_SHARE_BPS: u16 = 5_000;
+
+/// Trailing key clients append with `append_params_checksum`.
+pub const PARAMS_CHECKSUM_KEY: &str = "CHECKSUM";
+
+/// First 4 bytes of the sha256 of the params preceding the checksum.
+pub fn params_checksum(params: &[u8]) -> [u8; 4] {
+    let hash = solana_program::hash::hash(params).to_bytes();
+    [hash[0], hash[1], hash[2], hash[3]]
+}
+
+fn checksum_hex(checksum: [u8; 4]) -> String {
+    checksum
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+/// Appends `,CHECKSUM=<8 lowercase hex chars>` to an encoded params string.
+pub fn append_params_checksum(params: &str) -> String {
+    format!(
+        "{},{}={}",
+        params,
+        PARAMS_CHECKSUM_KEY,
+        checksum_hex(params_checksum(params.as_bytes()))
+    )
+}
+
+/// Verifies and strips a trailing checksum. Params without one predate the
+/// key and are passed through, so only clients that send it fail loudly.
+fn strip_params_checksum(container_params: &[u8]) -> std::result::Result<&[u8], FunctionError> {
+    let marker = format!(",{}=", PARAMS_CHECKSUM_KEY);
+    let marker = marker.as_bytes();
+    let position = container_params
+        .windows(marker.len())
+        .rposition(|window| window == marker);
+    let Some(position) = position else {
+        return Ok(container_params);
+    };
+
+    let (params, checksum) = (
+        &container_params[..position],
+        &container_params[position + marker.len()..],
+    );
+    if checksum != checksum_hex(params_checksum(params)).as_bytes() {
+        return Err(FunctionError::ParamsChecksumMismatch);
+    }
+    Ok(params)
+}
+
+/// The accounts every request type is made for.
+#[derive(Clone, Copy, Debug)]
+pub struct Requester {
+    pub program_id: Pubkey,
+    pub user: Pubkey,
+    pub realm_pda: Pubkey,
+    pub user_account_pda: Pubkey,
+}
+
+/// At least one winner, and no more winners than participants.
+fn valid_raffle(participant_count: u32, winner_count: u32) -> bool {
+    (1..=MAX_RAFFLE_WINNERS).contains(&winner_count) && winner_count <= participant_count
+}
+
+fn require_set(pubkeys: &[Pubkey]) -> std::result::Result<(), FunctionError> {
+    match pubkeys.iter().any(|pubkey| *pubkey == Pubkey::default()) {
+        true => Err(FunctionError::InvalidParams),
+        false => Ok(()),
+    }
+}
+
+fn require_distinct(pubkeys: &[Pubkey]) -> std::result::Result<(), FunctionError> {
+    let distinct = pubkeys
+        .iter()
+        .enumerate()
+        .all(|(i, pubkey)| !pubkeys[..i].contains(pubkey));
+    match distinct {
+        true => Ok(()),
+        false => Err(FunctionError::InvalidParams),
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
 pub struct ContainerParams {
+    pub version: u8,
+    pub request_type: RequestType,
     pub program_id: Pubkey,
     pub user: Pubkey,
     pub realm_pda: Pubkey,
     pub user_account_pda: Pubkey,
     pub spaceship_pda: Pubkey,
     pub faction: u8,
+    /// Only match against spaceships of another faction.
+    pub exclude_same_faction: bool,
+    /// Given as `QUEUE=1`, the candidates are read from the realm's
+    /// matchmaking queue rather than passed as `OS_<n>_PDA`s.
+    pub from_queue: bool,
+    /// Given as `MAX_REROLLS`, 0 cancels as soon as the opponent is taken.
+    pub max_rerolls: u8,
+    /// Inclusive bounds of the random result, given as `MIN` and `MAX`.
+    pub roll_min: u64,
+    pub roll_max: u64,
+    /// Biases the opponent towards the requester's power score when set,
+    /// given as `POWER_WEIGHTS`.
+    pub power_weights: Option<PowerWeights>,
     pub opponent_spaceship_1_pda: Pubkey,
     pub opponent_spaceship_2_pda: Pubkey,
     pub opponent_spaceship_3_pda: Pubkey,
     pub opponent_spaceship_4_pda: Pubkey,
     pub opponent_spaceship_5_pda: Pubkey,
+    // loot open only
+    pub loot_table: u8,
+    pub loot_weights: Option<RarityWeights>,
+    /// Shapes the matchmaking random result and the loot rarity roll,
+    /// tournament seeding is always uniform.
+    pub distribution: RollDistribution,
+    // tournament seed only
+    pub tournament_pda: Pubkey,
+    /// Given as `PARTICIPANTS=<pubkey>:<pubkey>:...`.
+    pub participants: Vec<Pubkey>,
+    // daily seed only
+    /// Given as `SEED_PDA`, receives the seed.
+    pub seed_pda: Pubkey,
+    /// Length of the period a seed covers in seconds, given as `SEED_PERIOD`.
+    pub seed_period: u64,
+    /// Seconds the periods start after midnight UTC, given as `SEED_OFFSET`,
+    /// e.g. 14_400 for a shop rotating at 04:00.
+    pub seed_offset: u64,
+    // custom roll only
+    /// The handler the roll settles with, given as `ROLL_IXN`.
+    pub roll_ixn: String,
+    /// Given as `ROLL_SCHEMA=<field>:<field>:...`, in instruction data order.
+    pub roll_schema: Vec<RollField>,
+    // raffle draw only
+    /// Given as `RAFFLE_PDA`, receives the winners.
+    pub raffle_pda: Pubkey,
+    /// Given as `PARTICIPANT_COUNT`, winners are indices below it.
+    pub participant_count: u32,
+    /// Given as `WINNER_COUNT`, at most `MAX_RAFFLE_WINNERS`.
+    pub winner_count: u32,
+    /// Optional address lookup table used to compact the settle transaction.
+    pub lookup_table: Pubkey,
+    /// Settlement approval of realms requiring a multisig co-signer.
+    pub approval_pda: Pubkey,
+    /// Overrides `DEFAULT_CU_LIMIT`, given as `CU_LIMIT`, at most `MAX_CU_LIMIT`.
+    pub cu_limit: Option<u32>,
+    /// Priority fee in micro-lamports per compute unit, given as `CU_PRICE`,
+    /// at most `MAX_CU_PRICE`.
+    pub cu_price: Option<u64>,
+    /// Matchmaking experiment the requester is bucketed for, given as
+    /// `EXPERIMENT_ID`, 0 runs no experiment.
+    pub experiment_id: u32,
+    /// Given as `EXPERIMENT_SHARE` in basis points, at most 10000.
+    pub experiment_share_bps: u16,
+    /// Only settled after this slot, given as `VALID_AFTER_SLOT`.
+    pub valid_after_slot: Option<u64>,
+    /// Only settled before this slot, given as `VALID_BEFORE_SLOT`.
+    pub valid_before_slot: Option<u64>,
+    /// Deprecated keys the request used, see `DEPRECATED_PARAMS`.
+    pub deprecated_keys: Vec<&'static DeprecatedParam>,
+}
+
+fn parse_pubkey(value: &str) -> std::result::Result<Pubkey, FunctionError> {
+    Pubkey::from_str(value).map_err(|_| FunctionError::InvalidParams)
+}
+
+fn parse_u8(value: &str) -> std::result::Result<u8, FunctionError> {
+    value
+        .parse::<u8>()
+        .map_err(|_| FunctionError::InvalidParams)
+}
+
+fn parse_u16(value: &str) -> std::result::Result<u16, FunctionError> {
+    value
+        .parse::<u16>()
+        .map_err(|_| FunctionError::InvalidParams)
+}
+
+fn parse_u32(value: &str) -> std::result::Result<u32, FunctionError> {
+    value
+        .parse::<u32>()
+        .map_err(|_| FunctionError::InvalidParams)
+}
+
+fn parse_u64(value: &str) -> std::result::Result<u64, FunctionError> {
+    value
+        .parse::<u64>()
+        .map_err(|_| FunctionError::InvalidParams)
+}
+
+fn parse_bool(value: &str) -> std::result::Result<bool, FunctionError> {
+    match value {
+        "1" | "true" => Ok(true),
+        "0" | "false" => Ok(false),
+        _ => Err(FunctionError::InvalidParams),
+    }
 }
 
 impl ContainerParams {
-    pub fn decode(container_params: &Vec<u8>) -> std::result::Result<Self, SwitchboardError> {
-        let params = String::from_utf8(container_params.clone()).unwrap();
+    /// Decodes the `KEY=VALUE,...` params string, checking its trailing
+    /// `CHECKSUM` when there is one. Runs on untrusted bytes inside the
+    /// enclave, so every malformed input must come back as an error rather
+    /// than a panic the runner cannot report. The program must be in the
+    /// baked in allowlist.
+    pub fn decode(container_params: &[u8]) -> std::result::Result<Self, FunctionError> {
+        Self::decode_for_programs(container_params, &baked_program_allowlist()?)
+    }
+
+    /// Like `decode`, the program must be in `program_allowlist` unless it is
+    /// empty. A requester could otherwise point the oracle at any program.
+    /// A custom roll's handler must be in the baked in roll allowlist.
+    pub fn decode_for_programs(
+        container_params: &[u8],
+        program_allowlist: &[Pubkey],
+    ) -> std::result::Result<Self, FunctionError> {
+        Self::decode_with_allowlists(
+            container_params,
+            program_allowlist,
+            &baked_roll_ixn_allowlist()?,
+        )
+    }
 
+    /// Like `decode_for_programs`, a custom roll's handler must be in
+    /// `roll_ixn_allowlist`, where an empty list allows none.
+    pub fn decode_with_allowlists(
+        container_params: &[u8],
+        program_allowlist: &[Pubkey],
+        roll_ixn_allowlist: &[String],
+    ) -> std::result::Result<Self, FunctionError> {
+        let params =
+            Self::decode_fields(strip_params_checksum(container_params)?, roll_ixn_allowlist)?;
+        if !program_allowlist.is_empty() && !program_allowlist.contains(&params.program_id) {
+            return Err(FunctionError::ProgramNotAllowed);
+        }
+        Ok(params)
+    }
+
+    fn decode_fields(
+        container_params: &[u8],
+        roll_ixn_allowlist: &[String],
+    ) -> std::result::Result<Self, FunctionError> {
+        let params =
+            std::str::from_utf8(container_params).map_err(|_| FunctionError::InvalidParams)?;
+
+        let mut version: u8 = PARAMS_VERSION;
+        let mut request_type: RequestType = RequestType::default();
         let mut program_id: Pubkey = Pubkey::default();
         let mut user: Pubkey = Pubkey::default();
         let mut realm_pda: Pubkey = Pubkey::default();
         let mut user_account_pda: Pubkey = Pubkey::default();
         let mut spaceship_pda: Pubkey = Pubkey::default();
         let mut faction: u8 = 0;
+        let mut exclude_same_faction: bool = false;
+        let mut from_queue: bool = false;
+        let mut max_rerolls: u8 = DEFAULT_MAX_REROLLS;
+        let mut roll_min: u64 = DEFAULT_ROLL_MIN;
+        let mut roll_max: u64 = DEFAULT_ROLL_MAX;
         let mut opponent_spaceship_1_pda: Pubkey = Pubkey::default();
         let mut opponent_spaceship_2_pda: Pubkey = Pubkey::default();
         let mut opponent_spaceship_3_pda: Pubkey = Pubkey::default();
         let mut opponent_spaceship_4_pda: Pubkey = Pubkey::default();
         let mut opponent_spaceship_5_pda: Pubkey = Pubkey::default();
+        let mut loot_table: u8 = DEFAULT_LOOT_TABLE;
+        let mut loot_weights: Option<RarityWeights> = None;
+        let mut power_weights: Option<PowerWeights> = None;
+        let mut distribution: RollDistribution = RollDistribution::default();
+        let mut tournament_pda: Pubkey = Pubkey::default();
+        let mut participants: Vec<Pubkey> = vec![];
+        let mut seed_pda: Pubkey = Pubkey::default();
+        let mut seed_period: u64 = DEFAULT_SEED_PERIOD_SECS;
+        let mut seed_offset: u64 = 0;
+        let mut roll_ixn: String = String::new();
+        let mut roll_schema: Vec<RollField> = vec![];
+        let mut raffle_pda: Pubkey = Pubkey::default();
+        let mut participant_count: u32 = 0;
+        let mut winner_count: u32 = 0;
+        let mut cu_limit: Option<u32> = None;
+        let mut cu_price: Option<u64> = None;
+        let mut experiment_id: u32 = 0;
+        let mut experiment_share_bps: u16 = DEFAULT_EXPERIMENT_SHARE_BPS;
+        let mut valid_after_slot: Option<u64> = None;
+        let mut valid_before_slot: Option<u64> = None;
+        let mut lookup_table: Pubkey = Pubkey::default();
+        let mut approval_pda: Pubkey = Pubkey::default();
+        let mut deprecated_keys: Vec<&'static DeprecatedParam> = vec![];
 
         for env_pair in params.split(',') {
             let pair: Vec<&str> = env_pair.splitn(2, '=').collect();
             if pair.len() == 2 {
+                if let Some(deprecated) = find_deprecated_param(DEPRECATED_PARAMS, pair[0]) {
+                    deprecated_keys.push(deprecated);
+                }
                 match pair[0] {
-                    "PID" => program_id = Pubkey::from_str(pair[1]).unwrap(),
-                    "USER" => user = Pubkey::from_str(pair[1]).unwrap(),
-                    "REALM_PDA" => realm_pda = Pubkey::from_str(pair[1]).unwrap(),
-                    "USER_ACCOUNT_PDA" => user_account_pda = Pubkey::from_str(pair[1]).unwrap(),
-                    "SPACESHIP_PDA" => spaceship_pda = Pubkey::from_str(pair[1]).unwrap(),
-                    "FACTION" => faction = pair[1].parse::<u8>().unwrap(),
-                    "OS_1_PDA" => opponent_spaceship_1_pda = Pubkey::from_str(pair[1]).unwrap(),
-                    "OS_2_PDA" => opponent_spaceship_2_pda = Pubkey::from_str(pair[1]).unwrap(),
-                    "OS_3_PDA" => opponent_spaceship_3_pda = Pubkey::from_str(pair[1]).unwrap(),
-                    "OS_4_PDA" => opponent_spaceship_4_pda = Pubkey::from_str(pair[1]).unwrap(),
-                    "OS_5_PDA" => opponent_spaceship_5_pda = Pubkey::from_str(pair[1]).unwrap(),
+                    "VERSION" => version = parse_u8(pair[1])?,
+                    "REQUEST_TYPE" => request_type = RequestType::from_str(pair[1])?,
+                    "PID" => program_id = parse_pubkey(pair[1])?,
+                    "USER" => user = parse_pubkey(pair[1])?,
+                    "REALM_PDA" => realm_pda = parse_pubkey(pair[1])?,
+                    "USER_ACCOUNT_PDA" => user_account_pda = parse_pubkey(pair[1])?,
+                    "SPACESHIP_PDA" => spaceship_pda = parse_pubkey(pair[1])?,
+                    "FACTION" => faction = parse_u8(pair[1])?,
+                    "MAX_REROLLS" => max_rerolls = parse_u8(pair[1])?,
+                    "MIN" => roll_min = parse_u64(pair[1])?,
+                    "MAX" => roll_max = parse_u64(pair[1])?,
+                    "EXCLUDE_SAME_FACTION" => exclude_same_faction = parse_bool(pair[1])?,
+                    "QUEUE" => from_queue = parse_bool(pair[1])?,
+                    "OS_1_PDA" => opponent_spaceship_1_pda = parse_pubkey(pair[1])?,
+                    "OS_2_PDA" => opponent_spaceship_2_pda = parse_pubkey(pair[1])?,
+                    "OS_3_PDA" => opponent_spaceship_3_pda = parse_pubkey(pair[1])?,
+                    "OS_4_PDA" => opponent_spaceship_4_pda = parse_pubkey(pair[1])?,
+                    "OS_5_PDA" => opponent_spaceship_5_pda = parse_pubkey(pair[1])?,
+                    "LOOT_TABLE" => loot_table = parse_u8(pair[1])?,
+                    "LOOT_WEIGHTS" => loot_weights = Some(RarityWeights::from_str(pair[1])?),
+                    "DISTRIBUTION" => distribution = RollDistribution::from_str(pair[1])?,
+                    "POWER_WEIGHTS" => power_weights = Some(PowerWeights::from_str(pair[1])?),
+                    "TOURNAMENT_PDA" => tournament_pda = parse_pubkey(pair[1])?,
+                    "PARTICIPANTS" => {
+                        participants = pair[1]
+                            .split(':')
+                            .map(parse_pubkey)
+                            .collect::<std::result::Result<_, _>>()?
+                    }
+                    "SEED_PDA" => seed_pda = parse_pubkey(pair[1])?,
+                    "SEED_PERIOD" => seed_period = parse_u64(pair[1])?,
+                    "SEED_OFFSET" => seed_offset = parse_u64(pair[1])?,
+                    "ROLL_IXN" => roll_ixn = pair[1].to_string(),
+                    "ROLL_SCHEMA" => {
+                        roll_schema = pair[1]
+                            .split(':')
+                            .map(RollField::from_str)
+                            .collect::<std::result::Result<_, _>>()?
+                    }
+                    "RAFFLE_PDA" => raffle_pda = parse_pubkey(pair[1])?,
+                    "PARTICIPANT_COUNT" => participant_count = parse_u32(pair[1])?,
+                    "WINNER_COUNT" => winner_count = parse_u32(pair[1])?,
+                    "ALT" => lookup_table = parse_pubkey(pair[1])?,
+                    "APPROVAL_PDA" => approval_pda = parse_pubkey(pair[1])?,
+                    "CU_LIMIT" => cu_limit = Some(parse_u32(pair[1])?),
+                    "CU_PRICE" => cu_price = Some(parse_u64(pair[1])?),
+                    "EXPERIMENT_ID" => experiment_id = parse_u32(pair[1])?,
+                    "EXPERIMENT_SHARE" => experiment_share_bps = parse_u16(pair[1])?,
+                    "VALID_AFTER_SLOT" => valid_after_slot = Some(parse_u64(pair[1])?),
+                    "VALID_BEFORE_SLOT" => valid_before_slot = Some(parse_u64(pair[1])?),
                     _ => {}
                 }
             }
         }
 
         if program_id == Pubkey::default() {
-            return Err(SwitchboardError::InvalidFunctionInput);
+            return Err(FunctionError::InvalidParams);
         }
         if user == Pubkey::default() {
-            return Err(SwitchboardError::InvalidFunctionInput);
+            return Err(FunctionError::InvalidParams);
         }
         if realm_pda == Pubkey::default() {
-            return Err(SwitchboardError::InvalidFunctionInput);
+            return Err(FunctionError::InvalidParams);
         }
         if user_account_pda == Pubkey::default() {
-            return Err(SwitchboardError::InvalidFunctionInput);
+            return Err(FunctionError::InvalidParams);
         }
-        if spaceship_pda == Pubkey::default() {
-            return Err(SwitchboardError::InvalidFunctionInput);
+        if roll_min > roll_max {
+            return Err(FunctionError::InvalidParams);
         }
-        if opponent_spaceship_1_pda == Pubkey::default() {
-            return Err(SwitchboardError::InvalidFunctionInput);
+        if cu_limit.is_some_and(|limit| limit == 0 || limit > MAX_CU_LIMIT) {
+            return Err(FunctionError::InvalidParams);
         }
-        if opponent_spaceship_2_pda == Pubkey::default() {
-            return Err(SwitchboardError::InvalidFunctionInput);
+        if cu_price.is_some_and(|price| price > MAX_CU_PRICE) {
+            return Err(FunctionError::InvalidParams);
         }
-        if opponent_spaceship_3_pda == Pubkey::default() {
-            return Err(SwitchboardError::InvalidFunctionInput);
+        if experiment_share_bps > 10_000 {
+            return Err(FunctionError::InvalidParams);
         }
-        if opponent_spaceship_4_pda == Pubkey::default() {
-            return Err(SwitchboardError::InvalidFunctionInput);
+        // a window no slot falls in could never settle
+        if let (Some(after), Some(before)) = (valid_after_slot, valid_before_slot) {
+            if after.saturating_add(1) >= before {
+                return Err(FunctionError::InvalidParams);
+            }
         }
-        if opponent_spaceship_5_pda == Pubkey::default() {
-            return Err(SwitchboardError::InvalidFunctionInput);
+
+        match request_type {
+            RequestType::Matchmaking => {
+                if spaceship_pda == Pubkey::default() {
+                    return Err(FunctionError::InvalidParams);
+                }
+                // unset opponent slots are empty, and with none set the
+                // requester is matched against a bot
+                let unset = [
+                    opponent_spaceship_1_pda,
+                    opponent_spaceship_2_pda,
+                    opponent_spaceship_3_pda,
+                    opponent_spaceship_4_pda,
+                    opponent_spaceship_5_pda,
+                ]
+                .iter()
+                .filter(|pda| **pda == Pubkey::default())
+                .count();
+                // queued candidates come from the realm, never from params
+                if from_queue && unset != OPPONENT_SLOT_COUNT {
+                    return Err(FunctionError::InvalidParams);
+                }
+            }
+            RequestType::LootOpen => {
+                if find_loot_table(loot_table).is_none() {
+                    return Err(FunctionError::InvalidParams);
+                }
+            }
+            RequestType::TournamentSeed => {
+                if tournament_pda == Pubkey::default() {
+                    return Err(FunctionError::InvalidParams);
+                }
+                if participants.len() < 2 || participants.len() > MAX_TOURNAMENT_PARTICIPANTS {
+                    return Err(FunctionError::InvalidParams);
+                }
+                // a skewed shuffle would favour some participants
+                if distribution != RollDistribution::Uniform {
+                    return Err(FunctionError::InvalidParams);
+                }
+            }
+            RequestType::DailySeed => {
+                if seed_pda == Pubkey::default() {
+                    return Err(FunctionError::InvalidParams);
+                }
+                if seed_period == 0 || seed_offset >= seed_period {
+                    return Err(FunctionError::InvalidParams);
+                }
+            }
+            RequestType::Cancel => {
+                if spaceship_pda == Pubkey::default() {
+                    return Err(FunctionError::InvalidParams);
+                }
+            }
+            RequestType::CustomRoll => {
+                if !valid_roll_ixn(&roll_ixn) {
+                    return Err(FunctionError::InvalidParams);
+                }
+                // checked here so a rejected handler never gets a settlement
+                // built, let alone signed
+                if !roll_ixn_allowlist.contains(&roll_ixn) {
+                    return Err(FunctionError::InvalidParams);
+                }
+                if roll_schema.is_empty() || roll_schema.len() > MAX_ROLL_FIELDS {
+                    return Err(FunctionError::InvalidParams);
+                }
+            }
+            RequestType::RaffleDraw => {
+                if raffle_pda == Pubkey::default() {
+                    return Err(FunctionError::InvalidParams);
+                }
+                if !valid_raffle(participant_count, winner_count) {
+                    return Err(FunctionError::InvalidParams);
+                }
+                // a skewed draw would favour some participants
+                if distribution != RollDistribution::Uniform {
+                    return Err(FunctionError::InvalidParams);
+                }
+            }
         }
 
         Ok(Self {
+            version,
+            request_type,
             program_id,
             user,
             realm_pda,
             user_account_pda,
             spaceship_pda,
             faction,
+            exclude_same_faction,
+            from_queue,
+            max_rerolls,
+            roll_min,
+            roll_max,
+            power_weights,
             opponent_spaceship_1_pda,
             opponent_spaceship_2_pda,
             opponent_spaceship_3_pda,
             opponent_spaceship_4_pda,
             opponent_spaceship_5_pda,
+            loot_table,
+            loot_weights,
+            distribution,
+            tournament_pda,
+            participants,
+            seed_pda,
+            seed_period,
+            seed_offset,
+            roll_ixn,
+            roll_schema,
+            raffle_pda,
+            participant_count,
+            winner_count,
+            lookup_table,
+            approval_pda,
+            cu_limit,
+            cu_price,
+            experiment_id,
+            experiment_share_bps,
+            valid_after_slot,
+            valid_before_slot,
+            deprecated_keys,
+        })
+    }
+
+    /// Warns about every deprecated key the request used, with a metric line
+    /// per key so removal can wait until usage drops to zero.
+    pub fn report_deprecated_keys(&self) {
+        for deprecated in self.deprecated_keys.iter() {
+            println!(
+                "WARNING: params key {} is deprecated since {}{}",
+                deprecated.key,
+                deprecated.since,
+                deprecated
+                    .replacement
+                    .map(|replacement| format!(", use {} instead", replacement))
+                    .unwrap_or_default()
+            );
+            record_counter("params_deprecated_key_total", &[("key", deprecated.key)]);
+        }
+    }
+
+    fn new(
+        request_type: RequestType,
+        requester: &Requester,
+    ) -> std::result::Result<Self, FunctionError> {
+        require_set(&[
+            requester.program_id,
+            requester.user,
+            requester.realm_pda,
+            requester.user_account_pda,
+        ])?;
+        Ok(Self {
+            version: PARAMS_VERSION,
+            request_type,
+            program_id: requester.program_id,
+            user: requester.user,
+            realm_pda: requester.realm_pda,
+            user_account_pda: requester.user_account_pda,
+            spaceship_pda: Pubkey::default(),
+            faction: 0,
+            exclude_same_faction: false,
+            from_queue: false,
+            max_rerolls: DEFAULT_MAX_REROLLS,
+            roll_min: DEFAULT_ROLL_MIN,
+            roll_max: DEFAULT_ROLL_MAX,
+            power_weights: None,
+            opponent_spaceship_1_pda: Pubkey::default(),
+            opponent_spaceship_2_pda: Pubkey::default(),
+            opponent_spaceship_3_pda: Pubkey::default(),
+            opponent_spaceship_4_pda: Pubkey::default(),
+            opponent_spaceship_5_pda: Pubkey::default(),
+            loot_table: DEFAULT_LOOT_TABLE,
+            loot_weights: None,
+            distribution: RollDistribution::default(),
+            tournament_pda: Pubkey::default(),
+            participants: vec![],
+            seed_pda: Pubkey::default(),
+            seed_period: DEFAULT_SEED_PERIOD_SECS,
+            seed_offset: 0,
+            roll_ixn: String::new(),
+            roll_schema: vec![],
+            raffle_pda: Pubkey::default(),
+            participant_count: 0,
+            winner_count: 0,
+            lookup_table: Pubkey::default(),
+            approval_pda: Pubkey::default(),
+            cu_limit: None,
+            cu_price: None,
+            experiment_id: 0,
+            experiment_share_bps: DEFAULT_EXPERIMENT_SHARE_BPS,
+            valid_after_slot: None,
+            valid_before_slot: None,
+            deprecated_keys: vec![],
+        })
+    }
+
+    /// Matchmaking params for clients, validated like the function validates
+    /// them. Optional fields can be set on the result before `to_bytes`. With
+    /// fewer than five candidates the remaining slots are left as
+    /// `Pubkey::default()` rather than repeating a candidate, at least one
+    /// must be set.
+    pub fn matchmaking(
+        requester: &Requester,
+        spaceship_pda: Pubkey,
+        faction: u8,
+        opponent_spaceship_pdas: [Pubkey; 5],
+    ) -> std::result::Result<Self, FunctionError> {
+        if faction >= FACTION_COUNT {
+            return Err(FunctionError::InvalidParams);
+        }
+        let mut spaceships: Vec<Pubkey> = opponent_spaceship_pdas
+            .into_iter()
+            .filter(|pda| *pda != Pubkey::default())
+            .collect();
+        if spaceships.is_empty() {
+            return Err(FunctionError::InvalidParams);
+        }
+        spaceships.push(spaceship_pda);
+        require_set(&spaceships)?;
+        require_distinct(&spaceships)?;
+        Self::matchmaking_unchecked(requester, spaceship_pda, faction, opponent_spaceship_pdas)
+    }
+
+    /// Matchmaking params leaving the candidates to the realm's queue, the
+    /// request only names the requester's spaceship.
+    pub fn matchmaking_from_queue(
+        requester: &Requester,
+        spaceship_pda: Pubkey,
+        faction: u8,
+    ) -> std::result::Result<Self, FunctionError> {
+        Ok(Self {
+            from_queue: true,
+            ..Self::matchmaking_vs_bot(requester, spaceship_pda, faction)?
+        })
+    }
+
+    /// Matchmaking params without opponents, settled against a bot rolled
+    /// by the function.
+    pub fn matchmaking_vs_bot(
+        requester: &Requester,
+        spaceship_pda: Pubkey,
+        faction: u8,
+    ) -> std::result::Result<Self, FunctionError> {
+        if faction >= FACTION_COUNT {
+            return Err(FunctionError::InvalidParams);
+        }
+        require_set(&[spaceship_pda])?;
+        Self::matchmaking_unchecked(
+            requester,
+            spaceship_pda,
+            faction,
+            [Pubkey::default(); OPPONENT_SLOT_COUNT],
+        )
+    }
+
+    fn matchmaking_unchecked(
+        requester: &Requester,
+        spaceship_pda: Pubkey,
+        faction: u8,
+        opponent_spaceship_pdas: [Pubkey; 5],
+    ) -> std::result::Result<Self, FunctionError> {
+        let [os_1, os_2, os_3, os_4, os_5] = opponent_spaceship_pdas;
+        Ok(Self {
+            spaceship_pda,
+            faction,
+            opponent_spaceship_1_pda: os_1,
+            opponent_spaceship_2_pda: os_2,
+            opponent_spaceship_3_pda: os_3,
+            opponent_spaceship_4_pda: os_4,
+            opponent_spaceship_5_pda: os_5,
+            ..Self::new(RequestType::Matchmaking, requester)?
+        })
+    }
+
+    pub fn loot_open(
+        requester: &Requester,
+        loot_table: u8,
+    ) -> std::result::Result<Self, FunctionError> {
+        find_loot_table(loot_table).ok_or(FunctionError::InvalidParams)?;
+        Ok(Self {
+            loot_table,
+            ..Self::new(RequestType::LootOpen, requester)?
+        })
+    }
+
+    pub fn tournament_seed(
+        requester: &Requester,
+        tournament_pda: Pubkey,
+        participants: Vec<Pubkey>,
+    ) -> std::result::Result<Self, FunctionError> {
+        if participants.len() < 2 || participants.len() > MAX_TOURNAMENT_PARTICIPANTS {
+            return Err(FunctionError::InvalidParams);
+        }
+        require_set(&[tournament_pda])?;
+        require_set(&participants)?;
+        require_distinct(&participants)?;
+        Ok(Self {
+            tournament_pda,
+            participants,
+            ..Self::new(RequestType::TournamentSeed, requester)?
+        })
+    }
+
+    /// A seed for every `seed_period` seconds, starting `seed_offset`
+    /// seconds after midnight UTC.
+    pub fn daily_seed(
+        requester: &Requester,
+        seed_pda: Pubkey,
+        seed_period: u64,
+        seed_offset: u64,
+    ) -> std::result::Result<Self, FunctionError> {
+        if seed_period == 0 || seed_offset >= seed_period {
+            return Err(FunctionError::InvalidParams);
+        }
+        require_set(&[seed_pda])?;
+        Ok(Self {
+            seed_pda,
+            seed_period,
+            seed_offset,
+            ..Self::new(RequestType::DailySeed, requester)?
+        })
+    }
+
+    /// Cancels the queue entry of `spaceship_pda`, queued for `faction`.
+    pub fn cancel(
+        requester: &Requester,
+        spaceship_pda: Pubkey,
+        faction: u8,
+    ) -> std::result::Result<Self, FunctionError> {
+        if faction >= FACTION_COUNT {
+            return Err(FunctionError::InvalidParams);
+        }
+        require_set(&[spaceship_pda])?;
+        Ok(Self {
+            spaceship_pda,
+            faction,
+            ..Self::new(RequestType::Cancel, requester)?
+        })
+    }
+
+    /// Rolls `roll_schema` for the program's `roll_ixn` handler, which
+    /// receives the settle header followed by the rolled fields.
+    pub fn custom_roll(
+        requester: &Requester,
+        roll_ixn: &str,
+        roll_schema: Vec<RollField>,
+    ) -> std::result::Result<Self, FunctionError> {
+        if !valid_roll_ixn(roll_ixn) {
+            return Err(FunctionError::InvalidParams);
+        }
+        if roll_schema.is_empty() || roll_schema.len() > MAX_ROLL_FIELDS {
+            return Err(FunctionError::InvalidParams);
+        }
+        for field in roll_schema.iter() {
+            if field.min > field.max || field.max > field.kind.max_value() {
+                return Err(FunctionError::InvalidParams);
+            }
+        }
+        Ok(Self {
+            roll_ixn: roll_ixn.to_string(),
+            roll_schema,
+            ..Self::new(RequestType::CustomRoll, requester)?
+        })
+    }
+
+    /// Draws `winner_count` distinct winners among `participant_count`
+    /// participants, stored in `raffle_pda`.
+    pub fn raffle_draw(
+        requester: &Requester,
+        raffle_pda: Pubkey,
+        participant_count: u32,
+        winner_count: u32,
+    ) -> std::result::Result<Self, FunctionError> {
+        if !valid_raffle(participant_count, winner_count) {
+            return Err(FunctionError::InvalidParams);
+        }
+        require_set(&[raffle_pda])?;
+        Ok(Self {
+            raffle_pda,
+            participant_count,
+            winner_count,
+            ..Self::new(RequestType::RaffleDraw, requester)?
         })
     }
+
+    /// Encodes the params the way `decode` reads them, with the checksum
+    /// appended. Only fields differing from their default are written.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut pairs: Vec<(&str, String)> = vec![
+            ("VERSION", self.version.to_string()),
+            ("REQUEST_TYPE", self.request_type.as_str().to_string()),
+            ("PID", self.program_id.to_string()),
+            ("USER", self.user.to_string()),
+            ("REALM_PDA", self.realm_pda.to_string()),
+            ("USER_ACCOUNT_PDA", self.user_account_pda.to_string()),
+        ];
+        let mut pubkey = |key, pubkey: &Pubkey| {
+            if *pubkey != Pubkey::default() {
+                pairs.push((key, pubkey.to_string()));
+            }
+        };
+        pubkey("SPACESHIP_PDA", &self.spaceship_pda);
+        pubkey("OS_1_PDA", &self.opponent_spaceship_1_pda);
+        pubkey("OS_2_PDA", &self.opponent_spaceship_2_pda);
+        pubkey("OS_3_PDA", &self.opponent_spaceship_3_pda);
+        pubkey("OS_4_PDA", &self.opponent_spaceship_4_pda);
+        pubkey("OS_5_PDA", &self.opponent_spaceship_5_pda);
+        pubkey("TOURNAMENT_PDA", &self.tournament_pda);
+        pubkey("SEED_PDA", &self.seed_pda);
+        pubkey("RAFFLE_PDA", &self.raffle_pda);
+        pubkey("ALT", &self.lookup_table);
+        pubkey("APPROVAL_PDA", &self.approval_pda);
+        if self.faction != 0 {
+            pairs.push(("FACTION", self.faction.to_string()));
+        }
+        if self.exclude_same_faction {
+            pairs.push(("EXCLUDE_SAME_FACTION", "1".to_string()));
+        }
+        if self.from_queue {
+            pairs.push(("QUEUE", "1".to_string()));
+        }
+        if self.max_rerolls != DEFAULT_MAX_REROLLS {
+            pairs.push(("MAX_REROLLS", self.max_rerolls.to_string()));
+        }
+        if self.roll_min != DEFAULT_ROLL_MIN {
+            pairs.push(("MIN", self.roll_min.to_string()));
+        }
+        if self.roll_max != DEFAULT_ROLL_MAX {
+            pairs.push(("MAX", self.roll_max.to_string()));
+        }
+        if self.loot_table != DEFAULT_LOOT_TABLE {
+            pairs.push(("LOOT_TABLE", self.loot_table.to_string()));
+        }
+        if let Some(loot_weights) = &self.loot_weights {
+            pairs.push(("LOOT_WEIGHTS", loot_weights.to_string()));
+        }
+        if self.distribution != RollDistribution::default() {
+            pairs.push(("DISTRIBUTION", self.distribution.to_string()));
+        }
+        if let Some(power_weights) = &self.power_weights {
+            pairs.push(("POWER_WEIGHTS", power_weights.to_string()));
+        }
+        if self.seed_period != DEFAULT_SEED_PERIOD_SECS {
+            pairs.push(("SEED_PERIOD", self.seed_period.to_string()));
+        }
+        if self.seed_offset != 0 {
+            pairs.push(("SEED_OFFSET", self.seed_offset.to_string()));
+        }
+        if !self.roll_ixn.is_empty() {
+            pairs.push(("ROLL_IXN", self.roll_ixn.clone()));
+        }
+        if !self.roll_schema.is_empty() {
+            let fields: Vec<String> = self
+                .roll_schema
+                .iter()
+                .map(|field| field.to_string())
+                .collect();
+            pairs.push(("ROLL_SCHEMA", fields.join(":")));
+        }
+        if self.participant_count != 0 {
+            pairs.push(("PARTICIPANT_COUNT", self.participant_count.to_string()));
+        }
+        if self.winner_count != 0 {
+            pairs.push(("WINNER_COUNT", self.winner_count.to_string()));
+        }
+        if let Some(cu_limit) = self.cu_limit {
+            pairs.push(("CU_LIMIT", cu_limit.to_string()));
+        }
+        if let Some(cu_price) = self.cu_price {
+            pairs.push(("CU_PRICE", cu_price.to_string()));
+        }
+        if self.experiment_id != 0 {
+            pairs.push(("EXPERIMENT_ID", self.experiment_id.to_string()));
+        }
+        if self.experiment_share_bps != DEFAULT_EXPERIMENT_SHARE_BPS {
+            pairs.push(("EXPERIMENT_SHARE", self.experiment_share_bps.to_string()));
+        }
+        if let Some(after) = self.valid_after_slot {
+            pairs.push(("VALID_AFTER_SLOT", after.to_string()));
+        }
+        if let Some(before) = self.valid_before_slot {
+            pairs.push(("VALID_BEFORE_SLOT", before.to_string()));
+        }
+        if !self.participants.is_empty() {
+            let participants: Vec<String> =
+                self.participants.iter().map(|p| p.to_string()).collect();
+            pairs.push(("PARTICIPANTS", participants.join(":")));
+        }
+
+        let params: Vec<String> = pairs
+            .iter()
+            .map(|(key, value)| format!("{}={}", key, value))
+            .collect();
+        append_params_checksum(&params.join(",")).into_bytes()
+    }
+
+    /// The params with the opponent slots set to `opponents`, e.g. the
+    /// candidates read from the queue.
+    pub fn with_opponents(&self, opponents: [Pubkey; OPPONENT_SLOT_COUNT]) -> Self {
+        let mut params = self.clone();
+        [
+            params.opponent_spaceship_1_pda,
+            params.opponent_spaceship_2_pda,
+            params.opponent_spaceship_3_pda,
+            params.opponent_spaceship_4_pda,
+            params.opponent_spaceship_5_pda,
+        ] = opponents;
+        params
+    }
+
+    /// A matchmaking request leaving every opponent slot empty, which the
+    /// function settles against a bot so new players always get a match.
+    pub fn is_bot_match(&self) -> bool {
+        self.request_type == RequestType::Matchmaking
+            && !self.from_queue
+            && self
+                .opponent_spaceship_pdas()
+                .iter()
+                .all(|pda| *pda == Pubkey::default())
+    }
+
+    /// Bit `n` is set when opponent slot `n` holds a spaceship rather than
+    /// the `Pubkey::default()` sentinel of an empty slot.
+    pub fn opponent_mask(&self) -> u8 {
+        self.opponent_spaceship_pdas()
+            .iter()
+            .enumerate()
+            .filter(|(_, pda)| **pda != Pubkey::default())
+            .fold(0, |mask, (slot, _)| mask | 1 << slot)
+    }
+
+    pub fn opponent_spaceship_pdas(&self) -> [Pubkey; 5] {
+        [
+            self.opponent_spaceship_1_pda,
+            self.opponent_spaceship_2_pda,
+            self.opponent_spaceship_3_pda,
+            self.opponent_spaceship_4_pda,
+            self.opponent_spaceship_5_pda,
+        ]
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::test_fixtures::test_params_string;
 
     #[test]
     fn test_params_decode() {
@@ -121,16 +1332,844 @@ mod tests {
 
         let params = ContainerParams::decode(&request_params_bytes).unwrap();
 
+        assert_eq!(params.request_type, RequestType::Matchmaking);
         assert_eq!(params.program_id, anchor_spl::token::ID);
         assert_eq!(params.user, anchor_spl::token::ID);
         assert_eq!(params.realm_pda, anchor_spl::token::ID);
         assert_eq!(params.user_account_pda, anchor_spl::token::ID);
         assert_eq!(params.spaceship_pda, anchor_spl::token::ID);
         assert_eq!(params.faction, 1);
+        assert!(!params.exclude_same_faction);
         assert_eq!(params.opponent_spaceship_1_pda, anchor_spl::token::ID);
         assert_eq!(params.opponent_spaceship_2_pda, anchor_spl::token::ID);
         assert_eq!(params.opponent_spaceship_3_pda, anchor_spl::token::ID);
         assert_eq!(params.opponent_spaceship_4_pda, anchor_spl::token::ID);
         assert_eq!(params.opponent_spaceship_5_pda, anchor_spl::token::ID);
     }
+
+    #[test]
+    fn test_params_decode_exclude_same_faction() {
+        let params = ContainerParams::decode(
+            format!("{},EXCLUDE_SAME_FACTION=1", test_params_string()).as_bytes(),
+        )
+        .unwrap();
+        assert!(params.exclude_same_faction);
+
+        assert!(ContainerParams::decode(
+            format!("{},EXCLUDE_SAME_FACTION=yes", test_params_string()).as_bytes()
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_find_deprecated_param() {
+        let deprecated = [DeprecatedParam {
+            key: "OLD_KEY",
+            since: "0.2.0",
+            replacement: Some("NEW_KEY"),
+        }];
+
+        assert_eq!(
+            find_deprecated_param(&deprecated, "OLD_KEY"),
+            Some(&deprecated[0])
+        );
+        assert_eq!(find_deprecated_param(&deprecated, "NEW_KEY"), None);
+        for param in DEPRECATED_PARAMS {
+            assert!(
+                !param.key.contains(['=', ',']),
+                "{} cannot be sent",
+                param.key
+            );
+        }
+    }
+
+    #[test]
+    fn test_params_decode_loot_open() {
+        let request_params_string = format!(
+            "REQUEST_TYPE=LOOT_OPEN,PID={},USER={},REALM_PDA={},USER_ACCOUNT_PDA={},LOOT_TABLE=1,LOOT_WEIGHTS=50:30:15:5",
+            anchor_spl::token::ID,
+            anchor_spl::token::ID,
+            anchor_spl::token::ID,
+            anchor_spl::token::ID,
+        );
+        let request_params_bytes = request_params_string.into_bytes();
+
+        let params = ContainerParams::decode(&request_params_bytes).unwrap();
+
+        assert_eq!(params.request_type, RequestType::LootOpen);
+        assert_eq!(params.loot_table, 1);
+        assert_eq!(params.loot_weights, Some(RarityWeights([50, 30, 15, 5])));
+        assert_eq!(params.spaceship_pda, Pubkey::default());
+    }
+
+    #[test]
+    fn test_params_decode_tournament_seed() {
+        let participants: Vec<Pubkey> = (0..4).map(|_| Pubkey::new_unique()).collect();
+        let request_params_string = format!(
+            "REQUEST_TYPE=TOURNAMENT_SEED,PID={},USER={},REALM_PDA={},USER_ACCOUNT_PDA={},TOURNAMENT_PDA={},PARTICIPANTS={}",
+            anchor_spl::token::ID,
+            anchor_spl::token::ID,
+            anchor_spl::token::ID,
+            anchor_spl::token::ID,
+            anchor_spl::token::ID,
+            participants
+                .iter()
+                .map(|p| p.to_string())
+                .collect::<Vec<String>>()
+                .join(":"),
+        );
+
+        let params = ContainerParams::decode(request_params_string.as_bytes()).unwrap();
+
+        assert_eq!(params.request_type, RequestType::TournamentSeed);
+        assert_eq!(params.tournament_pda, anchor_spl::token::ID);
+        assert_eq!(params.participants, participants);
+
+        let one_participant = request_params_string.split(':').next().unwrap();
+        assert!(ContainerParams::decode(one_participant.as_bytes()).is_err());
+        let bad_participant = format!("{}:notapubkey", request_params_string);
+        assert!(ContainerParams::decode(bad_participant.as_bytes()).is_err());
+    }
+
+    #[test]
+    fn test_params_decode_for_programs() {
+        let params = test_params_string();
+        let program_id = ContainerParams::decode(params.as_bytes())
+            .unwrap()
+            .program_id;
+
+        assert!(ContainerParams::decode_for_programs(params.as_bytes(), &[]).is_ok());
+        assert!(ContainerParams::decode_for_programs(
+            params.as_bytes(),
+            &[Pubkey::new_unique(), program_id]
+        )
+        .is_ok());
+        assert_eq!(
+            ContainerParams::decode_for_programs(params.as_bytes(), &[Pubkey::new_unique()]),
+            Err(FunctionError::ProgramNotAllowed)
+        );
+    }
+
+    #[test]
+    fn test_parse_roll_ixn_allowlist() {
+        assert_eq!(parse_roll_ixn_allowlist(""), Ok(vec![]));
+        assert_eq!(
+            parse_roll_ixn_allowlist("crit_roll_settle, loot_roll_settle,"),
+            Ok(vec![
+                "crit_roll_settle".to_string(),
+                "loot_roll_settle".to_string()
+            ])
+        );
+        // a handler of the IDL, settlement or not, is never a custom roll
+        for invalid in [
+            "crit_roll_settle,Crit Roll",
+            "crit_roll_settle,arena_matchmaking_settle",
+            "arena_matchmaking_report_failure",
+        ] {
+            assert_eq!(
+                parse_roll_ixn_allowlist(invalid),
+                Err(FunctionError::InvalidParams),
+                "{}",
+                invalid
+            );
+        }
+    }
+
+    #[test]
+    fn test_parse_program_allowlist() {
+        let (production, staging) = (Pubkey::new_unique(), Pubkey::new_unique());
+
+        assert_eq!(parse_program_allowlist(""), Ok(vec![]));
+        assert_eq!(
+            parse_program_allowlist(&format!("{}, {},", production, staging)),
+            Ok(vec![production, staging])
+        );
+        assert_eq!(
+            parse_program_allowlist(&format!("{},not-a-pubkey", production)),
+            Err(FunctionError::ProgramNotAllowed)
+        );
+    }
+
+    #[test]
+    fn test_params_decode_custom_roll() {
+        let base = format!(
+            "REQUEST_TYPE=CUSTOM_ROLL,PID={},USER={},REALM_PDA={},USER_ACCOUNT_PDA={}",
+            anchor_spl::token::ID,
+            anchor_spl::token::ID,
+            anchor_spl::token::ID,
+            anchor_spl::token::ID,
+        );
+        let roll_ixn_allowlist = vec!["loot_roll_settle".to_string()];
+        let decode = |extra: &str| {
+            ContainerParams::decode_with_allowlists(
+                format!("{},{}", base, extra).as_bytes(),
+                &[],
+                &roll_ixn_allowlist,
+            )
+        };
+
+        let params = decode("ROLL_IXN=loot_roll_settle,ROLL_SCHEMA=U32:U16/1/6:U8/0/0").unwrap();
+        assert_eq!(params.request_type, RequestType::CustomRoll);
+        assert_eq!(params.roll_ixn, "loot_roll_settle");
+        assert_eq!(
+            params.roll_schema,
+            vec![
+                RollField {
+                    kind: RollFieldKind::U32,
+                    min: 0,
+                    max: u32::MAX as u64,
+                },
+                RollField {
+                    kind: RollFieldKind::U16,
+                    min: 1,
+                    max: 6,
+                },
+                RollField {
+                    kind: RollFieldKind::U8,
+                    min: 0,
+                    max: 0,
+                },
+            ]
+        );
+
+        for invalid in [
+            "ROLL_SCHEMA=U32",
+            "ROLL_IXN=loot_roll_settle",
+            "ROLL_IXN=Loot Roll,ROLL_SCHEMA=U32",
+            "ROLL_IXN=loot_roll_settle,ROLL_SCHEMA=U128",
+            "ROLL_IXN=loot_roll_settle,ROLL_SCHEMA=U8/1/256",
+            "ROLL_IXN=loot_roll_settle,ROLL_SCHEMA=U8/6/1",
+            "ROLL_IXN=loot_roll_settle,ROLL_SCHEMA=U8/1",
+            // only the listed handlers receive a roll
+            "ROLL_IXN=crit_roll_settle,ROLL_SCHEMA=U32",
+            "ROLL_IXN=arena_matchmaking_settle,ROLL_SCHEMA=U32",
+        ] {
+            assert!(decode(invalid).is_err(), "{}", invalid);
+        }
+        // a build without a roll allowlist settles no custom roll
+        assert_eq!(
+            ContainerParams::decode(
+                format!("{},ROLL_IXN=loot_roll_settle,ROLL_SCHEMA=U32", base).as_bytes()
+            ),
+            Err(FunctionError::InvalidParams)
+        );
+        let too_many = vec!["U8"; MAX_ROLL_FIELDS + 1].join(":");
+        assert!(decode(&format!(
+            "ROLL_IXN=loot_roll_settle,ROLL_SCHEMA={}",
+            too_many
+        ))
+        .is_err());
+    }
+
+    #[test]
+    fn test_params_decode_experiment() {
+        let base = test_params_string();
+        let decode =
+            |extra: &str| ContainerParams::decode(format!("{},{}", base, extra).as_bytes());
+
+        let params = ContainerParams::decode(base.as_bytes()).unwrap();
+        assert_eq!(params.experiment_id, 0);
+        assert_eq!(params.experiment_share_bps, DEFAULT_EXPERIMENT_SHARE_BPS);
+
+        let params = decode("EXPERIMENT_ID=7,EXPERIMENT_SHARE=10000").unwrap();
+        assert_eq!(params.experiment_id, 7);
+        assert_eq!(params.experiment_share_bps, 10_000);
+
+        for invalid in [
+            "EXPERIMENT_ID=-1",
+            "EXPERIMENT_SHARE=10001",
+            "EXPERIMENT_SHARE=half",
+        ] {
+            assert_eq!(
+                decode(invalid).err(),
+                Some(FunctionError::InvalidParams),
+                "{}",
+                invalid
+            );
+        }
+    }
+
+    #[test]
+    fn test_params_decode_slot_window() {
+        let base = test_params_string();
+        let decode =
+            |extra: &str| ContainerParams::decode(format!("{},{}", base, extra).as_bytes());
+
+        let params = ContainerParams::decode(base.as_bytes()).unwrap();
+        assert_eq!(
+            (params.valid_after_slot, params.valid_before_slot),
+            (None, None)
+        );
+
+        let params = decode("VALID_AFTER_SLOT=100,VALID_BEFORE_SLOT=102").unwrap();
+        assert_eq!(params.valid_after_slot, Some(100));
+        assert_eq!(params.valid_before_slot, Some(102));
+        assert_eq!(
+            decode("VALID_BEFORE_SLOT=0").unwrap().valid_before_slot,
+            Some(0)
+        );
+
+        for invalid in [
+            "VALID_AFTER_SLOT=100,VALID_BEFORE_SLOT=101",
+            "VALID_AFTER_SLOT=100,VALID_BEFORE_SLOT=50",
+            "VALID_AFTER_SLOT=soon",
+        ] {
+            assert_eq!(
+                decode(invalid).err(),
+                Some(FunctionError::InvalidParams),
+                "{}",
+                invalid
+            );
+        }
+    }
+
+    #[test]
+    fn test_params_decode_compute_budget() {
+        let base = test_params_string();
+        let decode =
+            |extra: &str| ContainerParams::decode(format!("{},{}", base, extra).as_bytes());
+
+        let params = ContainerParams::decode(base.as_bytes()).unwrap();
+        assert_eq!((params.cu_limit, params.cu_price), (None, None));
+
+        let params = decode("CU_LIMIT=400000,CU_PRICE=10000").unwrap();
+        assert_eq!(params.cu_limit, Some(400_000));
+        assert_eq!(params.cu_price, Some(10_000));
+        let params = decode(&format!(
+            "CU_LIMIT={},CU_PRICE={}",
+            MAX_CU_LIMIT, MAX_CU_PRICE
+        ))
+        .unwrap();
+        assert_eq!(params.cu_limit, Some(MAX_CU_LIMIT));
+        assert_eq!(params.cu_price, Some(MAX_CU_PRICE));
+
+        for invalid in [
+            "CU_LIMIT=0".to_string(),
+            format!("CU_LIMIT={}", MAX_CU_LIMIT + 1),
+            format!("CU_PRICE={}", MAX_CU_PRICE + 1),
+            "CU_LIMIT=-1".to_string(),
+            "CU_PRICE=cheap".to_string(),
+        ] {
+            assert_eq!(
+                decode(&invalid).err(),
+                Some(FunctionError::InvalidParams),
+                "{}",
+                invalid
+            );
+        }
+    }
+
+    #[test]
+    fn test_params_decode_cancel() {
+        let base = format!(
+            "REQUEST_TYPE=CANCEL,PID={},USER={},REALM_PDA={},USER_ACCOUNT_PDA={}",
+            anchor_spl::token::ID,
+            anchor_spl::token::ID,
+            anchor_spl::token::ID,
+            anchor_spl::token::ID,
+        );
+
+        let params = ContainerParams::decode(
+            format!("{},SPACESHIP_PDA={},FACTION=2", base, anchor_spl::token::ID).as_bytes(),
+        )
+        .unwrap();
+        assert_eq!(params.request_type, RequestType::Cancel);
+        assert_eq!(params.spaceship_pda, anchor_spl::token::ID);
+        assert_eq!(params.faction, 2);
+
+        assert!(ContainerParams::decode(base.as_bytes()).is_err());
+        assert!(
+            ContainerParams::cancel(&test_requester(), Pubkey::new_unique(), FACTION_COUNT)
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_params_decode_daily_seed() {
+        let base = format!(
+            "REQUEST_TYPE=DAILY_SEED,PID={},USER={},REALM_PDA={},USER_ACCOUNT_PDA={}",
+            anchor_spl::token::ID,
+            anchor_spl::token::ID,
+            anchor_spl::token::ID,
+            anchor_spl::token::ID,
+        );
+
+        let params = ContainerParams::decode(
+            format!("{},SEED_PDA={}", base, anchor_spl::token::ID).as_bytes(),
+        )
+        .unwrap();
+        assert_eq!(params.request_type, RequestType::DailySeed);
+        assert_eq!(params.seed_pda, anchor_spl::token::ID);
+        assert_eq!(params.seed_period, DEFAULT_SEED_PERIOD_SECS);
+        assert_eq!(params.seed_offset, 0);
+
+        assert!(ContainerParams::decode(base.as_bytes()).is_err());
+        for schedule in ["SEED_PERIOD=0", "SEED_PERIOD=3600,SEED_OFFSET=3600"] {
+            let request = format!("{},SEED_PDA={},{}", base, anchor_spl::token::ID, schedule);
+            assert!(ContainerParams::decode(request.as_bytes()).is_err());
+        }
+    }
+
+    #[test]
+    fn test_params_decode_power_weights() {
+        let decode = |power_weights: &str| {
+            ContainerParams::decode(
+                format!("{},POWER_WEIGHTS={}", test_params_string(), power_weights).as_bytes(),
+            )
+            .map(|params| params.power_weights)
+        };
+
+        assert_eq!(decode("1:40:30:20:30"), Ok(Some(DEFAULT_POWER_WEIGHTS)));
+        assert_eq!(DEFAULT_POWER_WEIGHTS.to_string(), "1:40:30:20:30");
+        assert_eq!(
+            ContainerParams::decode(test_params_string().as_bytes())
+                .unwrap()
+                .power_weights,
+            None
+        );
+        for invalid in ["1:2:3:4", "1:2:3:4:5:6", "0:0:0:0:0", "a:1:1:1:1"] {
+            assert_eq!(decode(invalid), Err(FunctionError::InvalidParams));
+        }
+    }
+
+    #[test]
+    fn test_params_decode_distribution() {
+        let decode = |distribution: &str| {
+            ContainerParams::decode(
+                format!("{},DISTRIBUTION={}", test_params_string(), distribution).as_bytes(),
+            )
+            .map(|params| params.distribution)
+        };
+
+        assert_eq!(
+            ContainerParams::decode(test_params_string().as_bytes())
+                .unwrap()
+                .distribution,
+            RollDistribution::Uniform
+        );
+        assert_eq!(decode("UNIFORM").unwrap(), RollDistribution::Uniform);
+        assert_eq!(
+            decode("NORMAL:50000:12000").unwrap(),
+            RollDistribution::Normal {
+                mean: 50_000,
+                std_dev: 12_000
+            }
+        );
+        assert_eq!(
+            decode("WEIGHTED:64:16:4:1").unwrap(),
+            RollDistribution::Weighted(vec![64, 16, 4, 1])
+        );
+        for invalid in [
+            "",
+            "UNIFORM:1",
+            "NORMAL:50000",
+            "NORMAL:a:1",
+            "WEIGHTED",
+            "WEIGHTED:0:0",
+            "WEIGHTED:4294967295:1",
+            "EXPONENTIAL",
+        ] {
+            assert!(decode(invalid).is_err(), "{} decoded", invalid);
+        }
+    }
+
+    #[test]
+    fn test_params_decode_tournament_seed_rejects_skewed_distribution() {
+        let request_params_string = format!(
+            "REQUEST_TYPE=TOURNAMENT_SEED,PID={},USER={},REALM_PDA={},USER_ACCOUNT_PDA={},TOURNAMENT_PDA={},PARTICIPANTS={}:{}",
+            anchor_spl::token::ID,
+            anchor_spl::token::ID,
+            anchor_spl::token::ID,
+            anchor_spl::token::ID,
+            anchor_spl::token::ID,
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+        );
+
+        assert!(ContainerParams::decode(
+            format!("{},DISTRIBUTION=UNIFORM", request_params_string).as_bytes()
+        )
+        .is_ok());
+        assert!(ContainerParams::decode(
+            format!("{},DISTRIBUTION=WEIGHTED:1:2", request_params_string).as_bytes()
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_params_decode_roll_bounds() {
+        let params = ContainerParams::decode(test_params_string().as_bytes()).unwrap();
+        assert_eq!(
+            (params.roll_min, params.roll_max),
+            (DEFAULT_ROLL_MIN, DEFAULT_ROLL_MAX)
+        );
+
+        let params = ContainerParams::decode(
+            format!(
+                "{},MIN=5000000000,MAX=18446744073709551615",
+                test_params_string()
+            )
+            .as_bytes(),
+        )
+        .unwrap();
+        assert_eq!(
+            (params.roll_min, params.roll_max),
+            (5_000_000_000, u64::MAX)
+        );
+
+        for invalid in ["MIN=7,MAX=6", "MAX=0", "MIN=-1", "MAX=18446744073709551616"] {
+            assert!(ContainerParams::decode(
+                format!("{},{}", test_params_string(), invalid).as_bytes()
+            )
+            .is_err());
+        }
+    }
+
+    #[test]
+    fn test_params_decode_checksum() {
+        let unsigned = test_params_string();
+        let signed = append_params_checksum(&unsigned);
+        assert!(signed.ends_with(&format!(
+            ",CHECKSUM={}",
+            hex::encode(params_checksum(unsigned.as_bytes()))
+        )));
+        assert!(ContainerParams::decode(signed.as_bytes()).is_ok());
+
+        // tampered fields and a truncated checksum both fail loudly
+        let tampered = signed.replacen("FACTION=1", "FACTION=2", 1);
+        assert_ne!(tampered, signed);
+        assert_eq!(
+            ContainerParams::decode(tampered.as_bytes()).err(),
+            Some(FunctionError::ParamsChecksumMismatch)
+        );
+        assert_eq!(
+            ContainerParams::decode(&signed.as_bytes()[..signed.len() - 1]).err(),
+            Some(FunctionError::ParamsChecksumMismatch)
+        );
+
+        // params without a checksum predate it
+        assert!(ContainerParams::decode(unsigned.as_bytes()).is_ok());
+    }
+
+    fn test_requester() -> Requester {
+        Requester {
+            program_id: Pubkey::new_unique(),
+            user: Pubkey::new_unique(),
+            realm_pda: Pubkey::new_unique(),
+            user_account_pda: Pubkey::new_unique(),
+        }
+    }
+
+    #[test]
+    fn test_to_bytes_round_trips() {
+        let requester = test_requester();
+        let mut matchmaking = ContainerParams::matchmaking(
+            &requester,
+            Pubkey::new_unique(),
+            2,
+            [(); 5].map(|_| Pubkey::new_unique()),
+        )
+        .unwrap();
+        matchmaking.exclude_same_faction = true;
+        matchmaking.max_rerolls = 0;
+        matchmaking.roll_min = 0;
+        matchmaking.roll_max = u64::MAX;
+        matchmaking.distribution = RollDistribution::Weighted(vec![3, 1]);
+        matchmaking.power_weights = Some(PowerWeights {
+            rating: 2,
+            weapon: 0,
+            shield: 10,
+            engine: 5,
+            hull: 1,
+        });
+        matchmaking.approval_pda = Pubkey::new_unique();
+        matchmaking.cu_limit = Some(MAX_CU_LIMIT);
+        matchmaking.cu_price = Some(5_000);
+        matchmaking.experiment_id = 42;
+        matchmaking.experiment_share_bps = 1_000;
+        matchmaking.valid_after_slot = Some(1_000);
+        matchmaking.valid_before_slot = Some(u64::MAX);
+        let mut loot_open = ContainerParams::loot_open(&requester, 1).unwrap();
+        loot_open.loot_weights = Some(RarityWeights([50, 30, 15, 5]));
+        let tournament_seed = ContainerParams::tournament_seed(
+            &requester,
+            Pubkey::new_unique(),
+            (0..MAX_TOURNAMENT_PARTICIPANTS)
+                .map(|_| Pubkey::new_unique())
+                .collect(),
+        )
+        .unwrap();
+        let vs_bot =
+            ContainerParams::matchmaking_vs_bot(&requester, Pubkey::new_unique(), 0).unwrap();
+        let daily_seed =
+            ContainerParams::daily_seed(&requester, Pubkey::new_unique(), 3_600, 900).unwrap();
+        let cancel = ContainerParams::cancel(&requester, Pubkey::new_unique(), 2).unwrap();
+        let from_queue =
+            ContainerParams::matchmaking_from_queue(&requester, Pubkey::new_unique(), 1).unwrap();
+        let custom_roll = ContainerParams::custom_roll(
+            &requester,
+            "crit_roll_settle",
+            vec![
+                RollField::from_str("U8/1/6").unwrap(),
+                RollField::from_str("U64").unwrap(),
+            ],
+        )
+        .unwrap();
+        let raffle_draw =
+            ContainerParams::raffle_draw(&requester, Pubkey::new_unique(), 5_000, 3).unwrap();
+
+        for params in [
+            matchmaking,
+            vs_bot,
+            loot_open,
+            tournament_seed,
+            daily_seed,
+            cancel,
+            from_queue,
+            custom_roll,
+            raffle_draw,
+        ] {
+            let bytes = params.to_bytes();
+            let decode = |bytes: &[u8]| {
+                ContainerParams::decode_with_allowlists(bytes, &[], &["crit_roll_settle".into()])
+            };
+            assert_eq!(decode(&bytes).unwrap(), params);
+            // the encoding is canonical
+            assert_eq!(decode(&bytes).unwrap().to_bytes(), bytes);
+        }
+    }
+
+    #[test]
+    fn test_params_decode_bot_match() {
+        let params = test_params_string();
+        let without_opponents = params[..params.find(",OS_1_PDA").unwrap()].to_string();
+
+        let vs_bot = ContainerParams::decode(without_opponents.as_bytes()).unwrap();
+        assert!(vs_bot.is_bot_match());
+        assert!(!ContainerParams::decode(params.as_bytes())
+            .unwrap()
+            .is_bot_match());
+
+        assert_eq!(vs_bot.opponent_mask(), 0);
+    }
+
+    #[test]
+    fn test_params_decode_empty_opponent_slots() {
+        let params = test_params_string();
+        let without_opponents = params[..params.find(",OS_1_PDA").unwrap()].to_string();
+        let partial = format!(
+            "{},OS_1_PDA={},OS_2_PDA={},OS_4_PDA={}",
+            without_opponents,
+            Pubkey::new_unique(),
+            Pubkey::default(),
+            Pubkey::new_unique()
+        );
+
+        let params = ContainerParams::decode(partial.as_bytes()).unwrap();
+        assert!(!params.is_bot_match());
+        assert_eq!(params.opponent_mask(), 0b0_1001);
+        assert_eq!(
+            ContainerParams::decode(test_params_string().as_bytes())
+                .unwrap()
+                .opponent_mask(),
+            0b1_1111
+        );
+    }
+
+    #[test]
+    fn test_params_decode_from_queue() {
+        let params = test_params_string();
+        let without_opponents = params[..params.find(",OS_1_PDA").unwrap()].to_string();
+
+        let from_queue =
+            ContainerParams::decode(format!("{},QUEUE=1", without_opponents).as_bytes()).unwrap();
+        assert!(from_queue.from_queue);
+        assert!(!from_queue.is_bot_match());
+
+        // the candidates come from the queue or from params, never both
+        assert_eq!(
+            ContainerParams::decode(format!("{},QUEUE=1", params).as_bytes()),
+            Err(FunctionError::InvalidParams)
+        );
+
+        let opponents = [(); OPPONENT_SLOT_COUNT].map(|_| Pubkey::new_unique());
+        assert_eq!(
+            from_queue
+                .with_opponents(opponents)
+                .opponent_spaceship_pdas(),
+            opponents
+        );
+    }
+
+    #[test]
+    fn test_params_decode_raffle_draw() {
+        let base = format!(
+            "REQUEST_TYPE=RAFFLE_DRAW,PID={},USER={},REALM_PDA={},USER_ACCOUNT_PDA={},RAFFLE_PDA={}",
+            anchor_spl::token::ID,
+            anchor_spl::token::ID,
+            anchor_spl::token::ID,
+            anchor_spl::token::ID,
+            anchor_spl::token::ID,
+        );
+
+        let params = ContainerParams::decode(
+            format!("{},PARTICIPANT_COUNT=1200,WINNER_COUNT=10", base).as_bytes(),
+        )
+        .unwrap();
+        assert_eq!(params.request_type, RequestType::RaffleDraw);
+        assert_eq!(params.raffle_pda, anchor_spl::token::ID);
+        assert_eq!(params.participant_count, 1_200);
+        assert_eq!(params.winner_count, 10);
+
+        for invalid in [
+            base.clone(),
+            format!("{},PARTICIPANT_COUNT=10", base),
+            format!("{},PARTICIPANT_COUNT=10,WINNER_COUNT=0", base),
+            format!("{},PARTICIPANT_COUNT=3,WINNER_COUNT=4", base),
+            format!(
+                "{},PARTICIPANT_COUNT=1000,WINNER_COUNT={}",
+                base,
+                MAX_RAFFLE_WINNERS + 1
+            ),
+            format!(
+                "{},PARTICIPANT_COUNT=10,WINNER_COUNT=2,DISTRIBUTION=NORMAL:5:2",
+                base
+            ),
+            base.replace(",RAFFLE_PDA=", ",OTHER_PDA=") + ",PARTICIPANT_COUNT=10,WINNER_COUNT=2",
+        ] {
+            assert_eq!(
+                ContainerParams::decode(invalid.as_bytes()).err(),
+                Some(FunctionError::InvalidParams),
+                "{}",
+                invalid
+            );
+        }
+        // every participant can win
+        assert!(
+            ContainerParams::raffle_draw(&test_requester(), Pubkey::new_unique(), 2, 2).is_ok()
+        );
+        assert!(ContainerParams::raffle_draw(&test_requester(), Pubkey::default(), 2, 1).is_err());
+    }
+
+    #[test]
+    fn test_constructors_validate() {
+        let requester = test_requester();
+        let spaceship = Pubkey::new_unique();
+        let opponents = [(); 5].map(|_| Pubkey::new_unique());
+
+        assert!(
+            ContainerParams::matchmaking(&requester, spaceship, FACTION_COUNT, opponents).is_err()
+        );
+        assert!(ContainerParams::matchmaking(&requester, opponents[0], 0, opponents).is_err());
+        let mut unset = opponents;
+        unset[1] = Pubkey::default();
+        unset[4] = Pubkey::default();
+        assert_eq!(
+            ContainerParams::matchmaking(&requester, spaceship, 0, unset)
+                .unwrap()
+                .opponent_mask(),
+            0b0_1101
+        );
+        let mut repeated = unset;
+        repeated[3] = repeated[0];
+        assert!(ContainerParams::matchmaking(&requester, spaceship, 0, repeated).is_err());
+        assert!(ContainerParams::matchmaking(
+            &requester,
+            spaceship,
+            0,
+            [Pubkey::default(); OPPONENT_SLOT_COUNT]
+        )
+        .is_err());
+        let no_user = Requester {
+            user: Pubkey::default(),
+            ..requester
+        };
+        assert!(ContainerParams::matchmaking(&no_user, spaceship, 0, opponents).is_err());
+
+        assert!(ContainerParams::loot_open(&requester, 200).is_err());
+
+        let tournament = Pubkey::new_unique();
+        assert!(ContainerParams::tournament_seed(&requester, tournament, vec![spaceship]).is_err());
+        assert!(ContainerParams::tournament_seed(
+            &requester,
+            tournament,
+            vec![spaceship, spaceship]
+        )
+        .is_err());
+        assert!(ContainerParams::tournament_seed(
+            &requester,
+            Pubkey::default(),
+            opponents[..2].to_vec()
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_params_decode_loot_open_unknown_table() {
+        let request_params_string = format!(
+            "REQUEST_TYPE=LOOT_OPEN,PID={},USER={},REALM_PDA={},USER_ACCOUNT_PDA={},LOOT_TABLE=200",
+            anchor_spl::token::ID,
+            anchor_spl::token::ID,
+            anchor_spl::token::ID,
+            anchor_spl::token::ID,
+        );
+        let request_params_bytes = request_params_string.into_bytes();
+
+        assert!(ContainerParams::decode(&request_params_bytes).is_err());
+    }
+}
+
+#[cfg(test)]
+mod proptests {
+    use super::*;
+    use crate::test_fixtures::test_params_string;
+    use proptest::prelude::*;
+
+    const KEYS: &[&str] = &[
+        "REQUEST_TYPE",
+        "PID",
+        "USER",
+        "REALM_PDA",
+        "USER_ACCOUNT_PDA",
+        "SPACESHIP_PDA",
+        "FACTION",
+        "OS_1_PDA",
+        "OS_5_PDA",
+        "LOOT_TABLE",
+        "LOOT_WEIGHTS",
+        "ALT",
+    ];
+
+    proptest! {
+        #[test]
+        fn decode_never_panics_on_random_bytes(bytes in proptest::collection::vec(any::<u8>(), 0..512)) {
+            let _ = ContainerParams::decode(&bytes);
+        }
+
+        #[test]
+        fn decode_never_panics_on_truncated_params(cut in 0usize..600) {
+            let params = test_params_string().into_bytes();
+            let truncated = &params[..cut.min(params.len())];
+
+            let result = ContainerParams::decode(truncated);
+
+            // the last pair is an opponent, a cut one leaves its slot empty
+            // rather than decoding into a truncated pubkey
+            if !String::from_utf8_lossy(truncated).contains("OS_5_PDA=") {
+                prop_assert!(result.map_or(true, |params| params.opponent_mask() & 1 << 4 == 0));
+            }
+        }
+
+        #[test]
+        fn decode_never_panics_on_mangled_values(
+            pairs in proptest::collection::vec((proptest::sample::select(KEYS), ".{0,60}"), 0..16)
+        ) {
+            let mut params = test_params_string();
+            for (key, value) in pairs {
+                params += &format!(",{}={}", key, value);
+            }
+
+            let _ = ContainerParams::decode(params.as_bytes());
+        }
+    }
 }
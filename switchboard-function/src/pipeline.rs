@@ -0,0 +1,1162 @@
+use crate::*;
+use solana_program::address_lookup_table_account::AddressLookupTableAccount;
+use std::time::Instant;
+
+/// Everything needed to emit one settlement.
+pub struct Settlement {
+    pub ixs: Vec<Instruction>,
+    pub outcome: OutcomeSummary,
+    /// Only known when the candidates were fetched.
+    pub pool_diversity: Option<PoolDiversity>,
+    pub audit: AuditRecord,
+    /// The compute budget the request asked for, a batch pays for the
+    /// largest of its requests.
+    pub cu_limit: u32,
+    pub cu_price: Option<u64>,
+    /// The format the transaction is emitted in, the instructions were sized
+    /// for it.
+    pub encoding: MessageEncoding,
+}
+
+/// The compute budget instructions of a settle transaction, the priority fee
+/// only when one is set.
+pub fn compute_budget_ixns(cu_limit: u32, cu_price: Option<u64>) -> Vec<Instruction> {
+    let mut ixs = vec![Instruction::new_with_borsh(
+        solana_sdk::compute_budget::id(),
+        &solana_sdk::compute_budget::ComputeBudgetInstruction::SetComputeUnitLimit(cu_limit),
+        vec![],
+    )];
+    if let Some(cu_price) = cu_price {
+        ixs.push(Instruction::new_with_borsh(
+            solana_sdk::compute_budget::id(),
+            &solana_sdk::compute_budget::ComputeBudgetInstruction::SetComputeUnitPrice(cu_price),
+            vec![],
+        ));
+    }
+    ixs
+}
+
+/// Builds the instructions settling a request: draws the randomness, reads
+/// whatever accounts the request type needs, and fits the result in the
+/// transaction size budget. Stages above the budget's tier are skipped, and
+/// the tier can only drop while running, so every admission decision is made
+/// before the settle header records it.
+pub fn build_settlement<F: AccountFetcher + ?Sized>(
+    params: &ContainerParams,
+    runner_accounts: &RunnerAccounts,
+    payer: &Pubkey,
+    fetcher: &F,
+    rng: &dyn RandomSource,
+    simulator: Option<&dyn TransactionSimulator>,
+    budget: &mut TierBudget,
+) -> std::result::Result<Settlement, FunctionError> {
+    // read whatever the tier, a realm's rules are not optional
+    let rules = RealmRegistry::embedded().resolve(fetcher, params)?;
+    let params = &rules.apply(params, budget)?;
+    let recorder = RecordingRandomSource::new(rng);
+    let rng: &dyn RandomSource = &recorder;
+    let mut pool_diversity = None;
+    let mut bot = None;
+    let mut queued_opponents = None;
+    let mut faction_stats = None;
+    let selection = match params.request_type {
+        RequestType::Matchmaking if params.is_bot_match() => {
+            // there are no candidates to fetch, the requester is always read
+            // so the bot is rated around it
+            let started = Instant::now();
+            let spaceship = load_requester_spaceship(fetcher, params)?;
+            budget.record(Phase::Fetch, started);
+            check_matchmaking_queued(&spaceship, &runner_accounts.function_request)?;
+            bot = Some(roll_bot_opponent(
+                &spaceship,
+                params.faction,
+                params.exclude_same_faction,
+                rng,
+            )?);
+            None
+        }
+        RequestType::Matchmaking => {
+            let roll = rng.generate(0, u32::MAX - 1)?;
+            // the faction constraint can only be checked on the fetched
+            // spaceships, queued candidates are only known once read and the
+            // treatment selects by the candidates' power, so all three are
+            // honoured whatever the tier
+            if params.exclude_same_faction
+                || params.from_queue
+                || experiment_variant(params) == Variant::Treatment
+                || budget.admit(ExecutionTier::Standard, Phase::Fetch)
+            {
+                // Restrict the candidates to the requester's sub-pool and pick the opponent
+                let started = Instant::now();
+                let accounts = if params.from_queue {
+                    let (accounts, opponents) =
+                        MatchmakingAccounts::load_from_queue(fetcher, params)?;
+                    queued_opponents = Some(opponents);
+                    accounts
+                } else {
+                    MatchmakingAccounts::load(fetcher, params)?
+                };
+                budget.record(Phase::Fetch, started);
+                check_matchmaking_queued(&accounts.spaceship, &runner_accounts.function_request)?;
+                pool_diversity = Some(PoolDiversity::of(&accounts));
+                if shadow_selection_enabled() {
+                    if let Some(comparison) = compare_shadow_selection(&accounts, roll, params) {
+                        record_shadow_selection(&comparison);
+                    }
+                }
+                faction_stats = accounts.realm.faction_stats(params.faction).copied();
+                Some(select_fresh_opponent(
+                    fetcher, &accounts, roll, params, rng, budget,
+                )?)
+            } else {
+                Some(select_opponent_unvalidated(roll, params.opponent_mask()))
+            }
+        }
+        RequestType::Cancel => {
+            // a spaceship matched in the meantime has nothing left to cancel
+            if budget.admit(ExecutionTier::Standard, Phase::Fetch) {
+                let started = Instant::now();
+                let spaceship = load_requester_spaceship(fetcher, params)?;
+                budget.record(Phase::Fetch, started);
+                check_matchmaking_queued(&spaceship, &runner_accounts.function_request)?;
+            }
+            None
+        }
+        RequestType::LootOpen
+        | RequestType::TournamentSeed
+        | RequestType::DailySeed
+        | RequestType::CustomRoll
+        | RequestType::RaffleDraw => None,
+    };
+    if simulator.is_none() {
+        budget.limit(ExecutionTier::Standard);
+    }
+    let simulate = budget.admit(ExecutionTier::Rich, Phase::Simulate);
+    let header = SettleHeader::new(
+        &runner_accounts.function_request,
+        budget.tier(),
+        rng.is_fallback(),
+    );
+
+    let (mut settle_ixn, opponent) = match params.request_type {
+        RequestType::Matchmaking => {
+            // Generate our random result
+            let raw_result = params
+                .distribution
+                .sample(rng, params.roll_min, params.roll_max)?;
+            // the realm's faction stats are only read with the candidates, a
+            // bot match or a roll settled without them is not biased
+            let shaped = shape_roll(
+                FactionBias::from_env().as_ref(),
+                faction_stats.as_ref(),
+                raw_result,
+                params.roll_min,
+                params.roll_max,
+            );
+            let random_result = shaped.random_result;
+            let enclave_key = run_enclave_key()?;
+            let attestation = ResultAttestation(enclave_key.attest_result(
+                &runner_accounts.function_request,
+                random_result,
+                runner_accounts.request_slot,
+            ));
+            // only a batch the runner signs carries the key, see emission.rs
+            let attestation_signer = Some(enclave_key.pubkey())
+                .filter(|signer| *signer != runner_accounts.enclave_signer);
+            if let Some(bot) = &bot {
+                let args = ArenaMatchmakingVsBotSettleArgs::new(
+                    random_result,
+                    params.faction,
+                    bot,
+                    attestation,
+                    attestation_signer,
+                );
+                (
+                    arena_matchmaking_settle_vs_bot_ixn(params, runner_accounts, &header, &args)?,
+                    None,
+                )
+            } else {
+                let selection = selection.ok_or(FunctionError::NoEligibleOpponent)?;
+                // the settle instruction passes the queued candidates as the
+                // opponent slots
+                let queued_params =
+                    queued_opponents.map(|opponents| params.with_opponents(opponents));
+                let params = queued_params.as_ref().unwrap_or(params);
+                let args = ArenaMatchmakingSettleArgs {
+                    random_result,
+                    faction: params.faction,
+                    sub_pool_id: selection.sub_pool_id,
+                    opponent_index: selection.opponent_slot,
+                    opponent_mask: params.opponent_mask(),
+                    requester_power: selection.requester_power,
+                    opponent_power: selection.opponent_power,
+                    raw_result: shaped.raw_result,
+                    bias_strength_bps: shaped.bias_strength_bps,
+                    faction_win_rate_bps: shaped.faction_win_rate_bps,
+                    experiment_id: params.experiment_id,
+                    variant: experiment_variant(params) as u8,
+                    attestation,
+                    attestation_signer,
+                };
+                let opponent = params.opponent_spaceship_pdas()[selection.opponent_slot as usize];
+                (
+                    arena_matchmaking_settle_ixn(params, runner_accounts, &header, &args)?,
+                    Some(opponent),
+                )
+            }
+        }
+        RequestType::LootOpen => {
+            let table = find_loot_table(params.loot_table).ok_or(FunctionError::InvalidParams)?;
+            let weights = params.loot_weights.unwrap_or(table.weights);
+            let rarity_roll = params
+                .distribution
+                .sample(rng, 0, (weights.total() - 1) as u64)? as u32;
+            let item_roll = rng.generate(0, u32::MAX - 1)?;
+            let (item_id, rarity) = open_loot(table, &weights, rarity_roll, item_roll);
+            let args = LootOpenSettleArgs {
+                item_id,
+                rarity: rarity as u8,
+            };
+            (
+                loot_open_settle_ixn(params, runner_accounts, &header, &args)?,
+                None,
+            )
+        }
+        RequestType::DailySeed => {
+            let started = Instant::now();
+            let unix_timestamp = load_unix_timestamp(fetcher)?;
+            budget.record(Phase::Fetch, started);
+            let period_index =
+                seed_period_index(unix_timestamp, params.seed_period, params.seed_offset);
+            let args = DailySeedSettleArgs {
+                period_index,
+                seed: draw_daily_seed(rng, period_index)?,
+            };
+            (
+                daily_seed_settle_ixn(params, runner_accounts, &header, &args)?,
+                None,
+            )
+        }
+        RequestType::Cancel => {
+            let args = ArenaMatchmakingCancelSettleArgs {
+                faction: params.faction,
+            };
+            (
+                arena_matchmaking_cancel_settle_ixn(params, runner_accounts, &header, &args)?,
+                None,
+            )
+        }
+        RequestType::CustomRoll => {
+            let args = encode_roll_args(&params.roll_schema, rng)?;
+            (
+                custom_roll_settle_ixn(params, runner_accounts, &header, &args)?,
+                None,
+            )
+        }
+        RequestType::TournamentSeed => {
+            let args = TournamentSeedSettleArgs {
+                seed_order: shuffle_seed_order(params.participants.len(), rng)?,
+            };
+            (
+                tournament_seed_settle_ixn(params, runner_accounts, &header, &args)?,
+                None,
+            )
+        }
+        RequestType::RaffleDraw => {
+            let args = RaffleSettleArgs {
+                participant_count: params.participant_count,
+                winners: draw_raffle_winners(params.participant_count, params.winner_count, rng)?,
+            };
+            (
+                raffle_settle_ixn(params, runner_accounts, &header, &args)?,
+                None,
+            )
+        }
+    };
+
+    // Multisig governed realms only accept the settlement alongside its
+    // approval, which is checked whatever the tier like the faction constraint
+    if let Some(approval) = load_settlement_approval(fetcher, params, runner_accounts)? {
+        settle_ixn
+            .accounts
+            .extend(approval_account_metas(params, &approval));
+    }
+
+    // Then, write your own Rust logic and build a Vec of instructions.
+    // Should  be under 700 bytes after serialization, the compute budget can be
+    // dropped to fit since the settlement is still valid without it
+    // a v0 message resolves accounts through the lookup table, it is only
+    // read in the tiers that can spend the RPC call
+    let lookup_tables: Vec<AddressLookupTableAccount> = configured_lookup_table(params)
+        .filter(|_| budget.tier() >= ExecutionTier::Standard)
+        .into_iter()
+        .filter_map(|key| {
+            load_lookup_table(fetcher, key)
+                .inspect_err(|error| println!("failed to load lookup table {}: {}", key, error))
+                .ok()
+        })
+        .collect();
+    let encoding = MessageEncoding {
+        format: runner_accounts.tx_format,
+        lookup_tables,
+    };
+
+    let started = Instant::now();
+    let outcome = OutcomeSummary::new(params, runner_accounts, opponent, &settle_ixn);
+    let mut planned_ixs: Vec<PlannedIxn> =
+        compute_budget_ixns(params.cu_limit.unwrap_or(DEFAULT_CU_LIMIT), params.cu_price)
+            .into_iter()
+            .map(PlannedIxn::optional)
+            .collect();
+    planned_ixs.push(PlannedIxn::required(settle_ixn));
+    let ixs = fit_ixns(planned_ixs, payer, &encoding, MAX_IXNS_MESSAGE_SIZE)?;
+    budget.record(Phase::Build, started);
+
+    if let (true, Some(simulator)) = (simulate, simulator) {
+        let started = Instant::now();
+        simulator.simulate(&ixs, payer)?;
+        budget.record(Phase::Simulate, started);
+    }
+
+    encoding.log_sizes(&ixs, payer);
+
+    println!(
+        "settled with {:?} tier, stages {:?}, skipped {:?}",
+        budget.tier(),
+        budget.stage_timings(),
+        budget.skipped_phases()
+    );
+
+    Ok(Settlement {
+        audit: AuditRecord::new(params, &recorder, &outcome),
+        ixs,
+        outcome,
+        pool_diversity,
+        cu_limit: params.cu_limit.unwrap_or(DEFAULT_CU_LIMIT),
+        cu_price: params.cu_price,
+        encoding,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_fixtures::*;
+    use solana_sdk::compute_budget::ComputeBudgetInstruction;
+    use std::time::Duration;
+
+    #[test]
+    fn test_build_matchmaking_settlement() {
+        let params = test_params();
+        let fetcher = test_fetcher(&params, &test_realm(vec![]), [0; 6].map(test_spaceship));
+        let runner_accounts = test_runner_accounts();
+
+        let settlement = build_settlement(
+            &params,
+            &runner_accounts,
+            &runner_accounts.enclave_signer,
+            &fetcher,
+            &OsRandomSource,
+            None,
+            &mut test_budget(ExecutionTier::Standard),
+        )
+        .unwrap();
+
+        assert_eq!(settlement.ixs.len(), 2);
+        assert_eq!(
+            settlement.ixs[0].program_id,
+            solana_sdk::compute_budget::id()
+        );
+        let settle_ixn = &settlement.ixs[1];
+        assert_eq!(settle_ixn.program_id, params.program_id);
+        assert_eq!(
+            settle_ixn.data[..8],
+            get_ixn_discriminator("arena_matchmaking_settle")
+        );
+        assert_eq!(settle_ixn.data[41], ExecutionTier::Standard as u8);
+        assert!(settlement.pool_diversity.is_some());
+        let opponent_index = settle_ixn.data[53] as usize;
+        assert_eq!(
+            settlement.outcome.opponent,
+            Some(params.opponent_spaceship_pdas()[opponent_index].to_string())
+        );
+        let random_result = u64::from_le_bytes(settle_ixn.data[43..51].try_into().unwrap());
+        assert!(settlement.audit.random_values.contains(&random_result));
+        assert_eq!(settlement.audit.outcome, settlement.outcome);
+        // without FACTION_BIAS the roll is settled as drawn
+        assert_eq!(settle_ixn.data[83..91], settle_ixn.data[43..51]);
+        assert_eq!(settle_ixn.data[91..93], [0, 0]);
+
+        // the attestation only verifies for the roll and slot it was made on
+        let attestation: [u8; ATTESTATION_LEN] = settle_ixn.data[100..164].try_into().unwrap();
+        // by the instruction's signer, so the args leave the key out
+        assert_eq!(settle_ixn.data[164..], [0]);
+        let signer = settle_ixn.accounts[0].pubkey;
+        assert_eq!(signer, run_enclave_key().unwrap().pubkey());
+        let request = &runner_accounts.function_request;
+        assert!(verify_result_attestation(
+            &attestation,
+            &signer,
+            request,
+            random_result,
+            1_000
+        ));
+        assert!(!verify_result_attestation(
+            &attestation,
+            &signer,
+            request,
+            random_result + 1,
+            1_000
+        ));
+        assert!(!verify_result_attestation(
+            &attestation,
+            &signer,
+            request,
+            random_result,
+            1_001
+        ));
+    }
+
+    #[test]
+    fn test_compute_budget_overrides() {
+        let runner_accounts = test_runner_accounts();
+        let settle = |params: &ContainerParams| {
+            let fetcher = test_fetcher(params, &test_realm(vec![]), [0; 6].map(test_spaceship));
+            build_settlement(
+                params,
+                &runner_accounts,
+                &runner_accounts.enclave_signer,
+                &fetcher,
+                &OsRandomSource,
+                None,
+                &mut test_budget(ExecutionTier::Standard),
+            )
+            .unwrap()
+        };
+
+        let settlement = settle(&test_params());
+        assert_eq!(settlement.ixs.len(), 2);
+        assert_eq!(
+            ComputeBudgetInstruction::try_from_slice(&settlement.ixs[0].data).unwrap(),
+            ComputeBudgetInstruction::SetComputeUnitLimit(DEFAULT_CU_LIMIT)
+        );
+
+        let mut params = test_params();
+        params.cu_limit = Some(250_000);
+        params.cu_price = Some(1_000);
+        let settlement = settle(&params);
+        assert_eq!(settlement.ixs.len(), 3);
+        assert_eq!(
+            ComputeBudgetInstruction::try_from_slice(&settlement.ixs[0].data).unwrap(),
+            ComputeBudgetInstruction::SetComputeUnitLimit(250_000)
+        );
+        assert_eq!(
+            ComputeBudgetInstruction::try_from_slice(&settlement.ixs[1].data).unwrap(),
+            ComputeBudgetInstruction::SetComputeUnitPrice(1_000)
+        );
+        assert_eq!(settlement.ixs[2].program_id, params.program_id);
+    }
+
+    #[test]
+    fn test_build_settlement_from_queue() {
+        let owners: Vec<Pubkey> = (0..6).map(|_| Pubkey::new_unique()).collect();
+        let requester = Requester {
+            program_id: Pubkey::new_unique(),
+            user: owners[0],
+            realm_pda: Pubkey::new_unique(),
+            user_account_pda: Pubkey::new_unique(),
+        };
+        let pdas: Vec<Pubkey> = owners
+            .iter()
+            .map(|owner| spaceship_pda(&requester.program_id, &requester.realm_pda, owner))
+            .collect();
+        let params = ContainerParams::matchmaking_from_queue(&requester, pdas[0], 0).unwrap();
+        let mut realm = test_realm(vec![]);
+        realm.queues = vec![MatchmakingQueue {
+            sub_pool_id: DEFAULT_SUB_POOL_ID,
+            owners,
+        }];
+        let mut fetcher = MockFetcher::default();
+        fetcher.insert(params.realm_pda, encode_account(Realm::NAME, &realm));
+        for pda in pdas.iter() {
+            fetcher.insert(*pda, encode_account(Spaceship::NAME, &test_spaceship(0)));
+        }
+        let runner_accounts = test_runner_accounts();
+
+        // the queue is read even at the fast tier
+        let settlement = build_settlement(
+            &params,
+            &runner_accounts,
+            &runner_accounts.enclave_signer,
+            &fetcher,
+            &OsRandomSource,
+            None,
+            &mut test_budget(ExecutionTier::Fast),
+        )
+        .unwrap();
+
+        let settle_ixn = &settlement.ixs[1];
+        assert_eq!(
+            settle_ixn.data[..8],
+            get_ixn_discriminator("arena_matchmaking_settle")
+        );
+        assert_eq!(
+            settle_ixn.accounts[7..]
+                .iter()
+                .map(|meta| meta.pubkey)
+                .collect::<Vec<_>>(),
+            pdas[1..]
+        );
+        let opponent_index = settle_ixn.data[53] as usize;
+        assert_eq!(
+            settlement.outcome.opponent,
+            Some(pdas[1 + opponent_index].to_string())
+        );
+    }
+
+    #[test]
+    fn test_build_settlement_missing_accounts() {
+        let params = test_params();
+        let runner_accounts = test_runner_accounts();
+
+        let result = build_settlement(
+            &params,
+            &runner_accounts,
+            &runner_accounts.enclave_signer,
+            &MockFetcher::default(),
+            &OsRandomSource,
+            None,
+            &mut test_budget(ExecutionTier::Standard),
+        );
+
+        assert_eq!(result.err(), Some(FunctionError::AccountFetchFailed));
+    }
+
+    #[test]
+    fn test_empty_opponent_slots_are_passed_readonly() {
+        let mut params = test_params();
+        params.opponent_spaceship_2_pda = Pubkey::default();
+        params.opponent_spaceship_4_pda = Pubkey::default();
+        let runner_accounts = test_runner_accounts();
+
+        for tier in [ExecutionTier::Standard, ExecutionTier::Fast] {
+            let fetcher = test_fetcher(&params, &test_realm(vec![]), [0; 6].map(test_spaceship));
+            let settlement = build_settlement(
+                &params,
+                &runner_accounts,
+                &runner_accounts.enclave_signer,
+                &fetcher,
+                &OsRandomSource,
+                None,
+                &mut test_budget(tier),
+            )
+            .unwrap();
+
+            let settle_ixn = &settlement.ixs[1];
+            assert_eq!(settle_ixn.data[54], 0b1_0101);
+            assert!([0, 2, 4].contains(&settle_ixn.data[53]));
+            let sentinels: Vec<&AccountMeta> = settle_ixn
+                .accounts
+                .iter()
+                .filter(|meta| meta.pubkey == Pubkey::default())
+                .collect();
+            assert_eq!(sentinels.len(), 2);
+            assert!(sentinels.iter().all(|meta| !meta.is_writable));
+            assert!(settle_ixn
+                .accounts
+                .iter()
+                .filter(|meta| meta.pubkey == params.opponent_spaceship_1_pda)
+                .all(|meta| meta.is_writable));
+        }
+    }
+
+    #[test]
+    fn test_v0_settlement_resolves_through_the_lookup_table() {
+        use solana_address_lookup_table_program::state::{AddressLookupTable, LookupTableMeta};
+
+        let mut params = test_params();
+        params.lookup_table = Pubkey::new_unique();
+        let mut fetcher = test_fetcher(&params, &test_realm(vec![]), [0; 6].map(test_spaceship));
+        let addresses = params.opponent_spaceship_pdas();
+        fetcher.insert(
+            params.lookup_table,
+            AddressLookupTable {
+                meta: LookupTableMeta::new(Pubkey::new_unique()),
+                addresses: std::borrow::Cow::Borrowed(&addresses),
+            }
+            .serialize_for_tests()
+            .unwrap(),
+        );
+
+        for (tx_format, tier, lookup_tables) in [
+            (TxFormat::V0, ExecutionTier::Standard, 1),
+            (TxFormat::V0, ExecutionTier::Fast, 0),
+            (TxFormat::Legacy, ExecutionTier::Standard, 1),
+        ] {
+            let runner_accounts = RunnerAccounts {
+                tx_format,
+                ..test_runner_accounts()
+            };
+            let settlement = build_settlement(
+                &params,
+                &runner_accounts,
+                &runner_accounts.enclave_signer,
+                &fetcher,
+                &OsRandomSource,
+                None,
+                &mut test_budget(tier),
+            )
+            .unwrap();
+
+            assert_eq!(settlement.encoding.format, tx_format);
+            assert_eq!(settlement.encoding.lookup_tables.len(), lookup_tables);
+        }
+    }
+
+    #[test]
+    fn test_fast_tier_skips_account_fetches() {
+        let params = test_params();
+        let runner_accounts = test_runner_accounts();
+
+        let settlement = build_settlement(
+            &params,
+            &runner_accounts,
+            &runner_accounts.enclave_signer,
+            &MockFetcher::default(),
+            &OsRandomSource,
+            None,
+            &mut test_budget(ExecutionTier::Fast),
+        )
+        .unwrap();
+
+        let settle_ixn = &settlement.ixs[1];
+        assert_eq!(settle_ixn.data[41], ExecutionTier::Fast as u8);
+        assert_eq!(settle_ixn.data[52], DEFAULT_SUB_POOL_ID);
+        assert_eq!(settlement.pool_diversity, None);
+    }
+
+    #[test]
+    fn test_experiment_treatment_is_honoured_at_the_fast_tier() {
+        let mut params = test_params();
+        params.experiment_id = 5;
+        params.experiment_share_bps = 10_000;
+        let fetcher = test_fetcher(&params, &test_realm(vec![]), [500; 6].map(test_spaceship));
+        let runner_accounts = test_runner_accounts();
+
+        let settlement = build_settlement(
+            &params,
+            &runner_accounts,
+            &runner_accounts.enclave_signer,
+            &fetcher,
+            &OsRandomSource,
+            None,
+            &mut test_budget(ExecutionTier::Fast),
+        )
+        .unwrap();
+
+        // the treatment selected by power, so the candidates were read
+        let settle_ixn = &settlement.ixs[1];
+        assert!(settlement.pool_diversity.is_some());
+        assert_eq!(settle_ixn.data[55..59], 500u32.to_le_bytes());
+        assert_eq!(settle_ixn.data[95..99], 5u32.to_le_bytes());
+        assert_eq!(settle_ixn.data[99], Variant::Treatment as u8);
+
+        // the control group still settles without reading them
+        params.experiment_share_bps = 0;
+        let settlement = build_settlement(
+            &params,
+            &runner_accounts,
+            &runner_accounts.enclave_signer,
+            &MockFetcher::default(),
+            &OsRandomSource,
+            None,
+            &mut test_budget(ExecutionTier::Fast),
+        )
+        .unwrap();
+        assert_eq!(settlement.ixs[1].data[95..99], 5u32.to_le_bytes());
+        assert_eq!(settlement.ixs[1].data[99], Variant::Control as u8);
+    }
+
+    #[test]
+    fn test_bot_match_settles_vs_bot() {
+        let mut params = test_params();
+        params.opponent_spaceship_1_pda = Pubkey::default();
+        params.opponent_spaceship_2_pda = Pubkey::default();
+        params.opponent_spaceship_3_pda = Pubkey::default();
+        params.opponent_spaceship_4_pda = Pubkey::default();
+        params.opponent_spaceship_5_pda = Pubkey::default();
+        params.exclude_same_faction = true;
+        let mut fetcher = MockFetcher::default();
+        fetcher.insert(
+            params.spaceship_pda,
+            encode_account(Spaceship::NAME, &test_spaceship(1_200)),
+        );
+        let runner_accounts = test_runner_accounts();
+
+        let settlement = build_settlement(
+            &params,
+            &runner_accounts,
+            &runner_accounts.enclave_signer,
+            &fetcher,
+            &OsRandomSource,
+            None,
+            &mut test_budget(ExecutionTier::Fast),
+        )
+        .unwrap();
+
+        let settle_ixn = &settlement.ixs[1];
+        assert_eq!(
+            settle_ixn.data[..8],
+            get_ixn_discriminator("arena_matchmaking_settle_vs_bot")
+        );
+        assert_ne!(settle_ixn.data[52], params.faction);
+        let random_result = u64::from_le_bytes(settle_ixn.data[43..51].try_into().unwrap());
+        assert_eq!(settle_ixn.data[129..], [0]);
+        assert!(verify_result_attestation(
+            &settle_ixn.data[65..129].try_into().unwrap(),
+            &settle_ixn.accounts[0].pubkey,
+            &runner_accounts.function_request,
+            random_result,
+            runner_accounts.request_slot,
+        ));
+        assert_eq!(settlement.outcome.opponent, None);
+        assert_eq!(settlement.pool_diversity, None);
+
+        // the requester's rating is required to roll the bot
+        let result = build_settlement(
+            &params,
+            &runner_accounts,
+            &runner_accounts.enclave_signer,
+            &MockFetcher::default(),
+            &OsRandomSource,
+            None,
+            &mut test_budget(ExecutionTier::Fast),
+        );
+        assert_eq!(result.err(), Some(FunctionError::AccountFetchFailed));
+    }
+
+    #[test]
+    fn test_distribution_shapes_random_result() {
+        let mut params = test_params();
+        params.distribution = RollDistribution::Normal {
+            mean: 42_000,
+            std_dev: 0,
+        };
+        let runner_accounts = test_runner_accounts();
+
+        let settlement = build_settlement(
+            &params,
+            &runner_accounts,
+            &runner_accounts.enclave_signer,
+            &MockFetcher::default(),
+            &OsRandomSource,
+            None,
+            &mut test_budget(ExecutionTier::Fast),
+        )
+        .unwrap();
+
+        assert_eq!(settlement.ixs[1].data[43..51], 42_000u64.to_le_bytes());
+    }
+
+    #[test]
+    fn test_rich_tier_simulates() {
+        let params = test_params();
+        let fetcher = test_fetcher(&params, &test_realm(vec![]), [0; 6].map(test_spaceship));
+        let runner_accounts = test_runner_accounts();
+        let failing = MockSimulator {
+            result: Err(FunctionError::SimulationFailed),
+        };
+
+        let result = build_settlement(
+            &params,
+            &runner_accounts,
+            &runner_accounts.enclave_signer,
+            &fetcher,
+            &OsRandomSource,
+            Some(&failing),
+            &mut test_budget(ExecutionTier::Rich),
+        );
+        assert_eq!(result.err(), Some(FunctionError::SimulationFailed));
+
+        let mut budget = test_budget(ExecutionTier::Rich);
+        let settlement = build_settlement(
+            &params,
+            &runner_accounts,
+            &runner_accounts.enclave_signer,
+            &fetcher,
+            &OsRandomSource,
+            Some(&MockSimulator { result: Ok(()) }),
+            &mut budget,
+        )
+        .unwrap();
+        assert_eq!(settlement.ixs[1].data[41], ExecutionTier::Rich as u8);
+        let stages: Vec<&str> = budget
+            .stage_timings()
+            .iter()
+            .map(|(stage, _)| *stage)
+            .collect();
+        assert_eq!(stages, vec!["fetch", "validate", "build", "simulation"]);
+    }
+
+    #[test]
+    fn test_rich_tier_without_simulator_settles_as_standard() {
+        let params = test_params();
+        let fetcher = test_fetcher(&params, &test_realm(vec![]), [0; 6].map(test_spaceship));
+        let runner_accounts = test_runner_accounts();
+
+        let settlement = build_settlement(
+            &params,
+            &runner_accounts,
+            &runner_accounts.enclave_signer,
+            &fetcher,
+            &OsRandomSource,
+            None,
+            &mut test_budget(ExecutionTier::Rich),
+        )
+        .unwrap();
+
+        assert_eq!(settlement.ixs[1].data[41], ExecutionTier::Standard as u8);
+    }
+
+    #[test]
+    fn test_entropy_fallback_is_flagged_in_the_header() {
+        let params = test_params();
+        let fetcher = test_fetcher(&params, &test_realm(vec![]), [0; 6].map(test_spaceship));
+        let runner_accounts = test_runner_accounts();
+        let settle = |rng: &dyn RandomSource| {
+            build_settlement(
+                &params,
+                &runner_accounts,
+                &runner_accounts.enclave_signer,
+                &fetcher,
+                rng,
+                None,
+                &mut test_budget(ExecutionTier::Standard),
+            )
+            .unwrap()
+        };
+
+        assert_eq!(settle(&OsRandomSource).ixs[1].data[42], 0);
+        let fallback = RunEntropy::<GramineRandomSource>::Fallback(OsRandomSource);
+        assert_eq!(settle(&fallback).ixs[1].data[42], 1);
+    }
+
+    #[test]
+    fn test_realm_rules_shape_the_settlement() {
+        let params = test_params();
+        let mut fetcher = test_fetcher(&params, &test_realm(vec![]), [0; 6].map(test_spaceship));
+        let rules = RealmRulesAccount {
+            realm: params.realm_pda,
+            roll_min: Some(40),
+            roll_max: Some(41),
+            max_execution_tier: Some(ExecutionTier::Standard as u8),
+            ..Default::default()
+        };
+        fetcher.insert(
+            realm_rules_pda(&params.program_id, &params.realm_pda),
+            encode_account(RealmRulesAccount::NAME, &rules),
+        );
+        let runner_accounts = test_runner_accounts();
+
+        let settlement = build_settlement(
+            &params,
+            &runner_accounts,
+            &runner_accounts.enclave_signer,
+            &fetcher,
+            &OsRandomSource,
+            Some(&MockSimulator { result: Ok(()) }),
+            &mut test_budget(ExecutionTier::Rich),
+        )
+        .unwrap();
+
+        let data = &settlement.ixs[1].data;
+        assert_eq!(data[41], ExecutionTier::Standard as u8);
+        let random_result = u64::from_le_bytes(data[43..51].try_into().unwrap());
+        assert!((40..=41).contains(&random_result), "{}", random_result);
+    }
+
+    #[test]
+    fn test_late_start_skips_optional_phases_but_settles() {
+        let params = test_params();
+        let fetcher = test_fetcher(&params, &test_realm(vec![]), [0; 6].map(test_spaceship));
+        let runner_accounts = test_runner_accounts();
+        // a slow RPC took 14s of the 20s before the settlement got to run
+        let mut budget = TierBudget::new(
+            Instant::now() - Duration::from_secs(14),
+            Duration::from_secs(20),
+            ExecutionTier::Rich,
+        );
+        let failing = MockSimulator {
+            result: Err(FunctionError::SimulationFailed),
+        };
+
+        let settlement = build_settlement(
+            &params,
+            &runner_accounts,
+            &runner_accounts.enclave_signer,
+            &fetcher,
+            &OsRandomSource,
+            Some(&failing),
+            &mut budget,
+        )
+        .unwrap();
+
+        assert_eq!(settlement.ixs[1].data[41], ExecutionTier::Fast as u8);
+        assert_eq!(budget.skipped_phases(), vec!["fetch"]);
+        let stages: Vec<&str> = budget
+            .stage_timings()
+            .iter()
+            .map(|(stage, _)| *stage)
+            .collect();
+        assert_eq!(stages, vec!["build"]);
+    }
+
+    #[test]
+    fn test_approved_settlement_carries_approval_accounts() {
+        let params = ContainerParams::decode(
+            format!(
+                "{},APPROVAL_PDA={}",
+                test_params_string(),
+                Pubkey::new_unique()
+            )
+            .as_bytes(),
+        )
+        .unwrap();
+        let runner_accounts = test_runner_accounts();
+        let mut fetcher = test_fetcher(&params, &test_realm(vec![]), [0; 6].map(test_spaceship));
+        let approval = SettlementApproval {
+            bump: 255,
+            realm: params.realm_pda,
+            function_request: runner_accounts.function_request,
+            multisig: Pubkey::new_unique(),
+            status: ApprovalStatus::Approved,
+        };
+        fetcher.insert(
+            params.approval_pda,
+            encode_account(SettlementApproval::NAME, &approval),
+        );
+
+        let settlement = build_settlement(
+            &params,
+            &runner_accounts,
+            &runner_accounts.enclave_signer,
+            &fetcher,
+            &OsRandomSource,
+            None,
+            &mut test_budget(ExecutionTier::Fast),
+        )
+        .unwrap();
+
+        let accounts = &settlement.ixs.last().unwrap().accounts;
+        assert_eq!(accounts.len(), 14);
+        assert_eq!(accounts[12].pubkey, params.approval_pda);
+        assert_eq!(accounts[13].pubkey, approval.multisig);
+    }
+
+    #[test]
+    fn test_build_daily_seed_settlement() {
+        let requester = Requester {
+            program_id: Pubkey::new_unique(),
+            user: Pubkey::new_unique(),
+            realm_pda: Pubkey::new_unique(),
+            user_account_pda: Pubkey::new_unique(),
+        };
+        let params =
+            ContainerParams::daily_seed(&requester, Pubkey::new_unique(), 86_400, 14_400).unwrap();
+        let mut fetcher = MockFetcher::default();
+        fetcher.insert(
+            solana_program::sysvar::clock::ID,
+            clock_account(1_704_164_400),
+        );
+        let runner_accounts = test_runner_accounts();
+
+        let settlement = build_settlement(
+            &params,
+            &runner_accounts,
+            &runner_accounts.enclave_signer,
+            &fetcher,
+            &OsRandomSource,
+            None,
+            &mut test_budget(ExecutionTier::Fast),
+        )
+        .unwrap();
+
+        let settle_ixn = &settlement.ixs[1];
+        assert_eq!(
+            settle_ixn.data[..8],
+            get_ixn_discriminator("daily_seed_settle")
+        );
+        assert_eq!(settle_ixn.data[43..51], 19_723u64.to_le_bytes());
+        assert_eq!(settle_ixn.accounts[3].pubkey, params.seed_pda);
+    }
+
+    #[test]
+    fn test_build_cancel_settlement() {
+        let requester = Requester {
+            program_id: Pubkey::new_unique(),
+            user: Pubkey::new_unique(),
+            realm_pda: Pubkey::new_unique(),
+            user_account_pda: Pubkey::new_unique(),
+        };
+        let params = ContainerParams::cancel(&requester, Pubkey::new_unique(), 1).unwrap();
+        let runner_accounts = test_runner_accounts();
+        let mut spaceship = test_spaceship(1_000);
+        let mut fetcher = MockFetcher::default();
+        fetcher.insert(
+            params.spaceship_pda,
+            encode_account(Spaceship::NAME, &spaceship),
+        );
+        let settle = |fetcher: &MockFetcher| {
+            build_settlement(
+                &params,
+                &runner_accounts,
+                &runner_accounts.enclave_signer,
+                fetcher,
+                &OsRandomSource,
+                None,
+                &mut test_budget(ExecutionTier::Standard),
+            )
+        };
+
+        let settlement = settle(&fetcher).unwrap();
+        let settle_ixn = &settlement.ixs[1];
+        assert_eq!(
+            settle_ixn.data[..8],
+            get_ixn_discriminator("arena_matchmaking_cancel_settle")
+        );
+        assert_eq!(settle_ixn.data[43], 1);
+        assert_eq!(settle_ixn.accounts[4].pubkey, params.spaceship_pda);
+        assert!(settle_ixn.accounts[4].is_writable);
+        assert!(settlement.audit.random_values.is_empty());
+
+        spaceship.current_match = Some(Pubkey::new_unique());
+        fetcher.insert(
+            params.spaceship_pda,
+            encode_account(Spaceship::NAME, &spaceship),
+        );
+        assert_eq!(settle(&fetcher).err(), Some(FunctionError::AlreadySettled));
+    }
+
+    #[test]
+    fn test_build_custom_roll_settlement() {
+        let requester = Requester {
+            program_id: Pubkey::new_unique(),
+            user: Pubkey::new_unique(),
+            realm_pda: Pubkey::new_unique(),
+            user_account_pda: Pubkey::new_unique(),
+        };
+        let schema = ["U8/1/20", "U16/100/100", "U64"]
+            .iter()
+            .map(|field| RollField::from_str(field).unwrap())
+            .collect();
+        let params = ContainerParams::custom_roll(&requester, "crit_roll_settle", schema).unwrap();
+        let runner_accounts = test_runner_accounts();
+
+        let settlement = build_settlement(
+            &params,
+            &runner_accounts,
+            &runner_accounts.enclave_signer,
+            &MockFetcher::default(),
+            &OsRandomSource,
+            None,
+            &mut test_budget(ExecutionTier::Standard),
+        )
+        .unwrap();
+
+        let settle_ixn = &settlement.ixs[1];
+        assert_eq!(settle_ixn.program_id, requester.program_id);
+        assert_eq!(
+            settle_ixn.data[..8],
+            get_ixn_discriminator("crit_roll_settle")
+        );
+        assert_eq!(settle_ixn.data.len(), 43 + 1 + 2 + 8);
+        assert!((1..=20).contains(&settle_ixn.data[43]));
+        assert_eq!(settle_ixn.data[44..46], 100u16.to_le_bytes());
+        assert_eq!(settlement.audit.random_values.len(), 3);
+    }
+
+    #[test]
+    fn test_largest_tournament_seed_fits() {
+        let participants: Vec<String> = (0..MAX_TOURNAMENT_PARTICIPANTS)
+            .map(|_| Pubkey::new_unique().to_string())
+            .collect();
+        let params = ContainerParams::decode(
+            format!(
+                "REQUEST_TYPE=TOURNAMENT_SEED,PID={},USER={},REALM_PDA={},USER_ACCOUNT_PDA={},TOURNAMENT_PDA={},PARTICIPANTS={}",
+                Pubkey::new_unique(),
+                Pubkey::new_unique(),
+                Pubkey::new_unique(),
+                Pubkey::new_unique(),
+                Pubkey::new_unique(),
+                participants.join(":"),
+            )
+            .as_bytes(),
+        )
+        .unwrap();
+        let runner_accounts = test_runner_accounts();
+
+        let settlement = build_settlement(
+            &params,
+            &runner_accounts,
+            &Pubkey::new_unique(),
+            &MockFetcher::default(),
+            &OsRandomSource,
+            None,
+            &mut test_budget(ExecutionTier::Standard),
+        )
+        .unwrap();
+
+        // the compute budget instruction is kept too
+        assert_eq!(settlement.ixs.len(), 2);
+        let settle_ixn = &settlement.ixs[1];
+        assert_eq!(
+            settle_ixn.data[..8],
+            get_ixn_discriminator("tournament_seed_settle")
+        );
+        assert_eq!(
+            settle_ixn.data[43..47],
+            (MAX_TOURNAMENT_PARTICIPANTS as u32).to_le_bytes()
+        );
+        assert_eq!(settle_ixn.data.len(), 47 + MAX_TOURNAMENT_PARTICIPANTS);
+        assert_eq!(settle_ixn.accounts.len(), 6 + MAX_TOURNAMENT_PARTICIPANTS);
+    }
+
+    #[test]
+    fn test_largest_raffle_draw_fits() {
+        let requester = Requester {
+            program_id: Pubkey::new_unique(),
+            user: Pubkey::new_unique(),
+            realm_pda: Pubkey::new_unique(),
+            user_account_pda: Pubkey::new_unique(),
+        };
+        let params = ContainerParams::raffle_draw(
+            &requester,
+            Pubkey::new_unique(),
+            u32::MAX,
+            MAX_RAFFLE_WINNERS,
+        )
+        .unwrap();
+        let runner_accounts = test_runner_accounts();
+
+        let settlement = build_settlement(
+            &params,
+            &runner_accounts,
+            &runner_accounts.enclave_signer,
+            &MockFetcher::default(),
+            &OsRandomSource,
+            None,
+            &mut test_budget(ExecutionTier::Standard),
+        )
+        .unwrap();
+
+        // the compute budget instruction is kept too
+        assert_eq!(settlement.ixs.len(), 2);
+        let settle_ixn = &settlement.ixs[1];
+        assert_eq!(settle_ixn.data[..8], get_ixn_discriminator("raffle_settle"));
+        assert_eq!(settle_ixn.data[43..47], u32::MAX.to_le_bytes());
+        assert_eq!(settle_ixn.data[47..51], MAX_RAFFLE_WINNERS.to_le_bytes());
+        assert_eq!(settle_ixn.data.len(), 51 + 4 * MAX_RAFFLE_WINNERS as usize);
+        assert_eq!(settle_ixn.accounts[3].pubkey, params.raffle_pda);
+        assert!(settle_ixn.accounts[3].is_writable);
+    }
+}
@@ -0,0 +1,238 @@
+use crate::*;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+
+/// Runs remembered when deciding whether pools are consistently degenerate.
+pub const POOL_DIVERSITY_WINDOW: usize = 20;
+
+/// How varied the opponents passed with a matchmaking request are. The
+/// candidates come from the on-chain queue, so a pool of copies of the same
+/// spaceship, or of spaceships the requester owns, means the queue selection
+/// is broken rather than that the player was unlucky.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PoolDiversity {
+    pub candidates: u8,
+    pub distinct_spaceships: u8,
+    /// Owners other than the requester's.
+    pub distinct_owners: u8,
+    /// Difference between the highest and lowest candidate rating.
+    pub rating_spread: u32,
+}
+
+impl PoolDiversity {
+    pub fn of(accounts: &MatchmakingAccounts) -> Self {
+        let spaceships: HashSet<&Pubkey> = accounts
+            .candidates
+            .iter()
+            .map(|candidate| &candidate.pubkey)
+            .collect();
+        let owners: HashSet<&Pubkey> = accounts
+            .candidates
+            .iter()
+            .map(|candidate| &candidate.spaceship.owner)
+            .filter(|owner| **owner != accounts.spaceship.owner)
+            .collect();
+        let ratings = accounts
+            .candidates
+            .iter()
+            .map(|candidate| candidate.spaceship.rating);
+        let rating_spread =
+            ratings.clone().max().unwrap_or_default() - ratings.min().unwrap_or_default();
+
+        Self {
+            candidates: accounts.candidates.len() as u8,
+            distinct_spaceships: spaceships.len() as u8,
+            distinct_owners: owners.len() as u8,
+            rating_spread,
+        }
+    }
+
+    pub fn is_degenerate(&self) -> bool {
+        self.distinct_spaceships < 2 || self.distinct_owners < 2 || self.rating_spread == 0
+    }
+
+    pub fn record_metrics(&self) {
+        record_gauge("pool_candidates", self.candidates as f64, &[]);
+        record_gauge(
+            "pool_distinct_spaceships",
+            self.distinct_spaceships as f64,
+            &[],
+        );
+        record_gauge("pool_distinct_owners", self.distinct_owners as f64, &[]);
+        record_gauge("pool_rating_spread", self.rating_spread as f64, &[]);
+        if self.is_degenerate() {
+            record_counter("pool_degenerate_total", &[]);
+        }
+    }
+
+    pub fn metric_line(&self) -> String {
+        format!(
+            "pool_diversity candidates={} distinct_spaceships={} distinct_owners={} rating_spread={} degenerate={}",
+            self.candidates,
+            self.distinct_spaceships,
+            self.distinct_owners,
+            self.rating_spread,
+            self.is_degenerate()
+        )
+    }
+}
+
+/// Degenerate flags of the latest runs, persisted in the sealed stats artifact.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, Eq)]
+pub struct PoolDiversityHistory {
+    pub recent_degenerate: Vec<bool>,
+}
+
+impl PoolDiversityHistory {
+    pub fn load(storage: &SealedStorage) -> Self {
+        storage
+            .read(ArtifactKind::Stats)
+            .and_then(|data| serde_json::from_slice(&data).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn save(&self, storage: &SealedStorage) -> std::io::Result<()> {
+        let data = serde_json::to_vec(self)?;
+        storage.write(ArtifactKind::Stats, &data)
+    }
+
+    pub fn push(&mut self, diversity: &PoolDiversity) {
+        self.recent_degenerate.push(diversity.is_degenerate());
+        if self.recent_degenerate.len() > POOL_DIVERSITY_WINDOW {
+            self.recent_degenerate.remove(0);
+        }
+    }
+
+    /// A full window with at least half of the pools degenerate. A single bad
+    /// pool can happen while a realm is still filling up.
+    pub fn should_alert(&self) -> bool {
+        let degenerate = self.recent_degenerate.iter().filter(|d| **d).count();
+        self.recent_degenerate.len() == POOL_DIVERSITY_WINDOW
+            && degenerate * 2 >= POOL_DIVERSITY_WINDOW
+    }
+}
+
+/// Logs the pool's diversity and records it, alerting when the recent pools
+/// are consistently degenerate. Storage failures only cost us the history.
+pub fn record_pool_diversity(storage: &SealedStorage, diversity: &PoolDiversity) {
+    println!("{}", diversity.metric_line());
+    diversity.record_metrics();
+
+    let mut history = PoolDiversityHistory::load(storage);
+    history.push(diversity);
+    if history.should_alert() {
+        record_counter("pool_degenerate_alert_total", &[]);
+        println!(
+            "ALERT: {} of the last {} candidate pools were degenerate, check the on-chain queue selection",
+            history.recent_degenerate.iter().filter(|d| **d).count(),
+            POOL_DIVERSITY_WINDOW
+        );
+    }
+    if let Err(error) = history.save(storage) {
+        println!("failed to save pool diversity history: {}", error);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_fixtures::*;
+
+    fn accounts(spaceships: Vec<Spaceship>) -> MatchmakingAccounts {
+        MatchmakingAccounts {
+            realm: test_realm(vec![]),
+            spaceship: test_spaceship(1_000),
+            candidates: spaceships
+                .into_iter()
+                .enumerate()
+                .map(|(slot, spaceship)| Candidate {
+                    slot: slot as u8,
+                    pubkey: Pubkey::new_unique(),
+                    spaceship,
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn test_diverse_pool() {
+        let accounts = accounts(
+            [900, 1_000, 1_000, 1_100, 1_250]
+                .map(test_spaceship)
+                .to_vec(),
+        );
+
+        let diversity = PoolDiversity::of(&accounts);
+
+        assert_eq!(
+            diversity,
+            PoolDiversity {
+                candidates: 5,
+                distinct_spaceships: 5,
+                distinct_owners: 5,
+                rating_spread: 350,
+            }
+        );
+        assert!(!diversity.is_degenerate());
+    }
+
+    #[test]
+    fn test_pool_of_requester_owned_spaceships_is_degenerate() {
+        let mut accounts = accounts([900, 1_000, 1_100].map(test_spaceship).to_vec());
+        for candidate in accounts.candidates.iter_mut() {
+            candidate.spaceship.owner = accounts.spaceship.owner;
+        }
+
+        let diversity = PoolDiversity::of(&accounts);
+
+        assert_eq!(diversity.distinct_owners, 0);
+        assert!(diversity.is_degenerate());
+    }
+
+    #[test]
+    fn test_pool_of_one_spaceship_is_degenerate() {
+        let mut accounts = accounts([1_000; 5].map(test_spaceship).to_vec());
+        let pubkey = accounts.candidates[0].pubkey;
+        for candidate in accounts.candidates.iter_mut() {
+            candidate.pubkey = pubkey;
+        }
+
+        let diversity = PoolDiversity::of(&accounts);
+
+        assert_eq!(diversity.distinct_spaceships, 1);
+        assert_eq!(diversity.rating_spread, 0);
+        assert!(diversity.is_degenerate());
+    }
+
+    #[test]
+    fn test_history_alerts_on_consistently_degenerate_pools() {
+        let healthy = PoolDiversity::of(&accounts([900, 1_100].map(test_spaceship).to_vec()));
+        let degenerate = PoolDiversity::of(&accounts(vec![]));
+        let mut history = PoolDiversityHistory::default();
+
+        for _ in 0..POOL_DIVERSITY_WINDOW / 2 {
+            history.push(&degenerate);
+        }
+        // not enough runs yet to tell
+        assert!(!history.should_alert());
+        for _ in 0..POOL_DIVERSITY_WINDOW / 2 {
+            history.push(&healthy);
+        }
+        assert!(history.should_alert());
+
+        history.push(&healthy);
+        assert_eq!(history.recent_degenerate.len(), POOL_DIVERSITY_WINDOW);
+        assert!(!history.should_alert());
+    }
+
+    #[test]
+    fn test_history_roundtrip() {
+        let storage = SealedStorage::new(crate::storage::test_storage_dir("pool_diversity"));
+        let mut history = PoolDiversityHistory::default();
+        history.push(&PoolDiversity::of(&accounts(vec![])));
+
+        history.save(&storage).unwrap();
+
+        assert_eq!(PoolDiversityHistory::load(&storage), history);
+    }
+}
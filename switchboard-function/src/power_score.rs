@@ -0,0 +1,143 @@
+use crate::*;
+use serde::Serialize;
+
+/// Power difference that halves a candidate's selection weight, and thirds
+/// it at twice the difference.
+pub const POWER_BIAS_STEP: u32 = 100;
+/// Weight of a candidate with the requester's exact power score.
+const MAX_POWER_BIAS_WEIGHT: u32 = 1_000_000;
+
+/// A spaceship's power score per component, as settled on-chain so players
+/// can see why they were paired.
+#[derive(AnchorSerialize, Serialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct PowerBreakdown {
+    pub rating: u32,
+    pub weapon: u32,
+    pub shield: u32,
+    pub engine: u32,
+    pub hull: u32,
+    /// Sum of the components, saturating.
+    pub total: u32,
+}
+
+/// Scores the spaceship's rating and loadout. Every component saturates, an
+/// absurd weight cannot wrap a strong spaceship into a weak one.
+pub fn power_breakdown(spaceship: &Spaceship, weights: &PowerWeights) -> PowerBreakdown {
+    let levels = |kind: ModuleKind| -> u32 {
+        spaceship
+            .modules
+            .iter()
+            .filter(|module| module.kind == kind)
+            .map(|module| module.level as u32)
+            .sum()
+    };
+    let rating = spaceship.rating.saturating_mul(weights.rating);
+    let weapon = levels(ModuleKind::Weapon).saturating_mul(weights.weapon);
+    let shield = levels(ModuleKind::Shield).saturating_mul(weights.shield);
+    let engine = levels(ModuleKind::Engine).saturating_mul(weights.engine);
+    let hull = levels(ModuleKind::Hull).saturating_mul(weights.hull);
+    PowerBreakdown {
+        rating,
+        weapon,
+        shield,
+        engine,
+        hull,
+        total: [rating, weapon, shield, engine, hull]
+            .iter()
+            .fold(0u32, |total, component| total.saturating_add(*component)),
+    }
+}
+
+/// Selection weight of a candidate, highest for an even match. Uneven
+/// candidates keep a chance so a queue of strong spaceships still finds
+/// the weak one a fight.
+pub fn power_bias_weight(requester_power: u32, candidate_power: u32) -> u32 {
+    (MAX_POWER_BIAS_WEIGHT / (1 + requester_power.abs_diff(candidate_power) / POWER_BIAS_STEP))
+        .max(1)
+}
+
+/// Maps `roll`, which may be any value, onto the candidates weighted by
+/// their power difference to the requester. Returns the picked index.
+pub fn pick_by_power(requester_power: u32, candidate_powers: &[u32], roll: u32) -> Option<usize> {
+    let weights: Vec<u32> = candidate_powers
+        .iter()
+        .map(|power| power_bias_weight(requester_power, *power))
+        .collect();
+    // at most OPPONENT_SLOT_COUNT weights of MAX_POWER_BIAS_WEIGHT each
+    let total: u32 = weights.iter().sum();
+    if total == 0 {
+        return None;
+    }
+    let mut target = roll % total;
+    for (index, weight) in weights.iter().enumerate() {
+        if target < *weight {
+            return Some(index);
+        }
+        target -= weight;
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_fixtures::*;
+
+    fn fitted(modules: &[(ModuleKind, u8)]) -> Spaceship {
+        Spaceship {
+            modules: modules
+                .iter()
+                .map(|(kind, level)| ShipModule {
+                    kind: *kind,
+                    level: *level,
+                })
+                .collect(),
+            ..test_spaceship(1_000)
+        }
+    }
+
+    #[test]
+    fn test_power_breakdown() {
+        let spaceship = fitted(&[
+            (ModuleKind::Weapon, 3),
+            (ModuleKind::Weapon, 2),
+            (ModuleKind::Hull, 4),
+        ]);
+
+        assert_eq!(
+            power_breakdown(&spaceship, &DEFAULT_POWER_WEIGHTS),
+            PowerBreakdown {
+                rating: 1_000,
+                weapon: 200,
+                shield: 0,
+                engine: 0,
+                hull: 120,
+                total: 1_320,
+            }
+        );
+
+        let absurd = PowerWeights {
+            rating: u32::MAX,
+            ..DEFAULT_POWER_WEIGHTS
+        };
+        assert_eq!(power_breakdown(&spaceship, &absurd).total, u32::MAX);
+    }
+
+    #[test]
+    fn test_power_bias_prefers_even_matches() {
+        assert_eq!(power_bias_weight(1_000, 1_000), MAX_POWER_BIAS_WEIGHT);
+        assert_eq!(power_bias_weight(1_000, 1_150), MAX_POWER_BIAS_WEIGHT / 2);
+        assert_eq!(power_bias_weight(1_000, 750), MAX_POWER_BIAS_WEIGHT / 3);
+        assert!(power_bias_weight(0, u32::MAX) > 0);
+
+        let powers = [3_000, 1_000, 5_000];
+        let mut picks = [0u32; 3];
+        for roll in (0..u32::MAX).step_by(65_537) {
+            picks[pick_by_power(1_000, &powers, roll).unwrap()] += 1;
+        }
+        assert!(picks[1] > picks[0] * 10);
+        assert!(picks[0] > 0 && picks[2] > 0);
+        assert_eq!(pick_by_power(1_000, &[], 7), None);
+    }
+}
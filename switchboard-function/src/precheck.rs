@@ -0,0 +1,135 @@
+use crate::*;
+use std::collections::HashSet;
+
+fn all_distinct(pubkeys: &[Pubkey]) -> bool {
+    pubkeys.iter().collect::<HashSet<_>>().len() == pubkeys.len()
+}
+
+/// Validations that need neither the chain nor the enclave, run before any
+/// RPC work so malformed requests are rejected within milliseconds.
+/// `program_allowlist` is the run's, see `load_program_allowlist`.
+pub fn precheck(
+    params: &ContainerParams,
+    program_allowlist: &[Pubkey],
+) -> std::result::Result<(), FunctionError> {
+    if params.version != PARAMS_VERSION {
+        return Err(FunctionError::UnsupportedParamsVersion);
+    }
+    if !program_allowlist.is_empty() && !program_allowlist.contains(&params.program_id) {
+        return Err(FunctionError::ProgramNotAllowed);
+    }
+
+    match params.request_type {
+        RequestType::Matchmaking => {
+            if params.faction >= FACTION_COUNT {
+                return Err(FunctionError::InvalidParams);
+            }
+            // the requester cannot be matched against itself, and a repeated
+            // candidate would skew the selection towards it. Empty slots all
+            // hold the same sentinel.
+            let mut spaceships: Vec<Pubkey> = params
+                .opponent_spaceship_pdas()
+                .into_iter()
+                .filter(|pda| *pda != Pubkey::default())
+                .collect();
+            spaceships.push(params.spaceship_pda);
+            if !params.is_bot_match() && !params.from_queue && !all_distinct(&spaceships) {
+                return Err(FunctionError::InvalidParams);
+            }
+        }
+        RequestType::Cancel => {
+            if params.faction >= FACTION_COUNT {
+                return Err(FunctionError::InvalidParams);
+            }
+        }
+        // a custom roll's handler is checked against the roll allowlist as
+        // the params are decoded
+        RequestType::LootOpen
+        | RequestType::DailySeed
+        | RequestType::RaffleDraw
+        | RequestType::CustomRoll => (),
+        RequestType::TournamentSeed => {
+            if !all_distinct(&params.participants) {
+                return Err(FunctionError::InvalidParams);
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_fixtures::*;
+
+    #[test]
+    fn test_precheck_accepts_valid_params() {
+        let params = test_params();
+
+        assert_eq!(precheck(&params, &[]), Ok(()));
+        assert_eq!(precheck(&params, &[params.program_id]), Ok(()));
+    }
+
+    #[test]
+    fn test_precheck_rejects_unlisted_program() {
+        let params = test_params();
+
+        assert_eq!(
+            precheck(&params, &[Pubkey::new_unique()]),
+            Err(FunctionError::ProgramNotAllowed)
+        );
+    }
+
+    #[test]
+    fn test_precheck_rejects_unsupported_version() {
+        let params = ContainerParams::decode(
+            format!("{},VERSION={}", test_params_string(), PARAMS_VERSION + 1).as_bytes(),
+        )
+        .unwrap();
+
+        assert_eq!(
+            precheck(&params, &[]),
+            Err(FunctionError::UnsupportedParamsVersion)
+        );
+    }
+
+    #[test]
+    fn test_precheck_rejects_invalid_matchmaking() {
+        let mut params = test_params();
+        params.faction = FACTION_COUNT;
+        assert_eq!(precheck(&params, &[]), Err(FunctionError::InvalidParams));
+
+        let mut params = test_params();
+        params.opponent_spaceship_4_pda = params.opponent_spaceship_1_pda;
+        assert_eq!(precheck(&params, &[]), Err(FunctionError::InvalidParams));
+
+        let mut params = test_params();
+        params.opponent_spaceship_2_pda = params.spaceship_pda;
+        assert_eq!(precheck(&params, &[]), Err(FunctionError::InvalidParams));
+    }
+
+    #[test]
+    fn test_precheck_accepts_bot_match() {
+        let mut params = test_params();
+        params.opponent_spaceship_1_pda = Pubkey::default();
+        params.opponent_spaceship_2_pda = Pubkey::default();
+        params.opponent_spaceship_3_pda = Pubkey::default();
+        params.opponent_spaceship_4_pda = Pubkey::default();
+        params.opponent_spaceship_5_pda = Pubkey::default();
+
+        assert_eq!(precheck(&params, &[]), Ok(()));
+    }
+
+    #[test]
+    fn test_precheck_accepts_queue_match() {
+        let mut params = test_params();
+        params.opponent_spaceship_1_pda = Pubkey::default();
+        params.opponent_spaceship_2_pda = Pubkey::default();
+        params.opponent_spaceship_3_pda = Pubkey::default();
+        params.opponent_spaceship_4_pda = Pubkey::default();
+        params.opponent_spaceship_5_pda = Pubkey::default();
+        params.from_queue = true;
+
+        assert_eq!(precheck(&params, &[]), Ok(()));
+    }
+}
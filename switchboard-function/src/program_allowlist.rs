@@ -0,0 +1,118 @@
+use crate::*;
+
+/// The programs requests may settle for: the sealed allowlist when an
+/// operator set one with `--storage allow-programs`, the baked in one
+/// otherwise, narrowed by the `PROGRAM_ALLOWLIST` setting. Empty allows every
+/// program. The one list both decoding and `precheck` check against.
+pub fn load_program_allowlist(
+    storage: &SealedStorage,
+) -> std::result::Result<Vec<Pubkey>, FunctionError> {
+    let image_allowlist = match storage.read(ArtifactKind::ProgramAllowlist) {
+        Some(data) => {
+            let entries: Vec<String> =
+                serde_json::from_slice(&data).map_err(|_| FunctionError::ProgramNotAllowed)?;
+            parse_program_allowlist(&entries.join(","))?
+        }
+        None => baked_program_allowlist()?,
+    };
+    narrow_program_allowlist(image_allowlist, setting("PROGRAM_ALLOWLIST").as_deref())
+}
+
+/// `image_allowlist` restricted to the programs of `setting`. The setting is
+/// not covered by the enclave measurement, so it may only narrow the list: a
+/// program outside it or a malformed entry fails the whole list.
+pub fn narrow_program_allowlist(
+    image_allowlist: Vec<Pubkey>,
+    setting: Option<&str>,
+) -> std::result::Result<Vec<Pubkey>, FunctionError> {
+    let narrowed = setting.map_or(Ok(vec![]), parse_program_allowlist)?;
+    if narrowed.is_empty() {
+        return Ok(image_allowlist);
+    }
+    if let Some(program_id) = narrowed
+        .iter()
+        .find(|program_id| !image_allowlist.is_empty() && !image_allowlist.contains(program_id))
+    {
+        println!(
+            "PROGRAM_ALLOWLIST names {}, which the image does not allow",
+            program_id
+        );
+        return Err(FunctionError::ProgramNotAllowed);
+    }
+    Ok(narrowed)
+}
+
+pub fn save_program_allowlist(
+    storage: &SealedStorage,
+    program_allowlist: &[Pubkey],
+) -> std::io::Result<()> {
+    let entries: Vec<String> = program_allowlist.iter().map(Pubkey::to_string).collect();
+    storage.write(
+        ArtifactKind::ProgramAllowlist,
+        &serde_json::to_vec(&entries)?,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sealed_program_allowlist_overrides_baked() {
+        let storage = SealedStorage::new(test_storage_dir("program-allowlist"));
+        assert_eq!(load_program_allowlist(&storage), baked_program_allowlist());
+
+        let staging = vec![Pubkey::new_unique(), Pubkey::new_unique()];
+        save_program_allowlist(&storage, &staging).unwrap();
+        assert_eq!(load_program_allowlist(&storage), Ok(staging));
+
+        storage
+            .write(ArtifactKind::ProgramAllowlist, b"[\"not-a-pubkey\"]")
+            .unwrap();
+        assert_eq!(
+            load_program_allowlist(&storage),
+            Err(FunctionError::ProgramNotAllowed)
+        );
+    }
+
+    #[test]
+    fn test_setting_only_narrows_the_allowlist() {
+        let (production, staging) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let image_allowlist = vec![production, staging];
+
+        assert_eq!(
+            narrow_program_allowlist(image_allowlist.clone(), None),
+            Ok(image_allowlist.clone())
+        );
+        assert_eq!(
+            narrow_program_allowlist(image_allowlist.clone(), Some(" ,")),
+            Ok(image_allowlist.clone())
+        );
+        assert_eq!(
+            narrow_program_allowlist(image_allowlist.clone(), Some(&staging.to_string())),
+            Ok(vec![staging])
+        );
+        // an image allowing every program takes the setting as is
+        assert_eq!(
+            narrow_program_allowlist(vec![], Some(&staging.to_string())),
+            Ok(vec![staging])
+        );
+
+        // malformed entries and programs the image does not allow fail closed
+        // rather than leaving an empty list that allows every program
+        for setting in [
+            "not-a-pubkey".to_string(),
+            format!("{},not-a-pubkey", staging),
+            Pubkey::new_unique().to_string(),
+        ] {
+            assert_eq!(
+                narrow_program_allowlist(image_allowlist.clone(), Some(&setting)),
+                Err(FunctionError::ProgramNotAllowed)
+            );
+        }
+        assert_eq!(
+            narrow_program_allowlist(vec![], Some("not-a-pubkey")),
+            Err(FunctionError::ProgramNotAllowed)
+        );
+    }
+}
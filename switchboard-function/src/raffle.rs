@@ -0,0 +1,93 @@
+use crate::*;
+use std::collections::HashMap;
+
+/// Draws `winners` distinct participant indices below `participants`,
+/// without replacement and from enclave entropy. It is the first `winners`
+/// steps of a Fisher-Yates shuffle of `0..participants`, with the swapped
+/// positions kept in a map so a raffle among every player of a realm costs
+/// no more than its winner count. `winners[i]` is the participant drawn
+/// `i`th, e.g. for the first prize.
+pub fn draw_raffle_winners(
+    participants: u32,
+    winners: u32,
+    rng: &dyn RandomSource,
+) -> std::result::Result<Vec<u32>, FunctionError> {
+    if winners > participants {
+        return Err(FunctionError::InvalidParams);
+    }
+    // the participant at each position moved away from its own
+    let mut swapped: HashMap<u32, u32> = HashMap::new();
+    let mut drawn = Vec::with_capacity(winners as usize);
+    for i in 0..winners {
+        let j = rng.generate(i, participants - 1)?;
+        let at_j = swapped.get(&j).copied().unwrap_or(j);
+        let at_i = swapped.get(&i).copied().unwrap_or(i);
+        swapped.insert(j, at_i);
+        drawn.push(at_j);
+    }
+    Ok(drawn)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_fixtures::*;
+    use std::collections::BTreeSet;
+
+    #[test]
+    fn test_draw_raffle_winners_swaps() {
+        // i=0 takes 3 and leaves 0 there, i=1 takes 3 again and gets 0,
+        // i=2 keeps its own participant
+        let rng = ScriptedRandomSource::new(vec![3, 3, 2]);
+
+        assert_eq!(draw_raffle_winners(4, 3, &rng).unwrap(), vec![3, 0, 2]);
+    }
+
+    #[test]
+    fn test_draw_raffle_winners_are_distinct() {
+        for (participants, winners) in [(1, 1), (2, 2), (10, 10), (50, 7), (u32::MAX, 32)] {
+            for _ in 0..200 {
+                let drawn = draw_raffle_winners(participants, winners, &OsRandomSource).unwrap();
+
+                assert_eq!(drawn.len(), winners as usize);
+                assert!(drawn.iter().all(|winner| *winner < participants));
+                assert_eq!(
+                    drawn.iter().collect::<BTreeSet<_>>().len(),
+                    winners as usize,
+                    "{:?}",
+                    drawn
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_draw_raffle_winners_is_uniform() {
+        let runs = 10_000;
+        let mut counts = [0u32; 5];
+        for _ in 0..runs {
+            for winner in draw_raffle_winners(5, 2, &OsRandomSource).unwrap() {
+                counts[winner as usize] += 1;
+            }
+        }
+
+        // each participant wins 2 in 5 draws, 4000 times, a fair draw stays
+        // well within 10%
+        for (participant, count) in counts.iter().enumerate() {
+            assert!(
+                (3_600..4_400).contains(count),
+                "participant {} won {} times",
+                participant,
+                count
+            );
+        }
+    }
+
+    #[test]
+    fn test_draw_raffle_winners_rejects_more_winners_than_participants() {
+        assert_eq!(
+            draw_raffle_winners(3, 4, &OsRandomSource),
+            Err(FunctionError::InvalidParams)
+        );
+    }
+}
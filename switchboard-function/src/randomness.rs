@@ -0,0 +1,197 @@
+use crate::*;
+use rand::RngCore;
+
+/// Where the function draws its entropy from. Production always uses
+/// Gramine, the OS source only exists for running outside an enclave.
+pub trait RandomSource: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    fn fill_bytes(&self, buf: &mut [u8]) -> std::result::Result<(), FunctionError>;
+
+    /// Whether the draws stand in for the enclave's entropy, see entropy.rs.
+    fn is_fallback(&self) -> bool {
+        false
+    }
+
+    /// A random u32 in the inclusive range `[min, max]`, bounds may be flipped.
+    fn generate(&self, min: u32, max: u32) -> std::result::Result<u32, FunctionError> {
+        Ok(self.generate_u64(min as u64, max as u64)? as u32)
+    }
+
+    /// A random u64 in the inclusive range `[min, max]`, bounds may be flipped.
+    /// Every value is equally likely, draws falling in the partial window at
+    /// the bottom of the u64 range are rejected rather than folded by the modulo.
+    fn generate_u64(&self, min: u64, max: u64) -> std::result::Result<u64, FunctionError> {
+        if min == max {
+            return Ok(min);
+        }
+        if min > max {
+            return self.generate_u64(max, min);
+        }
+
+        // We add one so its inclusive [min, max], the only window that doesn't
+        // fit in a u64 is the full range which needs no reduction
+        let window = (max - min).checked_add(1);
+        // 2^64 % window, the number of draws that would bias the result
+        let rejected = window.map(|window| window.wrapping_neg() % window);
+
+        let started = std::time::Instant::now();
+        let raw_result = loop {
+            let mut bytes: [u8; 8] = [0u8; 8];
+            self.fill_bytes(&mut bytes)?;
+            // not bytemuck::cast_slice, a stack [u8; 8] isn't guaranteed to be u64 aligned
+            let raw_result = u64::from_le_bytes(bytes);
+            if raw_result >= rejected.unwrap_or(0) {
+                break raw_result;
+            }
+        };
+        record_timing(
+            "randomness_ms",
+            started.elapsed(),
+            &[("source", self.name())],
+        );
+
+        match window {
+            Some(window) => Ok((raw_result % window) + min),
+            None => Ok(raw_result),
+        }
+    }
+}
+
+pub struct GramineRandomSource;
+
+impl RandomSource for GramineRandomSource {
+    fn name(&self) -> &'static str {
+        "gramine"
+    }
+
+    fn fill_bytes(&self, buf: &mut [u8]) -> std::result::Result<(), FunctionError> {
+        Gramine::read_rand(buf).map_err(|_| FunctionError::EntropyUnavailable)
+    }
+}
+
+/// The operating system RNG, for local development without SGX.
+pub struct OsRandomSource;
+
+impl RandomSource for OsRandomSource {
+    fn name(&self) -> &'static str {
+        "os"
+    }
+
+    fn fill_bytes(&self, buf: &mut [u8]) -> std::result::Result<(), FunctionError> {
+        rand::rngs::OsRng
+            .try_fill_bytes(buf)
+            .map_err(|_| FunctionError::EntropyUnavailable)
+    }
+}
+
+const SEEDED_DOMAIN: &[u8] = b"arena-imperium-seeded-randomness";
+
+/// Expands a fixed seed into a reproducible stream, so a replayed request
+/// draws the same values on every run. Never used to settle live requests.
+pub struct SeededRandomSource {
+    seed: [u8; 32],
+    blocks: std::sync::atomic::AtomicU64,
+}
+
+impl SeededRandomSource {
+    pub fn new(seed: [u8; 32]) -> Self {
+        Self {
+            seed,
+            blocks: std::sync::atomic::AtomicU64::new(0),
+        }
+    }
+}
+
+impl RandomSource for SeededRandomSource {
+    fn name(&self) -> &'static str {
+        "seeded"
+    }
+
+    fn fill_bytes(&self, buf: &mut [u8]) -> std::result::Result<(), FunctionError> {
+        for chunk in buf.chunks_mut(32) {
+            let block = self
+                .blocks
+                .fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+            let bytes =
+                solana_program::hash::hashv(&[SEEDED_DOMAIN, &self.seed, &block.to_le_bytes()])
+                    .to_bytes();
+            chunk.copy_from_slice(&bytes[..chunk.len()]);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_os_random_source_within_bounds() {
+        for _ in 0..100 {
+            let result = OsRandomSource.generate(10, 20).unwrap();
+            assert!((10..=20).contains(&result));
+        }
+        assert_eq!(OsRandomSource.generate(7, 7).unwrap(), 7);
+        assert!(OsRandomSource.generate(20, 10).unwrap() >= 10);
+    }
+
+    #[test]
+    fn test_full_range_does_not_overflow() {
+        OsRandomSource.generate(0, u32::MAX).unwrap();
+        assert!(GramineRandomSource.generate(1, u32::MAX).unwrap() >= 1);
+        OsRandomSource.generate_u64(0, u64::MAX).unwrap();
+        assert!(OsRandomSource.generate_u64(1, u64::MAX).unwrap() >= 1);
+    }
+
+    /// Returns the scripted raw u64 draws in order.
+    struct ScriptedBytes(std::sync::Mutex<Vec<u64>>);
+
+    impl RandomSource for ScriptedBytes {
+        fn name(&self) -> &'static str {
+            "scripted"
+        }
+
+        fn fill_bytes(&self, buf: &mut [u8]) -> std::result::Result<(), FunctionError> {
+            let value = self.0.lock().unwrap().remove(0);
+            buf.copy_from_slice(&value.to_le_bytes());
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_generate_u64_rejects_biased_draws() {
+        // 2^64 % 3 == 1, so a raw 0 would make 10 one draw more likely than
+        // 11 and 12 and is drawn again
+        let rng = ScriptedBytes(std::sync::Mutex::new(vec![0, 4]));
+        assert_eq!(rng.generate_u64(10, 12).unwrap(), 11);
+        assert!(rng.0.lock().unwrap().is_empty());
+
+        // a power of two window rejects nothing
+        let rng = ScriptedBytes(std::sync::Mutex::new(vec![0]));
+        assert_eq!(rng.generate_u64(10, 13).unwrap(), 10);
+    }
+
+    #[test]
+    fn test_seeded_random_source_is_reproducible() {
+        let draws = |seed| {
+            let rng = SeededRandomSource::new(seed);
+            let mut bytes = [0u8; 40];
+            rng.fill_bytes(&mut bytes).unwrap();
+            (rng.generate(0, 1_000).unwrap(), bytes)
+        };
+
+        assert_eq!(draws([1; 32]), draws([1; 32]));
+        assert_ne!(draws([1; 32]), draws([2; 32]));
+    }
+
+    #[test]
+    fn test_generate_u64_within_bounds() {
+        let (min, max) = (u32::MAX as u64 * 3, u32::MAX as u64 * 7);
+        for _ in 0..100 {
+            let result = OsRandomSource.generate_u64(min, max).unwrap();
+            assert!((min..=max).contains(&result));
+        }
+        assert!(OsRandomSource.generate_u64(max, min).unwrap() >= min);
+    }
+}
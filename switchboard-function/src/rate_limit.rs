@@ -0,0 +1,304 @@
+use crate::*;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+// A user flooding the queue with requests makes the function pay for every
+// settlement out of its escrow. A single request run checks the program's
+// counter on the user account, a routine run has its own window of the
+// requests it admitted.
+
+/// At most `max_requests` per user within `window_slots`, from
+/// `RATE_LIMIT=<requests>/<slots>`. Unset, requests are not limited.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RateLimit {
+    pub max_requests: u32,
+    pub window_slots: u64,
+}
+
+impl FromStr for RateLimit {
+    type Err = FunctionError;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        let (max_requests, window_slots) = s.split_once('/').ok_or(FunctionError::InvalidParams)?;
+        let rate_limit = RateLimit {
+            max_requests: max_requests
+                .parse()
+                .map_err(|_| FunctionError::InvalidParams)?,
+            window_slots: window_slots
+                .parse()
+                .map_err(|_| FunctionError::InvalidParams)?,
+        };
+        if rate_limit.max_requests == 0 || rate_limit.window_slots == 0 {
+            return Err(FunctionError::InvalidParams);
+        }
+        Ok(rate_limit)
+    }
+}
+
+impl RateLimit {
+    pub fn from_env() -> Option<Self> {
+        let value = setting("RATE_LIMIT")?;
+        match RateLimit::from_str(&value) {
+            Ok(rate_limit) => Some(rate_limit),
+            Err(_) => {
+                println!("ignoring invalid RATE_LIMIT {}", value);
+                None
+            }
+        }
+    }
+
+    /// Fails when the user account's counter, made over a window still open
+    /// at `request_slot`, is past the limit.
+    pub fn check_user_account(
+        &self,
+        user_account: &UserAccount,
+        request_slot: u64,
+    ) -> std::result::Result<(), FunctionError> {
+        let window_age = request_slot.saturating_sub(user_account.request_window_start_slot);
+        if window_age < self.window_slots && user_account.request_count > self.max_requests {
+            println!(
+                "user {} made {} requests in {} slots (max {})",
+                user_account.owner, user_account.request_count, window_age, self.max_requests
+            );
+            record_counter("rate_limited_total", &[("source", "user_account")]);
+            return Err(FunctionError::RateLimited);
+        }
+        Ok(())
+    }
+}
+
+/// Reads the requester's user account and checks its request counter.
+pub fn check_user_rate_limit<F: AccountFetcher + ?Sized>(
+    fetcher: &F,
+    params: &ContainerParams,
+    rate_limit: &RateLimit,
+    request_slot: u64,
+) -> std::result::Result<(), FunctionError> {
+    let data = fetcher
+        .fetch_multiple_account_data(&[params.user_account_pda])?
+        .pop()
+        .flatten()
+        .ok_or(FunctionError::AccountFetchFailed)?;
+    rate_limit.check_user_account(&UserAccount::decode(&data)?, request_slot)
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct AdmittedRequest {
+    pub request: String,
+    pub request_slot: u64,
+}
+
+/// The requests routine runs admitted per user, persisted in the sealed
+/// rate limits artifact.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, Eq)]
+pub struct RequestWindow {
+    pub users: BTreeMap<String, Vec<AdmittedRequest>>,
+}
+
+impl RequestWindow {
+    pub fn load(storage: &SealedStorage) -> Self {
+        storage
+            .read(ArtifactKind::RateLimits)
+            .and_then(|data| serde_json::from_slice(&data).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn save(&self, storage: &SealedStorage) -> std::io::Result<()> {
+        let data = serde_json::to_vec(self)?;
+        storage.write(ArtifactKind::RateLimits, &data)
+    }
+
+    /// Admits the request unless its user already made `max_requests` in the
+    /// window before it. A deferred request coming back is not counted twice.
+    pub fn admit(
+        &mut self,
+        rate_limit: &RateLimit,
+        user: &Pubkey,
+        request: &Pubkey,
+        request_slot: u64,
+    ) -> std::result::Result<(), FunctionError> {
+        let request = request.to_string();
+        let admitted = self.users.entry(user.to_string()).or_default();
+        if admitted.iter().any(|admitted| admitted.request == request) {
+            return Ok(());
+        }
+        admitted.retain(|admitted| {
+            request_slot.saturating_sub(admitted.request_slot) < rate_limit.window_slots
+        });
+        if admitted.len() >= rate_limit.max_requests as usize {
+            println!(
+                "user {} made {} requests in the last {} slots (max {})",
+                user,
+                admitted.len() + 1,
+                rate_limit.window_slots,
+                rate_limit.max_requests
+            );
+            record_counter("rate_limited_total", &[("source", "window")]);
+            return Err(FunctionError::RateLimited);
+        }
+        admitted.push(AdmittedRequest {
+            request,
+            request_slot,
+        });
+        Ok(())
+    }
+
+    /// Drops the users without a request in the window ending at `slot`, so
+    /// the artifact only grows with the active users.
+    pub fn prune(&mut self, rate_limit: &RateLimit, slot: u64) {
+        self.users.retain(|_, admitted| {
+            admitted.retain(|admitted| {
+                slot.saturating_sub(admitted.request_slot) < rate_limit.window_slots
+            });
+            !admitted.is_empty()
+        });
+    }
+}
+
+/// Refuses the batch's requests whose user is past the limit, persisting the
+/// window of admitted requests. Storage failures only cost us the history.
+pub fn limit_batch_requests(
+    requests: Vec<(Pubkey, std::result::Result<PendingRequest, FunctionError>)>,
+    rate_limit: &RateLimit,
+    storage: &SealedStorage,
+) -> Vec<(Pubkey, std::result::Result<PendingRequest, FunctionError>)> {
+    let mut window = RequestWindow::load(storage);
+    let requests: Vec<_> = requests
+        .into_iter()
+        .map(|(request, pending)| {
+            let pending = pending.and_then(|pending| {
+                window.admit(
+                    rate_limit,
+                    &pending.params.user,
+                    &request,
+                    pending.request_slot,
+                )?;
+                Ok(pending)
+            });
+            (request, pending)
+        })
+        .collect();
+
+    let latest_slot = requests
+        .iter()
+        .filter_map(|(_, pending)| pending.as_ref().ok())
+        .map(|pending| pending.request_slot)
+        .max();
+    if let Some(latest_slot) = latest_slot {
+        window.prune(rate_limit, latest_slot);
+    }
+    if let Err(error) = window.save(storage) {
+        println!("failed to save the rate limit window: {}", error);
+    }
+    requests
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_fixtures::*;
+
+    const LIMIT: RateLimit = RateLimit {
+        max_requests: 2,
+        window_slots: 100,
+    };
+
+    #[test]
+    fn test_rate_limit_from_str() {
+        assert_eq!(RateLimit::from_str("2/100"), Ok(LIMIT));
+        assert!(RateLimit::from_str("0/100").is_err());
+        assert!(RateLimit::from_str("2/0").is_err());
+        assert!(RateLimit::from_str("2").is_err());
+        assert!(RateLimit::from_str("a/100").is_err());
+    }
+
+    #[test]
+    fn test_check_user_rate_limit() {
+        let params = test_params();
+        let mut user_account = UserAccount {
+            bump: 255,
+            owner: params.user,
+            request_window_start_slot: 1_000,
+            request_count: 3,
+        };
+        let mut fetcher = MockFetcher::default();
+        let mut check = |user_account: &UserAccount, request_slot| {
+            fetcher.insert(
+                params.user_account_pda,
+                encode_account(UserAccount::NAME, user_account),
+            );
+            check_user_rate_limit(&fetcher, &params, &LIMIT, request_slot)
+        };
+
+        assert_eq!(check(&user_account, 1_050), Err(FunctionError::RateLimited));
+        // the counter is for a window that closed since
+        assert_eq!(check(&user_account, 1_100), Ok(()));
+        user_account.request_count = 2;
+        assert_eq!(check(&user_account, 1_050), Ok(()));
+
+        assert_eq!(
+            check_user_rate_limit(&MockFetcher::default(), &params, &LIMIT, 1_050),
+            Err(FunctionError::AccountFetchFailed)
+        );
+    }
+
+    #[test]
+    fn test_request_window_slides() {
+        let mut window = RequestWindow::default();
+        let user = Pubkey::new_unique();
+        let requests: Vec<Pubkey> = (0..4).map(|_| Pubkey::new_unique()).collect();
+
+        assert_eq!(window.admit(&LIMIT, &user, &requests[0], 1_000), Ok(()));
+        assert_eq!(window.admit(&LIMIT, &user, &requests[1], 1_010), Ok(()));
+        assert_eq!(
+            window.admit(&LIMIT, &user, &requests[2], 1_020),
+            Err(FunctionError::RateLimited)
+        );
+        // a deferred request is admitted again
+        assert_eq!(window.admit(&LIMIT, &user, &requests[1], 1_010), Ok(()));
+        // other users have their own window
+        assert_eq!(
+            window.admit(&LIMIT, &Pubkey::new_unique(), &requests[2], 1_020),
+            Ok(())
+        );
+        // the first request left the window
+        assert_eq!(window.admit(&LIMIT, &user, &requests[3], 1_100), Ok(()));
+
+        window.prune(&LIMIT, 1_200);
+        assert!(window.users.is_empty());
+    }
+
+    #[test]
+    fn test_limit_batch_requests_persists_the_window() {
+        let storage = SealedStorage::new(test_storage_dir("rate-limit"));
+        let params = test_params();
+        let pending = |request_slot| {
+            Ok(PendingRequest {
+                params: params.clone(),
+                request_slot,
+            })
+        };
+        let batch = |slots: &[u64]| {
+            slots
+                .iter()
+                .map(|slot| (Pubkey::new_unique(), pending(*slot)))
+                .collect::<Vec<_>>()
+        };
+
+        let limited = limit_batch_requests(batch(&[1_000, 1_001, 1_002]), &LIMIT, &storage);
+        let errors: Vec<_> = limited
+            .iter()
+            .map(|(_, pending)| pending.as_ref().err())
+            .collect();
+        assert_eq!(errors, [None, None, Some(&FunctionError::RateLimited)]);
+
+        // the next run remembers the user's requests
+        let limited = limit_batch_requests(batch(&[1_050]), &LIMIT, &storage);
+        assert_eq!(
+            limited[0].1.as_ref().err(),
+            Some(&FunctionError::RateLimited)
+        );
+        let limited = limit_batch_requests(batch(&[1_100]), &LIMIT, &storage);
+        assert!(limited[0].1.is_ok());
+    }
+}
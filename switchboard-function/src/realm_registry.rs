@@ -0,0 +1,326 @@
+use crate::*;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::sync::OnceLock;
+
+// One deployment serves every realm, each with its own rules. The rules of a
+// realm come from realms/registry.toml, baked into the enclave so they are
+// covered by its measurement, or failing an entry there from the realm's
+// RealmRules account. A realm with neither settles by the request's params.
+
+pub const EMBEDDED_REALM_REGISTRY: &str = include_str!("../realms/registry.toml");
+
+#[derive(Deserialize, Clone, Debug, Default, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+struct RegistryEntry {
+    roll_min: Option<u64>,
+    roll_max: Option<u64>,
+    max_execution_tier: Option<String>,
+    #[serde(default)]
+    min_opponents: u8,
+}
+
+#[derive(Deserialize, Clone, Debug, Default)]
+#[serde(deny_unknown_fields)]
+struct RegistryFile {
+    #[serde(default)]
+    realms: BTreeMap<String, RegistryEntry>,
+}
+
+/// The rules a realm's requests are settled by.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct RealmRules {
+    /// Inclusive, replacing the request's `MIN` and `MAX`.
+    pub roll_range: Option<(u64, u64)>,
+    pub max_execution_tier: Option<ExecutionTier>,
+    /// Fewest opponent spaceships a direct matchmaking request may pass.
+    pub min_opponents: u8,
+}
+
+fn roll_range(
+    roll_min: Option<u64>,
+    roll_max: Option<u64>,
+) -> std::result::Result<Option<(u64, u64)>, FunctionError> {
+    match (roll_min, roll_max) {
+        (None, None) => Ok(None),
+        (Some(min), Some(max)) if min <= max => Ok(Some((min, max))),
+        _ => Err(FunctionError::InvalidParams),
+    }
+}
+
+fn execution_tier(tier: u8) -> Option<ExecutionTier> {
+    match tier {
+        0 => Some(ExecutionTier::Fast),
+        1 => Some(ExecutionTier::Standard),
+        2 => Some(ExecutionTier::Rich),
+        _ => None,
+    }
+}
+
+impl TryFrom<&RegistryEntry> for RealmRules {
+    type Error = FunctionError;
+
+    fn try_from(entry: &RegistryEntry) -> std::result::Result<Self, Self::Error> {
+        Ok(RealmRules {
+            roll_range: roll_range(entry.roll_min, entry.roll_max)?,
+            max_execution_tier: entry
+                .max_execution_tier
+                .as_deref()
+                .map(ExecutionTier::from_str)
+                .transpose()
+                .map_err(|_| FunctionError::InvalidParams)?,
+            min_opponents: entry.min_opponents,
+        })
+    }
+}
+
+impl TryFrom<&RealmRulesAccount> for RealmRules {
+    type Error = FunctionError;
+
+    /// A malformed account is reported like one that did not decode.
+    fn try_from(account: &RealmRulesAccount) -> std::result::Result<Self, Self::Error> {
+        Ok(RealmRules {
+            roll_range: roll_range(account.roll_min, account.roll_max)
+                .map_err(|_| FunctionError::AccountDecodeFailed)?,
+            max_execution_tier: account
+                .max_execution_tier
+                .map(|tier| execution_tier(tier).ok_or(FunctionError::AccountDecodeFailed))
+                .transpose()?,
+            min_opponents: account.min_opponents,
+        })
+    }
+}
+
+impl RealmRules {
+    /// The params the request is settled with, limiting `budget` to the
+    /// realm's highest tier.
+    pub fn apply(
+        &self,
+        params: &ContainerParams,
+        budget: &mut TierBudget,
+    ) -> std::result::Result<ContainerParams, FunctionError> {
+        let mut params = params.clone();
+        if let Some((min, max)) = self.roll_range {
+            params.roll_min = min;
+            params.roll_max = max;
+        }
+        if params.request_type == RequestType::Matchmaking
+            && !params.from_queue
+            && params.opponent_mask().count_ones() < self.min_opponents as u32
+        {
+            return Err(FunctionError::InvalidParams);
+        }
+        if let Some(tier) = self.max_execution_tier {
+            budget.limit(tier);
+        }
+        Ok(params)
+    }
+}
+
+/// Realm PDA to rules, keys that are not a pubkey are rejected.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct RealmRegistry {
+    realms: BTreeMap<Pubkey, RealmRules>,
+}
+
+impl FromStr for RealmRegistry {
+    type Err = FunctionError;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        let file: RegistryFile = toml::from_str(s).map_err(|_| FunctionError::InvalidParams)?;
+        let realms = file
+            .realms
+            .iter()
+            .map(|(realm, entry)| {
+                let realm = Pubkey::from_str(realm).map_err(|_| FunctionError::InvalidParams)?;
+                Ok((realm, RealmRules::try_from(entry)?))
+            })
+            .collect::<std::result::Result<_, FunctionError>>()?;
+        Ok(RealmRegistry { realms })
+    }
+}
+
+impl RealmRegistry {
+    /// The registry baked into the enclave, checked by the tests.
+    pub fn embedded() -> &'static RealmRegistry {
+        static EMBEDDED: OnceLock<RealmRegistry> = OnceLock::new();
+        EMBEDDED.get_or_init(|| {
+            RealmRegistry::from_str(EMBEDDED_REALM_REGISTRY).expect("invalid realms/registry.toml")
+        })
+    }
+
+    pub fn get(&self, realm: &Pubkey) -> Option<&RealmRules> {
+        self.realms.get(realm)
+    }
+
+    pub fn len(&self) -> usize {
+        self.realms.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.realms.is_empty()
+    }
+
+    /// The realm's entry, else its RealmRules account, else no rules. Only
+    /// a realm without an entry costs a read.
+    pub fn resolve<F: AccountFetcher + ?Sized>(
+        &self,
+        fetcher: &F,
+        params: &ContainerParams,
+    ) -> std::result::Result<RealmRules, FunctionError> {
+        if let Some(rules) = self.get(&params.realm_pda) {
+            return Ok(*rules);
+        }
+        let rules_pda = realm_rules_pda(&params.program_id, &params.realm_pda);
+        match fetcher
+            .fetch_multiple_account_data(std::slice::from_ref(&rules_pda))?
+            .pop()
+            .flatten()
+        {
+            Some(data) => RealmRules::try_from(&RealmRulesAccount::decode(&data)?),
+            None => Ok(RealmRules::default()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_fixtures::*;
+
+    fn rules_account(params: &ContainerParams, account: &RealmRulesAccount) -> MockFetcher {
+        let mut fetcher = MockFetcher::default();
+        fetcher.insert(
+            realm_rules_pda(&params.program_id, &params.realm_pda),
+            encode_account(RealmRulesAccount::NAME, account),
+        );
+        fetcher
+    }
+
+    #[test]
+    fn test_embedded_registry_is_valid() {
+        assert!(RealmRegistry::from_str(EMBEDDED_REALM_REGISTRY).is_ok());
+        RealmRegistry::embedded();
+    }
+
+    #[test]
+    fn test_registry_from_str() {
+        let realm = Pubkey::new_unique();
+        let registry = RealmRegistry::from_str(&format!(
+            "[realms.\"{}\"]\nroll_min = 1\nroll_max = 1000\nmax_execution_tier = \"STANDARD\"\nmin_opponents = 3\n",
+            realm
+        ))
+        .unwrap();
+
+        assert_eq!(registry.len(), 1);
+        assert_eq!(
+            registry.get(&realm),
+            Some(&RealmRules {
+                roll_range: Some((1, 1_000)),
+                max_execution_tier: Some(ExecutionTier::Standard),
+                min_opponents: 3,
+            })
+        );
+        assert!(RealmRegistry::from_str("").unwrap().is_empty());
+
+        let key = format!("[realms.\"{}\"]\n", realm);
+        for invalid in [
+            "[realms.not-a-pubkey]\n".to_string(),
+            format!("{}roll_min = 1\n", key),
+            format!("{}roll_min = 2\nroll_max = 1\n", key),
+            format!("{}max_execution_tier = \"fast\"\n", key),
+            format!("{}min_opponent = 3\n", key),
+        ] {
+            assert!(RealmRegistry::from_str(&invalid).is_err(), "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn test_resolve_prefers_the_registry() {
+        let params = test_params();
+        let fetcher = rules_account(
+            &params,
+            &RealmRulesAccount {
+                realm: params.realm_pda,
+                roll_min: Some(5),
+                roll_max: Some(10),
+                max_execution_tier: Some(ExecutionTier::Fast as u8),
+                min_opponents: 2,
+                ..Default::default()
+            },
+        );
+        let on_chain = RealmRules {
+            roll_range: Some((5, 10)),
+            max_execution_tier: Some(ExecutionTier::Fast),
+            min_opponents: 2,
+        };
+
+        assert_eq!(
+            RealmRegistry::default().resolve(&fetcher, &params),
+            Ok(on_chain)
+        );
+        let embedded = RealmRules {
+            min_opponents: 1,
+            ..Default::default()
+        };
+        let registry = RealmRegistry {
+            realms: BTreeMap::from([(params.realm_pda, embedded)]),
+        };
+        assert_eq!(registry.resolve(&fetcher, &params), Ok(embedded));
+
+        // neither an entry nor an account
+        assert_eq!(
+            RealmRegistry::default().resolve(&MockFetcher::default(), &params),
+            Ok(RealmRules::default())
+        );
+    }
+
+    #[test]
+    fn test_resolve_rejects_a_malformed_account() {
+        let params = test_params();
+        for account in [
+            RealmRulesAccount {
+                roll_min: Some(1),
+                ..Default::default()
+            },
+            RealmRulesAccount {
+                max_execution_tier: Some(3),
+                ..Default::default()
+            },
+        ] {
+            assert_eq!(
+                RealmRegistry::default().resolve(&rules_account(&params, &account), &params),
+                Err(FunctionError::AccountDecodeFailed)
+            );
+        }
+    }
+
+    #[test]
+    fn test_apply_rules() {
+        let params = test_params();
+        let mut budget = test_budget(ExecutionTier::Rich);
+        let rules = RealmRules {
+            roll_range: Some((7, 9)),
+            max_execution_tier: Some(ExecutionTier::Standard),
+            min_opponents: 5,
+        };
+
+        let applied = rules.apply(&params, &mut budget).unwrap();
+        assert_eq!((applied.roll_min, applied.roll_max), (7, 9));
+        assert_eq!(budget.tier(), ExecutionTier::Standard);
+        assert_eq!(
+            RealmRules::default().apply(&params, &mut budget),
+            Ok(params.clone())
+        );
+
+        // fewer opponents than the realm asks for, bots included
+        let mut bot = params.clone();
+        bot.opponent_spaceship_5_pda = Pubkey::default();
+        assert_eq!(
+            rules.apply(&bot, &mut budget),
+            Err(FunctionError::InvalidParams)
+        );
+        bot.from_queue = true;
+        assert!(rules.apply(&bot, &mut budget).is_ok());
+    }
+}
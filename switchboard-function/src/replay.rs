@@ -0,0 +1,236 @@
+use crate::*;
+use base64::Engine;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+/// Long enough that a replay never downgrades its tier for lack of time.
+const REPLAY_DEADLINE: Duration = Duration::from_secs(3_600);
+
+/// A request as recorded for `--replay`, everything the pipeline reads
+/// while settling it.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct RecordedRequest {
+    /// Hex encoded container params.
+    pub params: String,
+    /// Hex encoded 32 byte seed the replay draws its randomness from.
+    pub seed: String,
+    pub enclave_signer: String,
+    pub function: String,
+    pub function_request: String,
+    pub payer: String,
+    /// The slot the request was published in, signed with the result.
+    #[serde(default)]
+    pub request_slot: u64,
+    /// `FAST` or `STANDARD` (default). There is no simulator to replay
+    /// against, a `RICH` run replays as standard.
+    #[serde(default)]
+    pub execution_tier: Option<String>,
+    /// Base64 account data by pubkey, accounts left out read as missing.
+    #[serde(default)]
+    pub accounts: BTreeMap<String, String>,
+}
+
+/// Serves reads from the accounts recorded with the request.
+pub struct SnapshotFetcher {
+    accounts: HashMap<Pubkey, Vec<u8>>,
+}
+
+impl AccountFetcher for SnapshotFetcher {
+    fn fetch_multiple_account_data(
+        &self,
+        pubkeys: &[Pubkey],
+    ) -> std::result::Result<Vec<Option<Vec<u8>>>, FunctionError> {
+        Ok(pubkeys
+            .iter()
+            .map(|pubkey| self.accounts.get(pubkey).cloned())
+            .collect())
+    }
+}
+
+/// A recorded request decoded and ready to run through the pipeline.
+pub struct Replay {
+    pub params: ContainerParams,
+    pub runner_accounts: RunnerAccounts,
+    pub payer: Pubkey,
+    pub tier: ExecutionTier,
+    pub rng: SeededRandomSource,
+    pub fetcher: SnapshotFetcher,
+}
+
+fn parse_pubkey(field: &str, value: &str) -> std::result::Result<Pubkey, String> {
+    Pubkey::from_str(value).map_err(|_| format!("invalid {} pubkey {}", field, value))
+}
+
+impl Replay {
+    pub fn load(path: &Path) -> std::result::Result<Self, String> {
+        let contents = std::fs::read(path)
+            .map_err(|error| format!("failed to read {}: {}", path.display(), error))?;
+        let record: RecordedRequest = serde_json::from_slice(&contents)
+            .map_err(|error| format!("invalid recorded request: {}", error))?;
+        Self::from_record(&record)
+    }
+
+    pub fn from_record(record: &RecordedRequest) -> std::result::Result<Self, String> {
+        let params = hex::decode(&record.params).map_err(|_| "params are not hex".to_string())?;
+        // the request passed the program allowlist when it was recorded
+        let params = ContainerParams::decode_for_programs(&params, &[])
+            .map_err(|error| format!("invalid params: {}", error))?;
+        let seed: [u8; 32] = hex::decode(&record.seed)
+            .ok()
+            .and_then(|seed| seed.try_into().ok())
+            .ok_or_else(|| "seed is not 32 hex encoded bytes".to_string())?;
+        let tier = match record.execution_tier.as_deref() {
+            None => ExecutionTier::Standard,
+            Some(tier) => ExecutionTier::from_str(tier)
+                .map_err(|_| format!("invalid execution tier {}", tier))?,
+        };
+
+        let mut accounts = HashMap::new();
+        for (pubkey, data) in record.accounts.iter() {
+            let data = base64::engine::general_purpose::STANDARD
+                .decode(data)
+                .map_err(|_| format!("account {} data is not base64", pubkey))?;
+            accounts.insert(parse_pubkey("account", pubkey)?, data);
+        }
+
+        Ok(Self {
+            params,
+            runner_accounts: RunnerAccounts {
+                enclave_signer: parse_pubkey("enclave_signer", &record.enclave_signer)?,
+                function: parse_pubkey("function", &record.function)?,
+                function_request: parse_pubkey("function_request", &record.function_request)?,
+                request_slot: record.request_slot,
+                tx_format: TxFormat::Legacy,
+            },
+            payer: parse_pubkey("payer", &record.payer)?,
+            tier,
+            rng: SeededRandomSource::new(seed),
+            fetcher: SnapshotFetcher { accounts },
+        })
+    }
+
+    /// Runs the full instruction building pipeline. The same replay always
+    /// builds the same instructions.
+    pub fn settle(&self) -> std::result::Result<Settlement, FunctionError> {
+        build_settlement(
+            &self.params,
+            &self.runner_accounts,
+            &self.payer,
+            &self.fetcher,
+            &self.rng,
+            None,
+            &mut TierBudget::new(Instant::now(), REPLAY_DEADLINE, self.tier),
+        )
+    }
+}
+
+/// Replays the request recorded in `path` and prints the instructions it
+/// settles with as JSON. Returns the exit code: the request's error code if
+/// it fails to settle, 2 if the recording cannot be loaded.
+pub fn run_replay(path: &Path) -> i32 {
+    let replay = match Replay::load(path) {
+        Ok(replay) => replay,
+        Err(error) => {
+            println!("{}", error);
+            return 2;
+        }
+    };
+    match replay.settle() {
+        Ok(settlement) => {
+            println!("{}", ixns_to_json(&settlement.ixs));
+            0
+        }
+        Err(error) => {
+            println!("failed to build settlement: {}", error);
+            error.code() as i32
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_fixtures::*;
+
+    fn recorded_request() -> RecordedRequest {
+        let params_string = test_params_string();
+        let params = ContainerParams::decode(params_string.as_bytes()).unwrap();
+        let realm = test_realm(vec![]);
+        let spaceships = [1_000, 1_100, 1_200, 1_300, 1_400, 1_500].map(test_spaceship);
+        let fetcher = test_fetcher(&params, &realm, spaceships);
+        let runner_accounts = test_runner_accounts();
+
+        RecordedRequest {
+            params: hex::encode(params_string),
+            seed: hex::encode([7u8; 32]),
+            enclave_signer: runner_accounts.enclave_signer.to_string(),
+            function: runner_accounts.function.to_string(),
+            function_request: runner_accounts.function_request.to_string(),
+            payer: runner_accounts.enclave_signer.to_string(),
+            request_slot: runner_accounts.request_slot,
+            execution_tier: None,
+            accounts: fetcher
+                .accounts
+                .iter()
+                .map(|(pubkey, data)| {
+                    (
+                        pubkey.to_string(),
+                        base64::engine::general_purpose::STANDARD.encode(data),
+                    )
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn test_replay_is_deterministic() {
+        let record = recorded_request();
+
+        let first = Replay::from_record(&record).unwrap().settle().unwrap();
+        let second = Replay::from_record(&record).unwrap().settle().unwrap();
+
+        assert_eq!(first.ixs, second.ixs);
+        assert_eq!(first.ixs[1].data[41], ExecutionTier::Standard as u8);
+    }
+
+    #[test]
+    fn test_replay_loads_recording() {
+        let dir = test_storage_dir("replay");
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("request.json");
+        std::fs::write(&path, serde_json::to_vec(&recorded_request()).unwrap()).unwrap();
+
+        assert_eq!(run_replay(&path), 0);
+        assert_eq!(run_replay(&dir.join("missing.json")), 2);
+    }
+
+    #[test]
+    fn test_replay_rejects_malformed_records() {
+        let mut record = recorded_request();
+        record.seed = hex::encode([7u8; 16]);
+        assert!(Replay::from_record(&record).is_err());
+
+        let mut record = recorded_request();
+        record.execution_tier = Some("BOGUS".to_string());
+        assert!(Replay::from_record(&record).is_err());
+
+        let mut record = recorded_request();
+        record
+            .accounts
+            .insert("not-a-pubkey".to_string(), String::new());
+        assert!(Replay::from_record(&record).is_err());
+    }
+
+    #[test]
+    fn test_replay_without_snapshot_fails_like_the_request() {
+        let mut record = recorded_request();
+        record.accounts.clear();
+
+        assert_eq!(
+            Replay::from_record(&record).unwrap().settle().err(),
+            Some(FunctionError::AccountFetchFailed)
+        );
+    }
+}
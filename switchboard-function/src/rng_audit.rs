@@ -0,0 +1,228 @@
+use crate::*;
+use serde::Serialize;
+
+// `--rng-audit [samples]` draws from the production `RandomSource` and runs
+// basic statistical tests on what it returns, for auditors asking for evidence
+// that the Gramine entropy path of a deployed image behaves. The frequency and
+// runs tests follow NIST SP 800-22 on the raw bytes, the chi-squared test
+// checks the ranged draws the settlements use. Passing them does not prove the
+// source is random, failing one means it is not.
+
+pub const DEFAULT_RNG_AUDIT_SAMPLES: u32 = 100_000;
+/// Fewer samples are too few for the tests to mean anything.
+pub const MIN_RNG_AUDIT_SAMPLES: u32 = 1_000;
+pub const MAX_RNG_AUDIT_SAMPLES: u32 = 10_000_000;
+/// Each ranged draw lands in one of this many buckets.
+pub const RNG_AUDIT_BUCKETS: u32 = 256;
+/// A test fails below this p-value.
+pub const RNG_AUDIT_ALPHA: f64 = 0.01;
+
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct RngTestResult {
+    pub name: &'static str,
+    pub statistic: f64,
+    pub p_value: f64,
+    pub passed: bool,
+}
+
+impl RngTestResult {
+    fn new(name: &'static str, statistic: f64, p_value: f64) -> Self {
+        Self {
+            name,
+            statistic,
+            p_value,
+            passed: p_value >= RNG_AUDIT_ALPHA,
+        }
+    }
+}
+
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct RngAuditReport {
+    pub source: &'static str,
+    /// u32 samples drawn, as raw bytes and as ranged draws each.
+    pub samples: u32,
+    pub bits: u64,
+    pub buckets: u32,
+    pub alpha: f64,
+    pub tests: Vec<RngTestResult>,
+    pub passed: bool,
+}
+
+/// The complementary error function, Abramowitz and Stegun 7.1.26, accurate
+/// to 1.5e-7 which is plenty for p-values compared against 0.01.
+fn erfc(x: f64) -> f64 {
+    if x < 0.0 {
+        return 2.0 - erfc(-x);
+    }
+    let t = 1.0 / (1.0 + 0.327_591_1 * x);
+    let poly = t
+        * (0.254_829_592
+            + t * (-0.284_496_736
+                + t * (1.421_413_741 + t * (-1.453_152_027 + t * 1.061_405_429))));
+    poly * (-x * x).exp()
+}
+
+fn ones(bytes: &[u8]) -> u64 {
+    bytes.iter().map(|byte| byte.count_ones() as u64).sum()
+}
+
+/// Whether ones and zeros are about equally frequent.
+pub fn frequency_test(bytes: &[u8]) -> RngTestResult {
+    let bits = bytes.len() as f64 * 8.0;
+    let sum = 2.0 * ones(bytes) as f64 - bits;
+    let statistic = sum.abs() / bits.sqrt();
+    RngTestResult::new(
+        "frequency",
+        statistic,
+        erfc(statistic / std::f64::consts::SQRT_2),
+    )
+}
+
+/// Whether runs of identical bits are as long as they should be, runs too
+/// short or too long mean the bits oscillate or stick.
+pub fn runs_test(bytes: &[u8]) -> RngTestResult {
+    let bits = bytes.len() as f64 * 8.0;
+    let pi = ones(bytes) as f64 / bits;
+    // the test assumes the frequency test passed
+    if (pi - 0.5).abs() >= 2.0 / bits.sqrt() {
+        return RngTestResult::new("runs", 0.0, 0.0);
+    }
+    let bit = |i: usize| bytes[i / 8] >> (i % 8) & 1;
+    let runs = 1
+        + (1..bytes.len() * 8)
+            .filter(|i| bit(*i) != bit(i - 1))
+            .count() as u64;
+    let expected = 2.0 * bits * pi * (1.0 - pi);
+    let statistic = (runs as f64 - expected).abs() / (2.0 * (2.0 * bits).sqrt() * pi * (1.0 - pi));
+    RngTestResult::new("runs", runs as f64, erfc(statistic))
+}
+
+/// Whether ranged draws fill every bucket about equally. The p-value uses
+/// the Wilson-Hilferty normal approximation of the chi-squared distribution.
+pub fn chi_squared_test(counts: &[u64]) -> RngTestResult {
+    let total: u64 = counts.iter().sum();
+    let expected = total as f64 / counts.len() as f64;
+    let statistic: f64 = counts
+        .iter()
+        .map(|count| (*count as f64 - expected).powi(2) / expected)
+        .sum();
+    let df = (counts.len() - 1) as f64;
+    let z = ((statistic / df).cbrt() - (1.0 - 2.0 / (9.0 * df))) / (2.0 / (9.0 * df)).sqrt();
+    RngTestResult::new(
+        "chi_squared",
+        statistic,
+        0.5 * erfc(z / std::f64::consts::SQRT_2),
+    )
+}
+
+pub fn rng_audit(
+    rng: &dyn RandomSource,
+    samples: u32,
+) -> std::result::Result<RngAuditReport, FunctionError> {
+    let mut bytes = vec![0u8; samples as usize * 4];
+    rng.fill_bytes(&mut bytes)?;
+
+    let mut counts = vec![0u64; RNG_AUDIT_BUCKETS as usize];
+    for _ in 0..samples {
+        counts[rng.generate(0, RNG_AUDIT_BUCKETS - 1)? as usize] += 1;
+    }
+
+    let tests = vec![
+        frequency_test(&bytes),
+        runs_test(&bytes),
+        chi_squared_test(&counts),
+    ];
+    Ok(RngAuditReport {
+        source: rng.name(),
+        samples,
+        bits: bytes.len() as u64 * 8,
+        buckets: RNG_AUDIT_BUCKETS,
+        alpha: RNG_AUDIT_ALPHA,
+        passed: tests.iter().all(|test| test.passed),
+        tests,
+    })
+}
+
+/// Prints the report as JSON and returns the exit code: 0 when every test
+/// passed, 1 when one failed and 2 when the source returned no entropy.
+pub fn run_rng_audit(rng: &dyn RandomSource, samples: u32) -> i32 {
+    match rng_audit(rng, samples) {
+        Ok(report) => {
+            println!("{}", serde_json::to_string_pretty(&report).unwrap());
+            if report.passed {
+                0
+            } else {
+                1
+            }
+        }
+        Err(error) => {
+            println!("failed to draw from {}: {}", rng.name(), error);
+            2
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Returns the same byte forever.
+    struct StuckRandomSource;
+
+    impl RandomSource for StuckRandomSource {
+        fn name(&self) -> &'static str {
+            "stuck"
+        }
+
+        fn fill_bytes(&self, buf: &mut [u8]) -> std::result::Result<(), FunctionError> {
+            buf.fill(0b1010_1010);
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_erfc() {
+        assert!((erfc(0.0) - 1.0).abs() < 1e-6);
+        assert!((erfc(1.0) - 0.157_299_207).abs() < 1e-6);
+        assert!((erfc(-1.0) - 1.842_700_793).abs() < 1e-6);
+        assert!(erfc(6.0) < 1e-15);
+    }
+
+    #[test]
+    fn test_frequency_and_runs_by_hand() {
+        // 6 ones in 16 bits, |S| = 4 over sqrt(16)
+        let frequency = frequency_test(&[0b1010_1101, 0b0000_0010]);
+        assert!((frequency.statistic - 1.0).abs() < 1e-9);
+        assert!(frequency.passed);
+
+        // least significant bit first: 0100 1101 1011 0010 has 11 runs
+        let runs = runs_test(&[0b1011_0010, 0b0100_1101]);
+        assert_eq!(runs.statistic, 11.0);
+        assert!(runs.passed);
+
+        // all ones fails the frequency test and so the runs test
+        assert!(!frequency_test(&[0xff; 64]).passed);
+        assert!(!runs_test(&[0xff; 64]).passed);
+    }
+
+    #[test]
+    fn test_seeded_source_passes() {
+        let report = rng_audit(&SeededRandomSource::new([7; 32]), 20_000).unwrap();
+
+        assert_eq!(report.source, "seeded");
+        assert_eq!(report.bits, 640_000);
+        assert_eq!(report.tests.len(), 3);
+        assert!(report.passed, "{:?}", report.tests);
+    }
+
+    #[test]
+    fn test_stuck_source_fails() {
+        let report = rng_audit(&StuckRandomSource, MIN_RNG_AUDIT_SAMPLES).unwrap();
+
+        // alternating bits have the right frequency but far too many runs
+        let passed: Vec<bool> = report.tests.iter().map(|test| test.passed).collect();
+        assert_eq!(passed, vec![true, false, false]);
+        assert!(!report.passed);
+        assert_eq!(run_rng_audit(&StuckRandomSource, MIN_RNG_AUDIT_SAMPLES), 1);
+    }
+}
@@ -0,0 +1,134 @@
+use crate::*;
+
+/// The account reads the function needs from chain. Kept behind a trait so
+/// the selection logic can be exercised against in-memory accounts.
+pub trait AccountFetcher {
+    /// Returns the data of each account, or `None` if it does not exist.
+    fn fetch_multiple_account_data(
+        &self,
+        pubkeys: &[Pubkey],
+    ) -> std::result::Result<Vec<Option<Vec<u8>>>, FunctionError>;
+
+    /// Like `fetch_multiple_account_data`, but never served from a cache, for
+    /// the rechecks racing other settlements. See accounts_cache.rs.
+    fn fetch_fresh_account_data(
+        &self,
+        pubkeys: &[Pubkey],
+    ) -> std::result::Result<Vec<Option<Vec<u8>>>, FunctionError> {
+        self.fetch_multiple_account_data(pubkeys)
+    }
+}
+
+impl AccountFetcher for solana_client::rpc_client::RpcClient {
+    fn fetch_multiple_account_data(
+        &self,
+        pubkeys: &[Pubkey],
+    ) -> std::result::Result<Vec<Option<Vec<u8>>>, FunctionError> {
+        let accounts = self.get_multiple_accounts(pubkeys).map_err(|error| {
+            println!("failed to fetch accounts {:?}: {}", pubkeys, error);
+            FunctionError::AccountFetchFailed
+        })?;
+
+        Ok(accounts
+            .into_iter()
+            .map(|account| account.map(|account| account.data))
+            .collect())
+    }
+}
+
+/// Reads from the primary endpoint, retrying a failed read on the fallback.
+/// Used when the primary is a private endpoint so an outage of its provider
+/// degrades to the rate limited public RPC instead of failing the request.
+pub struct FailoverFetcher<'a> {
+    pub primary: &'a dyn AccountFetcher,
+    pub fallback: Option<Box<dyn AccountFetcher>>,
+}
+
+impl AccountFetcher for FailoverFetcher<'_> {
+    fn fetch_multiple_account_data(
+        &self,
+        pubkeys: &[Pubkey],
+    ) -> std::result::Result<Vec<Option<Vec<u8>>>, FunctionError> {
+        match (
+            self.primary.fetch_multiple_account_data(pubkeys),
+            &self.fallback,
+        ) {
+            (Err(_), Some(fallback)) => {
+                println!("retrying account fetch on the fallback endpoint");
+                fallback.fetch_multiple_account_data(pubkeys)
+            }
+            (result, _) => result,
+        }
+    }
+}
+
+#[cfg(test)]
+pub mod mock {
+    use super::*;
+    use std::collections::HashMap;
+
+    #[derive(Default)]
+    pub struct MockFetcher {
+        pub accounts: HashMap<Pubkey, Vec<u8>>,
+    }
+
+    impl MockFetcher {
+        pub fn insert(&mut self, pubkey: Pubkey, data: Vec<u8>) {
+            self.accounts.insert(pubkey, data);
+        }
+    }
+
+    impl AccountFetcher for MockFetcher {
+        fn fetch_multiple_account_data(
+            &self,
+            pubkeys: &[Pubkey],
+        ) -> std::result::Result<Vec<Option<Vec<u8>>>, FunctionError> {
+            Ok(pubkeys
+                .iter()
+                .map(|pubkey| self.accounts.get(pubkey).cloned())
+                .collect())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::mock::MockFetcher;
+    use super::*;
+
+    struct FailingFetcher;
+
+    impl AccountFetcher for FailingFetcher {
+        fn fetch_multiple_account_data(
+            &self,
+            _pubkeys: &[Pubkey],
+        ) -> std::result::Result<Vec<Option<Vec<u8>>>, FunctionError> {
+            Err(FunctionError::AccountFetchFailed)
+        }
+    }
+
+    #[test]
+    fn test_failover_fetcher() {
+        let pubkey = Pubkey::new_unique();
+        let mut fallback = MockFetcher::default();
+        fallback.insert(pubkey, vec![1, 2, 3]);
+
+        let fetcher = FailoverFetcher {
+            primary: &FailingFetcher,
+            fallback: Some(Box::new(fallback)),
+        };
+        assert_eq!(
+            fetcher.fetch_multiple_account_data(&[pubkey]),
+            Ok(vec![Some(vec![1, 2, 3])])
+        );
+
+        let fetcher = FailoverFetcher {
+            primary: &FailingFetcher,
+            fallback: None,
+        };
+        assert_eq!(
+            fetcher.fetch_multiple_account_data(&[pubkey]),
+            Err(FunctionError::AccountFetchFailed)
+        );
+    }
+}
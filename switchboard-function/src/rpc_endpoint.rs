@@ -0,0 +1,221 @@
+use crate::*;
+use std::collections::HashMap;
+use std::sync::OnceLock;
+
+/// Placeholder for the API key in URLs of providers that take it in the path.
+pub const API_KEY_PLACEHOLDER: &str = "{RPC_API_KEY}";
+
+/// The cluster used when no private endpoint is configured, `CLUSTER` or
+/// devnet.
+pub fn default_cluster() -> Cluster {
+    setting("CLUSTER")
+        .and_then(|cluster| Cluster::from_str(&cluster).ok())
+        .unwrap_or(Cluster::Devnet)
+}
+
+/// Scheme and host only, the rest of a URL may carry credentials.
+pub fn redact_url(url: &str) -> String {
+    match reqwest::Url::parse(url) {
+        Ok(url) => format!("{}://{}", url.scheme(), url.host_str().unwrap_or_default()),
+        Err(_) => "<invalid url>".to_string(),
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EndpointSource {
+    /// `RPC_URL` and `RPC_API_KEY` revealed by the secrets server.
+    SecretsServer,
+    /// `PRIVATE_RPC_URL` and `PRIVATE_RPC_API_KEY`, passed to the enclave as
+    /// encrypted env vars.
+    SealedEnv,
+    ClusterDefault,
+}
+
+/// The RPC endpoint every account fetch and simulation goes through.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RpcEndpoint {
+    url: String,
+    pub source: EndpointSource,
+}
+
+impl RpcEndpoint {
+    pub fn cluster_default() -> Self {
+        Self {
+            url: default_cluster().url().to_string(),
+            source: EndpointSource::ClusterDefault,
+        }
+    }
+
+    /// Puts the API key in place of `API_KEY_PLACEHOLDER`, or in an `api-key`
+    /// query parameter when the URL has no placeholder.
+    pub fn new(url: &str, api_key: Option<&str>, source: EndpointSource) -> Self {
+        let url = match api_key.filter(|api_key| !api_key.is_empty()) {
+            None => url.to_string(),
+            Some(api_key) if url.contains(API_KEY_PLACEHOLDER) => {
+                url.replace(API_KEY_PLACEHOLDER, api_key)
+            }
+            Some(api_key) => {
+                let separator = if url.contains('?') { '&' } else { '?' };
+                format!("{}{}api-key={}", url, separator, api_key)
+            }
+        };
+        Self { url, source }
+    }
+
+    pub fn from_secrets(keys: &HashMap<String, String>) -> Option<Self> {
+        let url = keys.get("RPC_URL").filter(|url| !url.is_empty())?;
+        Some(Self::new(
+            url,
+            keys.get("RPC_API_KEY").map(String::as_str),
+            EndpointSource::SecretsServer,
+        ))
+    }
+
+    pub fn from_sealed_env() -> Option<Self> {
+        let url = setting("PRIVATE_RPC_URL").filter(|url| !url.is_empty())?;
+        Some(Self::new(
+            &url,
+            setting("PRIVATE_RPC_API_KEY").as_deref(),
+            EndpointSource::SealedEnv,
+        ))
+    }
+
+    /// Scheme and host only, the rest of the URL may carry the API key.
+    pub fn redacted(&self) -> String {
+        redact_url(&self.url)
+    }
+
+    pub fn client(&self) -> solana_client::rpc_client::RpcClient {
+        solana_client::rpc_client::RpcClient::new(self.url.clone())
+    }
+
+    /// A client only made once it is read from.
+    pub fn lazy_client(&self) -> LazyRpcClient {
+        LazyRpcClient {
+            url: self.url.clone(),
+            client: OnceLock::new(),
+        }
+    }
+
+    /// The endpoint failed reads are retried on, none when already public.
+    pub fn fallback(&self) -> Option<Self> {
+        match self.source {
+            EndpointSource::ClusterDefault => None,
+            _ => Some(Self::cluster_default()),
+        }
+    }
+}
+
+/// Setting up a client takes a good share of a read, so one for an endpoint
+/// a run may never read from, like the fallback, is made on the first read
+/// and reused by every read after it.
+pub struct LazyRpcClient {
+    url: String,
+    client: OnceLock<solana_client::rpc_client::RpcClient>,
+}
+
+impl LazyRpcClient {
+    pub fn get(&self) -> &solana_client::rpc_client::RpcClient {
+        self.client
+            .get_or_init(|| solana_client::rpc_client::RpcClient::new(self.url.clone()))
+    }
+
+    pub fn is_initialized(&self) -> bool {
+        self.client.get().is_some()
+    }
+}
+
+impl AccountFetcher for LazyRpcClient {
+    fn fetch_multiple_account_data(
+        &self,
+        pubkeys: &[Pubkey],
+    ) -> std::result::Result<Vec<Option<Vec<u8>>>, FunctionError> {
+        self.get().fetch_multiple_account_data(pubkeys)
+    }
+}
+
+/// Asks the secrets server at `SECRETS_SERVER_URL` for the private endpoint,
+/// then falls back to the sealed env and finally to the public cluster RPC.
+pub async fn resolve_rpc_endpoint() -> RpcEndpoint {
+    if let Some(secrets_url) = setting("SECRETS_SERVER_URL").filter(|url| !url.is_empty()) {
+        match switchboard_solana::fetch_secrets(&secrets_url).await {
+            Ok(secrets) => {
+                if let Some(endpoint) = RpcEndpoint::from_secrets(&secrets.keys) {
+                    return endpoint;
+                }
+                println!("secrets server did not reveal an RPC_URL");
+            }
+            Err(error) => println!("failed to fetch secrets: {:?}", error),
+        }
+    }
+    RpcEndpoint::from_sealed_env().unwrap_or_else(RpcEndpoint::cluster_default)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_api_key_placement() {
+        let endpoint = RpcEndpoint::new(
+            "https://rpc.example.com/{RPC_API_KEY}",
+            Some("secret"),
+            EndpointSource::SealedEnv,
+        );
+        assert_eq!(endpoint.url, "https://rpc.example.com/secret");
+
+        let endpoint = RpcEndpoint::new(
+            "https://rpc.example.com/?cluster=devnet",
+            Some("secret"),
+            EndpointSource::SealedEnv,
+        );
+        assert_eq!(
+            endpoint.url,
+            "https://rpc.example.com/?cluster=devnet&api-key=secret"
+        );
+
+        let endpoint = RpcEndpoint::new(
+            "https://rpc.example.com",
+            Some(""),
+            EndpointSource::SealedEnv,
+        );
+        assert_eq!(endpoint.url, "https://rpc.example.com");
+    }
+
+    #[test]
+    fn test_from_secrets() {
+        let mut keys = HashMap::new();
+        assert_eq!(RpcEndpoint::from_secrets(&keys), None);
+
+        keys.insert("RPC_URL".to_string(), "https://rpc.example.com".to_string());
+        keys.insert("RPC_API_KEY".to_string(), "secret".to_string());
+        let endpoint = RpcEndpoint::from_secrets(&keys).unwrap();
+
+        assert_eq!(endpoint.source, EndpointSource::SecretsServer);
+        assert_eq!(endpoint.url, "https://rpc.example.com?api-key=secret");
+        assert_eq!(endpoint.fallback(), Some(RpcEndpoint::cluster_default()));
+        assert_eq!(RpcEndpoint::cluster_default().fallback(), None);
+    }
+
+    #[test]
+    fn test_lazy_client_is_made_once() {
+        let client = RpcEndpoint::cluster_default().lazy_client();
+        assert!(!client.is_initialized());
+
+        let first: *const _ = client.get();
+        assert!(client.is_initialized());
+        assert!(std::ptr::eq(first, client.get()));
+        assert_eq!(client.get().url(), client.url);
+    }
+
+    #[test]
+    fn test_redacted_hides_api_key() {
+        let endpoint = RpcEndpoint::new(
+            "https://rpc.example.com/{RPC_API_KEY}",
+            Some("secret"),
+            EndpointSource::SealedEnv,
+        );
+
+        assert_eq!(endpoint.redacted(), "https://rpc.example.com");
+    }
+}
@@ -0,0 +1,167 @@
+use crate::*;
+
+/// The checks `--self-test` runs, in order. Each failure exits with its own
+/// code so a deployment pipeline can tell what is wrong with the image.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SelfTestCheck {
+    Entropy,
+    Rpc,
+    FunctionAccount,
+    ParamsDecoder,
+}
+
+impl SelfTestCheck {
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            SelfTestCheck::Entropy => 10,
+            SelfTestCheck::Rpc => 11,
+            SelfTestCheck::FunctionAccount => 12,
+            SelfTestCheck::ParamsDecoder => 13,
+        }
+    }
+}
+
+/// Two draws of enclave entropy must be non zero and differ.
+pub fn check_entropy(rng: &dyn RandomSource) -> std::result::Result<(), String> {
+    let mut first = [0u8; 32];
+    let mut second = [0u8; 32];
+    rng.fill_bytes(&mut first)
+        .map_err(|error| error.to_string())?;
+    rng.fill_bytes(&mut second)
+        .map_err(|error| error.to_string())?;
+    if first == [0u8; 32] || first == second {
+        return Err(format!("{} returned degenerate bytes", rng.name()));
+    }
+    Ok(())
+}
+
+pub fn check_rpc(client: &solana_client::rpc_client::RpcClient) -> std::result::Result<(), String> {
+    client
+        .get_version()
+        .map(|version| println!("  rpc runs solana {}", version.solana_core))
+        .map_err(|error| error.to_string())
+}
+
+/// The function account in `FUNCTION_KEY` must exist and be a Switchboard
+/// function.
+pub fn check_function_account<F: AccountFetcher + ?Sized>(
+    fetcher: &F,
+    function: Option<Pubkey>,
+) -> std::result::Result<(), String> {
+    let function = function.ok_or("FUNCTION_KEY is not set")?;
+    let data = fetcher
+        .fetch_multiple_account_data(&[function])
+        .map_err(|error| error.to_string())?
+        .pop()
+        .flatten()
+        .ok_or(format!("function account {} does not exist", function))?;
+    if data.len() < 8 || data[..8] != FunctionAccountData::discriminator() {
+        return Err(format!(
+            "{} is not a switchboard function account",
+            function
+        ));
+    }
+    Ok(())
+}
+
+/// Params a matchmaking request could send, with distinct placeholder keys.
+pub fn self_test_params_blob() -> String {
+    let key = |i: u8| Pubkey::new_from_array([i; 32]).to_string();
+    format!(
+        "PID={},USER={},REALM_PDA={},USER_ACCOUNT_PDA={},SPACESHIP_PDA={},FACTION=1,OS_1_PDA={},OS_2_PDA={},OS_3_PDA={},OS_4_PDA={},OS_5_PDA={}",
+        key(1), key(2), key(3), key(4), key(5), key(6), key(7), key(8), key(9), key(10),
+    )
+}
+
+pub fn check_params_decoder() -> std::result::Result<(), String> {
+    let params = ContainerParams::decode(self_test_params_blob().as_bytes())
+        .map_err(|error| format!("fixture failed to decode: {}", error))?;
+    precheck(&params, &[]).map_err(|error| format!("fixture failed the precheck: {}", error))?;
+    if ContainerParams::decode(b"PID=not-a-pubkey").is_ok() {
+        return Err("decoder accepted an invalid pubkey".to_string());
+    }
+    Ok(())
+}
+
+/// Runs every check and returns the exit code of the first failure, or 0.
+pub async fn run_self_test() -> i32 {
+    let endpoint = resolve_rpc_endpoint().await;
+    let client = endpoint.client();
+    let function = std::env::var("FUNCTION_KEY")
+        .ok()
+        .and_then(|key| Pubkey::from_str(&key).ok());
+
+    let checks: [(SelfTestCheck, std::result::Result<(), String>); 4] = [
+        (SelfTestCheck::Entropy, check_entropy(&GramineRandomSource)),
+        (SelfTestCheck::Rpc, check_rpc(&client)),
+        (
+            SelfTestCheck::FunctionAccount,
+            check_function_account(&client, function),
+        ),
+        (SelfTestCheck::ParamsDecoder, check_params_decoder()),
+    ];
+
+    let mut exit_code = 0;
+    for (check, result) in checks.iter() {
+        match result {
+            Ok(()) => println!("{:?}: ok", check),
+            Err(error) => {
+                println!("{:?}: FAILED ({})", check, error);
+                if exit_code == 0 {
+                    exit_code = check.exit_code();
+                }
+            }
+        }
+    }
+    exit_code
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_fixtures::*;
+
+    #[test]
+    fn test_check_entropy() {
+        assert_eq!(check_entropy(&OsRandomSource), Ok(()));
+    }
+
+    #[test]
+    fn test_check_params_decoder() {
+        assert_eq!(check_params_decoder(), Ok(()));
+    }
+
+    #[test]
+    fn test_check_function_account() {
+        let function = Pubkey::new_unique();
+        let mut fetcher = MockFetcher::default();
+
+        assert!(check_function_account(&fetcher, None).is_err());
+        assert!(check_function_account(&fetcher, Some(function)).is_err());
+
+        fetcher.insert(function, vec![0; 64]);
+        assert!(check_function_account(&fetcher, Some(function)).is_err());
+
+        let mut data = FunctionAccountData::discriminator().to_vec();
+        data.extend_from_slice(&[0; 64]);
+        fetcher.insert(function, data);
+        assert_eq!(check_function_account(&fetcher, Some(function)), Ok(()));
+    }
+
+    #[test]
+    fn test_exit_codes_are_distinct() {
+        let codes = [
+            SelfTestCheck::Entropy,
+            SelfTestCheck::Rpc,
+            SelfTestCheck::FunctionAccount,
+            SelfTestCheck::ParamsDecoder,
+        ]
+        .map(|check| check.exit_code());
+
+        for (i, code) in codes.iter().enumerate() {
+            // 1 and 2 are taken by storage verification and usage errors
+            assert!(*code > 2);
+            assert!(!codes[i + 1..].contains(code));
+        }
+    }
+}
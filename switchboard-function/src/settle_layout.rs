@@ -0,0 +1,263 @@
+use crate::*;
+
+// Where a matchmaking settlement keeps its fields, shared with the params
+// builder so anyone can read a settled match back from chain without the
+// runtime, see src/bin/verify.rs. The function's tests check it against the
+// instructions it builds.
+
+/// Version of the settle args layout, bumped whenever a field is added.
+/// Version 1 was the unversioned `random_result, faction` layout, version 2
+/// had no execution tier, version 3 a u32 random result, version 4 no
+/// power scores, version 5 no result attestation, version 6 no opponent
+/// mask, version 7 no faction bias, version 8 no experiment variant,
+/// version 9 no entropy fallback flag and version 10 no attestation signer.
+pub const ARGS_VERSION: u8 = 11;
+
+/// Discriminator, header and the matchmaking settle args, without an
+/// attestation signer.
+pub const MATCHMAKING_SETTLE_DATA_LEN: usize = 165;
+
+/// Positions among the `arena_matchmaking_settle` accounts, the opponent
+/// slots follow the request.
+pub const SETTLE_ENCLAVE_SIGNER_INDEX: usize = 0;
+pub const SETTLE_USER_INDEX: usize = 1;
+pub const SETTLE_REALM_INDEX: usize = 2;
+pub const SETTLE_SPACESHIP_INDEX: usize = 4;
+pub const SETTLE_REQUEST_INDEX: usize = 6;
+pub const SETTLE_FIRST_OPPONENT_INDEX: usize = 7;
+
+/// A matchmaking settlement read back from its instruction.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SettledMatch {
+    /// The instruction's signer, the run's enclave key unless the runner
+    /// signed a routine run's batch.
+    pub enclave_signer: Pubkey,
+    pub user: Pubkey,
+    pub realm: Pubkey,
+    pub spaceship: Pubkey,
+    pub request: Pubkey,
+    /// The chosen opponent's spaceship.
+    pub opponent: Pubkey,
+    pub execution_tier: u8,
+    pub entropy_fallback: bool,
+    pub random_result: u64,
+    pub faction: u8,
+    pub sub_pool_id: u8,
+    pub opponent_index: u8,
+    pub requester_power: u32,
+    /// The total of the opponent's power breakdown.
+    pub opponent_power: u32,
+    /// `random_result` before the faction bias curve.
+    pub raw_result: u64,
+    pub bias_strength_bps: u16,
+    pub faction_win_rate_bps: u16,
+    pub experiment_id: u32,
+    pub variant: u8,
+    pub attestation: [u8; ATTESTATION_LEN],
+    /// The run's enclave key, whose SGX quote is posted with the outcome.
+    /// The enclave signer unless the runner signed the settlement.
+    pub attestation_signer: Pubkey,
+}
+
+impl SettledMatch {
+    /// `None` unless `data` is an `arena_matchmaking_settle` of the current
+    /// `ARGS_VERSION` and `accounts`, the instruction's in order, hold the
+    /// chosen opponent.
+    pub fn decode(data: &[u8], accounts: &[Pubkey]) -> Option<Self> {
+        if data.len() < MATCHMAKING_SETTLE_DATA_LEN
+            || data[..8] != MATCHMAKING_SETTLE_DISCRIMINATOR
+            || data[8] != ARGS_VERSION
+        {
+            return None;
+        }
+        let enclave_signer = *accounts.get(SETTLE_ENCLAVE_SIGNER_INDEX)?;
+        let attestation_signer = match (data[164], &data[165..]) {
+            (0, []) => enclave_signer,
+            (1, signer) => Pubkey::new_from_array(signer.try_into().ok()?),
+            _ => return None,
+        };
+        let u16_at =
+            |offset: usize| u16::from_le_bytes(data[offset..offset + 2].try_into().unwrap());
+        let u32_at =
+            |offset: usize| u32::from_le_bytes(data[offset..offset + 4].try_into().unwrap());
+        let u64_at =
+            |offset: usize| u64::from_le_bytes(data[offset..offset + 8].try_into().unwrap());
+        let opponent_index = data[53];
+        Some(SettledMatch {
+            enclave_signer,
+            user: *accounts.get(SETTLE_USER_INDEX)?,
+            realm: *accounts.get(SETTLE_REALM_INDEX)?,
+            spaceship: *accounts.get(SETTLE_SPACESHIP_INDEX)?,
+            request: *accounts.get(SETTLE_REQUEST_INDEX)?,
+            opponent: *accounts.get(SETTLE_FIRST_OPPONENT_INDEX + opponent_index as usize)?,
+            execution_tier: data[41],
+            entropy_fallback: data[42] != 0,
+            random_result: u64_at(43),
+            faction: data[51],
+            sub_pool_id: data[52],
+            opponent_index,
+            requester_power: u32_at(55),
+            opponent_power: u32_at(79),
+            raw_result: u64_at(83),
+            bias_strength_bps: u16_at(91),
+            faction_win_rate_bps: u16_at(93),
+            experiment_id: u32_at(95),
+            variant: data[99],
+            attestation: data[100..164].try_into().unwrap(),
+            attestation_signer,
+        })
+    }
+
+    /// Whether the attestation signer attested this result, `request_slot`
+    /// being the slot the request was published in.
+    pub fn verify_attestation(&self, request_slot: u64) -> bool {
+        verify_result_attestation(
+            &self.attestation,
+            &self.attestation_signer,
+            &self.request,
+            self.random_result,
+            request_slot,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_fixtures::*;
+
+    #[test]
+    fn test_settled_match_reads_back_a_settlement() {
+        let params = test_params();
+        let fetcher = test_fetcher(&params, &test_realm(vec![]), [0; 6].map(test_spaceship));
+        let runner_accounts = test_runner_accounts();
+        let settlement = build_settlement(
+            &params,
+            &runner_accounts,
+            &runner_accounts.enclave_signer,
+            &fetcher,
+            &OsRandomSource,
+            None,
+            &mut test_budget(ExecutionTier::Standard),
+        )
+        .unwrap();
+        let settle_ixn = &settlement.ixs[1];
+        let accounts: Vec<Pubkey> = settle_ixn
+            .accounts
+            .iter()
+            .map(|account| account.pubkey)
+            .collect();
+
+        let settled = SettledMatch::decode(&settle_ixn.data, &accounts).unwrap();
+
+        assert_eq!(settled.enclave_signer, runner_accounts.enclave_signer);
+        assert_eq!(settled.request, runner_accounts.function_request);
+        assert_eq!(
+            (settled.user, settled.realm),
+            (params.user, params.realm_pda)
+        );
+        assert_eq!(settled.spaceship, params.spaceship_pda);
+        assert_eq!(
+            settled.opponent,
+            params.opponent_spaceship_pdas()[settled.opponent_index as usize]
+        );
+        assert_eq!(settled.faction, params.faction);
+        assert_eq!(settled.execution_tier, ExecutionTier::Standard as u8);
+        assert!(!settled.entropy_fallback);
+        assert!((params.roll_min..=params.roll_max).contains(&settled.random_result));
+        assert_eq!(settled.raw_result, settled.random_result);
+        assert_eq!(settled.attestation[..], settle_ixn.data[100..164]);
+
+        // signed with the run's enclave key, which attested the result
+        assert_eq!(
+            settled.attestation_signer,
+            run_enclave_key().unwrap().pubkey()
+        );
+        assert_eq!(settled.attestation_signer, settled.enclave_signer);
+        let request_slot = runner_accounts.request_slot;
+        assert!(settled.verify_attestation(request_slot));
+        assert!(!settled.verify_attestation(request_slot + 1));
+
+        // a batch the runner signs carries the run's enclave key in the args
+        let runner_signed = RunnerAccounts {
+            enclave_signer: Pubkey::new_unique(),
+            ..runner_accounts
+        };
+        let settlement = build_settlement(
+            &params,
+            &runner_signed,
+            &runner_signed.enclave_signer,
+            &fetcher,
+            &OsRandomSource,
+            None,
+            &mut test_budget(ExecutionTier::Standard),
+        )
+        .unwrap();
+        let settle_ixn = settlement.ixs.last().unwrap();
+        let accounts: Vec<Pubkey> = settle_ixn
+            .accounts
+            .iter()
+            .map(|account| account.pubkey)
+            .collect();
+
+        let settled = SettledMatch::decode(&settle_ixn.data, &accounts).unwrap();
+
+        assert_eq!(settle_ixn.data.len(), MATCHMAKING_SETTLE_DATA_LEN + 32);
+        assert_eq!(settled.enclave_signer, runner_signed.enclave_signer);
+        assert_eq!(
+            settled.attestation_signer,
+            run_enclave_key().unwrap().pubkey()
+        );
+        assert!(settled.verify_attestation(request_slot));
+    }
+
+    #[test]
+    fn test_settled_match_rejects_other_instructions() {
+        let accounts: Vec<Pubkey> = (0..12).map(|_| Pubkey::new_unique()).collect();
+        let mut data = vec![0u8; MATCHMAKING_SETTLE_DATA_LEN];
+        data[..8].copy_from_slice(&MATCHMAKING_SETTLE_DISCRIMINATOR);
+        data[8] = ARGS_VERSION;
+        assert!(SettledMatch::decode(&data, &accounts).is_some());
+
+        // the opponent slot is past the accounts
+        data[53] = 5;
+        assert_eq!(SettledMatch::decode(&data, &accounts), None);
+        data[53] = 0;
+        assert_eq!(SettledMatch::decode(&data, &accounts[..7]), None);
+
+        let mut older = data.clone();
+        older[8] = ARGS_VERSION - 1;
+        assert_eq!(SettledMatch::decode(&older, &accounts), None);
+        let mut other = data.clone();
+        other[..8].copy_from_slice(&get_ixn_discriminator("arena_matchmaking_cancel_settle"));
+        assert_eq!(SettledMatch::decode(&other, &accounts), None);
+        assert_eq!(SettledMatch::decode(&data[..164], &accounts), None);
+        // a set attestation signer takes the whole key
+        data[164] = 1;
+        assert_eq!(SettledMatch::decode(&data, &accounts), None);
+        data.extend_from_slice(&[7; 32]);
+        assert_eq!(
+            SettledMatch::decode(&data, &accounts)
+                .unwrap()
+                .attestation_signer,
+            Pubkey::new_from_array([7; 32])
+        );
+        data.push(0);
+        assert_eq!(SettledMatch::decode(&data, &accounts), None);
+    }
+
+    #[test]
+    fn test_settle_accounts_match_the_schema() {
+        let schema = accounts_schema(SettleIxn::ArenaMatchmakingSettle, 1).unwrap();
+        for (index, source) in [
+            (SETTLE_ENCLAVE_SIGNER_INDEX, AccountSource::EnclaveSigner),
+            (SETTLE_USER_INDEX, AccountSource::User),
+            (SETTLE_REALM_INDEX, AccountSource::Realm),
+            (SETTLE_SPACESHIP_INDEX, AccountSource::Spaceship),
+            (SETTLE_REQUEST_INDEX, AccountSource::FunctionRequest),
+            (SETTLE_FIRST_OPPONENT_INDEX, AccountSource::Opponent(0)),
+        ] {
+            assert_eq!(schema[index].source, source);
+        }
+    }
+}
@@ -0,0 +1,127 @@
+use crate::*;
+
+// `SHADOW_SELECTION=1` runs the treatment's selection of bucketing.rs next to
+// whichever selection the request settles with, on the same candidates and
+// roll, and records whether they picked the same opponent. Only the current
+// selection is ever emitted, and the shadow never draws randomness, so the
+// settlement is the same with the flag on or off.
+
+pub fn shadow_selection_enabled() -> bool {
+    setting("SHADOW_SELECTION").is_some_and(|v| v == "1")
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ShadowComparison {
+    pub current_slot: u8,
+    /// `None` when the candidate found no eligible opponent.
+    pub candidate_slot: Option<u8>,
+}
+
+impl ShadowComparison {
+    pub fn diverged(&self) -> bool {
+        self.candidate_slot != Some(self.current_slot)
+    }
+}
+
+/// Both selections for the first pick with `roll`, before any opponent is
+/// re-read. `None` when the current selection fails, the request then fails
+/// the same way and there is nothing to compare.
+pub fn compare_shadow_selection(
+    accounts: &MatchmakingAccounts,
+    roll: u32,
+    params: &ContainerParams,
+) -> Option<ShadowComparison> {
+    let select = |variant| {
+        select_opponent(
+            accounts,
+            roll,
+            params.exclude_same_faction,
+            &[],
+            variant_power_weights(variant, params.power_weights.as_ref()),
+        )
+    };
+    let current = select(experiment_variant(params)).ok()?;
+    let candidate = select(Variant::Treatment).ok();
+    Some(ShadowComparison {
+        current_slot: current.opponent_slot,
+        candidate_slot: candidate.map(|candidate| candidate.opponent_slot),
+    })
+}
+
+/// Logs and counts the comparison, the divergence rate is the share of
+/// `shadow_selections_total` tagged `diverged:true`.
+pub fn record_shadow_selection(comparison: &ShadowComparison) {
+    let diverged = comparison.diverged();
+    if diverged {
+        println!(
+            "shadow selection diverged: current picked slot {}, candidate {:?}",
+            comparison.current_slot, comparison.candidate_slot
+        );
+    }
+    record_counter(
+        "shadow_selections_total",
+        &[("diverged", if diverged { "true" } else { "false" })],
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_fixtures::*;
+
+    /// Rolls spread over the whole u32 range.
+    fn rolls() -> impl Iterator<Item = u32> {
+        (0..64u32).map(|i| i.wrapping_mul(0x9e37_79b9))
+    }
+
+    fn accounts(ratings: [u32; 6]) -> (ContainerParams, MatchmakingAccounts) {
+        let params = test_params();
+        let fetcher = test_fetcher(&params, &test_realm(vec![]), ratings.map(test_spaceship));
+        let accounts = MatchmakingAccounts::load(&fetcher, &params).unwrap();
+        (params, accounts)
+    }
+
+    #[test]
+    fn test_shadow_selection_diverges_from_uniform() {
+        // the requester at 1000, one candidate right next to it
+        let (params, accounts) = accounts([1_000, 10, 20, 995, 30, 40]);
+
+        let comparisons: Vec<ShadowComparison> = rolls()
+            .filter_map(|roll| compare_shadow_selection(&accounts, roll, &params))
+            .collect();
+
+        assert_eq!(comparisons.len(), 64);
+        // uniform picks every slot, the candidate favours slot 2
+        assert!(comparisons.iter().any(ShadowComparison::diverged));
+        assert!(comparisons
+            .iter()
+            .all(|comparison| comparison.candidate_slot.is_some()));
+        let favoured = comparisons
+            .iter()
+            .filter(|comparison| comparison.candidate_slot == Some(2))
+            .count();
+        assert!(favoured > 64 / 5, "{}", favoured);
+    }
+
+    #[test]
+    fn test_shadow_selection_matches_the_treatment() {
+        let (mut params, accounts) = accounts([1_000, 10, 20, 995, 30, 40]);
+        params.experiment_id = 1;
+        params.experiment_share_bps = 10_000;
+
+        for roll in rolls() {
+            let comparison = compare_shadow_selection(&accounts, roll, &params).unwrap();
+            assert!(!comparison.diverged(), "{:?}", comparison);
+        }
+    }
+
+    #[test]
+    fn test_shadow_selection_without_eligible_opponents() {
+        let (params, mut accounts) = accounts([0; 6]);
+        for candidate in &mut accounts.candidates {
+            candidate.spaceship.current_match = Some(Pubkey::new_unique());
+        }
+
+        assert_eq!(compare_shadow_selection(&accounts, 0, &params), None);
+    }
+}
@@ -0,0 +1,113 @@
+use crate::*;
+use solana_client::rpc_config::RpcSimulateTransactionConfig;
+
+/// Dry runs the settlement against the cluster before it is emitted.
+pub trait TransactionSimulator: Sync {
+    fn simulate(
+        &self,
+        ixs: &[Instruction],
+        payer: &Pubkey,
+    ) -> std::result::Result<(), FunctionError>;
+}
+
+pub struct RpcSimulator<'a> {
+    pub client: &'a solana_client::rpc_client::RpcClient,
+    /// Instructions the runner adds in front of ours, without them the settle
+    /// handler would reject the enclave signer as unverified.
+    pub prefix_ixs: Vec<Instruction>,
+}
+
+impl TransactionSimulator for RpcSimulator<'_> {
+    fn simulate(
+        &self,
+        ixs: &[Instruction],
+        payer: &Pubkey,
+    ) -> std::result::Result<(), FunctionError> {
+        let mut all_ixs = self.prefix_ixs.clone();
+        all_ixs.extend_from_slice(ixs);
+        let tx =
+            solana_sdk::transaction::Transaction::new_unsigned(Message::new(&all_ixs, Some(payer)));
+
+        let result = self
+            .client
+            .simulate_transaction_with_config(
+                &tx,
+                RpcSimulateTransactionConfig {
+                    sig_verify: false,
+                    replace_recent_blockhash: true,
+                    ..Default::default()
+                },
+            )
+            .map_err(|error| {
+                println!("failed to simulate settlement: {}", error);
+                FunctionError::SimulationFailed
+            })?;
+
+        match result.value.err {
+            None => Ok(()),
+            Some(error) => {
+                println!("settlement simulation failed: {}", error);
+                for log in result.value.logs.unwrap_or_default() {
+                    println!("  {}", log);
+                }
+                Err(FunctionError::SimulationFailed)
+            }
+        }
+    }
+}
+
+/// Rebuilds the function_request_verify instruction the runner will prepend,
+/// for simulation only. Returns `None` when the oracle did not pass the
+/// verifier and queue details, rather than spending RPC calls to find them.
+pub fn simulation_verify_ixn(
+    runner: &FunctionRunner,
+    enclave_signer: Pubkey,
+) -> Option<Instruction> {
+    // the quote's measurement is only available inside the enclave, any
+    // allowed measurement behaves the same in a simulation
+    let mr_enclave = *runner.function_data.as_ref()?.mr_enclaves.first()?;
+    function_request_verify_ixn(
+        runner,
+        enclave_signer,
+        mr_enclave,
+        runner.verifier_enclave_signer?,
+        runner.queue_authority?,
+    )
+}
+
+/// The function_request_verify instruction of a successful run signed by
+/// `enclave_signer`, like the runner builds it. `None` without the request
+/// and function data.
+pub fn function_request_verify_ixn(
+    runner: &FunctionRunner,
+    enclave_signer: Pubkey,
+    mr_enclave: [u8; 32],
+    verifier_enclave_signer: Pubkey,
+    queue_authority: Pubkey,
+) -> Option<Instruction> {
+    let request_data = runner.function_request_data.as_ref()?;
+    let function_data = runner.function_data.as_ref()?;
+
+    FunctionRequestVerify::build_ix(
+        &FunctionRequestVerifyAccounts {
+            request: runner.function_request_key?,
+            request_enclave_signer: enclave_signer,
+            function: runner.function,
+            function_escrow_token_wallet: Some(function_data.escrow_token_wallet),
+            verifier: runner.verifier,
+            verifier_enclave_signer,
+            reward_receiver: runner.reward_receiver,
+            attestation_queue: function_data.attestation_queue,
+            queue_authority,
+        },
+        &FunctionRequestVerifyParams {
+            observed_time: unix_timestamp(),
+            error_code: 0,
+            mr_enclave,
+            request_slot: request_data.active_request.request_slot,
+            container_params_hash: solana_program::hash::hash(&request_data.container_params)
+                .to_bytes(),
+        },
+    )
+    .ok()
+}
@@ -0,0 +1,230 @@
+use crate::*;
+
+/// Budget for our own instructions once serialized as a message. The runner
+/// prepends its verify instruction and signatures, which need the rest of the
+/// 1232 byte transaction limit.
+pub const MAX_IXNS_MESSAGE_SIZE: usize = 700;
+
+/// An instruction queued for emission, and whether the settlement is still
+/// valid without it.
+#[derive(Clone, Debug)]
+pub struct PlannedIxn {
+    pub ixn: Instruction,
+    pub optional: bool,
+}
+
+impl PlannedIxn {
+    pub fn required(ixn: Instruction) -> Self {
+        Self {
+            ixn,
+            optional: false,
+        }
+    }
+
+    pub fn optional(ixn: Instruction) -> Self {
+        Self {
+            ixn,
+            optional: true,
+        }
+    }
+}
+
+pub fn message_size(ixs: &[Instruction], payer: &Pubkey) -> usize {
+    Message::new(ixs, Some(payer)).serialize().len()
+}
+
+/// Drops optional instructions, last first, until the message fits in
+/// `max_size` once encoded. Errors if the required instructions alone are too
+/// large.
+pub fn fit_ixns(
+    mut planned: Vec<PlannedIxn>,
+    payer: &Pubkey,
+    encoding: &MessageEncoding,
+    max_size: usize,
+) -> std::result::Result<Vec<Instruction>, FunctionError> {
+    loop {
+        let ixs: Vec<Instruction> = planned.iter().map(|p| p.ixn.clone()).collect();
+        let size = encoding.message_size(&ixs, payer)?;
+        if size <= max_size {
+            return Ok(ixs);
+        }
+
+        match planned.iter().rposition(|p| p.optional) {
+            Some(index) => {
+                println!(
+                    "message is {} bytes (max {}), dropping optional ixn for program {}",
+                    size, max_size, planned[index].ixn.program_id
+                );
+                planned.remove(index);
+            }
+            None => {
+                println!("message is {} bytes (max {})", size, max_size);
+                return Err(FunctionError::TransactionTooLarge);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use solana_program::address_lookup_table_account::AddressLookupTableAccount;
+
+    fn ixn_with_data_len(program_id: Pubkey, len: usize) -> Instruction {
+        Instruction {
+            program_id,
+            data: vec![0u8; len],
+            accounts: vec![AccountMeta::new(Pubkey::new_unique(), false)],
+        }
+    }
+
+    fn compute_budget_ixn() -> Instruction {
+        Instruction::new_with_borsh(
+            solana_sdk::compute_budget::id(),
+            &solana_sdk::compute_budget::ComputeBudgetInstruction::SetComputeUnitLimit(1_200_000),
+            vec![],
+        )
+    }
+
+    #[test]
+    fn test_fit_ixns_at_exact_limit() {
+        let payer = Pubkey::new_unique();
+        let program_id = Pubkey::new_unique();
+        // data length is encoded as a compact-u16, keep it in the 2 byte range
+        let ixn = ixn_with_data_len(program_id, 200);
+        let limit = message_size(std::slice::from_ref(&ixn), &payer);
+
+        let ixs = fit_ixns(
+            vec![PlannedIxn::required(ixn.clone())],
+            &payer,
+            &MessageEncoding::legacy(),
+            limit,
+        )
+        .unwrap();
+        assert_eq!(ixs.len(), 1);
+
+        assert_eq!(
+            fit_ixns(
+                vec![PlannedIxn::required(ixn)],
+                &payer,
+                &MessageEncoding::legacy(),
+                limit - 1
+            )
+            .unwrap_err(),
+            FunctionError::TransactionTooLarge
+        );
+    }
+
+    #[test]
+    fn test_fit_ixns_drops_optional_one_byte_over() {
+        let payer = Pubkey::new_unique();
+        let settle_ixn = ixn_with_data_len(Pubkey::new_unique(), 200);
+        let limit = message_size(&[compute_budget_ixn(), settle_ixn.clone()], &payer);
+
+        let planned = vec![
+            PlannedIxn::optional(compute_budget_ixn()),
+            PlannedIxn::required(settle_ixn.clone()),
+        ];
+
+        assert_eq!(
+            fit_ixns(planned.clone(), &payer, &MessageEncoding::legacy(), limit)
+                .unwrap()
+                .len(),
+            2
+        );
+
+        let ixs = fit_ixns(planned, &payer, &MessageEncoding::legacy(), limit - 1).unwrap();
+        assert_eq!(ixs.len(), 1);
+        assert_eq!(ixs[0], settle_ixn);
+    }
+
+    #[test]
+    fn test_fit_ixns_drops_last_optional_first() {
+        let payer = Pubkey::new_unique();
+        let first = ixn_with_data_len(Pubkey::new_unique(), 10);
+        let second = ixn_with_data_len(Pubkey::new_unique(), 10);
+        let settle_ixn = ixn_with_data_len(Pubkey::new_unique(), 10);
+        let limit = message_size(&[first.clone(), settle_ixn.clone()], &payer);
+
+        let ixs = fit_ixns(
+            vec![
+                PlannedIxn::optional(first.clone()),
+                PlannedIxn::optional(second),
+                PlannedIxn::required(settle_ixn.clone()),
+            ],
+            &payer,
+            &MessageEncoding::legacy(),
+            limit,
+        )
+        .unwrap();
+
+        assert_eq!(ixs, vec![first, settle_ixn]);
+    }
+
+    #[test]
+    fn test_current_matchmaking_ixns_fit() {
+        let payer = Pubkey::new_unique();
+        let settle_ixn = Instruction {
+            program_id: Pubkey::new_unique(),
+            data: vec![0u8; 15],
+            accounts: (0..12)
+                .map(|_| AccountMeta::new(Pubkey::new_unique(), false))
+                .collect(),
+        };
+
+        let ixs = fit_ixns(
+            vec![
+                PlannedIxn::optional(compute_budget_ixn()),
+                PlannedIxn::required(settle_ixn),
+            ],
+            &payer,
+            &MessageEncoding::legacy(),
+            MAX_IXNS_MESSAGE_SIZE,
+        )
+        .unwrap();
+
+        assert_eq!(ixs.len(), 2);
+    }
+
+    #[test]
+    fn test_fit_ixns_sizes_for_the_encoding() {
+        let payer = Pubkey::new_unique();
+        let accounts: Vec<Pubkey> = (0..24).map(|_| Pubkey::new_unique()).collect();
+        let settle_ixn = Instruction {
+            program_id: Pubkey::new_unique(),
+            data: vec![0u8; 64],
+            accounts: accounts
+                .iter()
+                .map(|pubkey| AccountMeta::new(*pubkey, false))
+                .collect(),
+        };
+        let planned = vec![
+            PlannedIxn::optional(compute_budget_ixn()),
+            PlannedIxn::required(settle_ixn),
+        ];
+        let v0 = MessageEncoding {
+            format: TxFormat::V0,
+            lookup_tables: vec![AddressLookupTableAccount {
+                key: Pubkey::new_unique(),
+                addresses: accounts,
+            }],
+        };
+
+        assert_eq!(
+            fit_ixns(
+                planned.clone(),
+                &payer,
+                &MessageEncoding::legacy(),
+                MAX_IXNS_MESSAGE_SIZE
+            )
+            .unwrap_err(),
+            FunctionError::TransactionTooLarge
+        );
+        assert_eq!(
+            fit_ixns(planned, &payer, &v0, MAX_IXNS_MESSAGE_SIZE)
+                .unwrap()
+                .len(),
+            2
+        );
+    }
+}
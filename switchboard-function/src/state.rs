@@ -0,0 +1,336 @@
+use crate::*;
+
+// Mirrors of the arena program accounts the function reads. Field order must
+// match the on-chain layout, new fields are only ever appended there.
+
+/// Anchor account discriminator, the first 8 bytes of sha256("account:<Name>").
+pub fn get_account_discriminator(account_name: &str) -> [u8; 8] {
+    let preimage = format!("account:{}", account_name);
+    let mut discriminator = [0u8; 8];
+    discriminator.copy_from_slice(&solana_program::hash::hash(preimage.as_bytes()).to_bytes()[..8]);
+    discriminator
+}
+
+/// Checks the discriminator and deserializes the account body. Trailing bytes
+/// (account padding or fields we don't mirror) are ignored.
+pub fn decode_account<T: AnchorDeserialize>(
+    account_name: &str,
+    data: &[u8],
+) -> std::result::Result<T, FunctionError> {
+    if data.len() < 8 || data[..8] != get_account_discriminator(account_name) {
+        return Err(FunctionError::AccountDecodeFailed);
+    }
+    T::deserialize(&mut &data[8..]).map_err(|_| FunctionError::AccountDecodeFailed)
+}
+
+/// A matchmaking division within a realm, e.g. a rating tier.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, PartialEq, Eq)]
+pub struct SubPool {
+    pub id: u8,
+    /// Inclusive rating bounds of the pool.
+    pub min_rating: u32,
+    pub max_rating: u32,
+}
+
+impl SubPool {
+    pub fn contains(&self, rating: u32) -> bool {
+        rating >= self.min_rating && rating <= self.max_rating
+    }
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, Default, PartialEq, Eq)]
+pub struct RealmConfig {
+    /// Empty when the realm matches everyone against everyone.
+    pub sub_pools: Vec<SubPool>,
+}
+
+/// The spaceships waiting for a match in one sub-pool, oldest first.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, PartialEq, Eq)]
+pub struct MatchmakingQueue {
+    pub sub_pool_id: u8,
+    /// Owners of the queued spaceships, see `spaceship_pda`.
+    pub owners: Vec<Pubkey>,
+}
+
+/// A faction's settled matches, see faction_bias.rs.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct FactionStats {
+    pub matches: u32,
+    pub wins: u32,
+}
+
+impl FactionStats {
+    pub fn win_rate_bps(&self) -> u16 {
+        if self.matches == 0 {
+            return 0;
+        }
+        (self.wins.min(self.matches) as u64 * 10_000 / self.matches as u64) as u16
+    }
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, PartialEq, Eq)]
+pub struct Realm {
+    pub bump: u8,
+    pub admin: Pubkey,
+    pub config: RealmConfig,
+    /// Realms created before the queues were stored on-chain have zeroed
+    /// padding here, which reads as no queue.
+    pub queues: Vec<MatchmakingQueue>,
+    /// Indexed by faction, realms that do not record them read as empty.
+    pub faction_stats: Vec<FactionStats>,
+}
+
+impl Realm {
+    pub const NAME: &'static str = "Realm";
+
+    pub fn decode(data: &[u8]) -> std::result::Result<Self, FunctionError> {
+        decode_account(Self::NAME, data)
+    }
+
+    pub fn queue(&self, sub_pool_id: u8) -> Option<&MatchmakingQueue> {
+        self.queues
+            .iter()
+            .find(|queue| queue.sub_pool_id == sub_pool_id)
+    }
+
+    pub fn faction_stats(&self, faction: u8) -> Option<&FactionStats> {
+        self.faction_stats.get(faction as usize)
+    }
+}
+
+/// Rules a realm's admin stores on-chain, see realm_registry.rs.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, Default, PartialEq, Eq)]
+pub struct RealmRulesAccount {
+    pub bump: u8,
+    pub realm: Pubkey,
+    /// Inclusive, replacing the request's `MIN`/`MAX` when both are set.
+    pub roll_min: Option<u64>,
+    pub roll_max: Option<u64>,
+    /// An `ExecutionTier` as u8.
+    pub max_execution_tier: Option<u8>,
+    pub min_opponents: u8,
+}
+
+impl RealmRulesAccount {
+    pub const NAME: &'static str = "RealmRules";
+
+    pub fn decode(data: &[u8]) -> std::result::Result<Self, FunctionError> {
+        decode_account(Self::NAME, data)
+    }
+}
+
+pub const REALM_RULES_SEED: &[u8] = b"realm_rules";
+
+pub fn realm_rules_pda(program_id: &Pubkey, realm: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[REALM_RULES_SEED, realm.as_ref()], program_id).0
+}
+
+pub const SPACESHIP_SEED: &[u8] = b"spaceship";
+
+/// A player's spaceship in a realm, at most one per realm.
+pub fn spaceship_pda(program_id: &Pubkey, realm: &Pubkey, owner: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(
+        &[SPACESHIP_SEED, realm.as_ref(), owner.as_ref()],
+        program_id,
+    )
+    .0
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ModuleKind {
+    Weapon,
+    Shield,
+    Engine,
+    Hull,
+}
+
+/// A module fitted to a spaceship's loadout.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ShipModule {
+    pub kind: ModuleKind,
+    pub level: u8,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, PartialEq, Eq)]
+pub struct Spaceship {
+    pub bump: u8,
+    pub owner: Pubkey,
+    pub faction: u8,
+    pub rating: u32,
+    /// The request that matched the spaceship, until its fight settles.
+    pub current_match: Option<Pubkey>,
+    /// Spaceships created before loadouts have zeroed padding here, which
+    /// reads as an empty loadout.
+    pub modules: Vec<ShipModule>,
+}
+
+impl Spaceship {
+    pub const NAME: &'static str = "Spaceship";
+
+    pub fn decode(data: &[u8]) -> std::result::Result<Self, FunctionError> {
+        decode_account(Self::NAME, data)
+    }
+}
+
+/// A player's account in a realm, owning their spaceship.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, PartialEq, Eq)]
+pub struct UserAccount {
+    pub bump: u8,
+    pub owner: Pubkey,
+    /// The slot the program's request counter window opened at.
+    pub request_window_start_slot: u64,
+    /// Requests made since `request_window_start_slot`, counting the one
+    /// being settled.
+    pub request_count: u32,
+}
+
+impl UserAccount {
+    pub const NAME: &'static str = "UserAccount";
+
+    pub fn decode(data: &[u8]) -> std::result::Result<Self, FunctionError> {
+        decode_account(Self::NAME, data)
+    }
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ApprovalStatus {
+    AwaitingSignatures,
+    /// Approved by the multisig and not settled yet.
+    Approved,
+    Consumed,
+}
+
+/// A multisig co-signer's approval of one settlement, required by realms
+/// whose settle handler is governed by a multisig (e.g. tournament finals).
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, PartialEq, Eq)]
+pub struct SettlementApproval {
+    pub bump: u8,
+    pub realm: Pubkey,
+    pub function_request: Pubkey,
+    pub multisig: Pubkey,
+    pub status: ApprovalStatus,
+}
+
+impl SettlementApproval {
+    pub const NAME: &'static str = "SettlementApproval";
+
+    pub fn decode(data: &[u8]) -> std::result::Result<Self, FunctionError> {
+        decode_account(Self::NAME, data)
+    }
+}
+
+/// Serializes an account the way anchor stores it, used to build fixtures.
+#[cfg(test)]
+pub fn encode_account<T: AnchorSerialize>(account_name: &str, account: &T) -> Vec<u8> {
+    let mut data = get_account_discriminator(account_name).to_vec();
+    data.append(&mut account.try_to_vec().unwrap());
+    data
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_fixtures::*;
+
+    #[test]
+    fn test_decode_spaceship_roundtrip() {
+        let spaceship = Spaceship {
+            bump: 254,
+            owner: Pubkey::new_unique(),
+            faction: 2,
+            rating: 1_450,
+            current_match: None,
+            modules: vec![ShipModule {
+                kind: ModuleKind::Shield,
+                level: 3,
+            }],
+        };
+        let mut data = encode_account(Spaceship::NAME, &spaceship);
+        // anchor accounts are usually allocated with some headroom
+        data.extend_from_slice(&[0u8; 64]);
+
+        assert_eq!(Spaceship::decode(&data).unwrap(), spaceship);
+    }
+
+    #[test]
+    fn test_decode_spaceship_without_loadout() {
+        let spaceship = Spaceship {
+            bump: 254,
+            owner: Pubkey::new_unique(),
+            faction: 1,
+            rating: 900,
+            current_match: None,
+            modules: vec![],
+        };
+        let data = encode_account(Spaceship::NAME, &spaceship);
+        // the account as the program wrote it before loadouts, with headroom
+        let mut legacy = data[..data.len() - 4].to_vec();
+        legacy.extend_from_slice(&[0u8; 64]);
+
+        assert_eq!(Spaceship::decode(&legacy).unwrap(), spaceship);
+    }
+
+    #[test]
+    fn test_decode_realm_queues() {
+        let mut realm = Realm {
+            bump: 1,
+            admin: Pubkey::new_unique(),
+            config: RealmConfig::default(),
+            queues: vec![MatchmakingQueue {
+                sub_pool_id: 2,
+                owners: vec![Pubkey::new_unique(), Pubkey::new_unique()],
+            }],
+            faction_stats: vec![],
+        };
+        let data = encode_account(Realm::NAME, &realm);
+        let decoded = Realm::decode(&data).unwrap();
+        assert_eq!(decoded, realm);
+        assert_eq!(decoded.queue(2), Some(&realm.queues[0]));
+        assert_eq!(decoded.queue(0), None);
+
+        realm.queues.clear();
+        let data = encode_account(Realm::NAME, &realm);
+        let mut legacy = data[..data.len() - 8].to_vec();
+        legacy.extend_from_slice(&[0u8; 64]);
+        assert_eq!(Realm::decode(&legacy).unwrap(), realm);
+    }
+
+    #[test]
+    fn test_decode_realm_faction_stats() {
+        let mut realm = test_realm(vec![]);
+        realm.faction_stats = vec![
+            FactionStats {
+                matches: 200,
+                wins: 50,
+            },
+            FactionStats::default(),
+        ];
+        let decoded = Realm::decode(&encode_account(Realm::NAME, &realm)).unwrap();
+
+        assert_eq!(decoded, realm);
+        assert_eq!(decoded.faction_stats(0).unwrap().win_rate_bps(), 2_500);
+        assert_eq!(decoded.faction_stats(1).unwrap().win_rate_bps(), 0);
+        assert_eq!(decoded.faction_stats(2), None);
+    }
+
+    #[test]
+    fn test_decode_rejects_wrong_discriminator() {
+        let realm = Realm {
+            bump: 1,
+            admin: Pubkey::new_unique(),
+            config: RealmConfig::default(),
+            queues: vec![],
+            faction_stats: vec![],
+        };
+        let data = encode_account(Realm::NAME, &realm);
+
+        assert_eq!(
+            Spaceship::decode(&data),
+            Err(FunctionError::AccountDecodeFailed)
+        );
+        assert_eq!(
+            Realm::decode(&data[..4]),
+            Err(FunctionError::AccountDecodeFailed)
+        );
+    }
+}
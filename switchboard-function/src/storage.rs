@@ -0,0 +1,215 @@
+use crate::setting;
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Gramine's encrypted mount, overridable with `SEALED_STORAGE_DIR`.
+pub const DEFAULT_SEALED_STORAGE_DIR: &str = "/data/protected_files";
+
+const DIGEST_EXTENSION: &str = "sha256";
+
+/// The state the function persists inside the enclave between runs.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ArtifactKind {
+    DedupLru,
+    IntentRecords,
+    AuditChain,
+    Stats,
+    /// Overrides the baked in program allowlist, see program_allowlist.rs.
+    ProgramAllowlist,
+    /// Recent requests per user of routine runs, see rate_limit.rs.
+    RateLimits,
+}
+
+impl ArtifactKind {
+    pub const ALL: [ArtifactKind; 6] = [
+        ArtifactKind::DedupLru,
+        ArtifactKind::IntentRecords,
+        ArtifactKind::AuditChain,
+        ArtifactKind::Stats,
+        ArtifactKind::ProgramAllowlist,
+        ArtifactKind::RateLimits,
+    ];
+
+    pub fn file_name(&self) -> &'static str {
+        match self {
+            ArtifactKind::DedupLru => "dedup_lru.json",
+            ArtifactKind::IntentRecords => "intent_records.jsonl",
+            ArtifactKind::AuditChain => "audit_chain.jsonl",
+            ArtifactKind::Stats => "stats.json",
+            ArtifactKind::ProgramAllowlist => "program_allowlist.json",
+            ArtifactKind::RateLimits => "rate_limits.json",
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Integrity {
+    Ok,
+    /// The artifact exists but was never sealed with a digest.
+    Unsealed,
+    Corrupt,
+}
+
+#[derive(Clone, Debug)]
+pub struct ArtifactStatus {
+    pub kind: ArtifactKind,
+    pub size: u64,
+    pub integrity: Integrity,
+}
+
+/// Artifacts are stored next to a sha256 digest so truncated or partially
+/// written files are detected instead of being loaded as valid state.
+pub struct SealedStorage {
+    dir: PathBuf,
+}
+
+impl SealedStorage {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    pub fn from_env() -> Self {
+        Self::new(
+            setting("SEALED_STORAGE_DIR").unwrap_or_else(|| DEFAULT_SEALED_STORAGE_DIR.to_string()),
+        )
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    fn artifact_path(&self, kind: ArtifactKind) -> PathBuf {
+        self.dir.join(kind.file_name())
+    }
+
+    fn digest_path(&self, kind: ArtifactKind) -> PathBuf {
+        self.dir
+            .join(format!("{}.{}", kind.file_name(), DIGEST_EXTENSION))
+    }
+
+    fn digest(data: &[u8]) -> String {
+        hex::encode(Sha256::digest(data))
+    }
+
+    pub fn write(&self, kind: ArtifactKind, data: &[u8]) -> std::io::Result<()> {
+        fs::create_dir_all(&self.dir)?;
+        fs::write(self.artifact_path(kind), data)?;
+        fs::write(self.digest_path(kind), Self::digest(data))
+    }
+
+    /// Reads an artifact, returning `None` if it is missing or fails its
+    /// integrity check.
+    pub fn read(&self, kind: ArtifactKind) -> Option<Vec<u8>> {
+        let data = fs::read(self.artifact_path(kind)).ok()?;
+        match self.integrity_of(kind, &data) {
+            Integrity::Ok => Some(data),
+            _ => None,
+        }
+    }
+
+    fn integrity_of(&self, kind: ArtifactKind, data: &[u8]) -> Integrity {
+        match fs::read_to_string(self.digest_path(kind)) {
+            Err(_) => Integrity::Unsealed,
+            Ok(digest) if digest.trim() == Self::digest(data) => Integrity::Ok,
+            Ok(_) => Integrity::Corrupt,
+        }
+    }
+
+    /// Returns the status of every artifact present on disk.
+    pub fn list(&self) -> Vec<ArtifactStatus> {
+        ArtifactKind::ALL
+            .iter()
+            .filter_map(|kind| {
+                let data = fs::read(self.artifact_path(*kind)).ok()?;
+                Some(ArtifactStatus {
+                    kind: *kind,
+                    size: data.len() as u64,
+                    integrity: self.integrity_of(*kind, &data),
+                })
+            })
+            .collect()
+    }
+
+    /// Removes artifacts that fail their integrity check, or every artifact
+    /// when `all` is set. Returns the kinds that were removed.
+    pub fn prune(&self, all: bool) -> std::io::Result<Vec<ArtifactKind>> {
+        let mut pruned = vec![];
+        for status in self.list() {
+            if all || status.integrity != Integrity::Ok {
+                fs::remove_file(self.artifact_path(status.kind))?;
+                let _ = fs::remove_file(self.digest_path(status.kind));
+                pruned.push(status.kind);
+            }
+        }
+        Ok(pruned)
+    }
+}
+
+#[cfg(test)]
+pub fn test_storage_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("arena-sealed-{}-{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    dir
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_write_read_roundtrip() {
+        let storage = SealedStorage::new(test_storage_dir("roundtrip"));
+        storage.write(ArtifactKind::Stats, b"{\"runs\":1}").unwrap();
+
+        assert_eq!(
+            storage.read(ArtifactKind::Stats).unwrap(),
+            b"{\"runs\":1}".to_vec()
+        );
+        assert!(storage.read(ArtifactKind::AuditChain).is_none());
+    }
+
+    #[test]
+    fn test_list_detects_tampering() {
+        let storage = SealedStorage::new(test_storage_dir("tamper"));
+        storage.write(ArtifactKind::Stats, b"{}").unwrap();
+        storage.write(ArtifactKind::DedupLru, b"[]").unwrap();
+        fs::write(storage.artifact_path(ArtifactKind::DedupLru), b"[1]").unwrap();
+        fs::write(storage.artifact_path(ArtifactKind::AuditChain), b"").unwrap();
+
+        let statuses = storage.list();
+        let integrity_of = |kind| {
+            statuses
+                .iter()
+                .find(|status| status.kind == kind)
+                .map(|status| status.integrity.clone())
+        };
+
+        assert_eq!(integrity_of(ArtifactKind::Stats), Some(Integrity::Ok));
+        assert_eq!(
+            integrity_of(ArtifactKind::DedupLru),
+            Some(Integrity::Corrupt)
+        );
+        assert_eq!(
+            integrity_of(ArtifactKind::AuditChain),
+            Some(Integrity::Unsealed)
+        );
+        assert_eq!(integrity_of(ArtifactKind::IntentRecords), None);
+        assert!(storage.read(ArtifactKind::DedupLru).is_none());
+    }
+
+    #[test]
+    fn test_prune() {
+        let storage = SealedStorage::new(test_storage_dir("prune"));
+        storage.write(ArtifactKind::Stats, b"{}").unwrap();
+        storage.write(ArtifactKind::DedupLru, b"[]").unwrap();
+        fs::write(storage.artifact_path(ArtifactKind::DedupLru), b"[1]").unwrap();
+
+        assert_eq!(storage.prune(false).unwrap(), vec![ArtifactKind::DedupLru]);
+        assert_eq!(storage.list().len(), 1);
+
+        assert_eq!(storage.prune(true).unwrap(), vec![ArtifactKind::Stats]);
+        assert!(storage.list().is_empty());
+        assert!(!storage.digest_path(ArtifactKind::Stats).exists());
+    }
+}
@@ -0,0 +1,82 @@
+use crate::*;
+use futures::future::LocalBoxFuture;
+
+/// Where a run's result goes. The runner is the only production emitter,
+/// the trait lets the failure paths run against injected faults, see chaos.rs.
+pub trait ResultEmitter {
+    fn emit(&self, ixs: Vec<Instruction>) -> LocalBoxFuture<'_, std::result::Result<(), SbError>>;
+
+    /// Emits `ixs` signed with the run's enclave key, see emission.rs.
+    fn emit_signed<'a>(
+        &'a self,
+        ixs: Vec<Instruction>,
+        encoding: &'a MessageEncoding,
+    ) -> LocalBoxFuture<'a, std::result::Result<(), SbError>>;
+
+    fn emit_error(&self, error_code: u8) -> LocalBoxFuture<'_, std::result::Result<(), SbError>>;
+}
+
+impl ResultEmitter for FunctionRunner {
+    fn emit(&self, ixs: Vec<Instruction>) -> LocalBoxFuture<'_, std::result::Result<(), SbError>> {
+        Box::pin(FunctionRunner::emit(self, ixs))
+    }
+
+    fn emit_signed<'a>(
+        &'a self,
+        ixs: Vec<Instruction>,
+        encoding: &'a MessageEncoding,
+    ) -> LocalBoxFuture<'a, std::result::Result<(), SbError>> {
+        Box::pin(async move {
+            let key =
+                run_enclave_key().map_err(|error| SbError::CustomMessage(error.to_string()))?;
+            signed_function_result(self, key, ixs, encoding)
+                .await?
+                .emit();
+            Ok(())
+        })
+    }
+
+    fn emit_error(&self, error_code: u8) -> LocalBoxFuture<'_, std::result::Result<(), SbError>> {
+        Box::pin(FunctionRunner::emit_error(self, error_code))
+    }
+}
+
+/// How a run ended, as far as the game program can tell.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Termination {
+    Settled,
+    /// A failure report was emitted so the program can refund the request.
+    Reported(FunctionError),
+    /// Only the bare error code was emitted.
+    ErrorCode(FunctionError),
+    /// Nothing could be emitted, the request times out on-chain.
+    Unreported(FunctionError),
+}
+
+/// Relays a failed run on-chain, as a failure report when `failure_report`
+/// builds one and as the bare error code otherwise.
+pub async fn finish_request<E: ResultEmitter + ?Sized>(
+    emitter: &E,
+    result: std::result::Result<(), FunctionError>,
+    failure_report: impl FnOnce(FunctionError) -> Option<Instruction>,
+) -> Termination {
+    let Err(error) = result else {
+        return Termination::Settled;
+    };
+    println!("failed to settle request: {}", error);
+    record_error(error);
+
+    if let Some(report) = failure_report(error) {
+        match emitter.emit(vec![report]).await {
+            Ok(()) => return Termination::Reported(error),
+            Err(emit_error) => println!("failed to emit failure report: {:?}", emit_error),
+        }
+    }
+    match emitter.emit_error(error.code()).await {
+        Ok(()) => Termination::ErrorCode(error),
+        Err(emit_error) => {
+            println!("failed to emit error {}: {:?}", error, emit_error);
+            Termination::Unreported(error)
+        }
+    }
+}
@@ -0,0 +1,160 @@
+//! Shared account and params fixtures for unit tests.
+
+pub use crate::rpc::mock::MockFetcher;
+use crate::*;
+use std::sync::atomic::{AtomicU32, Ordering};
+
+/// Returns scripted draws, each must lie within the requested bounds. Only
+/// draws are scripted, reading raw bytes fails.
+pub struct ScriptedRandomSource(std::sync::Mutex<Vec<u32>>);
+
+impl ScriptedRandomSource {
+    pub fn new(draws: Vec<u32>) -> Self {
+        Self(std::sync::Mutex::new(draws))
+    }
+}
+
+impl RandomSource for ScriptedRandomSource {
+    fn name(&self) -> &'static str {
+        "scripted"
+    }
+
+    fn fill_bytes(&self, _buf: &mut [u8]) -> std::result::Result<(), FunctionError> {
+        Err(FunctionError::Internal)
+    }
+
+    fn generate(&self, min: u32, max: u32) -> std::result::Result<u32, FunctionError> {
+        let value = self.0.lock().unwrap().remove(0);
+        assert!(value >= min && value <= max);
+        Ok(value)
+    }
+}
+
+/// Fails the first `failures` reads, then fills with 7s.
+pub struct FlakySource {
+    failures: u32,
+    pub reads: AtomicU32,
+}
+
+impl FlakySource {
+    pub fn new(failures: u32) -> Self {
+        Self {
+            failures,
+            reads: AtomicU32::new(0),
+        }
+    }
+}
+
+impl RandomSource for FlakySource {
+    fn name(&self) -> &'static str {
+        "flaky"
+    }
+
+    fn fill_bytes(&self, buf: &mut [u8]) -> std::result::Result<(), FunctionError> {
+        if self.reads.fetch_add(1, Ordering::SeqCst) < self.failures {
+            return Err(FunctionError::EntropyUnavailable);
+        }
+        buf.fill(7);
+        Ok(())
+    }
+}
+
+pub fn test_spaceship(rating: u32) -> Spaceship {
+    Spaceship {
+        bump: 255,
+        owner: Pubkey::new_unique(),
+        faction: 0,
+        rating,
+        current_match: None,
+        modules: vec![],
+    }
+}
+
+pub fn test_realm(sub_pools: Vec<SubPool>) -> Realm {
+    Realm {
+        bump: 255,
+        admin: Pubkey::new_unique(),
+        config: RealmConfig { sub_pools },
+        queues: vec![],
+        faction_stats: vec![],
+    }
+}
+
+pub fn test_params_string() -> String {
+    let pubkeys: Vec<String> = (0..10).map(|_| Pubkey::new_unique().to_string()).collect();
+    format!(
+        "PID={},USER={},REALM_PDA={},USER_ACCOUNT_PDA={},SPACESHIP_PDA={},FACTION=1,OS_1_PDA={},OS_2_PDA={},OS_3_PDA={},OS_4_PDA={},OS_5_PDA={}",
+        pubkeys[0], pubkeys[1], pubkeys[2], pubkeys[3], pubkeys[4],
+        pubkeys[5], pubkeys[6], pubkeys[7], pubkeys[8], pubkeys[9],
+    )
+}
+
+pub fn test_params() -> ContainerParams {
+    ContainerParams::decode(test_params_string().as_bytes()).unwrap()
+}
+
+/// A fetcher holding the realm, the requester (first of `spaceships`) and the
+/// five candidates.
+pub fn test_fetcher(
+    params: &ContainerParams,
+    realm: &Realm,
+    spaceships: [Spaceship; 6],
+) -> MockFetcher {
+    let mut fetcher = MockFetcher::default();
+    fetcher.insert(params.realm_pda, encode_account(Realm::NAME, realm));
+    fetcher.insert(
+        params.spaceship_pda,
+        encode_account(Spaceship::NAME, &spaceships[0]),
+    );
+    for (pubkey, spaceship) in params
+        .opponent_spaceship_pdas()
+        .iter()
+        .zip(&spaceships[1..])
+    {
+        fetcher.insert(*pubkey, encode_account(Spaceship::NAME, spaceship));
+    }
+    fetcher
+}
+
+/// Clock sysvar data at `unix_timestamp`.
+pub fn clock_account(unix_timestamp: i64) -> Vec<u8> {
+    let mut data = vec![0u8; 40];
+    data[CLOCK_UNIX_TIMESTAMP_OFFSET..].copy_from_slice(&unix_timestamp.to_le_bytes());
+    data
+}
+
+/// A request's accounts, signed with the run's enclave key like
+/// `RunnerAccounts::for_settlement`.
+pub fn test_runner_accounts() -> RunnerAccounts {
+    RunnerAccounts {
+        enclave_signer: run_enclave_key().unwrap().pubkey(),
+        function: Pubkey::new_unique(),
+        function_request: Pubkey::new_unique(),
+        request_slot: 1_000,
+        tx_format: TxFormat::Legacy,
+    }
+}
+
+/// A budget with plenty of time left, so only the tier limits the stages.
+pub fn test_budget(tier: ExecutionTier) -> TierBudget {
+    TierBudget::new(
+        std::time::Instant::now(),
+        std::time::Duration::from_secs(60),
+        tier,
+    )
+}
+
+/// Simulator returning a canned result.
+pub struct MockSimulator {
+    pub result: std::result::Result<(), FunctionError>,
+}
+
+impl TransactionSimulator for MockSimulator {
+    fn simulate(
+        &self,
+        _ixs: &[Instruction],
+        _payer: &Pubkey,
+    ) -> std::result::Result<(), FunctionError> {
+        self.result
+    }
+}
@@ -0,0 +1,167 @@
+use crate::*;
+use std::time::{Duration, Instant};
+
+/// How much work the function does for a request. Higher tiers make more RPC
+/// calls, each tier includes everything below it.
+#[repr(u8)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ExecutionTier {
+    /// No account fetches beyond the realm's rules, the roll alone picks
+    /// among the candidates.
+    Fast = 0,
+    /// Fetches and validates the accounts the request type relies on.
+    Standard = 1,
+    /// Also simulates the settle transaction before emitting it.
+    Rich = 2,
+}
+
+impl ExecutionTier {
+    fn below(&self) -> Self {
+        match self {
+            ExecutionTier::Rich => ExecutionTier::Standard,
+            _ => ExecutionTier::Fast,
+        }
+    }
+}
+
+impl FromStr for ExecutionTier {
+    type Err = SwitchboardError;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "FAST" => Ok(ExecutionTier::Fast),
+            "STANDARD" => Ok(ExecutionTier::Standard),
+            "RICH" => Ok(ExecutionTier::Rich),
+            _ => Err(SwitchboardError::InvalidFunctionInput),
+        }
+    }
+}
+
+pub const DEFAULT_EXECUTION_DEADLINE: Duration = Duration::from_secs(20);
+
+/// Tracks the time spent against the execution deadline, see deadline.rs,
+/// and downgrades the tier when an upcoming stage would put the emit at risk.
+pub struct TierBudget {
+    deadline: Deadline,
+    tier: ExecutionTier,
+    stage_timings: Vec<(&'static str, Duration)>,
+}
+
+impl TierBudget {
+    pub fn new(started: Instant, deadline: Duration, tier: ExecutionTier) -> Self {
+        Self {
+            deadline: Deadline::new(started, deadline),
+            tier,
+            stage_timings: vec![],
+        }
+    }
+
+    /// Reads `EXECUTION_TIER` (default `STANDARD`) and `EXECUTION_DEADLINE_MS`.
+    pub fn from_env(started: Instant) -> Self {
+        let tier = setting("EXECUTION_TIER")
+            .and_then(|tier| ExecutionTier::from_str(&tier).ok())
+            .unwrap_or(ExecutionTier::Standard);
+        let deadline = setting("EXECUTION_DEADLINE_MS")
+            .and_then(|ms| ms.parse::<u64>().ok())
+            .map_or(DEFAULT_EXECUTION_DEADLINE, Duration::from_millis);
+        Self::new(started, deadline, tier)
+    }
+
+    pub fn tier(&self) -> ExecutionTier {
+        self.tier
+    }
+
+    pub fn remaining(&self) -> Duration {
+        self.deadline.remaining()
+    }
+
+    /// Whether a stage of `phase` belonging to `tier` may run. If the current
+    /// tier includes it but the phase's budget doesn't admit it, the tier
+    /// drops below it so the settle data reflects the work that was actually
+    /// done.
+    pub fn admit(&mut self, tier: ExecutionTier, phase: Phase) -> bool {
+        if self.tier < tier {
+            return false;
+        }
+        if !self.deadline.admit(phase) {
+            println!("downgrading from {:?} to {:?}", self.tier, tier.below());
+            self.tier = tier.below();
+            return false;
+        }
+        true
+    }
+
+    /// Caps the tier when a stage cannot run for reasons other than time.
+    pub fn limit(&mut self, tier: ExecutionTier) {
+        self.tier = self.tier.min(tier);
+    }
+
+    pub fn record(&mut self, phase: Phase, started: Instant) {
+        let elapsed = started.elapsed();
+        record_timing("stage_duration_ms", elapsed, &[("stage", phase.name())]);
+        self.deadline.record(phase, elapsed);
+        self.stage_timings.push((phase.name(), elapsed));
+    }
+
+    pub fn stage_timings(&self) -> &[(&'static str, Duration)] {
+        &self.stage_timings
+    }
+
+    /// The phases that had work skipped to keep within the deadline.
+    pub fn skipped_phases(&self) -> Vec<&'static str> {
+        self.deadline
+            .skipped()
+            .iter()
+            .map(|phase| phase.name())
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_admit_within_budget() {
+        let mut budget =
+            TierBudget::new(Instant::now(), Duration::from_secs(60), ExecutionTier::Rich);
+
+        assert!(budget.admit(ExecutionTier::Standard, Phase::Fetch));
+        assert!(budget.admit(ExecutionTier::Rich, Phase::Simulate));
+        assert_eq!(budget.tier(), ExecutionTier::Rich);
+    }
+
+    #[test]
+    fn test_admit_respects_configured_tier() {
+        let mut budget =
+            TierBudget::new(Instant::now(), Duration::from_secs(60), ExecutionTier::Fast);
+
+        assert!(!budget.admit(ExecutionTier::Standard, Phase::Fetch));
+        assert_eq!(budget.tier(), ExecutionTier::Fast);
+    }
+
+    #[test]
+    fn test_admit_downgrades_when_deadline_at_risk() {
+        let deadline = EMIT_RESERVE + FETCH_ESTIMATE + Duration::from_millis(500);
+        let mut budget = TierBudget::new(Instant::now(), deadline, ExecutionTier::Rich);
+
+        assert!(budget.admit(ExecutionTier::Standard, Phase::Fetch));
+        assert!(!budget.admit(ExecutionTier::Rich, Phase::Simulate));
+        assert_eq!(budget.tier(), ExecutionTier::Standard);
+
+        let mut budget = TierBudget::new(Instant::now(), EMIT_RESERVE, ExecutionTier::Rich);
+        assert!(!budget.admit(ExecutionTier::Standard, Phase::Fetch));
+        assert_eq!(budget.tier(), ExecutionTier::Fast);
+        // a downgraded budget never climbs back up
+        assert!(!budget.admit(ExecutionTier::Rich, Phase::Simulate));
+    }
+
+    #[test]
+    fn test_execution_tier_from_str() {
+        assert_eq!(
+            ExecutionTier::from_str("RICH").unwrap(),
+            ExecutionTier::Rich
+        );
+        assert!(ExecutionTier::from_str("rich").is_err());
+    }
+}
@@ -0,0 +1,63 @@
+use crate::*;
+
+/// Fisher-Yates shuffle of the participant indices `0..participants`, drawn
+/// from enclave entropy so neither the organiser nor the client picks the
+/// bracket. `seed_order[i]` is the participant placed at seed `i`.
+pub fn shuffle_seed_order(
+    participants: usize,
+    rng: &dyn RandomSource,
+) -> std::result::Result<Vec<u8>, FunctionError> {
+    let mut seed_order: Vec<u8> = (0..participants as u8).collect();
+    for i in (1..seed_order.len()).rev() {
+        let j = rng.generate(0, i as u32)? as usize;
+        seed_order.swap(i, j);
+    }
+    Ok(seed_order)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_fixtures::*;
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_shuffle_seed_order_swaps() {
+        // i=3 swaps with 0, i=2 stays, i=1 swaps with 0
+        let rng = ScriptedRandomSource::new(vec![0, 2, 0]);
+
+        assert_eq!(shuffle_seed_order(4, &rng).unwrap(), vec![1, 3, 2, 0]);
+    }
+
+    #[test]
+    fn test_shuffle_seed_order_is_a_permutation() {
+        for participants in [0, 1, 2, 5, MAX_TOURNAMENT_PARTICIPANTS] {
+            let mut seed_order = shuffle_seed_order(participants, &OsRandomSource).unwrap();
+
+            seed_order.sort_unstable();
+            assert_eq!(seed_order, (0..participants as u8).collect::<Vec<u8>>());
+        }
+    }
+
+    #[test]
+    fn test_shuffle_seed_order_is_uniform() {
+        let runs = 6_000;
+        let mut counts: HashMap<Vec<u8>, u32> = HashMap::new();
+        for _ in 0..runs {
+            *counts
+                .entry(shuffle_seed_order(3, &OsRandomSource).unwrap())
+                .or_default() += 1;
+        }
+
+        // 1000 expected per ordering, a fair shuffle stays well within 20%
+        assert_eq!(counts.len(), 6);
+        for (seed_order, count) in counts {
+            assert!(
+                (800..1_200).contains(&count),
+                "{:?} seen {} times",
+                seed_order,
+                count
+            );
+        }
+    }
+}
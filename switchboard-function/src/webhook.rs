@@ -0,0 +1,118 @@
+use crate::*;
+use base64::Engine;
+use serde::Serialize;
+
+const WEBHOOK_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(3);
+
+/// The game backend endpoint notified of every emitted settlement, from the
+/// `OUTCOME_WEBHOOK_URL` env var.
+pub fn webhook_url_from_env() -> Option<String> {
+    setting("OUTCOME_WEBHOOK_URL").filter(|url| !url.is_empty())
+}
+
+#[derive(Serialize, Clone, Debug, PartialEq, Eq)]
+pub struct OutcomeSummary {
+    pub request_type: String,
+    pub request: String,
+    pub user: String,
+    pub opponent: Option<String>,
+    /// Hex encoded sha256 of the settle instruction data.
+    pub outcome_hash: String,
+    /// The enclave signer that signed the settle transaction, the run's
+    /// enclave key unless the runner signed a batch.
+    pub enclave_signer: String,
+    /// The transaction is submitted by the oracle after we exit, so the
+    /// backend should still expect it to land or fail on-chain.
+    pub status: String,
+}
+
+impl OutcomeSummary {
+    pub fn new(
+        params: &ContainerParams,
+        runner_accounts: &RunnerAccounts,
+        opponent: Option<Pubkey>,
+        settle_ixn: &Instruction,
+    ) -> Self {
+        Self {
+            request_type: format!("{:?}", params.request_type),
+            request: runner_accounts.function_request.to_string(),
+            user: params.user.to_string(),
+            opponent: opponent.map(|opponent| opponent.to_string()),
+            outcome_hash: solana_program::hash::hash(&settle_ixn.data).to_string(),
+            enclave_signer: runner_accounts.enclave_signer.to_string(),
+            status: "emitted".to_string(),
+        }
+    }
+}
+
+/// The body POSTed to the webhook. `signature` is over the exact `payload`
+/// string so the backend verifies it before parsing.
+#[derive(Serialize, Clone, Debug)]
+pub struct SignedOutcome {
+    pub payload: String,
+    pub signer: String,
+    pub signature: String,
+    /// Base64 SGX quote binding `signer` to the enclave measurement.
+    pub quote: String,
+}
+
+impl SignedOutcome {
+    pub fn sign(summary: &OutcomeSummary, key: &EnclaveKey) -> Self {
+        let payload = serde_json::to_string(summary).unwrap();
+        let signature = key.sign(payload.as_bytes());
+        Self {
+            signer: key.pubkey().to_string(),
+            signature: signature.to_string(),
+            quote: base64::engine::general_purpose::STANDARD.encode(key.quote()),
+            payload,
+        }
+    }
+}
+
+/// Best effort: failures are logged and never affect the settlement. Runs
+/// after the emit, so logs go to stderr to keep the emitted result the last
+/// word on stdout.
+pub async fn post_outcome(url: &str, outcome: &SignedOutcome) {
+    let client = match reqwest::Client::builder().timeout(WEBHOOK_TIMEOUT).build() {
+        Ok(client) => client,
+        Err(error) => {
+            eprintln!("failed to build webhook client: {}", error);
+            return;
+        }
+    };
+
+    match client.post(url).json(outcome).send().await {
+        Ok(response) if response.status().is_success() => (),
+        Ok(response) => eprintln!("outcome webhook returned {}", response.status()),
+        Err(error) => eprintln!("failed to post outcome webhook: {}", error),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_signed_outcome_verifies_against_payload() {
+        let summary = OutcomeSummary {
+            request_type: "Matchmaking".to_string(),
+            request: Pubkey::new_unique().to_string(),
+            user: Pubkey::new_unique().to_string(),
+            opponent: Some(Pubkey::new_unique().to_string()),
+            outcome_hash: solana_program::hash::hash(b"data").to_string(),
+            enclave_signer: Pubkey::new_unique().to_string(),
+            status: "emitted".to_string(),
+        };
+        let key = EnclaveKey::generate().unwrap();
+
+        let outcome = SignedOutcome::sign(&summary, &key);
+
+        let signature = solana_sdk::signature::Signature::from_str(&outcome.signature).unwrap();
+        let signer = Pubkey::from_str(&outcome.signer).unwrap();
+        assert!(signature.verify(signer.as_ref(), outcome.payload.as_bytes()));
+
+        let payload: serde_json::Value = serde_json::from_str(&outcome.payload).unwrap();
+        assert_eq!(payload["user"], summary.user);
+        assert_eq!(payload["status"], "emitted");
+    }
+}